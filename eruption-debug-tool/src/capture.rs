@@ -0,0 +1,486 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::util::HexSlice;
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// Version of the capture file format
+pub const CAPTURE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CaptureError {
+    #[error("Unsupported capture file format version: {version}")]
+    UnsupportedVersion { version: u32 },
+
+    #[error("The capture file has been recorded from a different device: {usb_vid:x}:{usb_pid:x}")]
+    DeviceMismatch { usb_vid: u16, usb_pid: u16 },
+}
+
+/// Direction of a captured transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ArgEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Direction {
+    /// From the device to the host
+    In,
+
+    /// From the host to the device
+    Out,
+}
+
+/// Type of a captured USB HID report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReportType {
+    /// Input or output report, transferred via an interrupt endpoint
+    Interrupt,
+
+    /// Feature report, transferred via the control endpoint
+    Feature,
+}
+
+/// A single captured USB HID report
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// Microseconds since the start of the capture
+    pub timestamp: u64,
+    pub direction: Direction,
+    pub report_type: ReportType,
+    pub interface: i32,
+    pub report_id: u8,
+    pub data: Vec<u8>,
+}
+
+impl Record {
+    /// Create a new record, the report ID is taken from the first byte of `data`
+    pub fn new(
+        timestamp: u64,
+        direction: Direction,
+        report_type: ReportType,
+        interface: i32,
+        data: &[u8],
+    ) -> Self {
+        Self {
+            timestamp,
+            direction,
+            report_type,
+            interface,
+            report_id: data.first().copied().unwrap_or(0),
+            data: data.to_vec(),
+        }
+    }
+}
+
+/// A capture of the USB HID traffic of a device, as recorded by the `trace` sub-command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Capture {
+    pub version: u32,
+    pub usb_vid: u16,
+    pub usb_pid: u16,
+    pub product: String,
+    pub serial: String,
+    pub records: Vec<Record>,
+}
+
+impl Capture {
+    pub fn new(usb_vid: u16, usb_pid: u16, product: &str, serial: &str) -> Self {
+        Self {
+            version: CAPTURE_FORMAT_VERSION,
+            usb_vid,
+            usb_pid,
+            product: product.to_string(),
+            serial: serial.to_string(),
+            records: Vec::new(),
+        }
+    }
+
+    /// Returns the records that are matched by `filter`
+    pub fn filtered_records(&self, filter: &Filter) -> Vec<&Record> {
+        self.records.iter().filter(|r| filter.matches(r)).collect()
+    }
+}
+
+/// Records the reports that are exchanged with a device by the sub-commands that access it
+/// directly, e.g. `write`, `write-raw` or `state-diff`
+///
+/// If the capture file already exists, the new records are appended to it, so that a sequence
+/// of commands can be recorded and later be sent to the device again using `replay`
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    capture: Capture,
+    offset: u64,
+    start: Instant,
+}
+
+impl Recorder {
+    pub fn new<P: AsRef<Path>>(
+        path: &P,
+        usb_vid: u16,
+        usb_pid: u16,
+        product: &str,
+        serial: &str,
+    ) -> Result<Self> {
+        let capture = if path.as_ref().exists() {
+            let capture = load_capture(path)?;
+
+            if capture.usb_vid != usb_vid || capture.usb_pid != usb_pid {
+                return Err(CaptureError::DeviceMismatch {
+                    usb_vid: capture.usb_vid,
+                    usb_pid: capture.usb_pid,
+                }
+                .into());
+            }
+
+            capture
+        } else {
+            Capture::new(usb_vid, usb_pid, product, serial)
+        };
+
+        // continue after the last record of a previous session
+        let offset = capture.records.last().map_or(0, |r| r.timestamp);
+
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            capture,
+            offset,
+            start: Instant::now(),
+        })
+    }
+
+    pub fn record(
+        &mut self,
+        direction: Direction,
+        report_type: ReportType,
+        interface: i32,
+        data: &[u8],
+    ) {
+        self.capture.records.push(Record::new(
+            self.offset + self.start.elapsed().as_micros() as u64,
+            direction,
+            report_type,
+            interface,
+            data,
+        ));
+    }
+
+    /// Write the capture file, including the records of previous sessions
+    pub fn save(&self) -> Result<()> {
+        save_capture(&self.path, &self.capture)
+    }
+}
+
+/// Selects a subset of the records of a capture
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Only match records with one of these report IDs, matches all records if empty
+    pub report_ids: Vec<u8>,
+
+    /// Only match records of this direction
+    pub direction: Option<Direction>,
+}
+
+impl Filter {
+    pub fn matches(&self, record: &Record) -> bool {
+        (self.report_ids.is_empty() || self.report_ids.contains(&record.report_id))
+            && self.direction.map_or(true, |d| d == record.direction)
+    }
+}
+
+/// A difference between two captures
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordDiff {
+    /// The record at `index` differs in the bytes at the positions `bytes`
+    Changed { index: usize, bytes: Vec<usize> },
+
+    /// The record at `index` only exists in the first capture
+    Removed { index: usize },
+
+    /// The record at `index` only exists in the second capture
+    Added { index: usize },
+}
+
+pub fn load_capture<P: AsRef<Path>>(path: &P) -> Result<Capture> {
+    let data = fs::read_to_string(&path.as_ref())?;
+    let result: Capture = serde_json::from_str(&data)?;
+
+    if result.version != CAPTURE_FORMAT_VERSION {
+        return Err(CaptureError::UnsupportedVersion {
+            version: result.version,
+        }
+        .into());
+    }
+
+    Ok(result)
+}
+
+pub fn save_capture<P: AsRef<Path>>(path: &P, capture: &Capture) -> Result<()> {
+    let data = serde_json::to_string(&capture)?;
+    fs::write(&path.as_ref(), &data)?;
+
+    Ok(())
+}
+
+/// Compare two sequences of records position by position
+pub fn diff_records(first: &[&Record], second: &[&Record]) -> Vec<RecordDiff> {
+    let mut result = Vec::new();
+
+    for index in 0..first.len().max(second.len()) {
+        match (first.get(index), second.get(index)) {
+            (Some(a), Some(b)) => {
+                let bytes: Vec<usize> = (0..a.data.len().max(b.data.len()))
+                    .filter(|i| a.data.get(*i) != b.data.get(*i))
+                    .collect();
+
+                if !bytes.is_empty() || a.direction != b.direction {
+                    result.push(RecordDiff::Changed { index, bytes });
+                }
+            }
+
+            (Some(_), None) => result.push(RecordDiff::Removed { index }),
+            (None, Some(_)) => result.push(RecordDiff::Added { index }),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    result
+}
+
+/// Hand the records to `send` one by one, either preserving the timing of the capture or
+/// waiting `delay` milliseconds between the records. Returns the number of records sent
+pub fn replay_records<F>(records: &[&Record], delay: Option<u64>, mut send: F) -> Result<usize>
+where
+    F: FnMut(&Record) -> Result<()>,
+{
+    let start = Instant::now();
+    let first_timestamp = records.first().map_or(0, |r| r.timestamp);
+
+    let mut count = 0;
+
+    for record in records {
+        if crate::QUIT.load(Ordering::SeqCst) {
+            break;
+        }
+
+        match delay {
+            Some(millis) => thread::sleep(Duration::from_millis(millis)),
+
+            None => {
+                // preserve the timing of the capture
+                let due = Duration::from_micros(record.timestamp.saturating_sub(first_timestamp));
+
+                if let Some(wait) = due.checked_sub(start.elapsed()) {
+                    thread::sleep(wait);
+                }
+            }
+        }
+
+        println!(
+            "{:?}/{:?}: [{}]",
+            record.direction,
+            record.report_type,
+            HexSlice::new(&record.data)
+        );
+
+        send(record)?;
+
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+fn format_record(record: &Record) -> String {
+    format!(
+        "{:?}/{:?} 0x{:02x}: [{}]",
+        record.direction,
+        record.report_type,
+        record.report_id,
+        HexSlice::new(&record.data)
+    )
+}
+
+pub fn print_diff(first: &[&Record], second: &[&Record], diff: &[RecordDiff]) {
+    for d in diff {
+        match d {
+            RecordDiff::Removed { index } => {
+                println!(
+                    "{}: {}\n",
+                    format!("#{}", index).red(),
+                    format_record(first[*index]).red()
+                );
+            }
+
+            RecordDiff::Added { index } => {
+                println!(
+                    "{}: {}\n",
+                    format!("#{}", index).green(),
+                    format_record(second[*index]).green()
+                );
+            }
+
+            RecordDiff::Changed { index, bytes } => {
+                let (a, b) = (first[*index], second[*index]);
+
+                println!("Changed bytes: {:?}", bytes);
+
+                print!(
+                    "{}: {:?}/{:?} [",
+                    format!("#{}", index).bold().on_green(),
+                    b.direction,
+                    b.report_type
+                );
+                for i in 0..a.data.len().max(b.data.len()) {
+                    let (old, new) = (a.data.get(i), b.data.get(i));
+
+                    if bytes.contains(&i) {
+                        let format_byte =
+                            |v: Option<&u8>| v.map_or("--".to_string(), |v| format!("0x{:02x}", v));
+
+                        print!(
+                            "{}=>{}, ",
+                            format_byte(old).bold().on_red(),
+                            format_byte(new).bold().on_green()
+                        );
+                    } else {
+                        print!("0x{:02x}, ", new.unwrap());
+                    }
+                }
+                println!("]\n");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(direction: Direction, data: &[u8]) -> Record {
+        Record::new(0, direction, ReportType::Interrupt, 0, data)
+    }
+
+    #[test]
+    fn filter_records() {
+        let mut capture = Capture::new(0x1e7d, 0x2dd2, "Kone Pure Ultra", "");
+        capture.records = vec![
+            record(Direction::In, &[0x03, 0x01]),
+            record(Direction::Out, &[0x04, 0x01]),
+            record(Direction::In, &[0x04, 0x02]),
+        ];
+
+        let filter = Filter {
+            report_ids: vec![0x04],
+            direction: None,
+        };
+        assert_eq!(capture.filtered_records(&filter).len(), 2);
+
+        let filter = Filter {
+            report_ids: vec![0x04],
+            direction: Some(Direction::In),
+        };
+        assert_eq!(capture.filtered_records(&filter), vec![&capture.records[2]]);
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let path = std::env::temp_dir().join(format!(
+            "eruption-debug-tool-capture-{}.json",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+
+        // two separate sessions, e.g. `state-diff` followed by `write`
+        let mut recorder = Recorder::new(&path, 0x1e7d, 0x2dd2, "Kone Pure Ultra", "").unwrap();
+        recorder.record(Direction::In, ReportType::Feature, 0, &[0x06, 0x01]);
+        recorder.save().unwrap();
+
+        let mut recorder = Recorder::new(&path, 0x1e7d, 0x2dd2, "Kone Pure Ultra", "").unwrap();
+        recorder.record(Direction::Out, ReportType::Feature, 0, &[0x06, 0x02]);
+        recorder.record(Direction::Out, ReportType::Interrupt, 0, &[0x0d, 0x03]);
+        recorder.save().unwrap();
+
+        assert!(Recorder::new(&path, 0x1e7d, 0x2dea, "Kone Pure Ultra", "").is_err());
+
+        let capture = load_capture(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(capture.records.len(), 3);
+        assert!(capture
+            .records
+            .windows(2)
+            .all(|w| w[0].timestamp <= w[1].timestamp));
+
+        let filter = Filter {
+            report_ids: vec![0x06],
+            direction: Some(Direction::Out),
+        };
+        let records = capture.filtered_records(&filter);
+
+        // dry run, only collect what would have been sent
+        let mut sent = Vec::new();
+        let count = replay_records(&records, Some(0), |r| {
+            sent.push((r.report_type, r.data.clone()));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(count, 1);
+        assert_eq!(sent, vec![(ReportType::Feature, vec![0x06, 0x02])]);
+    }
+
+    #[test]
+    fn diff_captures() {
+        let first = [
+            record(Direction::In, &[0x03, 0x01, 0x02]),
+            record(Direction::In, &[0x04, 0x00]),
+        ];
+        let second = [
+            record(Direction::In, &[0x03, 0x01, 0x05, 0x06]),
+            record(Direction::In, &[0x04, 0x00]),
+            record(Direction::In, &[0x05]),
+        ];
+
+        let diff = diff_records(
+            &first.iter().collect::<Vec<_>>(),
+            &second.iter().collect::<Vec<_>>(),
+        );
+
+        assert_eq!(
+            diff,
+            vec![
+                RecordDiff::Changed {
+                    index: 0,
+                    bytes: vec![2, 3]
+                },
+                RecordDiff::Added { index: 2 },
+            ]
+        );
+    }
+}
@@ -35,10 +35,12 @@ use std::{env, thread};
 use std::{path::PathBuf, time::Duration};
 use std::{sync::atomic::AtomicBool, sync::atomic::Ordering, time::Instant};
 
+mod capture;
 mod constants;
 mod hwdevices;
 mod util;
 
+use capture::{Capture, Direction, Filter, Record, Recorder, ReportType};
 use util::{DeviceState, HexSlice};
 
 #[derive(RustEmbed)]
@@ -153,12 +155,58 @@ pub enum Subcommands {
     Trace {
         /// The index of the device, can be found with the list sub-command
         device: usize,
+
+        /// Write the captured reports to the specified file, for use with replay and diff
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Send the reports of a capture file to the specified device (dangerous)
+    Replay {
+        /// The index of the device, can be found with the list sub-command
+        device: usize,
+
+        /// The capture file, as written by the trace sub-command
+        file: PathBuf,
+
+        /// Only replay reports with the specified report IDs
+        #[clap(long = "report-id", parse(try_from_str = util::parse_report_id))]
+        report_ids: Vec<u8>,
+
+        /// Delay between reports in milliseconds, instead of the timing of the capture
+        #[clap(long)]
+        delay: Option<u64>,
+
+        /// Only print the reports, do not send anything to the device
+        #[clap(long)]
+        dry_run: bool,
+    },
+
+    /// Show the differences between two capture files
+    Diff {
+        /// The first capture file
+        first: PathBuf,
+
+        /// The second capture file
+        second: PathBuf,
+
+        /// Only compare reports with the specified report IDs
+        #[clap(long = "report-id", parse(try_from_str = util::parse_report_id))]
+        report_ids: Vec<u8>,
+
+        /// Only compare reports of the specified direction
+        #[clap(long, arg_enum)]
+        direction: Option<Direction>,
     },
 
     /// Read out the device state and show differences to previous state (May hang the device)
     StateDiff {
         /// The index of the device, can be found with the list sub-command
         device: usize,
+
+        /// Record the exchanged reports to the specified capture file, for use with replay and diff
+        #[clap(short, long)]
+        capture: Option<PathBuf>,
     },
 
     /// Read a single USB HID feature report from device
@@ -181,6 +229,10 @@ pub enum Subcommands {
 
         /// Hex bytes e.g.: [0x09, 0x00, 0x1f]
         data: String,
+
+        /// Record the exchanged reports to the specified capture file, for use with replay and diff
+        #[clap(short, long)]
+        capture: Option<PathBuf>,
     },
 
     /// Read data from device
@@ -203,6 +255,10 @@ pub enum Subcommands {
 
         /// Hex bytes e.g.: [0x09, 0x00, 0x1f]
         data: String,
+
+        /// Record the exchanged reports to the specified capture file, for use with replay and diff
+        #[clap(short, long)]
+        capture: Option<PathBuf>,
     },
 
     /// Send a device specific init sequence and try to set colors
@@ -402,7 +458,7 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
             println!("-- End of report --");
         }
 
-        Subcommands::Trace { device } => {
+        Subcommands::Trace { device, output } => {
             println!("-- Start of trace --");
 
             // create the one and only hidapi instance
@@ -433,16 +489,50 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                             // wait to settle
                             thread::sleep(Duration::from_millis(500));
 
+                            let mut capture = Capture::new(
+                                device.vendor_id(),
+                                device.product_id(),
+                                device.product_string().unwrap_or("<unknown>"),
+                                device.serial_number().unwrap_or("<unknown>"),
+                            );
+
                             println!("Entering polling loop:");
 
+                            let start = Instant::now();
+
                             loop {
-                                let mut buf: [u8; 16] = [0; 16];
+                                if QUIT.load(Ordering::SeqCst) {
+                                    break;
+                                }
+
+                                let mut buf: [u8; 64] = [0; 64];
                                 buf[0] = 0xff;
 
-                                let bytes_read = dev.read(&mut buf)?;
+                                let bytes_read = dev.read_timeout(&mut buf, 100)?;
+
+                                if bytes_read > 0 {
+                                    println!("{:?}: {} bytes", Instant::now(), bytes_read);
+                                    hexdump::hexdump_iter(&buf[..bytes_read])
+                                        .for_each(|s| println!("  {}", s));
+
+                                    capture.records.push(Record::new(
+                                        start.elapsed().as_micros() as u64,
+                                        Direction::In,
+                                        ReportType::Interrupt,
+                                        device.interface_number(),
+                                        &buf[..bytes_read],
+                                    ));
+                                }
+                            }
 
-                                println!("{:?}: {} bytes", Instant::now(), bytes_read);
-                                hexdump::hexdump_iter(&buf).for_each(|s| println!("  {}", s));
+                            if let Some(output) = output {
+                                println!(
+                                    "Saving {} reports to {}",
+                                    capture.records.len(),
+                                    output.display()
+                                );
+
+                                capture::save_capture(&output, &capture)?;
                             }
                         } else {
                             error!("Could not open the device, is the device in use?");
@@ -460,8 +550,127 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
             println!("-- End of trace --");
         }
 
+        Subcommands::Replay {
+            device: device_index,
+            file,
+            report_ids,
+            delay,
+            dry_run,
+        } => {
+            let capture = capture::load_capture(&file)?;
+
+            // only reports that have been sent by the host may be replayed, sending the
+            // input reports of the device back to it would not make any sense
+            let filter = Filter {
+                report_ids,
+                direction: Some(Direction::Out),
+            };
+            let records = capture.filtered_records(&filter);
+
+            if records.is_empty() {
+                warn!("The capture does not contain any matching reports sent by the host");
+            }
+
+            println!(
+                "Capture of {:x}:{:x} {}: {} of {} reports selected",
+                capture.usb_vid,
+                capture.usb_pid,
+                capture.product.bold(),
+                records.len(),
+                capture.records.len()
+            );
+
+            // create the one and only hidapi instance
+            match hidapi::HidApi::new() {
+                Ok(hidapi) => {
+                    if let Some((index, device)) =
+                        hidapi.device_list().enumerate().nth(device_index)
+                    {
+                        println!(
+                            "Index: {}: ID: {:x}:{:x} {}/{} Subdev: {}",
+                            format!("{:02}", index).bold(),
+                            device.vendor_id(),
+                            device.product_id(),
+                            device.manufacturer_string().unwrap_or("<unknown>").bold(),
+                            device.product_string().unwrap_or("<unknown>").bold(),
+                            device.interface_number()
+                        );
+
+                        if device.vendor_id() != capture.usb_vid
+                            || device.product_id() != capture.usb_pid
+                        {
+                            warn!("The capture has been recorded from a different device");
+                        }
+
+                        if let Ok(dev) = device.open_device(&hidapi) {
+                            println!("Replaying reports...");
+
+                            capture::replay_records(&records, delay, |record| {
+                                if !dry_run {
+                                    match record.report_type {
+                                        ReportType::Feature => {
+                                            dev.send_feature_report(&record.data)?
+                                        }
+
+                                        ReportType::Interrupt => {
+                                            dev.write(&record.data)?;
+                                        }
+                                    }
+                                }
+
+                                Ok(())
+                            })?;
+
+                            println!("Done");
+                        } else {
+                            error!("Could not open the device, is the device in use?");
+                        }
+                    } else {
+                        error!("Invalid device index");
+                    }
+                }
+
+                Err(_) => {
+                    error!("Could not open HIDAPI");
+                }
+            }
+        }
+
+        Subcommands::Diff {
+            first,
+            second,
+            report_ids,
+            direction,
+        } => {
+            let first = capture::load_capture(&first)?;
+            let second = capture::load_capture(&second)?;
+
+            if first.usb_vid != second.usb_vid || first.usb_pid != second.usb_pid {
+                warn!("The captures have been recorded from different devices");
+            }
+
+            let filter = Filter {
+                report_ids,
+                direction,
+            };
+
+            let first = first.filtered_records(&filter);
+            let second = second.filtered_records(&filter);
+
+            let diff = capture::diff_records(&first, &second);
+
+            if diff.is_empty() {
+                println!("The captures are identical");
+            } else {
+                println!("The following reports differ:\n");
+
+                capture::print_diff(&first, &second, &diff);
+            }
+        }
+
         Subcommands::StateDiff {
             device: device_index,
+            capture,
         } => {
             // create the one and only hidapi instance
             match hidapi::HidApi::new() {
@@ -488,6 +697,19 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                                 device.product_string().unwrap_or("<unknown>"),
                             );
 
+                            let mut recorder = capture
+                                .as_ref()
+                                .map(|path| {
+                                    Recorder::new(
+                                        path,
+                                        device.vendor_id(),
+                                        device.product_id(),
+                                        device.product_string().unwrap_or("<unknown>"),
+                                        device.serial_number().unwrap_or("<unknown>"),
+                                    )
+                                })
+                                .transpose()?;
+
                             println!("Reading data from device...");
 
                             for report_id in 0x00..=0xff {
@@ -508,6 +730,15 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                                             );
                                         }
 
+                                        if let Some(recorder) = recorder.as_mut() {
+                                            recorder.record(
+                                                Direction::In,
+                                                ReportType::Feature,
+                                                device.interface_number(),
+                                                &buf,
+                                            );
+                                        }
+
                                         state.data.insert(report_id, buf);
                                     }
 
@@ -531,6 +762,12 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                             data_store.push(state);
                             util::save_data_to_file(&path, &data_store)?;
 
+                            if let Some(recorder) = recorder {
+                                println!("Saving the capture to {}", capture.unwrap().display());
+
+                                recorder.save()?;
+                            }
+
                             println!("Done");
                         } else {
                             error!("Could not open the device, is the device in use?");
@@ -591,6 +828,7 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
         Subcommands::Write {
             device: device_index,
             data,
+            capture,
         } => {
             // create the one and only hidapi instance
             match hidapi::HidApi::new() {
@@ -609,6 +847,19 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                         );
 
                         if let Ok(dev) = device.open_device(&hidapi) {
+                            let mut recorder = capture
+                                .as_ref()
+                                .map(|path| {
+                                    Recorder::new(
+                                        path,
+                                        device.vendor_id(),
+                                        device.product_id(),
+                                        device.product_string().unwrap_or("<unknown>"),
+                                        device.serial_number().unwrap_or("<unknown>"),
+                                    )
+                                })
+                                .transpose()?;
+
                             println!("Writing data to device...");
 
                             let buf = util::parse_hex_vec(&data)?;
@@ -616,6 +867,19 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                             println!("[{}]", HexSlice::new(&buf));
 
                             dev.send_feature_report(&buf)?;
+
+                            if let Some(mut recorder) = recorder {
+                                recorder.record(
+                                    Direction::Out,
+                                    ReportType::Feature,
+                                    device.interface_number(),
+                                    &buf,
+                                );
+
+                                println!("Saving the capture to {}", capture.unwrap().display());
+
+                                recorder.save()?;
+                            }
                         } else {
                             error!("Could not open the device, is the device in use?");
                         }
@@ -675,6 +939,7 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
         Subcommands::WriteRaw {
             device: device_index,
             data,
+            capture,
         } => {
             // create the one and only hidapi instance
             match hidapi::HidApi::new() {
@@ -693,6 +958,19 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                         );
 
                         if let Ok(dev) = device.open_device(&hidapi) {
+                            let mut recorder = capture
+                                .as_ref()
+                                .map(|path| {
+                                    Recorder::new(
+                                        path,
+                                        device.vendor_id(),
+                                        device.product_id(),
+                                        device.product_string().unwrap_or("<unknown>"),
+                                        device.serial_number().unwrap_or("<unknown>"),
+                                    )
+                                })
+                                .transpose()?;
+
                             println!("Writing data to device...");
 
                             let buf = util::parse_hex_vec(&data)?;
@@ -700,6 +978,19 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                             println!("[{}]", HexSlice::new(&buf));
 
                             dev.write(&buf)?;
+
+                            if let Some(mut recorder) = recorder {
+                                recorder.record(
+                                    Direction::Out,
+                                    ReportType::Interrupt,
+                                    device.interface_number(),
+                                    &buf,
+                                );
+
+                                println!("Saving the capture to {}", capture.unwrap().display());
+
+                                recorder.save()?;
+                            }
                         } else {
                             error!("Could not open the device, is the device in use?");
                         }
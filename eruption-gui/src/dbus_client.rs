@@ -1150,6 +1150,7 @@ mod devices {
     use dbus::blocking;
 
     pub trait OrgEruptionDevice {
+        fn get_device_capabilities(&self, device: u64) -> Result<String, dbus::Error>;
         fn get_device_config(&self, device: u64, param: &str) -> Result<String, dbus::Error>;
         fn get_device_status(&self, device: u64) -> Result<String, dbus::Error>;
         fn get_managed_devices(
@@ -1213,6 +1214,11 @@ mod devices {
     impl<'a, T: blocking::BlockingSender, C: ::std::ops::Deref<Target = T>> OrgEruptionDevice
         for blocking::Proxy<'a, C>
     {
+        fn get_device_capabilities(&self, device: u64) -> Result<String, dbus::Error> {
+            self.method_call("org.eruption.Device", "GetDeviceCapabilities", (device,))
+                .and_then(|r: (String,)| Ok(r.0))
+        }
+
         fn get_device_config(&self, device: u64, param: &str) -> Result<String, dbus::Error> {
             self.method_call("org.eruption.Device", "GetDeviceConfig", (device, param))
                 .and_then(|r: (String,)| Ok(r.0))
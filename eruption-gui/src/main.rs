@@ -43,12 +43,14 @@ use std::{env, process};
 
 use util::RGBA;
 
+mod capabilities;
 mod constants;
 mod dbus_client;
 mod device;
 mod manifest;
 mod preferences;
 mod profiles;
+mod trust;
 mod ui;
 mod util;
 
@@ -254,11 +256,64 @@ pub fn switch_to_slot(index: usize) -> Result<()> {
     Ok(())
 }
 
+/// Ask the user to confirm the capabilities requested by the 3rd party scripts of the
+/// profile `file_name`. Returns `true` if the profile may be activated
+fn confirm_requested_capabilities(file_name: &Path) -> Result<bool> {
+    let profile = profiles::Profile::from(file_name)?;
+
+    let mut requested_capabilities = Vec::new();
+
+    for f in &profile.get_active_scripts() {
+        let manifest = manifest::Manifest::from(&util::match_script_file(f)?)?;
+
+        if let Some(capabilities) = &manifest.capabilities {
+            if !trust::is_system_script(&manifest.script_file) && !capabilities.is_empty() {
+                let capabilities = capabilities
+                    .iter()
+                    .map(|c| c.get_description())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+
+                requested_capabilities.push(format!("{}: {}", manifest.name, capabilities));
+            }
+        }
+    }
+
+    if requested_capabilities.is_empty() {
+        Ok(true)
+    } else {
+        let message = format!("Activate the profile {}?", profile.name);
+        let secondary = format!(
+            "The profile contains 3rd party scripts that request the following capabilities:\n\n{}",
+            requested_capabilities.join("\n")
+        );
+
+        let message_dialog = MessageDialogBuilder::new()
+            .destroy_with_parent(true)
+            .decorated(true)
+            .message_type(gtk::MessageType::Question)
+            .text(&message)
+            .secondary_text(&secondary)
+            .title("Requested capabilities")
+            .buttons(gtk::ButtonsType::YesNo)
+            .build();
+
+        let response = message_dialog.run();
+        message_dialog.hide();
+
+        Ok(response == gtk::ResponseType::Yes)
+    }
+}
+
 /// Switch to profile `file_name`
 pub fn switch_to_profile<P: AsRef<Path>>(file_name: P) -> Result<()> {
     if !events::shall_ignore_pending_ui_event() {
         let file_name = file_name.as_ref();
 
+        if !confirm_requested_capabilities(file_name)? {
+            return Ok(());
+        }
+
         // log::info!(
         //     "Switching to profile: {}",
         //     file_name.to_string_lossy()
@@ -275,6 +330,10 @@ pub fn switch_to_slot_and_profile<P: AsRef<Path>>(slot_index: usize, file_name:
     if !events::shall_ignore_pending_ui_event() {
         let file_name = file_name.as_ref();
 
+        if !confirm_requested_capabilities(file_name)? {
+            return Ok(());
+        }
+
         // log::info!(
         //     "Switching to slot: {}, using profile: {}",
         //     slot_index,
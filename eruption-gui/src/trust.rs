@@ -0,0 +1 @@
+../../eruptionctl/src/trust.rs
\ No newline at end of file
@@ -66,7 +66,14 @@ pub fn initialize_mouse_page(
         template.object("mouse_battery_level_label").unwrap();
     let battery_level_progress: gtk::ProgressBar = template.object("mouse_battery_level").unwrap();
 
+    let mouse_rate_caption_label: gtk::Label = template.object("mouse_rate_caption_label").unwrap();
+    let mouse_dpi_caption_label: gtk::Label = template.object("mouse_dpi_caption_label").unwrap();
+    let mouse_profile_caption_label: gtk::Label =
+        template.object("mouse_profile_caption_label").unwrap();
+
+    let debounce_label: gtk::Label = template.object("debounce_label").unwrap();
     let debounce_switch: gtk::Switch = template.object("debounce_switch").unwrap();
+    let angle_snapping_label: gtk::Label = template.object("angle_snapping_label").unwrap();
     let angle_snapping_switch: gtk::Switch = template.object("angle_snapping_switch").unwrap();
 
     crate::dbus_client::ping().unwrap_or_else(|_e| {
@@ -84,6 +91,49 @@ pub fn initialize_mouse_page(
     let device_brightness = util::get_device_brightness(mouse_device_handle)?;
     device_brightness_scale.set_value(device_brightness as f64);
 
+    // only show the settings that are supported by the device
+    let capabilities = util::get_device_capabilities(mouse_device_handle)?;
+
+    let set_visible = |widgets: &[&gtk::Widget], visible: bool| {
+        for widget in widgets {
+            widget.set_no_show_all(!visible);
+            widget.set_visible(visible);
+        }
+    };
+
+    set_visible(
+        &[
+            mouse_rate_caption_label.upcast_ref(),
+            mouse_rate_label.upcast_ref(),
+        ],
+        capabilities.supports("rate"),
+    );
+    set_visible(
+        &[
+            mouse_dpi_caption_label.upcast_ref(),
+            mouse_dpi_label.upcast_ref(),
+        ],
+        capabilities.supports("dpi"),
+    );
+    set_visible(
+        &[
+            mouse_profile_caption_label.upcast_ref(),
+            mouse_profile_label.upcast_ref(),
+        ],
+        capabilities.supports("profile"),
+    );
+    set_visible(
+        &[debounce_label.upcast_ref(), debounce_switch.upcast_ref()],
+        capabilities.supports("debounce"),
+    );
+    set_visible(
+        &[
+            angle_snapping_label.upcast_ref(),
+            angle_snapping_switch.upcast_ref(),
+        ],
+        capabilities.supports("angle-snapping"),
+    );
+
     device_brightness_scale.connect_value_changed(move |s| {
         // if !events::shall_ignore_pending_ui_event() {
         util::set_device_brightness(mouse_device_handle, s.value() as i64).unwrap();
@@ -17,24 +17,24 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
-use crate::{dbus_client, profiles::FindConfig};
+use crate::dbus_client;
+use crate::{manifest::Manifest, util};
 use crate::{
-    manifest,
+    manifest::{self, ParseConfig},
     profiles::{self, Profile},
 };
-use crate::{manifest::Manifest, util};
 use glib::clone;
 use glib::IsA;
 use gtk::builders::{
-    AdjustmentBuilder, BoxBuilder, ButtonBuilder, ColorChooserWidgetBuilder, EntryBuilder,
-    ExpanderBuilder, FrameBuilder, LabelBuilder, MessageDialogBuilder, ScaleBuilder,
+    AdjustmentBuilder, BoxBuilder, ButtonBuilder, ColorButtonBuilder, ColorChooserWidgetBuilder,
+    EntryBuilder, ExpanderBuilder, FrameBuilder, LabelBuilder, MessageDialogBuilder, ScaleBuilder,
     ScrolledWindowBuilder, SwitchBuilder, TreeViewColumnBuilder,
 };
 use gtk::glib;
 use gtk::{
-    prelude::*, Align, Builder, ButtonsType, CellRendererText, IconSize, Image, Justification,
-    MessageType, Orientation, PositionType, ScrolledWindow, Stack, StackSwitcher, TextBuffer,
-    TreeStore, TreeView, TreeViewColumnSizing,
+    prelude::*, Align, Builder, ButtonsType, CellRendererText, ColorButton, ComboBoxText, IconSize,
+    Image, Justification, MessageType, Orientation, PositionType, ScrolledWindow, Stack,
+    StackSwitcher, TextBuffer, TreeStore, TreeView, TreeViewColumnSizing,
 };
 use gtk::{Frame, ShadowType};
 use paste::paste;
@@ -575,12 +575,223 @@ macro_rules! declare_config_widget_switch {
     };
 }
 
+macro_rules! declare_config_widget_combo {
+    ($t:ty) => {
+        paste! {
+            fn [<build_config_widget_combo_ $t:lower>] <F: Fn($t) + 'static>(
+                name: &str,
+                description: &str,
+                values: &[$t],
+                default: $t,
+                value: $t,
+                callback: F,
+            ) -> Result<gtk::Box> {
+                let container = BoxBuilder::new()
+                    .border_width(16)
+                    .halign(Align::Fill)
+                    .valign(Align::Fill)
+                    .orientation(Orientation::Vertical)
+                    .homogeneous(false)
+                    .build();
+
+                let row1 = BoxBuilder::new()
+                    .halign(Align::Fill)
+                    .valign(Align::Fill)
+                    .spacing(8)
+                    .orientation(Orientation::Horizontal)
+                    .homogeneous(false)
+                    .build();
+
+                container.pack_start(&row1, true, true, 8);
+
+                let row2 = BoxBuilder::new()
+                    .halign(Align::Fill)
+                    .valign(Align::Fill)
+                    .spacing(8)
+                    .orientation(Orientation::Horizontal)
+                    .homogeneous(false)
+                    .build();
+
+                container.pack_start(&row2, true, true, 8);
+
+                let label = LabelBuilder::new()
+                    .expand(false)
+                    .halign(Align::Start)
+                    .justify(Justification::Left)
+                    .use_markup(true)
+                    .label(&format!("<b>{}</b>", name))
+                    .build();
+
+                row1.pack_start(&label, false, false, 8);
+
+                let label = LabelBuilder::new()
+                    .expand(false)
+                    .halign(Align::Start)
+                    .justify(Justification::Left)
+                    .label(&description)
+                    .build();
+
+                row1.pack_start(&label, false, false, 8);
+
+                // "reset to default value" button
+                let image = Image::from_icon_name(Some("reload"), IconSize::Button);
+                let reset_button = ButtonBuilder::new()
+                    .halign(Align::Start)
+                    .image(&image)
+                    .tooltip_text("Reset this parameter to its default value")
+                    .build();
+
+                row2.pack_start(&reset_button, false, false, 8);
+
+                // combo box widget, listing all allowed values
+                let combo = ComboBoxText::new();
+
+                for v in values {
+                    combo.append(Some(&v.to_string()), &v.to_string());
+                }
+
+                combo.set_active_id(Some(&value.to_string()));
+
+                row2.pack_start(&combo, false, true, 8);
+
+                combo.connect_changed(move |c| {
+                    if let Some(value) = c.active_id() {
+                        callback(value.to_string());
+                    }
+                });
+
+                reset_button.connect_clicked(clone!(@weak combo => move |_b| {
+                    combo.set_active_id(Some(&default.to_string()));
+                }));
+
+                Ok(container)
+            }
+        }
+    };
+}
+
+macro_rules! declare_config_widget_color_array {
+    ($t:ty) => {
+        paste! {
+            fn [<build_config_widget_color_array_ $t>] <F: Fn(Vec<$t>) + 'static>(
+                name: &str,
+                description: &str,
+                default: Vec<$t>,
+                value: Vec<$t>,
+                callback: F,
+            ) -> Result<gtk::Box> {
+                let container = BoxBuilder::new()
+                    .border_width(16)
+                    .halign(Align::Fill)
+                    .valign(Align::Fill)
+                    .orientation(Orientation::Vertical)
+                    .homogeneous(false)
+                    .build();
+
+                let row1 = BoxBuilder::new()
+                    .halign(Align::Fill)
+                    .valign(Align::Fill)
+                    .spacing(8)
+                    .orientation(Orientation::Horizontal)
+                    .homogeneous(false)
+                    .build();
+
+                container.pack_start(&row1, true, true, 8);
+
+                let row2 = BoxBuilder::new()
+                    .halign(Align::Fill)
+                    .valign(Align::Fill)
+                    .spacing(8)
+                    .orientation(Orientation::Horizontal)
+                    .homogeneous(false)
+                    .build();
+
+                container.pack_start(&row2, true, true, 8);
+
+                let label = LabelBuilder::new()
+                    .expand(false)
+                    .halign(Align::Start)
+                    .justify(Justification::Left)
+                    .use_markup(true)
+                    .label(&format!("<b>{}</b>", name))
+                    .build();
+
+                row1.pack_start(&label, false, false, 8);
+
+                let label = LabelBuilder::new()
+                    .expand(false)
+                    .halign(Align::Start)
+                    .justify(Justification::Left)
+                    .label(&description)
+                    .build();
+
+                row1.pack_start(&label, false, false, 8);
+
+                // "reset to default value" button
+                let image = Image::from_icon_name(Some("reload"), IconSize::Button);
+                let reset_button = ButtonBuilder::new()
+                    .halign(Align::Start)
+                    .image(&image)
+                    .tooltip_text("Reset this parameter to its default value")
+                    .build();
+
+                row2.pack_start(&reset_button, false, false, 8);
+
+                // one color button per element of the array
+                let buttons = value
+                    .iter()
+                    .map(|color| {
+                        ColorButtonBuilder::new()
+                            .rgba(&util::color_to_gdk_rgba(*color))
+                            .use_alpha(true)
+                            .build()
+                    })
+                    .collect::<Vec<ColorButton>>();
+
+                for button in buttons.iter() {
+                    row2.pack_start(button, false, false, 0);
+                }
+
+                let buttons = Rc::new(buttons);
+                let callback = Rc::new(callback);
+
+                let colors_changed = clone!(@strong buttons, @strong callback => move || {
+                    let colors = buttons
+                        .iter()
+                        .map(|b| util::gdk_rgba_to_color(&b.rgba()))
+                        .collect::<Vec<$t>>();
+
+                    callback(colors);
+                });
+
+                for button in buttons.iter() {
+                    button.connect_color_set(clone!(@strong colors_changed => move |_b| {
+                        colors_changed();
+                    }));
+                }
+
+                reset_button.connect_clicked(clone!(@strong buttons, @strong callback => move |_b| {
+                    for (button, color) in buttons.iter().zip(default.iter()) {
+                        button.set_rgba(&util::color_to_gdk_rgba(*color));
+                    }
+
+                    callback(default.clone());
+                }));
+
+                Ok(container)
+            }
+        }
+    };
+}
+
 declare_config_widget_numeric!(i64);
 declare_config_widget_numeric!(f64);
 
 declare_config_widget_input!(String);
 declare_config_widget_color!(u32);
 declare_config_widget_switch!(bool);
+declare_config_widget_combo!(String);
+declare_config_widget_color_array!(u32);
 
 fn create_config_editor(
     profile: &Profile,
@@ -837,11 +1048,196 @@ fn create_config_editor(
 
             outer.add(&widget);
         }
+
+        manifest::ConfigParam::Enum {
+            name,
+            description,
+            values,
+            default,
+        } => {
+            let value = if let Some(value) = *value {
+                match value {
+                    profiles::ConfigParam::Enum { name: _, value, .. } => value.clone(),
+
+                    _ => return Err(ProfilesError::TypeMismatch {}.into()),
+                }
+            } else {
+                profile
+                    .get_default_enum(&script.name, &name)
+                    .unwrap_or_else(|| default.clone())
+            };
+
+            let default = profile
+                .get_default_enum(&script.name, &name)
+                .unwrap_or_else(|| default.clone());
+
+            let widget = build_config_widget_combo_string(
+                &name,
+                &description,
+                &values,
+                default,
+                value,
+                clone!(@strong profile, @strong script, @strong name => move |value| {
+                    parameter_changed(&profile, &script, &name, &value);
+                }),
+            )?;
+
+            outer.add(&widget);
+        }
+
+        manifest::ConfigParam::ColorArray {
+            name,
+            description,
+            default,
+        } => {
+            let value = if let Some(value) = *value {
+                match value {
+                    profiles::ConfigParam::ColorArray { name: _, value, .. } => value.clone(),
+
+                    _ => return Err(ProfilesError::TypeMismatch {}.into()),
+                }
+            } else {
+                profile
+                    .get_default_color_array(&script.name, &name)
+                    .unwrap_or_else(|| default.clone())
+            };
+
+            let default = profile
+                .get_default_color_array(&script.name, &name)
+                .unwrap_or_else(|| default.clone());
+
+            let widget = build_config_widget_color_array_u32(
+                &name,
+                &description,
+                default,
+                value,
+                clone!(@strong profile, @strong script, @strong name => move |value| {
+                    parameter_changed(&profile, &script, &name, profiles::format_colors(&value));
+                }),
+            )?;
+
+            outer.add(&widget);
+        }
+
+        manifest::ConfigParam::KeySet {
+            name,
+            description,
+            default,
+        } => {
+            let value = if let Some(value) = *value {
+                match value {
+                    profiles::ConfigParam::KeySet { name: _, value, .. } => value.clone(),
+
+                    _ => return Err(ProfilesError::TypeMismatch {}.into()),
+                }
+            } else {
+                profile
+                    .get_default_keyset(&script.name, &name)
+                    .unwrap_or_else(|| default.clone())
+            };
+
+            let default = profile
+                .get_default_keyset(&script.name, &name)
+                .unwrap_or_else(|| default.clone());
+
+            // key sets are edited as a comma separated list of key indices
+            let widget = build_config_widget_input_string(
+                &name,
+                &description,
+                profiles::format_keys(&default),
+                profiles::format_keys(&value),
+                clone!(@strong profile, @strong script, @strong name => move |value| {
+                    // only submit the list of key indices if it is valid
+                    let valid = script
+                        .config
+                        .as_ref()
+                        .map(|config| config.parse_config_param(&name, &value).is_ok())
+                        .unwrap_or(false);
+
+                    if valid {
+                        parameter_changed(&profile, &script, &name, &value);
+                    }
+                }),
+            )?;
+
+            outer.add(&widget);
+        }
+
+        manifest::ConfigParam::Duration {
+            name,
+            description,
+            min,
+            max,
+            default,
+        } => {
+            let value = if let Some(value) = value {
+                match value {
+                    profiles::ConfigParam::Duration { name: _, value, .. } => *value,
+
+                    _ => return Err(ProfilesError::TypeMismatch {}.into()),
+                }
+            } else {
+                profile
+                    .get_default_duration(&script.name, &name)
+                    .unwrap_or(*default)
+            };
+
+            let default = profile
+                .get_default_duration(&script.name, &name)
+                .unwrap_or(*default);
+
+            // durations are specified in milliseconds
+            let widget = build_config_widget_i64(
+                &name,
+                &description,
+                default as i64,
+                min.map(|v| v as i64),
+                max.map(|v| v as i64),
+                value as i64,
+                clone!(@strong profile, @strong script, @strong name => move |value| {
+                    parameter_changed(&profile, &script, &name, &value);
+                }),
+            )?;
+
+            outer.add(&widget);
+        }
     }
 
     Ok(outer)
 }
 
+/// Modify the static key override `name` of the profile, and instruct the daemon to
+/// write the profile file. Modifying an inherited override creates a local override of
+/// the same name. The daemon picks up the modified profile automatically
+fn key_override_changed<F>(profile: &Profile, name: &str, f: F)
+where
+    F: FnOnce(&mut profiles::KeyOverride),
+{
+    let result = Profile::from(&profile.profile_file).and_then(|profile| {
+        let mut overrides = profile.overrides.clone();
+
+        match overrides.iter_mut().find(|o| o.name == name) {
+            Some(key_override) => f(key_override),
+
+            None => {
+                if let Some(mut key_override) =
+                    profile.get_overrides().into_iter().find(|o| o.name == name)
+                {
+                    f(&mut key_override);
+                    overrides.push(key_override);
+                }
+            }
+        }
+
+        let data = profile.serialize_with_overrides(&overrides)?;
+        dbus_client::write_file(&profile.profile_file, &data)
+    });
+
+    if let Err(e) = result {
+        log::error!("Could not save the profile: {}", e);
+    }
+}
+
 /// Populate the configuration tab with settings/GUI controls
 fn populate_visual_config_editor<P: AsRef<Path>>(builder: &Builder, profile: P) -> Result<()> {
     let config_window: ScrolledWindow = builder.object("config_window").unwrap();
@@ -872,7 +1268,7 @@ fn populate_visual_config_editor<P: AsRef<Path>>(builder: &Builder, profile: P)
 
     container.pack_start(&label, false, false, 8);
 
-    for f in &profile.active_scripts {
+    for f in &profile.get_active_scripts() {
         let manifest = Manifest::from(&util::match_script_file(f)?)?;
 
         let expander = ExpanderBuilder::new()
@@ -895,6 +1291,24 @@ fn populate_visual_config_editor<P: AsRef<Path>>(builder: &Builder, profile: P)
 
         container.pack_start(&expander, false, false, 8);
 
+        if let Some(capabilities) = &manifest.capabilities {
+            if !capabilities.is_empty() {
+                let capabilities = capabilities
+                    .iter()
+                    .map(|c| c.get_description())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+
+                let label = LabelBuilder::new()
+                    .label(&format!("Requested capabilities: {}", capabilities))
+                    .justify(Justification::Fill)
+                    .halign(Align::Start)
+                    .build();
+
+                expander_container.pack_start(&label, false, false, 8);
+            }
+        }
+
         if let Some(params) = &manifest.config {
             for param in params {
                 let name = match &param {
@@ -907,24 +1321,100 @@ fn populate_visual_config_editor<P: AsRef<Path>>(builder: &Builder, profile: P)
                     manifest::ConfigParam::String { name, .. } => name,
 
                     manifest::ConfigParam::Color { name, .. } => name,
-                };
 
-                let value = if let Some(ref values) = profile.config {
-                    match values.get(name) {
-                        Some(e) => e.find_config_param(name),
+                    manifest::ConfigParam::Enum { name, .. } => name,
 
-                        None => None,
-                    }
-                } else {
-                    None
+                    manifest::ConfigParam::ColorArray { name, .. } => name,
+
+                    manifest::ConfigParam::KeySet { name, .. } => name,
+
+                    manifest::ConfigParam::Duration { name, .. } => name,
                 };
 
+                // resolve the value through the chain of parent profiles
+                let value = profile.find_config_param(&manifest.name, name);
+
                 let child = create_config_editor(&profile, &manifest, param, &value)?;
                 expander_container.pack_start(&child, false, true, 0);
             }
         }
     }
 
+    // show the resolved overrides, including the ones inherited from the parent profiles
+    let overrides = profile.get_overrides();
+
+    if !overrides.is_empty() {
+        let expander = ExpanderBuilder::new()
+            .border_width(8)
+            .label("Key overrides")
+            .build();
+
+        let expander_frame = FrameBuilder::new()
+            .border_width(8)
+            .shadow_type(ShadowType::None)
+            .build();
+
+        let expander_container = BoxBuilder::new()
+            .orientation(Orientation::Vertical)
+            .homogeneous(false)
+            .build();
+
+        expander_frame.add(&expander_container);
+        expander.add(&expander_frame);
+
+        container.pack_start(&expander, false, false, 8);
+
+        for key_override in overrides.iter() {
+            let inherited = !profile
+                .overrides
+                .iter()
+                .any(|o| o.name == key_override.name);
+
+            let keys = key_override
+                .keys
+                .iter()
+                .map(|k| k.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            let description = format!(
+                "Keys: {} ({:?}, {:.0}%){}",
+                keys,
+                key_override.blend_mode,
+                key_override.opacity * 100.0,
+                if inherited { " [inherited]" } else { "" }
+            );
+
+            // gradients can only be edited in the profile file
+            if let Some(color) = key_override.color {
+                let name = key_override.name.clone();
+
+                let widget = build_config_widget_color_u32(
+                    &key_override.name,
+                    &description,
+                    color,
+                    None,
+                    None,
+                    color,
+                    clone!(@strong profile, @strong name => move |value| {
+                        key_override_changed(&profile, &name, |o| o.color = Some(value));
+                    }),
+                )?;
+
+                expander_container.pack_start(&widget, false, true, 0);
+            } else {
+                let label = LabelBuilder::new()
+                    .use_markup(true)
+                    .label(&format!("<b>{}</b> {}", key_override.name, description))
+                    .justify(Justification::Fill)
+                    .halign(Align::Start)
+                    .build();
+
+                expander_container.pack_start(&label, false, false, 8);
+            }
+        }
+    }
+
     config_window.add(&container);
     config_window.show_all();
 
@@ -1133,7 +1623,7 @@ cfg_if::cfg_if! {
 
             for p in util::enumerate_profiles()? {
                 if p.profile_file == profile.as_ref() {
-                    for f in &p.active_scripts {
+                    for f in &p.get_active_scripts() {
                         let abs_path = util::match_script_file(f)?;
 
                         let source_code = std::fs::read_to_string(&abs_path)?;
@@ -1282,7 +1772,7 @@ cfg_if::cfg_if! {
 
             for p in util::enumerate_profiles()? {
                 if p.profile_file == profile.as_ref() {
-                    for f in p.active_scripts {
+                    for f in p.get_active_scripts() {
                         let abs_path = util::match_script_file(&f)?;
 
                         let source_code = std::fs::read_to_string(&abs_path)?;
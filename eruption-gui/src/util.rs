@@ -24,6 +24,7 @@ use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
 use dbus::blocking::Connection;
 // use manifest::Manifest;
 // use std::fs;
+use crate::capabilities::DeviceCapabilities;
 use crate::manifest::ManifestError;
 use lazy_static::lazy_static;
 use parking_lot::Mutex;
@@ -284,6 +285,23 @@ pub fn get_device_status(device: u64) -> Result<HashMap<String, String>> {
     Ok(result)
 }
 
+/// Get the capabilities of a device
+pub fn get_device_capabilities(device: u64) -> Result<DeviceCapabilities> {
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/devices",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS as u64),
+    );
+
+    let (capabilities,): (String,) =
+        proxy.method_call("org.eruption.Device", "GetDeviceCapabilities", (device,))?;
+
+    let result: DeviceCapabilities = serde_json::from_str(&capabilities)?;
+
+    Ok(result)
+}
+
 /// Get a device specific config param
 pub fn get_device_config(device: u64, param: &str) -> Result<String> {
     let conn = Connection::new_system()?;
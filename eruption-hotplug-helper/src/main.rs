@@ -19,10 +19,6 @@
 
 use clap::{IntoApp, Parser};
 use clap_complete::Shell;
-use eruption_rs::{
-    connection::{Connection, ConnectionType},
-    hardware::HotplugInfo,
-};
 use i18n_embed::{
     fluent::{fluent_language_loader, FluentLanguageLoader},
     DesktopLanguageRequester,
@@ -284,52 +280,11 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                         // so restart the eruption daemon to be on the safe side
                         restart_eruption_daemon()?;
                     } else {
-                        // a hotplug event has been received while the system is up and running
-
-                        // sleep until udev has settled
-                        log::info!("Waiting for the devices to settle...");
-
-                        let status = Command::new("/bin/udevadm")
-                            .arg("settle")
-                            .stdout(Stdio::null())
-                            .status();
-
-                        if let Err(e) = status {
-                            // udev-settle has failed, sleep a while and let the devices settle
-                            log::error!("udevadm settle has failed: {}", e);
-
-                            thread::sleep(Duration::from_millis(3500));
-                        } else {
-                            log::info!("Done, all devices have settled");
-                        }
-
-                        log::info!("Connecting to the Eruption daemon...");
-
-                        let connection = Connection::new(ConnectionType::Local)?;
-                        match connection.connect() {
-                            Ok(()) => {
-                                log::debug!("Successfully connected to the Eruption daemon");
-                                let _status = connection.get_server_status()?;
-
-                                log::info!(
-                                    "Notifying the Eruption daemon about the hotplug event..."
-                                );
-
-                                // TODO: implement this
-                                let hotplug_info = HotplugInfo {
-                                    usb_vid: 0,
-                                    usb_pid: 0,
-                                };
-                                connection.notify_device_hotplug(&hotplug_info)?;
-
-                                connection.disconnect()?;
-                                log::info!("Disconnected from the Eruption daemon");
-                            }
-
-                            Err(e) => {
-                                log::error!("Failed to connect to the Eruption daemon: {}", e);
-                            }
-                        }
+                        // a hotplug event has been received while the system is up and running,
+                        // the Eruption daemon binds and releases the devices on its own
+                        log::info!(
+                            "Nothing to do, the Eruption daemon handles device hotplug itself"
+                        );
                     }
 
                     let _ = lock_file.release().map_err(|e| {
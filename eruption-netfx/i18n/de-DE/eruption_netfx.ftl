@@ -29,6 +29,9 @@ processing-image-files = Verarbeite Bilddateien...
 image-error = Fehler beim Verarbeiten der Datei: {$message}
 entering-loop = Betrete Hauptschleife...
 invalid-model = Ungültiges Modell
+listening-for-universes = Empfange DMX Universen auf: {$address}
+dmx-error = Fehler beim Verarbeiten des Pakets: {$message}
+multicast-error = Konnte den Multicast Gruppen nicht beitreten: {$message}
 
 license-header = Eruption ist freie Software. Sie können es unter den Bedingungen der
  GNU General Public License, wie von der Free Software Foundation
@@ -29,6 +29,9 @@ processing-image-files = Pre-processing image files...
 image-error = Error processing file: {$message}
 entering-loop = Entering loop...
 invalid-model = Invalid model
+listening-for-universes = Listening for DMX universes on: {$address}
+dmx-error = Error processing packet: {$message}
+multicast-error = Could not join the multicast groups: {$message}
 
 license-header = Eruption is free software: you can redistribute it and/or modify
  it under the terms of the GNU General Public License as published by
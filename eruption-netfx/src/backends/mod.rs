@@ -23,6 +23,8 @@ use dyn_clonable::{clonable, dyn_clone};
 
 #[cfg(feature = "backend-gnome")]
 pub mod gnome;
+#[cfg(feature = "backend-portal")]
+pub mod portal;
 #[cfg(feature = "backend-wayland")]
 pub mod wayland;
 #[cfg(feature = "backend-x11")]
@@ -32,6 +34,8 @@ pub mod x11;
 pub use self::x11::*;
 #[cfg(feature = "backend-gnome")]
 pub use gnome::*;
+#[cfg(feature = "backend-portal")]
+pub use portal::*;
 #[cfg(feature = "backend-wayland")]
 pub use wayland::*;
 
@@ -85,12 +89,17 @@ pub fn register_backends() -> Result<()> {
         println!("Registering backend plugins:");
     }
 
-    #[cfg(feature = "backend-x11")]
-    register_backend(X11Backend::new()?);
-
+    // backends are selected in order of registration; the Wayland backends only initialize
+    // successfully in Wayland sessions, where the X11 backend would only see XWayland clients
     #[cfg(feature = "backend-wayland")]
     register_backend(WaylandBackend::new()?);
 
+    #[cfg(feature = "backend-portal")]
+    register_backend(PortalBackend::new()?);
+
+    #[cfg(feature = "backend-x11")]
+    register_backend(X11Backend::new()?);
+
     #[cfg(feature = "backend-gnome")]
     register_backend(GnomeBackend::new()?);
 
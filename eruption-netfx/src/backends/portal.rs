@@ -0,0 +1,535 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use image::RgbaImage;
+use parking_lot::Mutex;
+
+use crate::hwdevices::{self, Keyboard};
+
+use super::{Backend, BackendData};
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// Captures the screen using the ScreenCast interface of xdg-desktop-portal,
+/// the frames are then received from a PipeWire stream
+#[derive(Clone)]
+pub struct PortalBackend {
+    pub device: Option<Box<dyn Keyboard + Sync + Send>>,
+
+    /// The most recently received frame
+    frame: Arc<Mutex<Option<RgbaImage>>>,
+
+    /// Set as soon as the capture thread has been spawned
+    started: Arc<AtomicBool>,
+
+    /// Set if the screen cast session has been terminated
+    terminated: Arc<AtomicBool>,
+
+    pub failed: bool,
+}
+
+impl PortalBackend {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            device: None,
+            frame: Arc::new(Mutex::new(None)),
+            started: Arc::new(AtomicBool::new(false)),
+            terminated: Arc::new(AtomicBool::new(false)),
+            failed: true,
+        })
+    }
+
+    fn start_capture_thread(&self) -> Result<()> {
+        let frame = self.frame.clone();
+        let terminated = self.terminated.clone();
+
+        thread::Builder::new()
+            .name("screencast".to_owned())
+            .spawn(move || {
+                if let Err(e) = screencast::run(frame) {
+                    eprintln!("Screen cast session failed: {}", e);
+                }
+
+                terminated.store(true, Ordering::SeqCst);
+            })?;
+
+        self.started.store(true, Ordering::SeqCst);
+
+        Ok(())
+    }
+}
+
+impl Backend for PortalBackend {
+    fn initialize(&mut self) -> Result<()> {
+        self.failed = true;
+
+        let opts = crate::OPTIONS.read().as_ref().unwrap().clone();
+        self.device = Some(hwdevices::get_keyboard_device(&opts.model)?);
+
+        // the portal is available in X11 sessions as well, but the X11 backend works
+        // without prompting the user, so we only want to be selected on Wayland
+        if env::var("WAYLAND_DISPLAY").is_err() {
+            return Err(eyre::eyre!("Not running in a Wayland session"));
+        }
+
+        let version = screencast::portal_version()?;
+        if opts.verbose > 0 {
+            println!(
+                "Found xdg-desktop-portal ScreenCast interface version {}",
+                version
+            );
+        }
+
+        // if we made it up to here, the initialization succeeded
+        self.failed = false;
+
+        Ok(())
+    }
+
+    fn get_id(&self) -> String {
+        "portal".to_string()
+    }
+
+    fn get_name(&self) -> String {
+        "xdg-desktop-portal".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Capture the screen's content using xdg-desktop-portal and PipeWire".to_string()
+    }
+
+    fn is_failed(&self) -> bool {
+        self.failed
+    }
+
+    fn set_failed(&mut self, failed: bool) {
+        self.failed = failed;
+    }
+
+    fn poll(&mut self) -> Result<BackendData> {
+        let device = self.device.as_ref().expect("Device is not initialized");
+
+        // the session is started lazily, since the portal may ask the user
+        // which monitor should be shared
+        if !self.started.load(Ordering::SeqCst) {
+            self.start_capture_thread()?;
+        }
+
+        if self.terminated.load(Ordering::SeqCst) {
+            return Err(eyre::eyre!("The screen cast session has been terminated"));
+        }
+
+        match self.frame.lock().take() {
+            Some(buffer) => super::utils::process_screenshot(buffer, device),
+
+            // no new frame has been received yet
+            None => Ok(String::new()),
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+mod screencast {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use dbus::arg::{self, PropMap, RefArg, Variant};
+    use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+    use dbus::blocking::LocalConnection;
+    use dbus::message::MatchRule;
+    use eyre::eyre;
+    use image::RgbaImage;
+    use libspa_sys as spa_sys;
+    use parking_lot::Mutex;
+    use pipewire as pw;
+    use pw::spa;
+    use spa::pod::deserialize::PodDeserializer;
+    use spa::pod::serialize::PodSerializer;
+    use spa::pod::{ChoiceValue, Object, Property, PropertyFlags, Value};
+    use spa::utils::{Choice, ChoiceEnum, ChoiceFlags, Id, Rectangle};
+
+    use crate::constants;
+
+    const PORTAL_BUS_NAME: &str = "org.freedesktop.portal.Desktop";
+    const PORTAL_OBJECT_PATH: &str = "/org/freedesktop/portal/desktop";
+    const SCREENCAST_INTERFACE: &str = "org.freedesktop.portal.ScreenCast";
+
+    /// Source type: Share a monitor
+    const SOURCE_TYPE_MONITOR: u32 = 1;
+
+    /// Max. time to wait for the user to confirm the screen cast dialog
+    const RESPONSE_TIMEOUT_SECS: u64 = 300;
+
+    /// The video formats that we are able to convert, in order of preference
+    const SUPPORTED_FORMATS: [u32; 4] = [
+        spa_sys::SPA_VIDEO_FORMAT_BGRx,
+        spa_sys::SPA_VIDEO_FORMAT_RGBx,
+        spa_sys::SPA_VIDEO_FORMAT_BGRA,
+        spa_sys::SPA_VIDEO_FORMAT_RGBA,
+    ];
+
+    /// The video format that has been negotiated with the PipeWire node
+    #[derive(Debug, Default, Copy, Clone)]
+    struct VideoFormat {
+        format: u32,
+        width: u32,
+        height: u32,
+    }
+
+    /// Query the version of the ScreenCast interface, this fails if
+    /// the portal or the interface is not available
+    pub fn portal_version() -> super::Result<u32> {
+        let conn = LocalConnection::new_session()?;
+        let proxy = conn.with_proxy(
+            PORTAL_BUS_NAME,
+            PORTAL_OBJECT_PATH,
+            Duration::from_millis(constants::DBUS_TIMEOUT_MILLIS as u64),
+        );
+
+        let version: u32 = proxy.get(SCREENCAST_INTERFACE, "version")?;
+
+        Ok(version)
+    }
+
+    /// Establish a screen cast session and receive frames into `frame`,
+    /// until the session is terminated
+    pub fn run(frame: Arc<Mutex<Option<RgbaImage>>>) -> super::Result<()> {
+        // the session is closed as soon as the connection is dropped,
+        // so keep it alive for as long as we receive frames
+        let conn = LocalConnection::new_session()?;
+
+        let node_id = start_session(&conn)?;
+
+        receive_frames(node_id, frame)
+    }
+
+    fn variant<T: RefArg + 'static>(value: T) -> Variant<Box<dyn RefArg>> {
+        Variant(Box::new(value))
+    }
+
+    /// Issue a call to a method of the portal, that returns a request object, and
+    /// wait for the `Response` signal of that request
+    fn call_with_response<A: arg::AppendAll>(
+        conn: &LocalConnection,
+        method: &str,
+        token: &str,
+        args: A,
+    ) -> super::Result<PropMap> {
+        // the path of the request object is well known, so subscribe to the
+        // signal before issuing the call to prevent a race
+        let sender = conn.unique_name().trim_start_matches(':').replace('.', "_");
+        let request_path = format!("{}/request/{}/{}", PORTAL_OBJECT_PATH, sender, token);
+
+        let response: Rc<RefCell<Option<(u32, PropMap)>>> = Rc::new(RefCell::new(None));
+
+        let rule = MatchRule::new_signal("org.freedesktop.portal.Request", "Response")
+            .with_path(request_path);
+
+        let token = conn.add_match(rule, {
+            let response = response.clone();
+
+            move |(code, results): (u32, PropMap), _, _| {
+                response.borrow_mut().replace((code, results));
+                false
+            }
+        })?;
+
+        let proxy = conn.with_proxy(
+            PORTAL_BUS_NAME,
+            PORTAL_OBJECT_PATH,
+            Duration::from_millis(constants::DBUS_TIMEOUT_MILLIS as u64),
+        );
+
+        let (_request,): (dbus::Path,) = proxy.method_call(SCREENCAST_INTERFACE, method, args)?;
+
+        let mut elapsed = 0;
+        while response.borrow().is_none() && elapsed < RESPONSE_TIMEOUT_SECS {
+            conn.process(Duration::from_secs(1))?;
+            elapsed += 1;
+        }
+
+        let _ = conn.remove_match(token);
+
+        let result = response.borrow_mut().take();
+        match result {
+            Some((0, results)) => Ok(results),
+
+            Some((code, _results)) => Err(eyre!(
+                "The portal denied the request '{}', response code: {}",
+                method,
+                code
+            )),
+
+            None => Err(eyre!("Timeout while waiting for the portal to respond")),
+        }
+    }
+
+    /// Negotiate a screen cast session with the portal, returns the id of the PipeWire node
+    fn start_session(conn: &LocalConnection) -> super::Result<u32> {
+        let token = format!("eruption_netfx_{}", std::process::id());
+
+        let mut options: PropMap = HashMap::new();
+        options.insert("handle_token".to_string(), variant(format!("{}_1", token)));
+        options.insert("session_handle_token".to_string(), variant(token.clone()));
+
+        let results =
+            call_with_response(conn, "CreateSession", &format!("{}_1", token), (options,))?;
+
+        let session_handle = arg::prop_cast::<String>(&results, "session_handle")
+            .cloned()
+            .ok_or_else(|| eyre!("Invalid response received from the portal"))?;
+        let session_handle = dbus::Path::new(session_handle).map_err(|e| eyre!(e))?;
+
+        let mut options: PropMap = HashMap::new();
+        options.insert("handle_token".to_string(), variant(format!("{}_2", token)));
+        options.insert("types".to_string(), variant(SOURCE_TYPE_MONITOR));
+        options.insert("multiple".to_string(), variant(false));
+
+        call_with_response(
+            conn,
+            "SelectSources",
+            &format!("{}_2", token),
+            (session_handle.clone(), options),
+        )?;
+
+        let mut options: PropMap = HashMap::new();
+        options.insert("handle_token".to_string(), variant(format!("{}_3", token)));
+
+        let results = call_with_response(
+            conn,
+            "Start",
+            &format!("{}_3", token),
+            (session_handle, "", options),
+        )?;
+
+        // streams: a(ua{sv}), we use the node id of the first stream
+        let node_id = results
+            .get("streams")
+            .and_then(|streams| streams.0.as_iter()?.next()?.as_iter()?.next()?.as_u64())
+            .ok_or_else(|| eyre!("The portal did not provide a stream"))?;
+
+        Ok(node_id as u32)
+    }
+
+    /// Connect to the PipeWire node `node_id` and receive frames, until the stream ends.
+    /// We are not sandboxed, so we may connect to the PipeWire daemon directly
+    fn receive_frames(node_id: u32, frame: Arc<Mutex<Option<RgbaImage>>>) -> super::Result<()> {
+        pw::init();
+
+        let mainloop = pw::MainLoop::new()?;
+
+        let stream = pw::stream::Stream::<VideoFormat>::with_user_data(
+            &mainloop,
+            "eruption-netfx",
+            pw::properties! {
+                *pw::keys::MEDIA_TYPE => "Video",
+                *pw::keys::MEDIA_CATEGORY => "Capture",
+                *pw::keys::MEDIA_ROLE => "Screen",
+            },
+            VideoFormat::default(),
+        )
+        .state_changed({
+            let mainloop = mainloop.clone();
+
+            move |_old, new| {
+                if let pw::stream::StreamState::Error(_) | pw::stream::StreamState::Unconnected =
+                    new
+                {
+                    mainloop.quit();
+                }
+            }
+        })
+        .param_changed(|id, video_format, param| {
+            if id != spa_sys::SPA_PARAM_Format || param.is_null() {
+                return;
+            }
+
+            match parse_video_format(param) {
+                Some(format) => *video_format = format,
+
+                None => {
+                    log::error!("Could not parse the negotiated video format");
+
+                    *video_format = VideoFormat::default();
+                }
+            }
+        })
+        .process(move |stream, video_format| {
+            if let Some(mut buffer) = stream.dequeue_buffer() {
+                let datas = buffer.datas_mut();
+                if datas.is_empty() {
+                    return;
+                }
+
+                let data = &mut datas[0];
+
+                // use the stride of the buffer, since rows may be padded
+                let stride = match data.chunk().stride() {
+                    stride if stride > 0 => stride as usize,
+                    _ => video_format.width as usize * 4,
+                };
+
+                if let Some(slice) = data.data() {
+                    if let Some(image) = convert_frame(slice, video_format, stride) {
+                        frame.lock().replace(image);
+                    }
+                }
+            }
+        })
+        .create()?;
+
+        let format = enum_format_pod()?;
+        let mut params = [format.as_ptr() as *const spa_sys::spa_pod];
+
+        stream.connect(
+            spa::Direction::Input,
+            Some(node_id),
+            pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+            &mut params,
+        )?;
+
+        mainloop.run();
+
+        Ok(())
+    }
+
+    /// Build the `EnumFormat` parameter, that restricts the stream to the formats we support
+    fn enum_format_pod() -> super::Result<Vec<u8>> {
+        let formats = SUPPORTED_FORMATS.iter().map(|f| Id(*f)).collect();
+
+        let object = Value::Object(Object {
+            type_: spa_sys::SPA_TYPE_OBJECT_Format,
+            id: spa_sys::SPA_PARAM_EnumFormat,
+            properties: vec![
+                Property {
+                    key: spa_sys::SPA_FORMAT_mediaType,
+                    flags: PropertyFlags::empty(),
+                    value: Value::Id(Id(spa_sys::SPA_MEDIA_TYPE_video)),
+                },
+                Property {
+                    key: spa_sys::SPA_FORMAT_mediaSubtype,
+                    flags: PropertyFlags::empty(),
+                    value: Value::Id(Id(spa_sys::SPA_MEDIA_SUBTYPE_raw)),
+                },
+                Property {
+                    key: spa_sys::SPA_FORMAT_VIDEO_format,
+                    flags: PropertyFlags::empty(),
+                    value: Value::Choice(ChoiceValue::Id(Choice(
+                        ChoiceFlags::empty(),
+                        ChoiceEnum::Enum {
+                            default: Id(SUPPORTED_FORMATS[0]),
+                            alternatives: formats,
+                        },
+                    ))),
+                },
+            ],
+        });
+
+        let (cursor, _len) = PodSerializer::serialize(std::io::Cursor::new(Vec::new()), &object)
+            .map_err(|e| eyre!("Could not serialize the format parameter: {:?}", e))?;
+
+        Ok(cursor.into_inner())
+    }
+
+    /// Extract the format and size of the video frames from a `Format` parameter
+    fn parse_video_format(param: *const spa_sys::spa_pod) -> Option<VideoFormat> {
+        // the pod header is followed by `size` bytes of body
+        let bytes = unsafe {
+            let len = (*param).size as usize + std::mem::size_of::<spa_sys::spa_pod>();
+
+            std::slice::from_raw_parts(param as *const u8, len)
+        };
+
+        let object = match PodDeserializer::deserialize_any_from(bytes) {
+            Ok((_, Value::Object(object))) => object,
+            _ => return None,
+        };
+
+        let mut result = VideoFormat::default();
+
+        for property in object.properties {
+            match (property.key, property.value) {
+                (spa_sys::SPA_FORMAT_VIDEO_format, Value::Id(Id(format))) => result.format = format,
+
+                (spa_sys::SPA_FORMAT_VIDEO_size, Value::Rectangle(Rectangle { width, height })) => {
+                    result.width = width;
+                    result.height = height;
+                }
+
+                _ => (),
+            }
+        }
+
+        if SUPPORTED_FORMATS.contains(&result.format) && result.width > 0 && result.height > 0 {
+            Some(result)
+        } else {
+            None
+        }
+    }
+
+    /// Convert a frame in one of the `SUPPORTED_FORMATS` to RGBA
+    fn convert_frame(data: &[u8], format: &VideoFormat, stride: usize) -> Option<RgbaImage> {
+        let width = format.width as usize;
+        let height = format.height as usize;
+
+        if width == 0 || height == 0 || stride < width * 4 || data.len() < stride * height {
+            return None;
+        }
+
+        // formats are named by their byte order in memory
+        let (swap_red_blue, has_alpha) = match format.format {
+            spa_sys::SPA_VIDEO_FORMAT_BGRx => (true, false),
+            spa_sys::SPA_VIDEO_FORMAT_RGBx => (false, false),
+            spa_sys::SPA_VIDEO_FORMAT_BGRA => (true, true),
+            spa_sys::SPA_VIDEO_FORMAT_RGBA => (false, true),
+
+            _ => return None,
+        };
+
+        Some(RgbaImage::from_fn(width as u32, height as u32, |x, y| {
+            let offset = y as usize * stride + x as usize * 4;
+            let pixel = &data[offset..offset + 4];
+
+            let (r, b) = if swap_red_blue {
+                (pixel[2], pixel[0])
+            } else {
+                (pixel[0], pixel[2])
+            };
+
+            let a = if has_alpha { pixel[3] } else { 0xff };
+
+            image::Rgba([r, pixel[1], b, a])
+        }))
+    }
+}
@@ -17,14 +17,11 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
-#[cfg(feature = "backend-x11")]
-use crate::backends::x11::xwrap::Image;
-
 use crate::hwdevices::KeyboardDevice;
 use image::{imageops::FilterType, GenericImageView};
 
 #[allow(unused_imports)]
-use image::DynamicImage;
+use image::{DynamicImage, RgbaImage};
 
 use std::path::Path;
 
@@ -104,12 +101,11 @@ pub fn process_image_file<P: AsRef<Path>>(filename: P, device: &KeyboardDevice)
     Ok(result)
 }
 
-/// Converts an image buffer to a Network FX command stream
-#[cfg(feature = "backend-x11")]
-pub fn process_screenshot(image: &Image, device: &KeyboardDevice) -> Result<String> {
+/// Converts a screenshot, as captured by one of the backends, to a Network FX command stream
+#[allow(dead_code)]
+pub fn process_screenshot(buffer: RgbaImage, device: &KeyboardDevice) -> Result<String> {
     let mut result = String::new();
 
-    let buffer = image.into_image_buffer().unwrap();
     let img = DynamicImage::ImageRgba8(buffer);
     let img = img.resize_exact(
         device.get_num_cols() as u32,
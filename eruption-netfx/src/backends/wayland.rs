@@ -17,23 +17,49 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use super::{Backend, BackendData};
+use crate::hwdevices::{self, Keyboard};
 
 type Result<T> = std::result::Result<T, eyre::Error>;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct WaylandBackend {
+    pub device: Option<Box<dyn Keyboard + Sync + Send>>,
+
+    /// The connection to the compositor, established on the first poll
+    session: Rc<RefCell<Option<wayshot::Session>>>,
+
     pub failed: bool,
 }
 
 impl WaylandBackend {
     pub fn new() -> Result<Self> {
-        Ok(Self { failed: true })
+        Ok(Self {
+            device: None,
+            session: Rc::new(RefCell::new(None)),
+            failed: true,
+        })
     }
 }
 
 impl Backend for WaylandBackend {
     fn initialize(&mut self) -> Result<()> {
+        self.failed = true;
+
+        let opts = crate::OPTIONS.read().as_ref().unwrap().clone();
+        self.device = Some(hwdevices::get_keyboard_device(&opts.model)?);
+
+        // only wlroots based compositors implement the screencopy protocol,
+        // other compositors are handled by the xdg-desktop-portal backend
+        if !wayshot::is_supported() {
+            return Err(eyre::eyre!(
+                "The compositor does not support the wlr-screencopy protocol"
+            ));
+        }
+
         // if we made it up to here, the initialization succeeded
         self.failed = false;
 
@@ -49,7 +75,7 @@ impl Backend for WaylandBackend {
     }
 
     fn get_description(&self) -> String {
-        "Capture the screen's content from a Wayland compositor".to_string()
+        "Capture the screen's content from a wlroots based Wayland compositor".to_string()
     }
 
     fn is_failed(&self) -> bool {
@@ -61,9 +87,25 @@ impl Backend for WaylandBackend {
     }
 
     fn poll(&mut self) -> Result<BackendData> {
-        let _result = wayshot::screenshot()?;
+        let device = self.device.as_ref().expect("Device is not initialized");
+
+        let mut session = self.session.borrow_mut();
+
+        if session.is_none() {
+            session.replace(wayshot::Session::connect()?);
+        }
+
+        let result = session.as_mut().unwrap().screenshot();
+
+        // reconnect on the next poll, the connection may be in an unusable state
+        if result.is_err() {
+            session.take();
+        }
+
+        let buffer = result?;
+        let commands = super::utils::process_screenshot(buffer, device)?;
 
-        Ok("".to_string())
+        Ok(commands)
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -88,12 +130,12 @@ mod wayshot {
         cell::RefCell,
         fs::File,
         os::unix::prelude::FromRawFd,
-        process::exit,
         rc::Rc,
         sync::atomic::{AtomicBool, Ordering},
     };
 
-    use image::{codecs::png::PngEncoder, ColorType::Rgba8, ImageEncoder};
+    use eyre::eyre;
+    use image::RgbaImage;
     use memmap2::MmapMut;
 
     use smithay_client_toolkit::{
@@ -101,7 +143,7 @@ mod wayshot {
         reexports::{
             client::{
                 protocol::{wl_output::WlOutput, wl_shm, wl_shm::Format},
-                Display, GlobalManager, Main,
+                Display, EventQueue, GlobalManager, Main,
             },
             protocols::wlr::unstable::screencopy::v1::client::{
                 zwlr_screencopy_frame_v1, zwlr_screencopy_frame_v1::ZwlrScreencopyFrameV1,
@@ -181,189 +223,250 @@ mod wayshot {
         Finished,
     }
 
-    pub fn screenshot() -> super::Result<()> {
-        let display = Display::connect_to_env()?;
+    /// Returns `true` if we are connected to a Wayland compositor, that
+    /// implements the wlr-screencopy protocol
+    pub fn is_supported() -> bool {
+        let display = match Display::connect_to_env() {
+            Ok(display) => display,
+            Err(_) => return false,
+        };
+
         let mut event_queue = display.create_event_queue();
         let attached_display = (*display).clone().attach(event_queue.token());
 
         let globals = GlobalManager::new(&attached_display);
-        event_queue.sync_roundtrip(&mut (), |_, _, _| unreachable!())?;
-
-        let valid_outputs = output::get_valid_outputs(display.clone());
-        let (output, _): (WlOutput, OutputInfo) = valid_outputs.first().unwrap().clone();
-
-        let frame_formats: Rc<RefCell<Vec<FrameFormat>>> = Rc::new(RefCell::new(Vec::new()));
-        let frame_state: Rc<RefCell<Option<FrameState>>> = Rc::new(RefCell::new(None));
-        let frame_buffer_done = Rc::new(AtomicBool::new(false));
-
-        let screencopy_manager = globals.instantiate_exact::<ZwlrScreencopyManagerV1>(3)?;
-
-        let frame: Main<ZwlrScreencopyFrameV1>;
-        let cursor_overlay = 0;
-
-        // if args.is_present("output") {
-        //     let mut is_present = false;
-        //     let valid_outputs = output::get_valid_outputs(display);
-
-        //     for device in valid_outputs {
-        //         let (output_device, info) = device;
-        //         if info.name == args.value_of("output").unwrap().trim() {
-        //             is_present = true;
-        //             output = output_device.clone();
-        //         }
-        //     }
-        //     if !is_present {
-        //         log::error!(
-        //             "\"{}\" is not a valid output.",
-        //             args.value_of("output").unwrap().trim()
-        //         );
-        //         exit(1);
-        //     }
-        // }
-
-        frame = screencopy_manager.capture_output(cursor_overlay, &output);
-
-        frame.quick_assign({
-            let frame_formats = frame_formats.clone();
-            let frame_state = frame_state.clone();
-            let frame_buffer_done = frame_buffer_done.clone();
-            move |_frame, event, _| {
-                match event {
-                    zwlr_screencopy_frame_v1::Event::Buffer {
-                        format,
-                        width,
-                        height,
-                        stride,
-                    } => {
-                        log::debug!("Received buffer event");
-                        frame_formats.borrow_mut().push(FrameFormat {
-                            format,
-                            width,
-                            height,
-                            stride,
-                        });
-                    }
-                    zwlr_screencopy_frame_v1::Event::Flags { .. } => {
-                        log::debug!("Received flags event");
-                    }
-                    zwlr_screencopy_frame_v1::Event::Ready { .. } => {
-                        log::debug!("Received ready event");
-                        frame_state.borrow_mut().replace(FrameState::Finished);
-                    }
-                    zwlr_screencopy_frame_v1::Event::Failed => {
-                        log::debug!("Received failed event");
-                        frame_state.borrow_mut().replace(FrameState::Failed);
-                    }
-                    zwlr_screencopy_frame_v1::Event::Damage { .. } => {
-                        log::debug!("Received Damaga event");
-                    }
-                    zwlr_screencopy_frame_v1::Event::LinuxDmabuf { .. } => {
-                        log::debug!("Received LinuxDmaBuf event");
-                    }
-                    zwlr_screencopy_frame_v1::Event::BufferDone => {
-                        log::debug!("Received bufferdone event");
-                        frame_buffer_done.store(true, Ordering::SeqCst);
-                    }
-                    _ => unreachable!(),
-                };
-            }
-        });
-
-        while !frame_buffer_done.load(Ordering::SeqCst) {
-            event_queue.dispatch(&mut (), |_, _, _| unreachable!())?;
+        if event_queue
+            .sync_roundtrip(&mut (), |_, _, _| unreachable!())
+            .is_err()
+        {
+            return false;
         }
 
-        log::debug!(
-            "Received compositor frame buffer formats: {:#?}",
-            frame_formats
-        );
-
-        let frame_format = frame_formats
-            .borrow()
+        globals
+            .list()
             .iter()
-            .filter(|f| {
-                matches!(
-                    f.format,
-                    wl_shm::Format::Argb8888 | wl_shm::Format::Xrgb8888 | wl_shm::Format::Xbgr8888
-                )
+            .any(|(_id, interface, _version)| interface == "zwlr_screencopy_manager_v1")
+    }
+
+    /// A connection to the compositor, that is kept open across frames
+    pub struct Session {
+        _display: Display,
+        event_queue: EventQueue,
+        output: WlOutput,
+        screencopy_manager: Main<ZwlrScreencopyManagerV1>,
+        shm: Main<wl_shm::WlShm>,
+    }
+
+    impl Session {
+        /// Connect to the compositor and bind the globals required for capturing the first output
+        pub fn connect() -> super::Result<Self> {
+            let display = Display::connect_to_env()?;
+            let mut event_queue = display.create_event_queue();
+            let attached_display = (*display).clone().attach(event_queue.token());
+
+            let globals = GlobalManager::new(&attached_display);
+            event_queue.sync_roundtrip(&mut (), |_, _, _| unreachable!())?;
+
+            let valid_outputs = output::get_valid_outputs(display.clone());
+            let (output, _): (WlOutput, OutputInfo) = valid_outputs
+                .first()
+                .ok_or_else(|| eyre!("No outputs found"))?
+                .clone();
+
+            let screencopy_manager = globals.instantiate_exact::<ZwlrScreencopyManagerV1>(3)?;
+            let shm = globals.instantiate_exact::<wl_shm::WlShm>(1)?;
+
+            Ok(Self {
+                _display: display,
+                event_queue,
+                output,
+                screencopy_manager,
+                shm,
             })
-            .nth(0)
-            .copied();
+        }
 
-        log::debug!("Selected frame buffer format: {:#?}", frame_format);
+        /// Capture the contents of the first output
+        pub fn screenshot(&mut self) -> super::Result<RgbaImage> {
+            let event_queue = &mut self.event_queue;
+
+            let frame_formats: Rc<RefCell<Vec<FrameFormat>>> = Rc::new(RefCell::new(Vec::new()));
+            let frame_state: Rc<RefCell<Option<FrameState>>> = Rc::new(RefCell::new(None));
+            let frame_buffer_done = Rc::new(AtomicBool::new(false));
+
+            let frame: Main<ZwlrScreencopyFrameV1>;
+            let cursor_overlay = 0;
+
+            // if args.is_present("output") {
+            //     let mut is_present = false;
+            //     let valid_outputs = output::get_valid_outputs(display);
+
+            //     for device in valid_outputs {
+            //         let (output_device, info) = device;
+            //         if info.name == args.value_of("output").unwrap().trim() {
+            //             is_present = true;
+            //             output = output_device.clone();
+            //         }
+            //     }
+            //     if !is_present {
+            //         log::error!(
+            //             "\"{}\" is not a valid output.",
+            //             args.value_of("output").unwrap().trim()
+            //         );
+            //         exit(1);
+            //     }
+            // }
+
+            frame = self
+                .screencopy_manager
+                .capture_output(cursor_overlay, &self.output);
+
+            frame.quick_assign({
+                let frame_formats = frame_formats.clone();
+                let frame_state = frame_state.clone();
+                let frame_buffer_done = frame_buffer_done.clone();
+                move |_frame, event, _| {
+                    match event {
+                        zwlr_screencopy_frame_v1::Event::Buffer {
+                            format,
+                            width,
+                            height,
+                            stride,
+                        } => {
+                            log::debug!("Received buffer event");
+                            frame_formats.borrow_mut().push(FrameFormat {
+                                format,
+                                width,
+                                height,
+                                stride,
+                            });
+                        }
+                        zwlr_screencopy_frame_v1::Event::Flags { .. } => {
+                            log::debug!("Received flags event");
+                        }
+                        zwlr_screencopy_frame_v1::Event::Ready { .. } => {
+                            log::debug!("Received ready event");
+                            frame_state.borrow_mut().replace(FrameState::Finished);
+                        }
+                        zwlr_screencopy_frame_v1::Event::Failed => {
+                            log::debug!("Received failed event");
+                            frame_state.borrow_mut().replace(FrameState::Failed);
+                        }
+                        zwlr_screencopy_frame_v1::Event::Damage { .. } => {
+                            log::debug!("Received Damaga event");
+                        }
+                        zwlr_screencopy_frame_v1::Event::LinuxDmabuf { .. } => {
+                            log::debug!("Received LinuxDmaBuf event");
+                        }
+                        zwlr_screencopy_frame_v1::Event::BufferDone => {
+                            log::debug!("Received bufferdone event");
+                            frame_buffer_done.store(true, Ordering::SeqCst);
+                        }
+                        _ => unreachable!(),
+                    };
+                }
+            });
 
-        let frame_format = match frame_format {
-            Some(format) => format,
-            None => {
-                log::error!("No suitable frame format found");
-                exit(1);
+            while !frame_buffer_done.load(Ordering::SeqCst) {
+                event_queue.dispatch(&mut (), |_, _, _| unreachable!())?;
             }
-        };
 
-        let frame_bytes = frame_format.stride * frame_format.height;
+            log::debug!(
+                "Received compositor frame buffer formats: {:#?}",
+                frame_formats
+            );
+
+            let frame_format = frame_formats
+                .borrow()
+                .iter()
+                .filter(|f| {
+                    matches!(
+                        f.format,
+                        wl_shm::Format::Argb8888
+                            | wl_shm::Format::Xrgb8888
+                            | wl_shm::Format::Xbgr8888
+                    )
+                })
+                .nth(0)
+                .copied();
+
+            log::debug!("Selected frame buffer format: {:#?}", frame_format);
+
+            let frame_format =
+                frame_format.ok_or_else(|| eyre!("No suitable frame format found"))?;
+
+            let frame_bytes = frame_format.stride * frame_format.height;
+
+            let mem_fd = create_shm_fd()?;
+            let mem_file = unsafe { File::from_raw_fd(mem_fd) };
+
+            mem_file.set_len(frame_bytes as u64)?;
+
+            let pool = self.shm.create_pool(mem_fd, frame_bytes as i32);
+            let buffer = pool.create_buffer(
+                0,
+                frame_format.width as i32,
+                frame_format.height as i32,
+                frame_format.stride as i32,
+                frame_format.format,
+            );
+
+            frame.copy(&buffer);
+
+            loop {
+                event_queue.dispatch(&mut (), |_, _, _| {})?;
+
+                if let Some(state) = frame_state.borrow_mut().take() {
+                    match state {
+                        FrameState::Failed => {
+                            return Err(eyre!("Frame copy failed"));
+                        }
+
+                        FrameState::Finished => {
+                            let mmap = unsafe { MmapMut::map_mut(&mem_file)? };
+
+                            // the byte order of the formats is little-endian, so e.g. ARGB8888
+                            // is laid out in memory as B, G, R, A
+                            let (swap_red_blue, has_alpha) = match frame_format.format {
+                                wl_shm::Format::Argb8888 => (true, true),
+                                wl_shm::Format::Xrgb8888 => (true, false),
+                                wl_shm::Format::Xbgr8888 => (false, false),
+
+                                other => {
+                                    return Err(eyre!("Unsupported buffer format: {:?}", other));
+                                }
+                            };
 
-        let mem_fd = create_shm_fd()?;
-        let mem_file = unsafe { File::from_raw_fd(mem_fd) };
+                            let stride = frame_format.stride as usize;
 
-        mem_file.set_len(frame_bytes as u64)?;
+                            let image = RgbaImage::from_fn(
+                                frame_format.width,
+                                frame_format.height,
+                                |x, y| {
+                                    let offset = y as usize * stride + x as usize * 4;
+                                    let pixel = &mmap[offset..offset + 4];
 
-        let shm = globals.instantiate_exact::<wl_shm::WlShm>(1)?;
-        let pool = shm.create_pool(mem_fd, frame_bytes as i32);
-        let buffer = pool.create_buffer(
-            0,
-            frame_format.width as i32,
-            frame_format.height as i32,
-            frame_format.stride as i32,
-            frame_format.format,
-        );
+                                    let (r, b) = if swap_red_blue {
+                                        (pixel[2], pixel[0])
+                                    } else {
+                                        (pixel[0], pixel[2])
+                                    };
 
-        frame.copy(&buffer);
+                                    let a = if has_alpha { pixel[3] } else { 0xff };
 
-        loop {
-            event_queue.dispatch(&mut (), |_, _, _| {})?;
+                                    image::Rgba([r, pixel[1], b, a])
+                                },
+                            );
 
-            if let Some(state) = frame_state.borrow_mut().take() {
-                match state {
-                    FrameState::Failed => {
-                        log::error!("Frame copy failed");
-                        break;
-                    }
+                            buffer.destroy();
+                            pool.destroy();
+                            frame.destroy();
 
-                    FrameState::Finished => {
-                        let mut mmap = unsafe { MmapMut::map_mut(&mem_file)? };
-                        let data = &mut *mmap;
-                        let color_type = match frame_format.format {
-                            wl_shm::Format::Argb8888 | wl_shm::Format::Xrgb8888 => {
-                                for chunk in data.chunks_exact_mut(4) {
-                                    // swap in place (b with r)
-                                    let tmp = chunk[0];
-                                    chunk[0] = chunk[2];
-                                    chunk[2] = tmp;
-                                }
-                                Rgba8
-                            }
-
-                            wl_shm::Format::Xbgr8888 => Rgba8,
-                            other => {
-                                log::error!("Unsupported buffer format: {:?}", other);
-                                break;
-                            }
-                        };
-
-                        let path = "eruption-netfx-screenshot.png";
-
-                        PngEncoder::new(File::create(path)?).write_image(
-                            &mmap,
-                            frame_format.width,
-                            frame_format.height,
-                            color_type,
-                        )?;
+                            return Ok(image);
+                        }
                     }
                 }
             }
         }
-
-        Ok(())
     }
 
     /// Create a memfd, only works on Linux
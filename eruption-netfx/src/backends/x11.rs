@@ -47,7 +47,9 @@ impl Backend for X11Backend {
         let opts = crate::OPTIONS.read().as_ref().unwrap().clone();
 
         self.device = Some(hwdevices::get_keyboard_device(&opts.model)?);
-        self.display = Some(xwrap::Display::open(None).unwrap());
+        self.display = Some(
+            xwrap::Display::open(None).ok_or_else(|| eyre::eyre!("Could not open the display"))?,
+        );
 
         // if we made it up to here, the initialization succeeded
         self.failed = false;
@@ -94,7 +96,11 @@ impl Backend for X11Backend {
             .get_image(window, sel, xwrap::ALL_PLANES, x11::xlib::ZPixmap)
             .unwrap();
 
-        let commands = super::utils::process_screenshot(&image, &device)?;
+        let buffer = image
+            .into_image_buffer()
+            .ok_or_else(|| eyre::eyre!("Unsupported pixel format"))?;
+
+        let commands = super::utils::process_screenshot(buffer, device)?;
 
         Ok(commands)
     }
@@ -31,5 +31,8 @@ pub const DEFAULT_ANIMATION_DELAY_MILLIS: u64 = 83;
 /// Default delay between screenshots, used for ambient mode
 pub const DEFAULT_FRAME_DELAY_MILLIS: u64 = 37;
 
+/// Size of the receive buffer for DMX packets, large enough for E1.31 and Art-Net
+pub const DMX_PACKET_BUFFER_SIZE: usize = 1024;
+
 /// Timeout value to use for D-Bus connections
 pub const DBUS_TIMEOUT_MILLIS: u32 = 250;
@@ -0,0 +1,542 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Receiver for DMX universes, transmitted via E1.31 (sACN) or Art-Net
+
+use serde::Deserialize;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::{fs, io};
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// Default UDP port of E1.31 (sACN)
+pub const E131_PORT: u16 = 5568;
+
+/// Default UDP port of Art-Net
+pub const ARTNET_PORT: u16 = 6454;
+
+/// Max. number of channels in a DMX universe
+pub const MAX_CHANNELS: usize = 512;
+
+const E131_ACN_PACKET_IDENTIFIER: &[u8; 12] = b"ASC-E1.17\0\0\0";
+const E131_VECTOR_ROOT_DATA: u32 = 0x0000_0004;
+const E131_VECTOR_FRAMING_DATA: u32 = 0x0000_0002;
+const E131_VECTOR_DMP_SET_PROPERTY: u8 = 0x02;
+const E131_OPTION_PREVIEW_DATA: u8 = 0x80;
+const E131_DATA_OFFSET: usize = 126;
+
+const ARTNET_ID: &[u8; 8] = b"Art-Net\0";
+const ARTNET_OPCODE_DMX: u16 = 0x5000;
+const ARTNET_DATA_OFFSET: usize = 18;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DmxError {
+    #[error("Packet is ill-formed")]
+    IllFormedPacket {},
+
+    #[error("Invalid mapping: {description}")]
+    InvalidMapping { description: String },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DmxProtocol {
+    E131,
+    ArtNet,
+}
+
+impl DmxProtocol {
+    pub fn default_port(&self) -> u16 {
+        match self {
+            DmxProtocol::E131 => E131_PORT,
+            DmxProtocol::ArtNet => ARTNET_PORT,
+        }
+    }
+}
+
+/// Maps a range of channels of a universe onto consecutive indices of the canvas
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Mapping {
+    pub universe: u16,
+
+    /// The DMX channel of the first color, starting at 1
+    #[serde(default = "default_start_channel")]
+    pub start_channel: usize,
+
+    /// The index into the canvas of the first color, starting at 1
+    pub first_index: usize,
+
+    /// The number of consecutive indices of the canvas
+    pub count: usize,
+
+    /// 3 for RGB, or 4 for RGBA fixtures
+    #[serde(default = "default_channels_per_pixel")]
+    pub channels_per_pixel: usize,
+}
+
+fn default_start_channel() -> usize {
+    1
+}
+
+fn default_channels_per_pixel() -> usize {
+    3
+}
+
+impl Mapping {
+    pub fn validate(&self) -> Result<()> {
+        let description = if self.channels_per_pixel != 3 && self.channels_per_pixel != 4 {
+            Some(format!(
+                "Unsupported number of channels per pixel: {}",
+                self.channels_per_pixel
+            ))
+        } else if self.start_channel == 0 || self.first_index == 0 || self.count == 0 {
+            Some("Channels and indices start at 1, count must not be zero".to_string())
+        } else if self.start_channel - 1 + self.count * self.channels_per_pixel > MAX_CHANNELS {
+            Some(format!(
+                "Universe {}: Mapping exceeds the {} channels of a universe",
+                self.universe, MAX_CHANNELS
+            ))
+        } else {
+            None
+        };
+
+        match description {
+            Some(description) => Err(DmxError::InvalidMapping { description }.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// The last index into the canvas that is covered by this mapping
+    pub fn end_index(&self) -> usize {
+        self.first_index + self.count - 1
+    }
+
+    /// Copy the colors contained in the `channels` of the universe to `frame`,
+    /// which holds RGBA quadruples for consecutive indices of the canvas
+    pub fn apply(&self, channels: &[u8], frame: &mut [u8]) {
+        for i in 0..self.count {
+            let offset = self.start_channel - 1 + i * self.channels_per_pixel;
+            let index = self.first_index - 1 + i;
+
+            if offset + self.channels_per_pixel > channels.len() || (index + 1) * 4 > frame.len() {
+                break;
+            }
+
+            let pixel = &channels[offset..offset + self.channels_per_pixel];
+            let alpha = if self.channels_per_pixel == 4 {
+                pixel[3]
+            } else {
+                255
+            };
+
+            frame[index * 4..(index + 1) * 4]
+                .copy_from_slice(&[pixel[0], pixel[1], pixel[2], alpha]);
+        }
+    }
+}
+
+/// Configuration of the receiver, loaded from a TOML file
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReceiverConfig {
+    pub protocol: DmxProtocol,
+
+    #[serde(default = "default_bind_address")]
+    pub bind_address: Ipv4Addr,
+
+    /// Defaults to the well known port of the selected protocol
+    pub port: Option<u16>,
+
+    #[serde(rename = "mapping", default)]
+    pub mappings: Vec<Mapping>,
+}
+
+fn default_bind_address() -> Ipv4Addr {
+    Ipv4Addr::UNSPECIFIED
+}
+
+impl ReceiverConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let config: Self = toml::from_str(&fs::read_to_string(path)?)?;
+
+        for mapping in config.mappings.iter() {
+            mapping.validate()?;
+        }
+
+        Ok(config)
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or_else(|| self.protocol.default_port())
+    }
+
+    /// The number of canvas indices that are covered by the frames we forward
+    pub fn frame_len(&self) -> usize {
+        self.mappings
+            .iter()
+            .map(|m| m.end_index())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Apply the channel data of `packet` to all mappings of its universe. Returns
+    /// `false` if the universe is not mapped, so there is nothing to forward
+    pub fn apply_packet(&self, packet: &DmxPacket, frame: &mut [u8]) -> bool {
+        let mut mapped = false;
+
+        for mapping in self
+            .mappings
+            .iter()
+            .filter(|m| m.universe == packet.universe)
+        {
+            mapping.apply(packet.channels, frame);
+            mapped = true;
+        }
+
+        mapped
+    }
+}
+
+/// The channel data of a single universe, as received from the network
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DmxPacket<'a> {
+    pub universe: u16,
+    pub channels: &'a [u8],
+}
+
+/// Parse a packet of the given `protocol`. Returns `None` for valid packets
+/// that carry no channel data, like e.g. Art-Net polls or E1.31 preview data
+pub fn parse_packet(protocol: DmxProtocol, buffer: &[u8]) -> Result<Option<DmxPacket>> {
+    match protocol {
+        DmxProtocol::E131 => parse_e131(buffer),
+        DmxProtocol::ArtNet => parse_artnet(buffer),
+    }
+}
+
+fn read_u16_be(buffer: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([buffer[offset], buffer[offset + 1]])
+}
+
+fn read_u32_be(buffer: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        buffer[offset],
+        buffer[offset + 1],
+        buffer[offset + 2],
+        buffer[offset + 3],
+    ])
+}
+
+/// Parse an E1.31 data packet
+fn parse_e131(buffer: &[u8]) -> Result<Option<DmxPacket>> {
+    if buffer.len() < 22 || &buffer[4..16] != E131_ACN_PACKET_IDENTIFIER {
+        return Err(DmxError::IllFormedPacket {}.into());
+    }
+
+    // ignore synchronization and discovery packets
+    if read_u32_be(buffer, 18) != E131_VECTOR_ROOT_DATA {
+        return Ok(None);
+    }
+
+    if buffer.len() < E131_DATA_OFFSET {
+        return Err(DmxError::IllFormedPacket {}.into());
+    }
+
+    if read_u32_be(buffer, 40) != E131_VECTOR_FRAMING_DATA
+        || buffer[117] != E131_VECTOR_DMP_SET_PROPERTY
+        || buffer[112] & E131_OPTION_PREVIEW_DATA != 0
+    {
+        return Ok(None);
+    }
+
+    let universe = read_u16_be(buffer, 113);
+
+    // the property value count includes the start code
+    let count = read_u16_be(buffer, 123) as usize;
+    let start_code = buffer[125];
+
+    if count == 0 || E131_DATA_OFFSET + count - 1 > buffer.len() {
+        return Err(DmxError::IllFormedPacket {}.into());
+    }
+
+    // only the null start code carries dimmer data
+    if start_code != 0 {
+        return Ok(None);
+    }
+
+    let channels = &buffer[E131_DATA_OFFSET..E131_DATA_OFFSET + count - 1];
+
+    Ok(Some(DmxPacket { universe, channels }))
+}
+
+/// Parse an Art-Net ArtDmx packet
+fn parse_artnet(buffer: &[u8]) -> Result<Option<DmxPacket>> {
+    if buffer.len() < 10 || &buffer[0..8] != ARTNET_ID {
+        return Err(DmxError::IllFormedPacket {}.into());
+    }
+
+    // the op-code is transmitted in little-endian byte order
+    let opcode = u16::from_le_bytes([buffer[8], buffer[9]]);
+    if opcode != ARTNET_OPCODE_DMX {
+        return Ok(None);
+    }
+
+    if buffer.len() < ARTNET_DATA_OFFSET {
+        return Err(DmxError::IllFormedPacket {}.into());
+    }
+
+    // 15 bit port-address: net (7 bits) + sub-net (4 bits) + universe (4 bits)
+    let universe = u16::from_le_bytes([buffer[14], buffer[15] & 0x7f]);
+    let len = read_u16_be(buffer, 16) as usize;
+
+    if len > MAX_CHANNELS || ARTNET_DATA_OFFSET + len > buffer.len() {
+        return Err(DmxError::IllFormedPacket {}.into());
+    }
+
+    let channels = &buffer[ARTNET_DATA_OFFSET..ARTNET_DATA_OFFSET + len];
+
+    Ok(Some(DmxPacket { universe, channels }))
+}
+
+/// The multicast group that E1.31 sources use to transmit `universe`
+pub fn e131_multicast_group(universe: u16) -> Ipv4Addr {
+    let [hi, lo] = universe.to_be_bytes();
+
+    Ipv4Addr::new(239, 255, hi, lo)
+}
+
+/// Join the multicast groups of all mapped universes, failures are not fatal
+/// since sources may transmit via unicast as well
+pub fn join_multicast_groups(
+    socket: &tokio::net::UdpSocket,
+    config: &ReceiverConfig,
+) -> io::Result<()> {
+    if config.protocol != DmxProtocol::E131 {
+        return Ok(());
+    }
+
+    let mut universes: Vec<u16> = config.mappings.iter().map(|m| m.universe).collect();
+    universes.sort_unstable();
+    universes.dedup();
+
+    for universe in universes {
+        socket.join_multicast_v4(e131_multicast_group(universe), config.bind_address)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket;
+
+    fn e131_packet(universe: u16, channels: &[u8]) -> Vec<u8> {
+        let mut buffer = vec![0; E131_DATA_OFFSET];
+
+        buffer[0..2].copy_from_slice(&0x0010_u16.to_be_bytes());
+        buffer[4..16].copy_from_slice(E131_ACN_PACKET_IDENTIFIER);
+        buffer[18..22].copy_from_slice(&E131_VECTOR_ROOT_DATA.to_be_bytes());
+        buffer[40..44].copy_from_slice(&E131_VECTOR_FRAMING_DATA.to_be_bytes());
+        buffer[113..115].copy_from_slice(&universe.to_be_bytes());
+        buffer[117] = E131_VECTOR_DMP_SET_PROPERTY;
+        buffer[118] = 0xa1;
+        buffer[121..123].copy_from_slice(&1_u16.to_be_bytes());
+        buffer[123..125].copy_from_slice(&(channels.len() as u16 + 1).to_be_bytes());
+        buffer.extend_from_slice(channels);
+
+        buffer
+    }
+
+    fn artnet_packet(universe: u16, channels: &[u8]) -> Vec<u8> {
+        let mut buffer = Vec::new();
+
+        buffer.extend_from_slice(ARTNET_ID);
+        buffer.extend_from_slice(&ARTNET_OPCODE_DMX.to_le_bytes());
+        buffer.extend_from_slice(&[0, 14, 0, 0]);
+        buffer.extend_from_slice(&universe.to_le_bytes());
+        buffer.extend_from_slice(&(channels.len() as u16).to_be_bytes());
+        buffer.extend_from_slice(channels);
+
+        buffer
+    }
+
+    #[test]
+    fn parse_e131_packet() {
+        let packet = e131_packet(7, &[1, 2, 3]);
+        let result = parse_packet(DmxProtocol::E131, &packet).unwrap().unwrap();
+
+        assert_eq!(result.universe, 7);
+        assert_eq!(result.channels, &[1, 2, 3]);
+
+        assert!(parse_packet(DmxProtocol::E131, &packet[..100]).is_err());
+    }
+
+    #[test]
+    fn parse_artnet_packet() {
+        let packet = artnet_packet(0x0123, &[4, 5, 6, 7]);
+        let result = parse_packet(DmxProtocol::ArtNet, &packet).unwrap().unwrap();
+
+        assert_eq!(result.universe, 0x0123);
+        assert_eq!(result.channels, &[4, 5, 6, 7]);
+
+        // ArtPoll
+        let mut poll = packet.clone();
+        poll[8..10].copy_from_slice(&0x2000_u16.to_le_bytes());
+        assert_eq!(parse_packet(DmxProtocol::ArtNet, &poll).unwrap(), None);
+    }
+
+    #[test]
+    fn mapping_apply() {
+        let mapping = Mapping {
+            universe: 1,
+            start_channel: 4,
+            first_index: 2,
+            count: 2,
+            channels_per_pixel: 3,
+        };
+
+        assert!(mapping.validate().is_ok());
+
+        let mut frame = vec![0; 3 * 4];
+        mapping.apply(&[0, 0, 0, 10, 20, 30, 40, 50, 60], &mut frame);
+
+        assert_eq!(frame, vec![0, 0, 0, 0, 10, 20, 30, 255, 40, 50, 60, 255]);
+    }
+
+    #[test]
+    fn receive_on_loopback() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        sender
+            .send_to(
+                &e131_packet(1, &[255, 0, 0]),
+                receiver.local_addr().unwrap(),
+            )
+            .unwrap();
+
+        let mut buffer = [0; 1024];
+        let len = receiver.recv(&mut buffer).unwrap();
+
+        let packet = parse_packet(DmxProtocol::E131, &buffer[..len])
+            .unwrap()
+            .unwrap();
+
+        let config: ReceiverConfig = toml::from_str(
+            r#"
+            protocol = "e131"
+
+            [[mapping]]
+            universe = 1
+            first_index = 1
+            count = 1
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.port(), E131_PORT);
+        assert_eq!(config.frame_len(), 1);
+
+        let mut frame = vec![0; config.frame_len() * 4];
+        config.mappings[0].apply(packet.channels, &mut frame);
+
+        assert_eq!(frame, vec![255, 0, 0, 255]);
+    }
+
+    #[tokio::test]
+    async fn forward_universe_to_server() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let config: ReceiverConfig = toml::from_str(
+            r#"
+            protocol = "artnet"
+
+            [[mapping]]
+            universe = 3
+            first_index = 1
+            count = 2
+            "#,
+        )
+        .unwrap();
+
+        // a mock Network FX server, that speaks the text protocol and
+        // replies to every command with a distinct line
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut socket = BufReader::new(socket);
+
+            let mut commands = Vec::new();
+
+            for i in 0..4 {
+                let mut line = String::new();
+                socket.read_line(&mut line).await.unwrap();
+
+                commands.push(line.trim().to_string());
+
+                socket
+                    .write_all(format!("OK {}\n", i).as_bytes())
+                    .await
+                    .unwrap();
+            }
+
+            commands
+        });
+
+        let mut connection = crate::protocol::Connection::connect(
+            &address,
+            crate::protocol::PROTOCOL_VERSION_1,
+            false,
+        )
+        .await
+        .unwrap();
+
+        let mut frame = vec![0; config.frame_len() * 4];
+        let mut replies = Vec::new();
+
+        for channels in [[255, 0, 0, 0, 255, 0], [0, 0, 255, 1, 2, 3]] {
+            let buffer = artnet_packet(3, &channels);
+            let packet = parse_packet(config.protocol, &buffer).unwrap().unwrap();
+
+            assert!(config.apply_packet(&packet, &mut frame));
+
+            replies.push(connection.send_frame(&frame).await.unwrap().unwrap());
+        }
+
+        // both commands of each frame have been replied to, in order
+        assert_eq!(replies, vec!["OK 1\n", "OK 3\n"]);
+
+        assert_eq!(
+            server.await.unwrap(),
+            vec![
+                "1:255:0:0:255",
+                "2:0:255:0:255",
+                "1:0:0:255:255",
+                "2:1:2:3:255"
+            ]
+        );
+
+        // universes that are not mapped are not forwarded
+        let buffer = artnet_packet(4, &[1, 2, 3]);
+        let packet = parse_packet(config.protocol, &buffer).unwrap().unwrap();
+        assert!(!config.apply_packet(&packet, &mut frame));
+    }
+}
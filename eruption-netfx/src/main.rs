@@ -32,12 +32,14 @@ use std::{cmp::Ordering, env, thread};
 use std::{path::PathBuf, sync::Arc};
 use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt};
 use tokio::io::{AsyncReadExt, BufReader};
-use tokio::net::TcpStream;
+use tokio::net::{TcpStream, UdpSocket};
 use tokio::time::Duration;
 
 mod backends;
 mod constants;
+mod dmx;
 mod hwdevices;
+mod protocol;
 mod utils;
 
 #[derive(RustEmbed)]
@@ -94,6 +96,14 @@ pub struct Options {
     hostname: Option<String>,
     port: Option<u16>,
 
+    /// The Network FX protocol version to use, version 1 is the plain text protocol
+    #[clap(short = 'p', long, default_value = "1")]
+    protocol_version: u8,
+
+    /// Send commands as UDP datagrams, without waiting for replies (requires protocol version 2)
+    #[clap(short, long)]
+    udp: bool,
+
     #[clap(subcommand)]
     command: Subcommands,
 }
@@ -119,6 +129,9 @@ pub enum Subcommands {
     /// Make the LEDs of connected devices reflect what is shown on the screen
     Ambient { frame_delay: Option<u64> },
 
+    /// Receive DMX universes via E1.31 (sACN) or Art-Net and forward them to the server
+    Receiver { config_file: PathBuf },
+
     /// Generate shell completions
     Completions {
         // #[clap(subcommand)]
@@ -279,8 +292,8 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
             if opts.verbose > 1 {
                 println!("{}", tr!("connecting-to", host = address.to_string()));
             }
-            let socket = TcpStream::connect(address).await?;
-            let mut buf_reader = BufReader::new(socket);
+            let mut connection =
+                protocol::Connection::connect(&address, opts.protocol_version, opts.udp).await?;
 
             if filename.to_string_lossy() == "-" {
                 let stdin = io::stdin();
@@ -299,16 +312,13 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                     if opts.verbose > 1 {
                         println!("{}", &commands);
                     }
-                    buf_reader.write_all(&Vec::from(commands)).await?;
-
                     // receive and print the response
-                    let mut buffer = String::new();
-                    buf_reader.read_line(&mut buffer).await?;
+                    if let Some(reply) = connection.send_commands(&commands).await? {
+                        println!("{}", reply.bold());
 
-                    println!("{}", buffer.bold());
-
-                    if buffer.starts_with("BYE") || buffer.starts_with("ERROR:") {
-                        break;
+                        if reply.starts_with("BYE") || reply.starts_with("ERROR:") {
+                            break;
+                        }
                     }
                 }
             } else {
@@ -321,13 +331,10 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                 if opts.verbose > 1 {
                     println!("{}", &commands);
                 }
-                buf_reader.write_all(&Vec::from(commands)).await?;
-
                 // receive and print the response
-                let mut buffer = String::new();
-                buf_reader.read_line(&mut buffer).await?;
-
-                println!("{}", &buffer.bold());
+                if let Some(reply) = connection.send_commands(&commands).await? {
+                    println!("{}", &reply.bold());
+                }
             }
         }
 
@@ -344,8 +351,8 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
             if opts.verbose > 1 {
                 println!("{}", tr!("connecting-to", host = address.to_string()));
             }
-            let socket = TcpStream::connect(address).await?;
-            let mut buf_reader = BufReader::new(socket);
+            let mut connection =
+                protocol::Connection::connect(&address, opts.protocol_version, opts.udp).await?;
 
             // holds pre-processed command-sequences for each image
             let processed_images = Arc::new(Mutex::new(vec![]));
@@ -408,18 +415,15 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                     if opts.verbose > 2 {
                         println!("{}", &commands);
                     }
-                    buf_reader.write_all(&Vec::from(commands.clone())).await?;
-
                     // receive and print the response
-                    let mut buffer = String::new();
-                    buf_reader.read_line(&mut buffer).await?;
-
-                    if opts.verbose > 1 {
-                        println!("{}", buffer.bold());
-                    }
-
-                    if buffer.starts_with("BYE") || buffer.starts_with("ERROR:") {
-                        break;
+                    if let Some(reply) = connection.send_commands(commands).await? {
+                        if opts.verbose > 1 {
+                            println!("{}", reply.bold());
+                        }
+
+                        if reply.starts_with("BYE") || reply.starts_with("ERROR:") {
+                            break;
+                        }
                     }
 
                     thread::sleep(Duration::from_millis(
@@ -439,8 +443,8 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
             if opts.verbose > 1 {
                 println!("{}", tr!("connecting-to", host = address.to_string()));
             }
-            let socket = TcpStream::connect(address).await?;
-            let mut buf_reader = BufReader::new(socket);
+            let mut connection =
+                protocol::Connection::connect(&address, opts.protocol_version, opts.udp).await?;
 
             // register all available screenshot backends
             backends::register_backends()?;
@@ -459,14 +463,11 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                 if opts.verbose > 1 {
                     println!("{}", &commands);
                 }
-                buf_reader.write_all(&Vec::from(commands)).await?;
-
-                // receive and print the response
-                let mut buffer = String::new();
-                buf_reader.read_line(&mut buffer).await?;
-
-                if buffer.starts_with("BYE") || buffer.starts_with("ERROR:") {
-                    break;
+                // receive the response
+                if let Some(reply) = connection.send_commands(&commands).await? {
+                    if reply.starts_with("BYE") || reply.starts_with("ERROR:") {
+                        break;
+                    }
                 }
 
                 thread::sleep(Duration::from_millis(
@@ -475,6 +476,75 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
             }
         }
 
+        Subcommands::Receiver { config_file } => {
+            let config = dmx::ReceiverConfig::load(&config_file)?;
+
+            let address = format!(
+                "{}:{}",
+                opts.hostname
+                    .unwrap_or_else(|| constants::DEFAULT_HOST.to_owned()),
+                opts.port.unwrap_or(constants::DEFAULT_PORT)
+            );
+            if opts.verbose > 1 {
+                println!("{}", tr!("connecting-to", host = address.to_string()));
+            }
+            let mut connection =
+                protocol::Connection::connect(&address, opts.protocol_version, opts.udp).await?;
+
+            let socket = UdpSocket::bind((config.bind_address, config.port())).await?;
+
+            // sources may transmit via unicast as well, so this is not fatal
+            if let Err(e) = dmx::join_multicast_groups(&socket, &config) {
+                eprintln!("{}", tr!("multicast-error", message = e.to_string()));
+            }
+
+            if opts.verbose > 0 {
+                println!(
+                    "{}",
+                    tr!(
+                        "listening-for-universes",
+                        address = socket.local_addr()?.to_string()
+                    )
+                );
+            }
+
+            // holds RGBA quadruples for all mapped indices of the canvas
+            let mut frame = vec![0; config.frame_len() * 4];
+            let mut buffer = vec![0; constants::DMX_PACKET_BUFFER_SIZE];
+
+            loop {
+                let len = socket.recv(&mut buffer).await?;
+
+                match dmx::parse_packet(config.protocol, &buffer[..len]) {
+                    Ok(Some(packet)) => {
+                        if !config.apply_packet(&packet, &mut frame) {
+                            continue;
+                        }
+
+                        if opts.verbose > 1 {
+                            println!("{}", tr!("sending-data"));
+                        }
+
+                        if let Some(reply) = connection.send_frame(&frame).await? {
+                            if reply.starts_with("BYE") || reply.starts_with("ERROR:") {
+                                println!("{}", reply.bold());
+                                break;
+                            }
+                        }
+                    }
+
+                    // packet without channel data
+                    Ok(None) => {}
+
+                    Err(e) => {
+                        if opts.verbose > 0 {
+                            eprintln!("{}", tr!("dmx-error", message = e.to_string()));
+                        }
+                    }
+                }
+            }
+        }
+
         Subcommands::Completions { shell } => {
             const BIN_NAME: &str = env!("CARGO_PKG_NAME");
 
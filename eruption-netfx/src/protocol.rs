@@ -0,0 +1,268 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{lookup_host, TcpStream, UdpSocket};
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// The plain text protocol, one command per line and one reply per command
+pub const PROTOCOL_VERSION_1: u8 = 1;
+
+/// The binary protocol, supports full frames and batches of commands
+pub const PROTOCOL_VERSION_2: u8 = 2;
+
+/// Size of the header of a v2 message: type (u8) + payload length (u32)
+const HEADER_SIZE: usize = 5;
+
+/// Size of the header of a v2 reply: status (u8) + payload length (u16)
+const REPLY_HEADER_SIZE: usize = 3;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProtocolError {
+    #[error("The server does not support protocol version {version}")]
+    UnsupportedVersion { version: u8 },
+
+    #[error("Datagrams require protocol version {}", PROTOCOL_VERSION_2)]
+    DatagramsRequireVersion2 {},
+
+    #[error("Could not resolve the address: {address}")]
+    InvalidAddress { address: String },
+}
+
+/// Types of v2 messages
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MessageType {
+    /// RGBA quadruples for consecutive indices of the canvas
+    Frame = 0x01,
+
+    /// Newline separated text protocol commands
+    Commands = 0x02,
+
+    Status = 0x03,
+    Canvas = 0x04,
+    Quit = 0x05,
+}
+
+/// Encode a v2 message
+pub fn encode_message(message_type: MessageType, payload: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(HEADER_SIZE + payload.len());
+
+    result.push(message_type as u8);
+    result.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    result.extend_from_slice(payload);
+
+    result
+}
+
+/// Convert the status code of a v2 reply to the equivalent text protocol reply
+pub fn status_to_reply(status: u8) -> String {
+    if status == 0 {
+        "OK".to_string()
+    } else {
+        format!("ERROR: {}", status)
+    }
+}
+
+/// A reply to a v2 message
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reply {
+    /// Zero on success, an error code otherwise
+    pub status: u8,
+    pub payload: Vec<u8>,
+}
+
+/// A connection to a Network FX server
+pub enum Connection {
+    /// Plain text protocol (v1)
+    Text(BufReader<TcpStream>),
+
+    /// Binary protocol (v2) over TCP
+    Binary(BufReader<TcpStream>),
+
+    /// Binary protocol (v2) over UDP, messages are never replied to
+    Datagram(UdpSocket),
+}
+
+impl Connection {
+    /// Connect to the server at `address` and negotiate the protocol `version`
+    pub async fn connect(address: &str, version: u8, udp: bool) -> Result<Self> {
+        if udp {
+            if version != PROTOCOL_VERSION_2 {
+                return Err(ProtocolError::DatagramsRequireVersion2 {}.into());
+            }
+
+            let peer = lookup_host(address).await?.next().ok_or_else(|| {
+                ProtocolError::InvalidAddress {
+                    address: address.to_string(),
+                }
+            })?;
+
+            let local: SocketAddr = match peer {
+                SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+                SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+            };
+
+            let socket = UdpSocket::bind(local).await?;
+            socket.connect(peer).await?;
+
+            Ok(Self::Datagram(socket))
+        } else {
+            let socket = TcpStream::connect(address).await?;
+            socket.set_nodelay(true)?;
+
+            let mut buf_reader = BufReader::new(socket);
+
+            match version {
+                PROTOCOL_VERSION_1 => Ok(Self::Text(buf_reader)),
+
+                PROTOCOL_VERSION_2 => {
+                    buf_reader
+                        .write_all(format!("PROTOCOL {}\n", version).as_bytes())
+                        .await?;
+
+                    let mut buffer = String::new();
+                    buf_reader.read_line(&mut buffer).await?;
+
+                    if buffer.trim() == format!("OK: {}", version) {
+                        Ok(Self::Binary(buf_reader))
+                    } else {
+                        Err(ProtocolError::UnsupportedVersion { version }.into())
+                    }
+                }
+
+                _ => Err(ProtocolError::UnsupportedVersion { version }.into()),
+            }
+        }
+    }
+
+    /// Send a batch of newline separated text protocol commands. Returns the reply
+    /// of the server to the last (or the first failed) command, or `None` if the
+    /// transport does not use replies
+    pub async fn send_commands(&mut self, commands: &str) -> Result<Option<String>> {
+        match self {
+            Self::Text(buf_reader) => {
+                buf_reader.write_all(commands.as_bytes()).await?;
+
+                // the server replies to every line, and closes the connection after
+                // the first erroneous command, so keep the reply of the last line
+                let mut buffer = String::new();
+
+                for _ in commands.lines() {
+                    buffer.clear();
+
+                    if buf_reader.read_line(&mut buffer).await? == 0 || buffer.starts_with("ERROR")
+                    {
+                        break;
+                    }
+                }
+
+                Ok(Some(buffer))
+            }
+
+            _ => Ok(self
+                .send_message(MessageType::Commands, commands.as_bytes())
+                .await?
+                .map(|reply| status_to_reply(reply.status))),
+        }
+    }
+
+    /// Send a frame, holding RGBA quadruples for consecutive indices of the canvas.
+    /// The text protocol does not support frames, so they are converted to commands
+    pub async fn send_frame(&mut self, frame: &[u8]) -> Result<Option<String>> {
+        match self {
+            Self::Text(_) => {
+                let commands: String = frame
+                    .chunks_exact(4)
+                    .enumerate()
+                    .map(|(i, c)| format!("{}:{}:{}:{}:{}\n", i + 1, c[0], c[1], c[2], c[3]))
+                    .collect();
+
+                self.send_commands(&commands).await
+            }
+
+            _ => Ok(self
+                .send_message(MessageType::Frame, frame)
+                .await?
+                .map(|reply| status_to_reply(reply.status))),
+        }
+    }
+
+    /// Send a single v2 message. Returns the reply of the server, or
+    /// `None` if the transport does not use replies
+    pub async fn send_message(
+        &mut self,
+        message_type: MessageType,
+        payload: &[u8],
+    ) -> Result<Option<Reply>> {
+        let message = encode_message(message_type, payload);
+
+        match self {
+            Self::Text(_) => Err(ProtocolError::UnsupportedVersion {
+                version: PROTOCOL_VERSION_2,
+            }
+            .into()),
+
+            Self::Binary(buf_reader) => {
+                buf_reader.write_all(&message).await?;
+
+                let mut header = [0_u8; REPLY_HEADER_SIZE];
+                buf_reader.read_exact(&mut header).await?;
+
+                let len = u16::from_be_bytes([header[1], header[2]]) as usize;
+
+                let mut payload = vec![0; len];
+                buf_reader.read_exact(&mut payload).await?;
+
+                Ok(Some(Reply {
+                    status: header[0],
+                    payload,
+                }))
+            }
+
+            Self::Datagram(socket) => {
+                socket.send(&message).await?;
+
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_frame_message() {
+        let message = encode_message(MessageType::Frame, &[255, 0, 0, 255]);
+
+        assert_eq!(message, vec![0x01, 0, 0, 0, 4, 255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn status_codes() {
+        assert_eq!(status_to_reply(0), "OK");
+        assert_eq!(status_to_reply(120), "ERROR: 120");
+    }
+}
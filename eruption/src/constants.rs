@@ -28,6 +28,9 @@ pub const DEFAULT_PROFILE_DIR: &str = "/var/lib/eruption/profiles/";
 /// Default script directory
 pub const DEFAULT_SCRIPT_DIR: &str = "/usr/share/eruption/scripts/";
 
+/// Default directory of the device definition files
+pub const DEFAULT_DEVICE_DEFINITION_DIR: &str = "/usr/share/eruption/devices/";
+
 /// The `/run/eruption/` directory
 pub const RUN_ERUPTION_DIR: &str = "/run/eruption/";
 
@@ -49,6 +52,19 @@ pub const UNIT_NAME_AUDIO_PROXY: &str = "eruption-audio-proxy.service";
 /// Eruption daemon control UNIX domain socket (SDK support)
 pub const CONTROL_SOCKET_NAME: &str = "/run/eruption/control.sock";
 
+/// Max. number of concurrently connected SDK clients
+pub const MAX_SDK_CLIENTS: usize = 16;
+
+/// Max. number of remote SDK clients that may be connected but not yet authenticated,
+/// so that unauthenticated peers can not occupy all of the available client slots
+pub const MAX_SDK_UNAUTHENTICATED_CLIENTS: usize = 4;
+
+/// Remote SDK clients have to complete the authentication within this time (in milliseconds)
+pub const SDK_AUTH_TIMEOUT_MILLIS: u64 = 5000;
+
+/// Max. number of full canvas frames that may be queued for sending to a slow SDK client
+pub const SDK_SEND_QUEUE_FRAMES: usize = 8;
+
 /// Eruption daemon audio data UNIX domain socket
 pub const AUDIO_SOCKET_NAME: &str = "/run/eruption/audio.sock";
 
@@ -70,21 +86,54 @@ pub const PROCESS_SPAWN_WAIT_MILLIS: u64 = 800;
 /// Target frames per second
 pub const TARGET_FPS: u64 = 20;
 
-/// The number of "pixels" on the canvas
+/// The number of "pixels" on the default canvas, this is also the size of the LED maps that are sent to the devices
 pub const CANVAS_SIZE: usize = 144 + 36;
 
-/// The width of the canvas
+/// The width of the default canvas
 pub const CANVAS_WIDTH: usize = 22 + 8;
 
-/// The height of the canvas
+/// The height of the default canvas
 pub const CANVAS_HEIGHT: usize = 6;
 
+/// Max. supported width of a user-defined canvas
+pub const MAX_CANVAS_WIDTH: usize = 128;
+
+/// Max. supported height of a user-defined canvas
+pub const MAX_CANVAS_HEIGHT: usize = 32;
+
 /// The capacity of the buffer used for receiving audio samples
 pub const NET_BUFFER_CAPACITY: usize = 4096;
 
 /// Timeout for waiting on condition variables of Lua upcalls
 pub const TIMEOUT_CONDITION_MILLIS: u64 = 50;
 
+/// Delay before the first restart of a crashed Lua script, doubled on each consecutive crash
+pub const SCRIPT_RESTART_BACKOFF_MILLIS: u64 = 500;
+
+/// Upper bound of the delay before a crashed Lua script is restarted
+pub const MAX_SCRIPT_RESTART_BACKOFF_MILLIS: u64 = 30000;
+
+/// Give up on restarting a Lua script after this many consecutive crashes
+pub const MAX_SCRIPT_RESTARTS: u32 = 8;
+
+/// A Lua script that ran for at least this long before crashing is not considered crash looping
+pub const SCRIPT_STABLE_RUNTIME_SECS: u64 = 60;
+
+/// Default time budget of a single upcall of a Lua script, e.g. `on_tick()`
+pub const SCRIPT_TIME_BUDGET_MILLIS: u64 = 20;
+
+/// Upcalls of Lua scripts that run for longer than this will be aborted
+pub const SCRIPT_TIME_LIMIT_MILLIS: u64 = 1000;
+
+/// Check the time limit of a Lua upcall every n instructions
+pub const SCRIPT_HOOK_INSTRUCTIONS: u32 = 1000;
+
+/// Max. number of ticks that will be skipped to throttle a Lua script that exceeded its time budget
+pub const MAX_SKIPPED_TICKS: u32 = 20;
+
+/// Disable a Lua script after it exceeded its time budget this many times in a row
+pub const MAX_CONSECUTIVE_BUDGET_OVERRUNS: u32 = 10;
+
 /// Max number of events that will be processed in each iteration of the main loop
 pub const MAX_EVENTS_PER_ITERATION: u64 = 128;
 
@@ -94,6 +143,12 @@ pub const EVENTS_UPCALL_RATE_LIMIT_MILLIS: u64 = 25;
 /// Amount of time that has to pass before we retry sending a command to the LED control device
 pub const DEVICE_SETTLE_MILLIS: u64 = 25;
 
+/// Time to wait for the HID interfaces of a newly connected device to appear
+pub const HOTPLUG_SETTLE_MILLIS: u64 = 1000;
+
+/// Timeout of poll(2) on the udev monitor socket
+pub const HOTPLUG_POLL_TIMEOUT_MILLIS: i32 = 250;
+
 /// Update sensors every n seconds
 /// It is recommended to use a prime number value here
 pub const SENSOR_UPDATE_TICKS: u64 = 19; // TARGET_FPS /* * 1 */;
@@ -26,12 +26,14 @@ use log::*;
 use std::path::PathBuf;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::scripting::manifest::ParseConfig;
+use crate::scripting::{budget, supervisor};
 use crate::{constants, plugins};
 use crate::{hwdevices, profiles};
+use crate::{overlay, profiles::FindConfig, script, zones};
 use crate::{plugins::audio, scripting::manifest};
-use crate::{profiles::FindConfig, script};
 
 /// D-Bus messages and signals that are processed by the main thread
 #[derive(Debug, Clone)]
@@ -52,8 +54,12 @@ pub enum DbusApiError {
 
     #[error("Invalid parameter")]
     InvalidParameter {},
-    // #[error("Operation not supported")]
-    // OpNotSupported {},
+
+    #[error("Invalid value")]
+    InvalidValue {},
+
+    #[error("Operation not supported by the device")]
+    OpNotSupported {},
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -328,13 +334,114 @@ impl DbusApi {
                                 })
                                 .outarg::<Vec<(u8, u8, u8, u8)>, _>("values"),
                             )
-                            // .add_m(
-                            //     f.method("SetLedColors", (), move |m| {
-                            //         *crate::LAST_DBUS_EVENT_TIME.lock() = Instant::now();
-                            //         let s = *script::LED_MAP.read();
-                            //         Ok(vec![m.msg.method_return().append_all(s)])
-                            //     }), // .outarg::<Vec<RGBA>, _>("values"),
-                            // )
+                            .add_m(
+                                f.method("GetCanvasSize", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap().to_string(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let s = (
+                                            crate::CANVAS_WIDTH.load(Ordering::SeqCst) as u64,
+                                            crate::CANVAS_HEIGHT.load(Ordering::SeqCst) as u64,
+                                        );
+
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .outarg::<(u64, u64), _>("dimensions"),
+                            )
+                            .add_m(
+                                f.method("SetLedColors", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap().to_string(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (colors, timeout_millis): (
+                                            Vec<(u32, u8, u8, u8, u8)>,
+                                            u64,
+                                        ) = m.msg.read2()?;
+
+                                        overlay::set_led_colors(
+                                            &colors,
+                                            Duration::from_millis(timeout_millis),
+                                        )
+                                        .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+
+                                        Ok(vec![m.msg.method_return().append1(true)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<Vec<(u32, u8, u8, u8, u8)>, _>("values")
+                                .inarg::<u64, _>("timeout_millis")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("GetScriptStatus", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap().to_string(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let s = supervisor::get_script_status()
+                                            .iter()
+                                            .map(|(script_file, status)| {
+                                                (
+                                                    script_file.to_string_lossy().to_string(),
+                                                    status.crash_count,
+                                                    status.last_error.clone().unwrap_or_default(),
+                                                )
+                                            })
+                                            .collect::<Vec<(String, u32, String)>>();
+
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .outarg::<Vec<(String, u32, String)>, _>("values"),
+                            )
+                            .add_m(
+                                f.method("GetScriptStats", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap().to_string(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let s = budget::get_script_stats()
+                                            .iter()
+                                            .map(|(script_file, stats)| {
+                                                (
+                                                    script_file.to_string_lossy().to_string(),
+                                                    stats.upcalls,
+                                                    stats.average_time().as_secs_f64() * 1000.0,
+                                                    stats.max_time.as_secs_f64() * 1000.0,
+                                                    stats.overruns,
+                                                    stats.skipped_ticks,
+                                                )
+                                            })
+                                            .collect::<Vec<(String, u64, f64, f64, u64, u64)>>();
+
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .outarg::<Vec<(
+                                    String,
+                                    u64,
+                                    f64,
+                                    f64,
+                                    u64,
+                                    u64,
+                                )>, _>(
+                                    "values"
+                                ),
+                            )
                             .add_m(
                                 f.method("GetManagedDevices", (), move |m| {
                                     if perms::has_monitor_permission_cached(
@@ -492,6 +599,106 @@ impl DbusApi {
                                 .inarg::<u64, _>("device")
                                 .outarg::<String, _>("status"),
                             )
+                            .add_m(
+                                f.method("GetDeviceCapabilities", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap().to_string(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let device: u64 = m.msg.read1()?;
+
+                                        trace!("Querying device [{}] capabilities", device);
+
+                                        let capabilities = query_device_capabilities(device)
+                                            .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+
+                                        let result = serde_json::to_string_pretty(&capabilities)
+                                            .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .outarg::<String, _>("capabilities"),
+                            )
+                            .add_m(
+                                f.method("SetDeviceZone", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap().to_string(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (device, x, y, width, height): (
+                                            u64,
+                                            i32,
+                                            i32,
+                                            i32,
+                                            i32,
+                                        ) = m.msg.read5()?;
+
+                                        debug!(
+                                            "Setting device [{}] zone to {}x{}+{}+{}",
+                                            device, width, height, x, y
+                                        );
+
+                                        let device_id = zones::get_device_id(device)
+                                            .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+
+                                        zones::set_zone(
+                                            device_id,
+                                            zones::Zone::new(x, y, width, height),
+                                        )
+                                        .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+
+                                        script::FRAME_GENERATION_COUNTER
+                                            .fetch_add(1, Ordering::SeqCst);
+
+                                        Ok(vec![m.msg.method_return().append1(true)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .inarg::<i32, _>("x")
+                                .inarg::<i32, _>("y")
+                                .inarg::<i32, _>("width")
+                                .inarg::<i32, _>("height")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("GetDeviceZone", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap().to_string(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let device: u64 = m.msg.read1()?;
+
+                                        trace!("Querying device [{}] zone", device);
+
+                                        let device_id = zones::get_device_id(device)
+                                            .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+
+                                        // an empty zone signals that the device mirrors the canvas
+                                        let zone = zones::get_zone(&device_id)
+                                            .unwrap_or_else(|| zones::Zone::new(0, 0, 0, 0));
+
+                                        Ok(vec![m.msg.method_return().append1((
+                                            zone.x,
+                                            zone.y,
+                                            zone.width,
+                                            zone.height,
+                                        ))])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .outarg::<(i32, i32, i32, i32), _>("zone"),
+                            )
                             .add_m(
                                 f.method("GetManagedDevices", (), move |m| {
                                     if perms::has_monitor_permission_cached(
@@ -1176,7 +1383,79 @@ fn query_device_specific_status(device: u64) -> Result<String> {
 //     Ok(json)
 // }
 
+/// Query the device driver for the capabilities of the device
+fn query_device_capabilities(device: u64) -> Result<hwdevices::DeviceCapabilities> {
+    if (device as usize) < crate::KEYBOARD_DEVICES.read().len() {
+        let device = &crate::KEYBOARD_DEVICES.read()[device as usize];
+
+        Ok(device.read().get_device_capabilities())
+    } else if (device as usize)
+        < (crate::KEYBOARD_DEVICES.read().len() + crate::MOUSE_DEVICES.read().len())
+    {
+        let index = device as usize - crate::KEYBOARD_DEVICES.read().len();
+        let device = &crate::MOUSE_DEVICES.read()[index];
+
+        Ok(device.read().get_device_capabilities())
+    } else if (device as usize)
+        < (crate::KEYBOARD_DEVICES.read().len()
+            + crate::MOUSE_DEVICES.read().len()
+            + crate::MISC_DEVICES.read().len())
+    {
+        let index = device as usize
+            - (crate::KEYBOARD_DEVICES.read().len() + crate::MOUSE_DEVICES.read().len());
+        let device = &crate::MISC_DEVICES.read()[index];
+
+        Ok(device.read().get_device_capabilities())
+    } else {
+        Err(DbusApiError::InvalidDevice {}.into())
+    }
+}
+
+/// All device specific configuration parameters, regardless of whether a device supports them
+const DEVICE_SPECIFIC_PARAMETERS: &[&str] = &[
+    "info",
+    "firmware",
+    "brightness",
+    "profile",
+    "dpi",
+    "rate",
+    "dcu",
+    "angle-snapping",
+    "debounce",
+];
+
+/// Reject parameters and values that are not declared in the capabilities of the device
+fn validate_device_specific_configuration(device: u64, param: &str, value: &str) -> Result<()> {
+    let capabilities = query_device_capabilities(device)?;
+
+    validate_parameter(&capabilities, param, value)
+}
+
+pub(crate) fn validate_parameter(
+    capabilities: &hwdevices::DeviceCapabilities,
+    param: &str,
+    value: &str,
+) -> Result<()> {
+    if !capabilities.supports(param) {
+        if DEVICE_SPECIFIC_PARAMETERS.contains(&param) {
+            return Err(DbusApiError::OpNotSupported {}.into());
+        } else {
+            return Err(DbusApiError::InvalidParameter {}.into());
+        }
+    }
+
+    if let Ok(value) = value.parse::<i32>() {
+        if !capabilities.accepts(param, value) {
+            return Err(DbusApiError::InvalidValue {}.into());
+        }
+    }
+
+    Ok(())
+}
+
 fn apply_device_specific_configuration(device: u64, param: &str, value: &str) -> Result<()> {
+    validate_device_specific_configuration(device, param, value)?;
+
     if (device as usize) < crate::KEYBOARD_DEVICES.read().len() {
         let device = &crate::KEYBOARD_DEVICES.read()[device as usize];
 
@@ -1279,6 +1558,10 @@ fn apply_device_specific_configuration(device: u64, param: &str, value: &str) ->
 }
 
 fn query_device_specific_configuration(device: u64, param: &str) -> Result<String> {
+    if !query_device_capabilities(device)?.supports(param) {
+        return Err(DbusApiError::InvalidParameter {}.into());
+    }
+
     if (device as usize) < crate::KEYBOARD_DEVICES.read().len() {
         let device = &crate::KEYBOARD_DEVICES.read()[device as usize];
 
@@ -2508,3 +2791,27 @@ mod perms {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reject_unsupported_parameters() {
+        let capabilities = hwdevices::DeviceCapabilities::default();
+
+        assert!(validate_parameter(&capabilities, "brightness", "50").is_ok());
+
+        let error = validate_parameter(&capabilities, "dpi", "1").unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<DbusApiError>(),
+            Some(DbusApiError::OpNotSupported {})
+        ));
+
+        let error = validate_parameter(&capabilities, "unknown", "1").unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<DbusApiError>(),
+            Some(DbusApiError::InvalidParameter {})
+        ));
+    }
+}
@@ -19,6 +19,7 @@
 
 use lazy_static::lazy_static;
 use parking_lot::Mutex;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 pub type Result<T> = std::result::Result<T, eyre::Error>;
@@ -43,6 +44,13 @@ pub enum Event {
     MouseButtonUp(u8),
     MouseMove(u8, i32),
     MouseWheelEvent(u8),
+
+    ProfileChanged(Option<PathBuf>),
+    SlotChanged(usize),
+    BrightnessChanged(isize),
+    DeviceHotplug((u16, u16), bool),
+
+    FrameRendered,
 }
 
 pub type Callback = dyn Fn(&Event) -> Result<bool> + Sync + Send + 'static;
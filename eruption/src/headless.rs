@@ -0,0 +1,398 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use crossbeam::channel::{unbounded, Sender};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::imageops::{self, FilterType};
+use image::{Delay, Frame, RgbaImage};
+use log::*;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use crate::hwdevices::RGBA;
+use crate::plugins::AudioPlugin;
+use crate::profiles::Profile;
+use crate::scripting::compositing;
+use crate::{constants, script, util};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum HeadlessError {
+    #[error("Invalid output format: {description}")]
+    InvalidFormat { description: String },
+
+    #[error("Invalid event: {description}")]
+    InvalidEvent { description: String },
+
+    #[error("Either a profile or a script has to be specified")]
+    NothingToRender {},
+
+    #[error("A Lua script terminated while rendering frame {frame}")]
+    ScriptTerminated { frame: usize },
+}
+
+/// File format of the rendered frames
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One PNG image per frame
+    Png,
+
+    /// A single animated GIF image
+    Gif,
+
+    /// One raw RGBA dump of the canvas per frame
+    Raw,
+}
+
+impl FromStr for OutputFormat {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "png" => Ok(Self::Png),
+            "gif" => Ok(Self::Gif),
+            "raw" => Ok(Self::Raw),
+
+            _ => Err(HeadlessError::InvalidFormat {
+                description: s.to_string(),
+            }
+            .into()),
+        }
+    }
+}
+
+/// A synthetic event, that is sent to the Lua scripts before a frame is rendered
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EventKind {
+    KeyDown(u8),
+    KeyUp(u8),
+    AudioLoudness(isize),
+    AudioSpectrum(f32),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SyntheticEvent {
+    pub frame: usize,
+    pub kind: EventKind,
+}
+
+impl FromStr for SyntheticEvent {
+    type Err = eyre::Error;
+
+    /// Parse an event of the form `frame:kind:value`, e.g. `10:key-down:42`
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid_event = || HeadlessError::InvalidEvent {
+            description: s.to_string(),
+        };
+
+        let mut fields = s.splitn(3, ':');
+
+        let frame = fields
+            .next()
+            .and_then(|f| f.parse::<usize>().ok())
+            .ok_or_else(invalid_event)?;
+
+        let kind = fields.next().ok_or_else(invalid_event)?;
+        let value = fields.next().ok_or_else(invalid_event)?;
+
+        let kind = match kind {
+            "key-down" => EventKind::KeyDown(value.parse().map_err(|_e| invalid_event())?),
+            "key-up" => EventKind::KeyUp(value.parse().map_err(|_e| invalid_event())?),
+            "loudness" => EventKind::AudioLoudness(value.parse().map_err(|_e| invalid_event())?),
+            "spectrum" => EventKind::AudioSpectrum(value.parse().map_err(|_e| invalid_event())?),
+
+            _ => return Err(invalid_event().into()),
+        };
+
+        Ok(Self { frame, kind })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Render the active scripts of this profile
+    pub profile: Option<PathBuf>,
+
+    /// Render a single script, with its default parameters
+    pub script: Option<PathBuf>,
+
+    /// Number of frames to render
+    pub frames: usize,
+
+    /// Output directory for PNG and raw frames, or output file for GIF images
+    pub output: PathBuf,
+
+    pub format: OutputFormat,
+
+    /// Scale each LED of the canvas to `scale` x `scale` pixels (PNG and GIF only)
+    pub scale: u32,
+
+    pub events: Vec<SyntheticEvent>,
+}
+
+/// Run the Lua scripts of a profile or a single script without any hardware attached, feed
+/// them synthetic timer ticks and events, and write the rendered frames of the canvas to disk
+pub fn render(options: &RenderOptions) -> Result<()> {
+    let (script_files, profile) = if let Some(profile_file) = &options.profile {
+        let profile = Profile::from(profile_file)?;
+
+        let script_files = profile
+            .get_active_scripts()
+            .iter()
+            .map(util::match_script_path)
+            .collect::<Result<Vec<PathBuf>>>()?;
+
+        (script_files, Some(profile))
+    } else if let Some(script_file) = &options.script {
+        (vec![util::match_script_path(script_file)?], None)
+    } else {
+        return Err(HeadlessError::NothingToRender {}.into());
+    };
+
+    let mut lua_txs: Vec<Sender<script::Message>> = Vec::new();
+    let mut threads = Vec::new();
+
+    for script_path in script_files {
+        info!("Loading Lua script: {}", script_path.display());
+
+        let (lua_tx, lua_rx) = unbounded();
+        let profile = profile.clone();
+
+        let thread = thread::Builder::new()
+            .name(format!(
+                "headless:{}",
+                script_path.file_name().unwrap().to_string_lossy()
+            ))
+            .spawn(move || script::run_script(script_path, profile, &lua_rx))?;
+
+        lua_txs.push(lua_tx);
+        threads.push(thread);
+    }
+
+    // use a fixed frame time, so that the output is reproducible
+    let delta = (1000 / constants::TARGET_FPS / constants::TARGET_FPS) as u32;
+
+    let mut frames = Vec::with_capacity(options.frames);
+
+    for frame in 0..options.frames {
+        for event in options.events.iter().filter(|e| e.frame == frame) {
+            match event.kind {
+                EventKind::KeyDown(index) => {
+                    send(&lua_txs, script::Message::KeyDown(index), frame)?
+                }
+                EventKind::KeyUp(index) => send(&lua_txs, script::Message::KeyUp(index), frame)?,
+                EventKind::AudioLoudness(loudness) => AudioPlugin::set_audio_loudness(loudness),
+                EventKind::AudioSpectrum(level) => AudioPlugin::set_audio_spectrum(level),
+            }
+        }
+
+        send(&lua_txs, script::Message::Tick(delta), frame)?;
+
+        let mut led_map = realize_color_maps(&lua_txs, frame)?;
+
+        if let Some(profile) = &profile {
+            let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
+
+            compositing::apply_overrides(&mut led_map, &profile.get_overrides(), brightness as i32);
+        }
+
+        frames.push(led_map);
+    }
+
+    for lua_tx in lua_txs.iter() {
+        lua_tx
+            .send(script::Message::Unload)
+            .unwrap_or_else(|e| error!("Could not send an event to a Lua VM: {}", e));
+    }
+
+    for thread in threads {
+        if let Ok(Ok(script::RunScriptResult::TerminatedWithErrors)) | Ok(Err(_)) = thread.join() {
+            warn!("A Lua script terminated with errors");
+        }
+    }
+
+    match options.format {
+        OutputFormat::Png => write_png_frames(&frames, options),
+        OutputFormat::Gif => write_gif(&frames, options),
+        OutputFormat::Raw => write_raw_frames(&frames, options),
+    }
+}
+
+/// Send the message `msg` to all Lua VMs
+fn send(lua_txs: &[Sender<script::Message>], msg: script::Message, frame: usize) -> Result<()> {
+    for lua_tx in lua_txs.iter() {
+        lua_tx
+            .send(msg.clone())
+            .map_err(|_e| HeadlessError::ScriptTerminated { frame })?;
+    }
+
+    Ok(())
+}
+
+/// Instruct the Lua VMs to blend their color maps with the canvas, in order, and return a copy
+/// of the resulting canvas. Unlike the main loop we never drop a frame, so wait until all of the
+/// Lua VMs are done, or one of them terminated
+fn realize_color_maps(lua_txs: &[Sender<script::Message>], frame: usize) -> Result<Vec<RGBA>> {
+    script::LED_MAP.write().fill(RGBA {
+        r: 0,
+        g: 0,
+        b: 0,
+        a: 0,
+    });
+
+    *crate::COLOR_MAPS_READY_CONDITION.0.lock() = lua_txs.len();
+
+    for (index, lua_tx) in lua_txs.iter().enumerate() {
+        let remaining = lua_txs.len() - index - 1;
+
+        let mut pending = crate::COLOR_MAPS_READY_CONDITION.0.lock();
+
+        lua_tx
+            .send(script::Message::RealizeColorMap)
+            .map_err(|_e| HeadlessError::ScriptTerminated { frame })?;
+
+        while *pending > remaining {
+            let result = crate::COLOR_MAPS_READY_CONDITION.1.wait_for(
+                &mut pending,
+                Duration::from_millis(constants::SCRIPT_TIME_LIMIT_MILLIS),
+            );
+
+            if result.timed_out() {
+                return Err(HeadlessError::ScriptTerminated { frame }.into());
+            }
+        }
+    }
+
+    Ok(script::LED_MAP.read().clone())
+}
+
+/// Convert the canvas `led_map` to an image, the LEDs don't have an alpha channel
+fn to_image(led_map: &[RGBA], scale: u32) -> RgbaImage {
+    let width = crate::CANVAS_WIDTH.load(Ordering::SeqCst) as u32;
+    let height = crate::CANVAS_HEIGHT.load(Ordering::SeqCst) as u32;
+
+    let image = RgbaImage::from_fn(width, height, |x, y| {
+        let color = led_map[(y * width + x) as usize];
+
+        image::Rgba([color.r, color.g, color.b, 0xff])
+    });
+
+    imageops::resize(
+        &image,
+        width * scale.max(1),
+        height * scale.max(1),
+        FilterType::Nearest,
+    )
+}
+
+fn write_png_frames(frames: &[Vec<RGBA>], options: &RenderOptions) -> Result<()> {
+    fs::create_dir_all(&options.output)?;
+
+    for (index, led_map) in frames.iter().enumerate() {
+        let path = options.output.join(format!("frame-{:05}.png", index));
+
+        to_image(led_map, options.scale).save(&path)?;
+    }
+
+    info!(
+        "Wrote {} frames to {}",
+        frames.len(),
+        options.output.display()
+    );
+
+    Ok(())
+}
+
+fn write_gif(frames: &[Vec<RGBA>], options: &RenderOptions) -> Result<()> {
+    let mut encoder = GifEncoder::new(File::create(&options.output)?);
+    encoder.set_repeat(Repeat::Infinite)?;
+
+    let delay =
+        Delay::from_saturating_duration(Duration::from_millis(1000 / constants::TARGET_FPS));
+
+    encoder.encode_frames(
+        frames
+            .iter()
+            .map(|led_map| Frame::from_parts(to_image(led_map, options.scale), 0, 0, delay)),
+    )?;
+
+    info!(
+        "Wrote {} frames to {}",
+        frames.len(),
+        options.output.display()
+    );
+
+    Ok(())
+}
+
+fn write_raw_frames(frames: &[Vec<RGBA>], options: &RenderOptions) -> Result<()> {
+    fs::create_dir_all(&options.output)?;
+
+    for (index, led_map) in frames.iter().enumerate() {
+        let path = options.output.join(format!("frame-{:05}.rgba", index));
+
+        let data = led_map
+            .iter()
+            .flat_map(|color| [color.r, color.g, color.b, color.a])
+            .collect::<Vec<u8>>();
+
+        fs::write(&path, data)?;
+    }
+
+    info!(
+        "Wrote {} frames to {}",
+        frames.len(),
+        options.output.display()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_synthetic_events() {
+        assert_eq!(
+            "10:key-down:42".parse::<SyntheticEvent>().unwrap(),
+            SyntheticEvent {
+                frame: 10,
+                kind: EventKind::KeyDown(42)
+            }
+        );
+
+        assert_eq!(
+            "0:loudness:500".parse::<SyntheticEvent>().unwrap(),
+            SyntheticEvent {
+                frame: 0,
+                kind: EventKind::AudioLoudness(500)
+            }
+        );
+
+        assert!("10:key-down".parse::<SyntheticEvent>().is_err());
+        assert!("x:key-up:1".parse::<SyntheticEvent>().is_err());
+        assert!("1:mouse-move:1".parse::<SyntheticEvent>().is_err());
+    }
+}
@@ -0,0 +1,240 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use nix::poll::{poll, PollFd, PollFlags};
+use parking_lot::Mutex;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::constants;
+use crate::hwdevices;
+use crate::plugins::sdk_support::{self, HotplugInfo};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Devices that have been unplugged, identified by the path of their hidraw device node,
+    /// since multiple devices may share the same USB IDs. They will be released the next
+    /// time the main loop is re-entered
+    pub static ref PENDING_REMOVALS: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HotplugError {
+    #[error("Could not parse the USB product identifier: {product}")]
+    ParseProductError { product: String },
+}
+
+/// A device hotplug event, as received from udev
+#[derive(Debug, Clone, PartialEq)]
+pub enum HotplugEvent {
+    /// A USB device has been connected
+    Added { usb_vid: u16, usb_pid: u16 },
+
+    /// A hidraw device node has been removed, e.g. `/dev/hidraw3`
+    Removed { devnode: String },
+}
+
+/// Parse the `PRODUCT` property of an udev USB device event, e.g. `1e7d/3098/101`
+fn parse_product(product: &str) -> Result<(u16, u16)> {
+    let mut parts = product.split('/');
+
+    let mut next = || {
+        parts
+            .next()
+            .and_then(|v| u16::from_str_radix(v, 16).ok())
+            .ok_or_else(|| HotplugError::ParseProductError {
+                product: product.to_string(),
+            })
+    };
+
+    Ok((next()?, next()?))
+}
+
+fn parse_event(event: &udev::Event) -> Option<HotplugEvent> {
+    let device = event.device();
+    let subsystem = device.subsystem()?.to_string_lossy().to_string();
+
+    match (event.event_type(), subsystem.as_str()) {
+        (udev::EventType::Add, "usb") => {
+            let product = device
+                .property_value("PRODUCT")?
+                .to_string_lossy()
+                .to_string();
+
+            let (usb_vid, usb_pid) = parse_product(&product)
+                .map_err(|e| debug!("Ignoring udev event: {}", e))
+                .ok()?;
+
+            Some(HotplugEvent::Added { usb_vid, usb_pid })
+        }
+
+        // the USB device may not be identified by its USB IDs alone, so use the
+        // hidraw device nodes of the device, that are removed along with it
+        (udev::EventType::Remove, "hidraw") => {
+            let devnode = device.devnode()?.to_string_lossy().to_string();
+
+            Some(HotplugEvent::Removed { devnode })
+        }
+
+        _ => None,
+    }
+}
+
+/// Returns `true` if one of the bound devices uses the hidraw device node `devnode`
+fn is_bound_device(devnode: &str) -> bool {
+    crate::KEYBOARD_DEVICES
+        .read()
+        .iter()
+        .any(|d| d.read().get_usb_path() == devnode)
+        || crate::MOUSE_DEVICES
+            .read()
+            .iter()
+            .any(|d| d.read().get_usb_path() == devnode)
+        || crate::MISC_DEVICES
+            .read()
+            .iter()
+            .any(|d| d.read().get_usb_path() == devnode)
+}
+
+/// Returns `true` if we have a driver for the device, and the device has not been blacklisted
+fn is_supported_device(usb_vid: u16, usb_pid: u16) -> bool {
+    let has_driver = hwdevices::DRIVERS
+        .lock()
+        .iter()
+        .any(|d| d.get_usb_vid() == usb_vid && d.get_usb_pid() == usb_pid);
+
+    has_driver && !hwdevices::is_device_blacklisted(usb_vid, usb_pid).unwrap_or(true)
+}
+
+fn process_event(event: HotplugEvent) {
+    // the devices will be in an indeterminate state after a resume from system sleep,
+    // so leave them to eruption-hotplug-helper, which will restart the daemon
+    if Path::new("/run/lock/eruption-sleep.lock").exists() {
+        debug!("Ignoring hotplug event during system sleep: {:?}", event);
+        return;
+    }
+
+    match event {
+        HotplugEvent::Added { usb_vid, usb_pid } => {
+            if is_supported_device(usb_vid, usb_pid) {
+                info!(
+                    "A supported device has been connected: 0x{:04x}:0x{:04x}",
+                    usb_vid, usb_pid
+                );
+
+                // give the HID interfaces of the device some time to appear
+                thread::sleep(Duration::from_millis(constants::HOTPLUG_SETTLE_MILLIS));
+
+                sdk_support::claim_hotplugged_devices(&HotplugInfo { usb_vid, usb_pid })
+                    .unwrap_or_else(|e| error!("Could not bind the hotplugged device: {}", e));
+            }
+        }
+
+        HotplugEvent::Removed { devnode } => {
+            if is_bound_device(&devnode) {
+                info!("A bound device has been disconnected: {}", devnode);
+
+                PENDING_REMOVALS.lock().push(devnode);
+
+                // the devices will be released when the main loop is re-entered
+                crate::REENTER_MAIN_LOOP.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+/// Returns `true` if the device with the hidraw device node `devnode` shall be released,
+/// because it has been unplugged
+pub fn is_removal_pending(devnode: &str) -> bool {
+    PENDING_REMOVALS.lock().iter().any(|d| d == devnode)
+}
+
+/// Forget about pending removals of devices that have been released
+pub fn clear_pending_removals() {
+    PENDING_REMOVALS.lock().clear();
+}
+
+/// Spawn a thread that listens for udev events of USB and hidraw devices, and binds or releases
+/// supported devices while the daemon is running
+pub fn spawn_udev_monitor_thread() -> Result<()> {
+    let socket = udev::MonitorBuilder::new()?
+        .match_subsystem_devtype("usb", "usb_device")?
+        .match_subsystem("hidraw")?
+        .listen()?;
+
+    thread::Builder::new()
+        .name("udev-monitor".to_owned())
+        .spawn(move || {
+            let mut socket = socket;
+
+            loop {
+                if crate::QUIT.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                let mut poll_fds = [PollFd::new(socket.as_raw_fd(), PollFlags::POLLIN)];
+
+                match poll(&mut poll_fds, constants::HOTPLUG_POLL_TIMEOUT_MILLIS) {
+                    Ok(result) if result > 0 => {
+                        for event in socket.by_ref() {
+                            if let Some(event) = parse_event(&event) {
+                                debug!("Received a udev event: {:?}", event);
+
+                                process_event(event);
+                            }
+                        }
+                    }
+
+                    Ok(_) => { /* timeout */ }
+
+                    Err(e) => {
+                        error!("Could not poll the udev monitor socket: {}", e);
+                        break;
+                    }
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_product() {
+        assert_eq!(
+            super::parse_product("1e7d/3098/101").unwrap(),
+            (0x1e7d, 0x3098)
+        );
+        assert_eq!(
+            super::parse_product("1b1c/1b15/0").unwrap(),
+            (0x1b1c, 0x1b15)
+        );
+
+        assert!(super::parse_product("1e7d").is_err());
+        assert!(super::parse_product("").is_err());
+    }
+}
@@ -0,0 +1,173 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use serde::{Deserialize, Serialize};
+
+/// A range of values, e.g. the DPI levels that are supported by a mouse
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValueRange {
+    pub min: i32,
+    pub max: i32,
+    pub step: i32,
+}
+
+impl ValueRange {
+    // the constructor and `contains` are only used by the daemon, not by the client crates
+    #[allow(dead_code)]
+    pub fn new(min: i32, max: i32, step: i32) -> Self {
+        Self { min, max, step }
+    }
+
+    /// Returns `true` if `value` lies within the range, and is a multiple of `step`
+    #[allow(dead_code)]
+    pub fn contains(&self, value: i32) -> bool {
+        value >= self.min && value <= self.max && (value - self.min) % self.step.max(1) == 0
+    }
+}
+
+/// A named group of LEDs of a device, e.g. the scroll wheel of a mouse
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedZone {
+    pub name: String,
+
+    /// Index of the first LED of the zone, relative to the LEDs of the device
+    pub first_led: usize,
+    pub num_leds: usize,
+}
+
+impl LedZone {
+    #[allow(dead_code)]
+    pub fn new(name: &str, first_led: usize, num_leds: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            first_led,
+            num_leds,
+        }
+    }
+}
+
+/// Status LEDs of a device, that are not part of the LED map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StatusLed {
+    AudioMute,
+    Fx,
+    Volume,
+    NumLock,
+    CapsLock,
+    ScrollLock,
+    GameMode,
+}
+
+/// Describes the features that are supported by a device and its driver. Settings that are
+/// not declared here are rejected by the Eruption daemon
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceCapabilities {
+    /// Number of individually addressable LEDs
+    pub num_leds: usize,
+
+    /// Named groups of LEDs, empty if the device does not expose any
+    pub led_zones: Vec<LedZone>,
+
+    /// Values accepted by the `dpi` parameter
+    pub dpi: Option<ValueRange>,
+
+    /// Polling rates in Hz, accepted by the `rate` parameter
+    pub polling_rates: Vec<i32>,
+
+    /// Number of onboard (hardware) profiles
+    pub num_profiles: i32,
+
+    /// The device reports its battery level
+    pub has_battery: bool,
+
+    /// Status LEDs that may be switched on and off
+    pub status_leds: Vec<StatusLed>,
+
+    pub has_dcu_config: bool,
+    pub has_angle_snapping: bool,
+    pub has_debounce: bool,
+}
+
+impl DeviceCapabilities {
+    /// Returns `true` if the device specific configuration parameter `param` is supported
+    pub fn supports(&self, param: &str) -> bool {
+        match param {
+            "info" | "firmware" | "brightness" => true,
+
+            "profile" => self.num_profiles > 0,
+            "dpi" => self.dpi.is_some(),
+            "rate" => !self.polling_rates.is_empty(),
+            "dcu" => self.has_dcu_config,
+            "angle-snapping" => self.has_angle_snapping,
+            "debounce" => self.has_debounce,
+
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if `value` is valid for the supported parameter `param`
+    #[allow(dead_code)]
+    pub fn accepts(&self, param: &str, value: i32) -> bool {
+        match param {
+            "profile" => value >= 0 && value < self.num_profiles,
+            "dpi" => self.dpi.map_or(false, |dpi| dpi.contains(value)),
+            "rate" => self.polling_rates.contains(&value),
+
+            _ => self.supports(param),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supported_parameters() {
+        let capabilities = DeviceCapabilities {
+            dpi: Some(ValueRange::new(0, 4, 1)),
+            polling_rates: vec![125, 250, 500, 1000],
+            num_profiles: 5,
+            ..Default::default()
+        };
+
+        assert!(capabilities.supports("brightness"));
+        assert!(capabilities.supports("rate"));
+        assert!(!capabilities.supports("debounce"));
+        assert!(!capabilities.supports("unknown"));
+
+        assert!(capabilities.accepts("rate", 500));
+        assert!(!capabilities.accepts("rate", 300));
+        assert!(capabilities.accepts("profile", 4));
+        assert!(!capabilities.accepts("profile", 5));
+        assert!(!capabilities.accepts("dpi", -1));
+    }
+
+    #[test]
+    fn value_range_steps() {
+        let range = ValueRange::new(50, 16000, 50);
+
+        assert!(range.contains(50));
+        assert!(range.contains(800));
+        assert!(!range.contains(825));
+        assert!(!range.contains(16050));
+    }
+}
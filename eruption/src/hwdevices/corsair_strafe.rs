@@ -29,8 +29,9 @@ use std::{sync::Arc, thread};
 use crate::constants;
 
 use super::{
-    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError, KeyboardDevice,
-    KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind, MouseDeviceTrait, RGBA,
+    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HidTransport, HwDeviceError,
+    KeyboardDevice, KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind,
+    MouseDeviceTrait, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -106,7 +107,7 @@ pub struct CorsairStrafe {
 
     pub is_opened: bool,
     // pub ctrl_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
-    pub led_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
+    pub led_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
 
     pub has_failed: bool,
 
@@ -136,6 +137,24 @@ impl CorsairStrafe {
         }
     }
 
+    /// Binds the driver to already opened transports, used by the tests to drive it with mocks
+    #[cfg(test)]
+    pub fn with_transport(led_dev: Box<dyn HidTransport>) -> Self {
+        Self {
+            is_initialized: false,
+
+            is_bound: true,
+            led_hiddev_info: None,
+
+            is_opened: true,
+            led_hiddev: Arc::new(Mutex::new(Some(led_dev))),
+
+            has_failed: false,
+
+            brightness: 100,
+        }
+    }
+
     // pub(self) fn query_ctrl_report(&mut self, id: u8) -> Result<()> {
     //     trace!("Querying control device feature report");
 
@@ -331,7 +350,10 @@ impl CorsairStrafe {
 
 impl DeviceInfoTrait for CorsairStrafe {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities {}
+        DeviceCapabilities {
+            num_leds: NUM_KEYS,
+            ..Default::default()
+        }
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -422,7 +444,7 @@ impl DeviceTrait for CorsairStrafe {
             trace!("Opening LED device...");
 
             match self.led_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.led_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.led_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
@@ -1276,3 +1298,34 @@ pub const COLS_TOPOLOGY: [u8; 252] = [
 	0x7c, 0x7d, 0x7e, 0x7f, 0x80, 0xff,
 	0x81, 0x82, 0x83, 0xff, 0xff, 0xff
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwdevices::transport::mock::{led_map, MockTransport};
+
+    #[test]
+    fn send_led_map() {
+        let led_dev = MockTransport::new();
+
+        let mut device = CorsairStrafe::with_transport(Box::new(led_dev.clone()));
+        device.is_initialized = true;
+
+        device.send_led_map(&led_map(&[])).unwrap();
+
+        let packets = led_dev.written_reports();
+
+        assert_eq!(packets.len(), 5);
+        assert!(packets.iter().all(|p| p.len() == 64));
+
+        // four data packets, followed by the commit packet
+        assert_eq!(packets[0][0..4], [0x7f, 0x01, 0x3c, 0x00]);
+        assert_eq!(packets[1][0..4], [0x7f, 0x02, 0x3c, 0x00]);
+        assert_eq!(packets[2][0..4], [0x7f, 0x03, 0x3c, 0x00]);
+        assert_eq!(packets[3][0..4], [0x7f, 0x04, 0x30, 0x00]);
+        assert_eq!(packets[4][0..5], [0x07, 0x27, 0x00, 0x00, 0xd8]);
+
+        // the colors are inverted, black keys have all of their bits set
+        assert!(packets[0][4..64].iter().all(|b| *b == 0xff));
+    }
+}
@@ -61,7 +61,10 @@ impl CustomSerialLeds {
 
 impl DeviceInfoTrait for CustomSerialLeds {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities {}
+        DeviceCapabilities {
+            num_leds: NUM_LEDS,
+            ..Default::default()
+        }
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -0,0 +1,1017 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use evdev_rs::enums::EV_KEY;
+use hidapi::HidApi;
+use log::*;
+use parking_lot::{Mutex, RwLock};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{any::Any, thread};
+
+use crate::constants;
+
+use super::{
+    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, DriverMetadata, HidTransport,
+    HwDeviceError, KeyboardDevice, KeyboardDeviceTrait, KeyboardDriver, KeyboardHidEvent,
+    KeyboardHidEventCode, LedKind, MaturityLevel, MiscDevice, MiscDeviceTrait, MiscDriver,
+    MouseDeviceTrait, RGBA,
+};
+
+pub type Result<T> = super::Result<T>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DeclarativeError {
+    #[error("Invalid device definition: {description}")]
+    InvalidDefinition { description: String },
+}
+
+/// The device classes that may be described by a device definition file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum DefinitionClass {
+    #[serde(rename = "keyboard")]
+    Keyboard,
+    #[serde(rename = "misc")]
+    Misc,
+}
+
+/// The way a report is transferred to the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ReportType {
+    #[serde(rename = "feature")]
+    Feature,
+    #[serde(rename = "output")]
+    Output,
+}
+
+/// The `[device]` section of a device definition file
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceSection {
+    pub make: String,
+    pub name: String,
+
+    pub usb_vid: u16,
+    pub usb_pid: u16,
+
+    pub class: DefinitionClass,
+
+    #[serde(default = "default_maturity_level")]
+    pub maturity_level: MaturityLevel,
+
+    /// The support script of the device, e.g. `keyboards/generic_keyboard`
+    pub support_script: Option<String>,
+}
+
+/// A single HID report, e.g. one step of the init sequence
+#[derive(Debug, Clone, Deserialize)]
+pub struct Report {
+    /// The HID interface number the report is sent to
+    pub interface: i32,
+    pub report_type: ReportType,
+    pub data: Vec<u8>,
+
+    /// Time to wait for the device to settle after the report has been sent
+    #[serde(default = "default_delay_millis")]
+    pub delay_millis: u64,
+}
+
+/// Describes the packet layout of the LED map, that is sent to the device on each frame
+#[derive(Debug, Clone, Deserialize)]
+pub struct LedMapSection {
+    /// The HID interface number the LED map is sent to
+    pub interface: i32,
+    pub report_type: ReportType,
+
+    /// Bytes at the start of the LED map buffer, e.g. the report ID
+    #[serde(default)]
+    pub header: Vec<u8>,
+
+    /// Total size of the LED map buffer in bytes, including the header
+    pub size: usize,
+
+    /// Split the buffer into packets of this size, the last packet is padded with zeroes
+    pub packet_size: Option<usize>,
+
+    /// Bytes that are prepended to each packet
+    #[serde(default)]
+    pub packet_prefix: Vec<u8>,
+
+    /// Order of the color channels, a permutation of `rgb`
+    #[serde(default = "default_color_order")]
+    pub color_order: String,
+
+    /// If set, the colors are sorted by channel in blocks of `block_size` LEDs, e.g. the red
+    /// values of all the LEDs of a block come first, then the green values, and so on.
+    /// Otherwise the channels of each LED are interleaved
+    pub block_size: Option<usize>,
+
+    /// The canvas index of each LED, in the order the LEDs appear in the buffer
+    pub leds: Vec<usize>,
+}
+
+/// The `[keyboard]` section of a device definition file
+#[derive(Debug, Clone, Deserialize)]
+pub struct KeyboardSection {
+    pub num_keys: usize,
+    pub num_rows: usize,
+    pub num_cols: usize,
+
+    /// Key indices of the keys in each row, `num_cols` entries per row
+    pub rows_topology: Vec<u8>,
+
+    /// Key indices of the keys in each column, `num_rows` entries per column
+    pub cols_topology: Vec<u8>,
+
+    /// Maps evdev key codes to key indices: `[[code, index], ...]`
+    #[serde(default)]
+    pub key_map: Vec<(u32, u8)>,
+}
+
+/// A data-driven description of a simple RGB peripheral, loaded from a `.toml` file
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceDefinition {
+    pub device: DeviceSection,
+
+    /// Reports that are sent to the device in order, to initialize it
+    #[serde(default)]
+    pub init: Vec<Report>,
+
+    pub led_map: LedMapSection,
+
+    pub keyboard: Option<KeyboardSection>,
+}
+
+fn default_maturity_level() -> MaturityLevel {
+    MaturityLevel::Experimental
+}
+
+fn default_delay_millis() -> u64 {
+    constants::DEVICE_SETTLE_MILLIS
+}
+
+fn default_color_order() -> String {
+    "rgb".to_string()
+}
+
+fn invalid(description: &str) -> eyre::Error {
+    DeclarativeError::InvalidDefinition {
+        description: description.to_string(),
+    }
+    .into()
+}
+
+impl DeviceDefinition {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let toml = fs::read_to_string(path)?;
+
+        Self::parse(&toml)
+    }
+
+    pub fn parse(toml: &str) -> Result<Self> {
+        let result: Self = toml::de::from_str(toml)?;
+        result.validate()?;
+
+        Ok(result)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let led_map = &self.led_map;
+
+        let mut channels = led_map.color_order.chars().collect::<Vec<char>>();
+        channels.sort_unstable();
+
+        if channels != ['b', 'g', 'r'] {
+            return Err(invalid("'color_order' must be a permutation of 'rgb'"));
+        }
+
+        if led_map.packet_size == Some(0) || led_map.block_size == Some(0) {
+            return Err(invalid("'packet_size' and 'block_size' must not be zero"));
+        }
+
+        if led_map.header.len() + led_map.payload_size() > led_map.size {
+            return Err(invalid("The LEDs do not fit into the LED map buffer"));
+        }
+
+        match (self.device.class, &self.keyboard) {
+            (DefinitionClass::Keyboard, Some(keyboard)) => {
+                let size = keyboard.num_rows * keyboard.num_cols;
+
+                if keyboard.rows_topology.len() != size || keyboard.cols_topology.len() != size {
+                    return Err(invalid(
+                        "The topology tables must hold 'num_rows' * 'num_cols' entries",
+                    ));
+                }
+            }
+
+            (DefinitionClass::Keyboard, None) => {
+                return Err(invalid("Keyboards require a [keyboard] section"));
+            }
+
+            (DefinitionClass::Misc, _) => { /* no additional requirements */ }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the distinct HID interface numbers that are used by the device
+    pub fn interfaces(&self) -> Vec<i32> {
+        let mut result = self
+            .init
+            .iter()
+            .map(|report| report.interface)
+            .chain(std::iter::once(self.led_map.interface))
+            .collect::<Vec<i32>>();
+
+        result.sort_unstable();
+        result.dedup();
+
+        result
+    }
+
+    /// Register a driver for the described device. The driver stays registered for the
+    /// lifetime of the daemon, so it borrows the definition for `'static`
+    pub fn register(&'static self) -> Box<(dyn DriverMetadata + Sync + Send + 'static)> {
+        let device = &self.device;
+
+        match device.class {
+            DefinitionClass::Keyboard => KeyboardDriver::register(
+                &device.make,
+                &device.name,
+                device.usb_vid,
+                device.usb_pid,
+                Box::leak(Box::new(
+                    move |hidapi: &HidApi, usb_vid: u16, usb_pid: u16, serial: &str| {
+                        bind_keyboard(hidapi, usb_vid, usb_pid, serial, self)
+                    },
+                )),
+                device.maturity_level,
+            ),
+
+            DefinitionClass::Misc => MiscDriver::register(
+                &device.make,
+                &device.name,
+                device.usb_vid,
+                device.usb_pid,
+                Box::leak(Box::new(
+                    move |hidapi: &HidApi, usb_vid: u16, usb_pid: u16, serial: &str| {
+                        bind_misc(hidapi, usb_vid, usb_pid, serial, self)
+                    },
+                )),
+                device.maturity_level,
+            ),
+        }
+    }
+}
+
+impl LedMapSection {
+    /// Number of bytes occupied by the colors of the LEDs
+    fn payload_size(&self) -> usize {
+        match self.block_size {
+            Some(block_size) => ((self.leds.len() + block_size - 1) / block_size) * block_size * 3,
+            None => self.leds.len() * 3,
+        }
+    }
+
+    /// Encode the colors of the canvas `led_map` into the LED map buffer of the device
+    pub fn encode(&self, led_map: &[RGBA], brightness: i32) -> Vec<u8> {
+        let mut buffer = vec![0; self.size];
+        buffer[..self.header.len()].copy_from_slice(&self.header);
+
+        let offset = self.header.len();
+
+        for (i, canvas_index) in self.leds.iter().enumerate() {
+            let color = led_map.get(*canvas_index).copied().unwrap_or(RGBA {
+                r: 0x00,
+                g: 0x00,
+                b: 0x00,
+                a: 0x00,
+            });
+
+            for (channel_index, channel) in self.color_order.chars().enumerate() {
+                let value = match channel {
+                    'r' => color.r,
+                    'g' => color.g,
+                    _ => color.b,
+                };
+
+                let position = match self.block_size {
+                    Some(block_size) => {
+                        (i / block_size) * block_size * 3
+                            + channel_index * block_size
+                            + i % block_size
+                    }
+
+                    None => i * 3 + channel_index,
+                };
+
+                buffer[offset + position] =
+                    (value as f32 * (brightness as f32 / 100.0)).round() as u8;
+            }
+        }
+
+        buffer
+    }
+
+    /// Split the encoded LED map buffer into the packets that are sent to the device
+    pub fn packets(&self, buffer: &[u8]) -> Vec<Vec<u8>> {
+        let packet_size = self.packet_size.unwrap_or(buffer.len()).max(1);
+
+        buffer
+            .chunks(packet_size)
+            .map(|chunk| {
+                let mut packet = self.packet_prefix.clone();
+
+                packet.extend_from_slice(chunk);
+                packet.resize(self.packet_prefix.len() + packet_size, 0);
+
+                packet
+            })
+            .collect()
+    }
+}
+
+/// Load all device definitions (`*.toml`) from the directories `dirs`.
+/// Invalid definitions will be logged and skipped
+pub fn load_definitions(dirs: &[PathBuf]) -> Vec<DeviceDefinition> {
+    let mut result = Vec::new();
+
+    for dir in dirs {
+        let mut paths = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().map(|e| e == "toml").unwrap_or(false))
+                .collect::<Vec<PathBuf>>(),
+
+            Err(e) => {
+                debug!(
+                    "Skipping the device definition directory {}: {}",
+                    dir.display(),
+                    e
+                );
+                continue;
+            }
+        };
+
+        paths.sort();
+
+        for path in paths {
+            match DeviceDefinition::from_file(&path) {
+                Ok(definition) => {
+                    debug!("Loaded device definition: {}", path.display());
+
+                    result.push(definition)
+                }
+
+                Err(e) => error!(
+                    "Could not load the device definition {}: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+    }
+
+    result
+}
+
+/// Binds the driver to a keyboard device
+pub fn bind_keyboard(
+    hidapi: &HidApi,
+    usb_vid: u16,
+    usb_pid: u16,
+    serial: &str,
+    definition: &'static DeviceDefinition,
+) -> super::Result<KeyboardDevice> {
+    Ok(Arc::new(RwLock::new(Box::new(DeclarativeDevice::bind(
+        hidapi, usb_vid, usb_pid, serial, definition,
+    )?))))
+}
+
+/// Binds the driver to a misc device
+pub fn bind_misc(
+    hidapi: &HidApi,
+    usb_vid: u16,
+    usb_pid: u16,
+    serial: &str,
+    definition: &'static DeviceDefinition,
+) -> super::Result<MiscDevice> {
+    Ok(Arc::new(RwLock::new(Box::new(DeclarativeDevice::bind(
+        hidapi, usb_vid, usb_pid, serial, definition,
+    )?))))
+}
+
+#[derive(Clone)]
+/// Device specific code for devices that are described by a device definition file
+pub struct DeclarativeDevice {
+    pub definition: &'static DeviceDefinition,
+
+    pub is_initialized: bool,
+
+    pub is_bound: bool,
+    pub hiddev_infos: HashMap<i32, hidapi::DeviceInfo>,
+    pub is_opened: bool,
+    pub hiddevs: Arc<Mutex<HashMap<i32, Box<dyn HidTransport>>>>,
+    pub has_failed: bool,
+
+    // device specific configuration options
+    pub brightness: i32,
+
+    // device status
+    pub device_status: DeviceStatus,
+}
+
+impl DeclarativeDevice {
+    /// Binds the driver to the HID interfaces of the device
+    pub fn bind(
+        hidapi: &HidApi,
+        usb_vid: u16,
+        usb_pid: u16,
+        serial: &str,
+        definition: &'static DeviceDefinition,
+    ) -> Result<Self> {
+        let mut hiddev_infos = HashMap::new();
+
+        for interface in definition.interfaces() {
+            let device_info = hidapi
+                .device_list()
+                .find(|&device| {
+                    device.vendor_id() == usb_vid
+                        && device.product_id() == usb_pid
+                        && device.serial_number().unwrap_or("") == serial
+                        && device.interface_number() == interface
+                })
+                .ok_or(HwDeviceError::EnumerationError {})?;
+
+            hiddev_infos.insert(interface, device_info.clone());
+        }
+
+        info!(
+            "Bound driver: {} {} (device definition)",
+            definition.device.make, definition.device.name
+        );
+
+        Ok(Self {
+            definition,
+
+            is_initialized: false,
+
+            is_bound: true,
+            hiddev_infos,
+            is_opened: false,
+            hiddevs: Arc::new(Mutex::new(HashMap::new())),
+            has_failed: false,
+            brightness: 100,
+
+            device_status: DeviceStatus(HashMap::new()),
+        })
+    }
+
+    fn send_report(&self, report: &Report) -> Result<()> {
+        trace!("Sending report to interface {}", report.interface);
+
+        let hiddevs = self.hiddevs.lock();
+        let hiddev = hiddevs
+            .get(&report.interface)
+            .ok_or(HwDeviceError::DeviceNotOpened {})?;
+
+        let result = match report.report_type {
+            ReportType::Feature => hiddev.send_feature_report(&report.data),
+            ReportType::Output => hiddev.write(&report.data).map(|_| ()),
+        };
+
+        match result {
+            Ok(()) => {
+                hexdump::hexdump_iter(&report.data).for_each(|s| trace!("  {}", s));
+
+                Ok(())
+            }
+
+            Err(_) => Err(HwDeviceError::InvalidResult {}.into()),
+        }
+    }
+
+    fn write_led_map(&mut self, led_map: &[RGBA]) -> Result<()> {
+        trace!("Setting LEDs from supplied map...");
+
+        if !self.is_bound {
+            Err(HwDeviceError::DeviceNotBound {}.into())
+        } else if !self.is_opened {
+            Err(HwDeviceError::DeviceNotOpened {}.into())
+        } else if !self.is_initialized {
+            Err(HwDeviceError::DeviceNotInitialized {}.into())
+        } else {
+            let section = &self.definition.led_map;
+            let buffer = section.encode(led_map, self.brightness);
+
+            for data in section.packets(&buffer) {
+                let report = Report {
+                    interface: section.interface,
+                    report_type: section.report_type,
+                    data,
+                    delay_millis: 0,
+                };
+
+                if let Err(e) = self.send_report(&report) {
+                    // the device has failed or has been disconnected
+                    self.is_initialized = false;
+                    self.is_opened = false;
+                    self.has_failed = true;
+
+                    return Err(e);
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    fn write_blank_led_map(&mut self) -> Result<()> {
+        let led_map: [RGBA; constants::CANVAS_SIZE] = [RGBA {
+            r: 0x00,
+            g: 0x00,
+            b: 0x00,
+            a: 0x00,
+        }; constants::CANVAS_SIZE];
+
+        self.write_led_map(&led_map)
+    }
+}
+
+impl DeviceInfoTrait for DeclarativeDevice {
+    fn get_device_capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            num_leds: self.definition.led_map.leds.len(),
+            ..Default::default()
+        }
+    }
+
+    fn get_device_info(&self) -> Result<super::DeviceInfo> {
+        trace!("Querying the device for information...");
+
+        let result = super::DeviceInfo::new(0);
+        Ok(result)
+    }
+
+    fn get_firmware_revision(&self) -> String {
+        "<unknown>".to_string()
+    }
+}
+
+impl DeviceTrait for DeclarativeDevice {
+    fn get_usb_path(&self) -> String {
+        self.hiddev_infos[&self.definition.led_map.interface]
+            .path()
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn get_usb_vid(&self) -> u16 {
+        self.definition.device.usb_vid
+    }
+
+    fn get_usb_pid(&self) -> u16 {
+        self.definition.device.usb_pid
+    }
+
+    fn get_serial(&self) -> Option<&str> {
+        self.hiddev_infos[&self.definition.led_map.interface].serial_number()
+    }
+
+    fn get_support_script_file(&self) -> String {
+        match (
+            &self.definition.device.support_script,
+            self.definition.device.class,
+        ) {
+            (Some(support_script), _) => support_script.clone(),
+
+            (None, DefinitionClass::Keyboard) => "keyboards/generic_keyboard".to_string(),
+            (None, DefinitionClass::Misc) => "misc/generic_misc".to_string(),
+        }
+    }
+
+    fn open(&mut self, api: &hidapi::HidApi) -> Result<()> {
+        trace!("Opening HID devices now...");
+
+        if !self.is_bound {
+            Err(HwDeviceError::DeviceNotBound {}.into())
+        } else {
+            let mut hiddevs = self.hiddevs.lock();
+
+            for (interface, device_info) in self.hiddev_infos.iter() {
+                trace!("Opening interface {}...", interface);
+
+                match device_info.open_device(api) {
+                    Ok(dev) => hiddevs.insert(*interface, Box::new(dev)),
+                    Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
+                };
+            }
+
+            self.is_opened = true;
+
+            Ok(())
+        }
+    }
+
+    fn close_all(&mut self) -> Result<()> {
+        trace!("Closing HID devices now...");
+
+        if !self.is_bound {
+            Err(HwDeviceError::DeviceNotBound {}.into())
+        } else if !self.is_opened {
+            Err(HwDeviceError::DeviceNotOpened {}.into())
+        } else {
+            self.hiddevs.lock().clear();
+
+            self.is_opened = false;
+
+            Ok(())
+        }
+    }
+
+    fn send_init_sequence(&mut self) -> Result<()> {
+        trace!("Sending device init sequence...");
+
+        if !self.is_bound {
+            Err(HwDeviceError::DeviceNotBound {}.into())
+        } else if !self.is_opened {
+            Err(HwDeviceError::DeviceNotOpened {}.into())
+        } else {
+            for (index, report) in self.definition.init.iter().enumerate() {
+                self.send_report(report)
+                    .unwrap_or_else(|e| error!("Step {}: {}", index + 1, e));
+
+                thread::sleep(Duration::from_millis(report.delay_millis));
+            }
+
+            self.is_initialized = true;
+
+            Ok(())
+        }
+    }
+
+    fn is_initialized(&self) -> Result<bool> {
+        Ok(self.is_initialized)
+    }
+
+    fn has_failed(&self) -> Result<bool> {
+        Ok(self.has_failed)
+    }
+
+    fn write_data_raw(&self, buf: &[u8]) -> Result<()> {
+        if !self.is_bound {
+            Err(HwDeviceError::DeviceNotBound {}.into())
+        } else if !self.is_opened {
+            Err(HwDeviceError::DeviceNotOpened {}.into())
+        } else if !self.is_initialized {
+            Err(HwDeviceError::DeviceNotInitialized {}.into())
+        } else {
+            let report = Report {
+                interface: self.definition.led_map.interface,
+                report_type: ReportType::Output,
+                data: buf.to_vec(),
+                delay_millis: 0,
+            };
+
+            self.send_report(&report)
+        }
+    }
+
+    fn read_data_raw(&self, size: usize) -> Result<Vec<u8>> {
+        if !self.is_bound {
+            Err(HwDeviceError::DeviceNotBound {}.into())
+        } else if !self.is_opened {
+            Err(HwDeviceError::DeviceNotOpened {}.into())
+        } else if !self.is_initialized {
+            Err(HwDeviceError::DeviceNotInitialized {}.into())
+        } else {
+            let hiddevs = self.hiddevs.lock();
+            let hiddev = hiddevs
+                .get(&self.definition.led_map.interface)
+                .ok_or(HwDeviceError::DeviceNotOpened {})?;
+
+            let mut buf = Vec::new();
+            buf.resize(size, 0);
+
+            match hiddev.read(buf.as_mut_slice()) {
+                Ok(_result) => {
+                    hexdump::hexdump_iter(&buf).for_each(|s| trace!("  {}", s));
+
+                    Ok(buf)
+                }
+
+                Err(_) => Err(HwDeviceError::InvalidResult {}.into()),
+            }
+        }
+    }
+
+    fn device_status(&self) -> Result<DeviceStatus> {
+        Ok(self.device_status.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_device(&self) -> &dyn DeviceTrait {
+        self
+    }
+
+    fn as_device_mut(&mut self) -> &mut dyn DeviceTrait {
+        self
+    }
+
+    fn as_mouse_device(&self) -> Option<&dyn MouseDeviceTrait> {
+        None
+    }
+
+    fn as_mouse_device_mut(&mut self) -> Option<&mut dyn MouseDeviceTrait> {
+        None
+    }
+}
+
+impl KeyboardDeviceTrait for DeclarativeDevice {
+    fn set_status_led(&self, _led_kind: LedKind, _on: bool) -> Result<()> {
+        trace!("Setting status LED state");
+
+        // status LEDs can not be described by a device definition
+        Ok(())
+    }
+
+    fn set_local_brightness(&mut self, brightness: i32) -> Result<()> {
+        trace!("Setting device specific brightness");
+
+        self.brightness = brightness;
+
+        Ok(())
+    }
+
+    fn get_local_brightness(&self) -> Result<i32> {
+        trace!("Querying device specific brightness");
+
+        Ok(self.brightness)
+    }
+
+    #[inline]
+    fn get_next_event(&self) -> Result<KeyboardHidEvent> {
+        self.get_next_event_timeout(-1)
+    }
+
+    fn get_next_event_timeout(&self, _millis: i32) -> Result<KeyboardHidEvent> {
+        trace!("Querying control device for next event");
+
+        // key events are received via evdev only
+        Err(HwDeviceError::InvalidResult {}.into())
+    }
+
+    fn ev_key_to_key_index(&self, key: EV_KEY) -> u8 {
+        self.definition
+            .keyboard
+            .as_ref()
+            .and_then(|keyboard| {
+                keyboard
+                    .key_map
+                    .iter()
+                    .find(|(code, _)| *code == key as u32)
+            })
+            .map(|(_, index)| *index)
+            .unwrap_or(0)
+    }
+
+    fn hid_event_code_to_key_index(&self, _code: &KeyboardHidEventCode) -> u8 {
+        0
+    }
+
+    fn hid_event_code_to_report(&self, code: &KeyboardHidEventCode) -> u8 {
+        match code {
+            KeyboardHidEventCode::Unknown(code) => *code,
+
+            _ => 0,
+        }
+    }
+
+    fn send_led_map(&mut self, led_map: &[RGBA]) -> Result<()> {
+        self.write_led_map(led_map)
+    }
+
+    fn set_led_init_pattern(&mut self) -> Result<()> {
+        trace!("Setting LED init pattern...");
+
+        self.write_blank_led_map()
+    }
+
+    fn set_led_off_pattern(&mut self) -> Result<()> {
+        trace!("Setting LED off pattern...");
+
+        self.write_blank_led_map()
+    }
+
+    /// Returns the number of keys
+    fn get_num_keys(&self) -> usize {
+        self.definition
+            .keyboard
+            .as_ref()
+            .map(|keyboard| keyboard.num_keys)
+            .unwrap_or(0)
+    }
+
+    /// Returns the number of rows (vertical number of keys)
+    fn get_num_rows(&self) -> usize {
+        self.definition
+            .keyboard
+            .as_ref()
+            .map(|keyboard| keyboard.num_rows)
+            .unwrap_or(0)
+    }
+
+    /// Returns the number of columns (horizontal number of keys)
+    fn get_num_cols(&self) -> usize {
+        self.definition
+            .keyboard
+            .as_ref()
+            .map(|keyboard| keyboard.num_cols)
+            .unwrap_or(0)
+    }
+
+    /// Returns the indices of the keys in row `row`
+    fn get_row_topology(&self, row: usize) -> &'static [u8] {
+        match &self.definition.keyboard {
+            Some(keyboard) if row < keyboard.num_rows => {
+                let idx = row * keyboard.num_cols;
+                &keyboard.rows_topology[idx..(idx + keyboard.num_cols)]
+            }
+
+            _ => &[],
+        }
+    }
+
+    /// Returns the indices of the keys in column `col`
+    fn get_col_topology(&self, col: usize) -> &'static [u8] {
+        match &self.definition.keyboard {
+            Some(keyboard) if col < keyboard.num_cols => {
+                let idx = col * keyboard.num_rows;
+                &keyboard.cols_topology[idx..(idx + keyboard.num_rows)]
+            }
+
+            _ => &[],
+        }
+    }
+}
+
+impl MiscDeviceTrait for DeclarativeDevice {
+    fn has_input_device(&self) -> bool {
+        false
+    }
+
+    fn set_local_brightness(&mut self, brightness: i32) -> Result<()> {
+        trace!("Setting device specific brightness");
+
+        self.brightness = brightness;
+
+        Ok(())
+    }
+
+    fn get_local_brightness(&self) -> Result<i32> {
+        trace!("Querying device specific brightness");
+
+        Ok(self.brightness)
+    }
+
+    fn send_led_map(&mut self, led_map: &[RGBA]) -> Result<()> {
+        self.write_led_map(led_map)
+    }
+
+    fn set_led_init_pattern(&mut self) -> Result<()> {
+        trace!("Setting LED init pattern...");
+
+        self.write_blank_led_map()
+    }
+
+    fn set_led_off_pattern(&mut self) -> Result<()> {
+        trace!("Setting LED off pattern...");
+
+        self.write_blank_led_map()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(r: u8, g: u8, b: u8) -> RGBA {
+        RGBA { r, g, b, a: 0xff }
+    }
+
+    #[test]
+    fn load_example_definition() {
+        let path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+        let definitions = load_definitions(&[path.join("../support/tests/assets/devices/")]);
+
+        assert_eq!(definitions.len(), 1);
+
+        let definition = &definitions[0];
+
+        assert_eq!(definition.device.usb_vid, 0x1234);
+        assert_eq!(definition.device.class, DefinitionClass::Misc);
+        assert_eq!(definition.interfaces(), vec![0]);
+        assert_eq!(definition.init.len(), 2);
+    }
+
+    #[test]
+    fn encode_planar_led_map() {
+        let definition = DeviceDefinition::parse(
+            r#"
+            [device]
+            make = "Example"
+            name = "Example Keyboard"
+            usb_vid = 0x1234
+            usb_pid = 0x5678
+            class = "keyboard"
+
+            [led_map]
+            interface = 3
+            report_type = "output"
+            header = [0xa1, 0x01]
+            size = 16
+            packet_size = 8
+            packet_prefix = [0x00]
+            block_size = 2
+            leds = [0, 1, 2]
+
+            [keyboard]
+            num_keys = 3
+            num_rows = 1
+            num_cols = 3
+            rows_topology = [0, 1, 2]
+            cols_topology = [0, 1, 2]
+            key_map = [[1, 1]]
+            "#,
+        )
+        .unwrap();
+
+        let led_map = [color(1, 2, 3), color(4, 5, 6), color(7, 8, 9)];
+        let buffer = definition.led_map.encode(&led_map, 100);
+
+        assert_eq!(
+            buffer,
+            vec![0xa1, 0x01, 1, 4, 2, 5, 3, 6, 7, 0, 8, 0, 9, 0, 0, 0]
+        );
+
+        let packets = definition.led_map.packets(&buffer);
+
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[1], vec![0x00, 7, 0, 8, 0, 9, 0, 0, 0]);
+    }
+
+    #[test]
+    fn reject_invalid_definitions() {
+        let definition = r#"
+            [device]
+            make = "Example"
+            name = "Example Pad"
+            usb_vid = 0x1234
+            usb_pid = 0x5678
+            class = "keyboard"
+
+            [led_map]
+            interface = 0
+            report_type = "feature"
+            size = 4
+            leds = [0, 1]
+            "#;
+
+        // the LEDs do not fit, and the [keyboard] section is missing
+        assert!(DeviceDefinition::parse(definition).is_err());
+        assert!(DeviceDefinition::parse(&definition.replace("size = 4", "size = 6")).is_err());
+
+        assert!(DeviceDefinition::parse(
+            &definition
+                .replace("size = 4", "size = 6")
+                .replace("\"keyboard\"", "\"misc\"")
+        )
+        .is_ok());
+    }
+}
@@ -81,7 +81,7 @@ impl GenericKeyboard {
 
 impl DeviceInfoTrait for GenericKeyboard {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities {}
+        DeviceCapabilities::default()
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -75,7 +75,7 @@ impl GenericMouse {
 
 impl DeviceInfoTrait for GenericMouse {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities {}
+        DeviceCapabilities::default()
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -29,8 +29,10 @@ use std::{any::Any, sync::Arc, thread};
 use std::{path::PathBuf, time::Duration};
 use udev::Enumerator;
 
+mod capabilities;
 mod corsair_strafe;
 mod custom_serial_leds;
+mod declarative;
 mod generic_keyboard;
 mod generic_mouse;
 mod roccat_aimo_pad;
@@ -50,6 +52,10 @@ mod roccat_vulcan_1xx;
 mod roccat_vulcan_pro;
 mod roccat_vulcan_pro_tkl;
 mod roccat_vulcan_tkl;
+mod transport;
+
+pub use capabilities::{DeviceCapabilities, LedZone, StatusLed, ValueRange};
+pub use transport::HidTransport;
 
 pub type KeyboardDevice = Arc<RwLock<Box<dyn KeyboardDeviceTrait + Sync + Send>>>;
 pub type MouseDevice = Arc<RwLock<Box<dyn MouseDeviceTrait + Sync + Send>>>;
@@ -71,7 +77,7 @@ pub type Result<T> = std::result::Result<T, eyre::Error>;
 #[rustfmt::skip]
 lazy_static! {
     // List of supported devices
-    pub static ref DRIVERS: Arc<Mutex<[Box<(dyn DriverMetadata + Sync + Send + 'static)>; 25]>> = Arc::new(Mutex::new([
+    pub static ref DRIVERS: Arc<Mutex<Vec<Box<(dyn DriverMetadata + Sync + Send + 'static)>>>> = Arc::new(Mutex::new(vec![
         // Supported keyboards
 
         // ROCCAT
@@ -137,6 +143,9 @@ lazy_static! {
 
         // Eruption Custom Hardware
         // MiscSerialDriver::register("Eruption", "Custom Serial LEDs", &custom_serial_leds::bind_serial, Status::Testing),
+
+        // Drivers for devices that are described by device definition files will be
+        // appended by `register_declarative_drivers()`
     ]));
 }
 
@@ -183,6 +192,9 @@ pub enum HwDeviceError {
 
     #[error("Could not map an evdev event code to a key or button")]
     MappingError {},
+
+    #[error("Invalid value: {description}")]
+    ValueError { description: String },
 }
 
 pub trait DriverMetadata {
@@ -567,10 +579,6 @@ impl Into<u8> for LedKind {
     }
 }
 
-/// Generic Device capabilities
-#[derive(Debug, Clone)]
-pub struct DeviceCapabilities {}
-
 /// Generic Device info
 #[derive(Debug, Clone)]
 pub struct DeviceInfo {
@@ -899,6 +907,40 @@ pub fn is_device_blacklisted(vid: u16, pid: u16) -> Result<bool> {
         Ok(false)
     }
 }
+
+/// Load the device definition files, and register a driver for each of the described devices.
+/// Built-in drivers take precedence over device definitions with the same USB VID/PID
+pub fn register_declarative_drivers() {
+    let mut drivers = DRIVERS.lock();
+
+    for definition in declarative::load_definitions(&crate::util::get_device_definition_dirs()) {
+        let usb_vid = definition.device.usb_vid;
+        let usb_pid = definition.device.usb_pid;
+
+        if drivers
+            .iter()
+            .any(|d| d.get_usb_vid() == usb_vid && d.get_usb_pid() == usb_pid)
+        {
+            warn!(
+                "Ignoring the device definition of {} {}: A driver for 0x{:04x}:0x{:04x} is already registered",
+                definition.device.make, definition.device.name, usb_vid, usb_pid
+            );
+
+            continue;
+        }
+
+        info!(
+            "Registering driver for device definition: {} {} (0x{:04x}:0x{:04x})",
+            definition.device.make, definition.device.name, usb_vid, usb_pid
+        );
+
+        // drivers stay registered until the daemon exits
+        let definition: &'static declarative::DeviceDefinition = Box::leak(Box::new(definition));
+
+        drivers.push(definition.register());
+    }
+}
+
 /// Returns a Vec of non plug and play devices declared in eruption.conf
 pub fn get_non_pnp_devices() -> Result<Vec<NonPnPDevice>> {
     let mut result = vec![];
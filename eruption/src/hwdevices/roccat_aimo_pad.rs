@@ -29,8 +29,8 @@ use std::{mem::size_of, sync::Arc};
 use crate::constants;
 
 use super::{
-    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError, MiscDevice,
-    MiscDeviceTrait, MouseDeviceTrait, RGBA,
+    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HidTransport, HwDeviceError,
+    MiscDevice, MiscDeviceTrait, MouseDeviceTrait, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -98,7 +98,7 @@ pub struct RoccatAimoPad {
     pub ctrl_hiddev_info: Option<hidapi::DeviceInfo>,
     // pub led_hiddev_info: Option<hidapi::DeviceInfo>,
     pub is_opened: bool,
-    pub ctrl_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
+    pub ctrl_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
     // pub led_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
     pub has_failed: bool,
 
@@ -130,6 +130,23 @@ impl RoccatAimoPad {
         }
     }
 
+    /// Binds the driver to already opened transports, used by the tests to drive it with mocks
+    #[cfg(test)]
+    pub fn with_transport(ctrl_dev: Box<dyn HidTransport>) -> Self {
+        Self {
+            is_initialized: false,
+
+            is_bound: true,
+            ctrl_hiddev_info: None,
+            is_opened: true,
+            ctrl_hiddev: Arc::new(Mutex::new(Some(ctrl_dev))),
+            has_failed: false,
+            brightness: 100,
+
+            device_status: DeviceStatus(HashMap::new()),
+        }
+    }
+
     // pub(self) fn query_ctrl_report(&mut self, id: u8) -> Result<()> {
     //     trace!("Querying control device feature report");
     //
@@ -293,7 +310,10 @@ impl RoccatAimoPad {
 
 impl DeviceInfoTrait for RoccatAimoPad {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities {}
+        DeviceCapabilities {
+            num_leds: 2,
+            ..Default::default()
+        }
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -374,7 +394,7 @@ impl DeviceTrait for RoccatAimoPad {
             trace!("Opening control device...");
 
             match self.ctrl_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.ctrl_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.ctrl_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
@@ -660,3 +680,43 @@ impl MiscDeviceTrait for RoccatAimoPad {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwdevices::transport::mock::MockTransport;
+
+    #[test]
+    fn send_led_map() {
+        let ctrl_dev = MockTransport::new();
+        let mut device = RoccatAimoPad::with_transport(Box::new(ctrl_dev.clone()));
+        device.is_initialized = true;
+
+        let mut led_map = [RGBA {
+            r: 0x00,
+            g: 0x00,
+            b: 0x00,
+            a: 0x00,
+        }; constants::CANVAS_SIZE];
+
+        led_map[LED_0] = RGBA {
+            r: 0x10,
+            g: 0x20,
+            b: 0x30,
+            a: 0xff,
+        };
+        led_map[LED_1] = RGBA {
+            r: 0x40,
+            g: 0x50,
+            b: 0x60,
+            a: 0x0f,
+        };
+
+        device.send_led_map(&led_map).unwrap();
+
+        assert_eq!(
+            ctrl_dev.sent_feature_reports(),
+            vec![vec![0x03, 0x10, 0x20, 0x30, 0x00, 0x40, 0x50, 0x60, 0xf0]]
+        );
+    }
+}
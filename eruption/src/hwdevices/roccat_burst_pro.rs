@@ -31,8 +31,8 @@ use std::{mem::size_of, sync::Arc};
 use crate::constants;
 
 use super::{
-    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError, MouseDevice,
-    MouseDeviceTrait, MouseHidEvent, RGBA,
+    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HidTransport, HwDeviceError,
+    MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -89,7 +89,7 @@ pub struct RoccatBurstPro {
     pub ctrl_hiddev_info: Option<hidapi::DeviceInfo>,
 
     pub is_opened: bool,
-    pub ctrl_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
+    pub ctrl_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
 
     pub button_states: Arc<Mutex<BitVec>>,
 
@@ -121,6 +121,26 @@ impl RoccatBurstPro {
         }
     }
 
+    /// Binds the driver to already opened transports, used by the tests to drive it with mocks
+    #[cfg(test)]
+    pub fn with_transport(ctrl_dev: Box<dyn HidTransport>) -> Self {
+        Self {
+            is_initialized: false,
+
+            is_bound: true,
+            ctrl_hiddev_info: None,
+
+            is_opened: true,
+            ctrl_hiddev: Arc::new(Mutex::new(Some(ctrl_dev))),
+
+            button_states: Arc::new(Mutex::new(bitvec![0; constants::MAX_MOUSE_BUTTONS])),
+
+            has_failed: false,
+
+            brightness: 100,
+        }
+    }
+
     // pub(self) fn query_ctrl_report(&mut self, id: u8) -> Result<()> {
     //     trace!("Querying control device feature report");
 
@@ -264,7 +284,11 @@ impl RoccatBurstPro {
 
 impl DeviceInfoTrait for RoccatBurstPro {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities {}
+        DeviceCapabilities {
+            num_leds: 2,
+            num_profiles: 5,
+            ..Default::default()
+        }
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -345,7 +369,7 @@ impl DeviceTrait for RoccatBurstPro {
             trace!("Opening control device...");
 
             match self.ctrl_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.ctrl_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.ctrl_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
@@ -942,3 +966,41 @@ impl MouseDeviceTrait for RoccatBurstPro {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwdevices::transport::mock::{led_map, MockTransport};
+
+    #[test]
+    fn send_led_map() {
+        let ctrl_dev = MockTransport::new();
+
+        let mut device = RoccatBurstPro::with_transport(Box::new(ctrl_dev.clone()));
+        device.is_initialized = true;
+        device.brightness = 50;
+
+        device
+            .send_led_map(&led_map(&[(LED_0, 0xff102030), (LED_1, 0xff405060)]))
+            .unwrap();
+
+        assert_eq!(
+            ctrl_dev.sent_feature_reports(),
+            vec![vec![
+                0x0d, 0x0b, 0x08, 0x10, 0x18, 0x20, 0x28, 0x30, 0x00, 0x00, 0x00
+            ]]
+        );
+    }
+
+    #[test]
+    fn send_led_map_fails_on_disconnect() {
+        let ctrl_dev = MockTransport::new();
+        ctrl_dev.disconnect();
+
+        let mut device = RoccatBurstPro::with_transport(Box::new(ctrl_dev.clone()));
+        device.is_initialized = true;
+
+        assert!(device.send_led_map(&led_map(&[])).is_err());
+        assert!(device.has_failed);
+    }
+}
@@ -29,8 +29,8 @@ use std::{mem::size_of, sync::Arc};
 use crate::constants;
 
 use super::{
-    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError, MiscDevice,
-    MiscDeviceTrait, MouseDeviceTrait, RGBA,
+    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HidTransport, HwDeviceError,
+    MiscDevice, MiscDeviceTrait, MouseDeviceTrait, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -104,7 +104,7 @@ pub struct RoccatElo71Air {
     pub ctrl_hiddev_info: Option<hidapi::DeviceInfo>,
     // pub led_hiddev_info: Option<hidapi::DeviceInfo>,
     pub is_opened: bool,
-    pub ctrl_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
+    pub ctrl_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
     // pub led_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
 
     // device specific configuration options
@@ -138,6 +138,24 @@ impl RoccatElo71Air {
         }
     }
 
+    /// Binds the driver to already opened transports, used by the tests to drive it with mocks
+    #[cfg(test)]
+    pub fn with_transport(ctrl_dev: Box<dyn HidTransport>) -> Self {
+        Self {
+            is_initialized: false,
+
+            is_bound: true,
+            ctrl_hiddev_info: None,
+            is_opened: true,
+            ctrl_hiddev: Arc::new(Mutex::new(Some(ctrl_dev))),
+            brightness: 100,
+
+            has_failed: false,
+
+            device_status: DeviceStatus(HashMap::new()),
+        }
+    }
+
     // pub(self) fn query_ctrl_report(&mut self, id: u8) -> Result<()> {
     //     trace!("Querying control device feature report");
 
@@ -415,7 +433,11 @@ impl RoccatElo71Air {
 
 impl DeviceInfoTrait for RoccatElo71Air {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities {}
+        DeviceCapabilities {
+            num_leds: 1,
+            has_battery: true,
+            ..Default::default()
+        }
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -496,7 +518,7 @@ impl DeviceTrait for RoccatElo71Air {
             trace!("Opening control device...");
 
             match self.ctrl_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.ctrl_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.ctrl_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
@@ -837,3 +859,36 @@ impl MiscDeviceTrait for RoccatElo71Air {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwdevices::transport::mock::MockTransport;
+
+    #[test]
+    fn parse_battery_status() {
+        let ctrl_dev = MockTransport::new();
+        ctrl_dev.push_input_report(&[0xe6, 0x06, 0x03, 0x00, 0x03]);
+
+        let mut device = RoccatElo71Air::with_transport(Box::new(ctrl_dev.clone()));
+        device.is_initialized = true;
+
+        assert_eq!(device.query_ctrl_dev().unwrap(), QueryResult::Invalid);
+
+        let status = device.device_status().unwrap();
+
+        assert_eq!(status.0["battery-level-percent"], "75");
+        assert_eq!(status.0["battery-level-raw"], "3");
+    }
+
+    #[test]
+    fn detect_required_reset() {
+        let ctrl_dev = MockTransport::new();
+        ctrl_dev.push_input_report(&[0xe6, 0x06, 0x03, 0x00, 0x04]);
+
+        let mut device = RoccatElo71Air::with_transport(Box::new(ctrl_dev.clone()));
+        device.is_initialized = true;
+
+        assert_eq!(device.query_ctrl_dev().unwrap(), QueryResult::ResetRequired);
+    }
+}
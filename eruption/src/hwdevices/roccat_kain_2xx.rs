@@ -32,8 +32,8 @@ use std::{mem::size_of, sync::Arc};
 use crate::constants;
 
 use super::{
-    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError, MouseDevice,
-    MouseDeviceTrait, MouseHidEvent, RGBA,
+    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HidTransport, HwDeviceError,
+    MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -104,8 +104,8 @@ pub struct RoccatKain2xx {
     pub led_hiddev_info: Option<hidapi::DeviceInfo>,
 
     pub is_opened: bool,
-    pub ctrl_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
-    pub led_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
+    pub ctrl_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
+    pub led_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
 
     pub button_states: Arc<Mutex<BitVec>>,
 
@@ -139,6 +139,28 @@ impl RoccatKain2xx {
         }
     }
 
+    /// Binds the driver to already opened transports, used by the tests to drive it with mocks
+    #[cfg(test)]
+    pub fn with_transport(ctrl_dev: Box<dyn HidTransport>, led_dev: Box<dyn HidTransport>) -> Self {
+        Self {
+            is_initialized: false,
+
+            is_bound: true,
+            ctrl_hiddev_info: None,
+            led_hiddev_info: None,
+
+            is_opened: true,
+            ctrl_hiddev: Arc::new(Mutex::new(Some(ctrl_dev))),
+            led_hiddev: Arc::new(Mutex::new(Some(led_dev))),
+
+            button_states: Arc::new(Mutex::new(bitvec![0; constants::MAX_MOUSE_BUTTONS])),
+
+            has_failed: false,
+
+            brightness: 100,
+        }
+    }
+
     // pub(self) fn query_ctrl_report(&mut self, id: u8) -> Result<()> {
     //     trace!("Querying control device feature report");
 
@@ -269,7 +291,11 @@ impl RoccatKain2xx {
 
 impl DeviceInfoTrait for RoccatKain2xx {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities {}
+        DeviceCapabilities {
+            num_leds: 2,
+            has_battery: true,
+            ..Default::default()
+        }
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -350,14 +376,14 @@ impl DeviceTrait for RoccatKain2xx {
             trace!("Opening control device...");
 
             match self.ctrl_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.ctrl_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.ctrl_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
             trace!("Opening LED device...");
 
             match self.led_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.led_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.led_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
@@ -1098,3 +1124,36 @@ impl MouseDeviceTrait for RoccatKain2xx {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwdevices::transport::mock::{led_map, MockTransport};
+
+    #[test]
+    fn send_led_map() {
+        let ctrl_dev = MockTransport::new();
+        let led_dev = MockTransport::new();
+
+        let mut device =
+            RoccatKain2xx::with_transport(Box::new(ctrl_dev.clone()), Box::new(led_dev.clone()));
+        device.is_initialized = true;
+
+        device
+            .send_led_map(&led_map(&[(LED_0, 0xff102030), (LED_1, 0xff405060)]))
+            .unwrap();
+
+        // the LEDs are controlled via the LED sub-device only
+        assert!(ctrl_dev.transfers().is_empty());
+
+        let reports = led_dev.sent_feature_reports();
+
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].len(), 22);
+
+        assert_eq!(
+            reports[0][0..10],
+            [0x08, 0x09, 0x33, 0x00, 0x10, 0x20, 0x30, 0x40, 0x50, 0x60]
+        );
+    }
+}
@@ -27,8 +27,8 @@ use std::{any::Any, collections::HashMap, mem::size_of, sync::Arc, thread, time:
 use crate::{constants, hwdevices::DeviceStatus};
 
 use super::{
-    DeviceCapabilities, DeviceInfoTrait, DeviceTrait, HwDeviceError, MouseDevice, MouseDeviceTrait,
-    MouseHidEvent, RGBA,
+    DeviceCapabilities, DeviceInfoTrait, DeviceTrait, HidTransport, HwDeviceError, LedZone,
+    MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -92,7 +92,7 @@ pub struct RoccatKoneAimoRemastered {
     pub ctrl_hiddev_info: Option<hidapi::DeviceInfo>,
 
     pub is_opened: bool,
-    pub ctrl_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
+    pub ctrl_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
 
     pub button_states: Arc<Mutex<BitVec>>,
 
@@ -124,6 +124,26 @@ impl RoccatKoneAimoRemastered {
         }
     }
 
+    /// Binds the driver to already opened transports, used by the tests to drive it with mocks
+    #[cfg(test)]
+    pub fn with_transport(ctrl_dev: Box<dyn HidTransport>) -> Self {
+        Self {
+            is_initialized: false,
+
+            is_bound: true,
+            ctrl_hiddev_info: None,
+
+            is_opened: true,
+            ctrl_hiddev: Arc::new(Mutex::new(Some(ctrl_dev))),
+
+            button_states: Arc::new(Mutex::new(bitvec![0; constants::MAX_MOUSE_BUTTONS])),
+
+            has_failed: false,
+
+            brightness: 100,
+        }
+    }
+
     // pub(self) fn query_ctrl_report(&mut self, id: u8) -> Result<()> {
     //     trace!("Querying control device feature report");
 
@@ -272,7 +292,14 @@ impl RoccatKoneAimoRemastered {
 
 impl DeviceInfoTrait for RoccatKoneAimoRemastered {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities {}
+        DeviceCapabilities {
+            num_leds: 11,
+            led_zones: vec![
+                LedZone::new("scroll-wheel", 0, 1),
+                LedZone::new("logo", 1, 1),
+            ],
+            ..Default::default()
+        }
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -355,7 +382,7 @@ impl DeviceTrait for RoccatKoneAimoRemastered {
             trace!("Opening control device...");
 
             match self.ctrl_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.ctrl_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.ctrl_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
@@ -912,3 +939,30 @@ impl MouseDeviceTrait for RoccatKoneAimoRemastered {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwdevices::transport::mock::{led_map, MockTransport};
+
+    #[test]
+    fn send_led_map() {
+        let ctrl_dev = MockTransport::new();
+
+        let mut device = RoccatKoneAimoRemastered::with_transport(Box::new(ctrl_dev.clone()));
+        device.is_initialized = true;
+
+        device
+            .send_led_map(&led_map(&[(LED_0, 0xff102030), (LED_10, 0x80405060)]))
+            .unwrap();
+
+        let reports = ctrl_dev.sent_feature_reports();
+
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].len(), 46);
+
+        assert_eq!(reports[0][0..6], [0x0d, 0x2e, 0x10, 0x20, 0x30, 0xff]);
+        assert!(reports[0][6..42].iter().all(|b| *b == 0x00));
+        assert_eq!(reports[0][42..46], [0x40, 0x50, 0x60, 0x80]);
+    }
+}
@@ -19,7 +19,7 @@
 
 use bitvec::prelude::*;
 use evdev_rs::enums::EV_KEY;
-use hidapi::{HidApi, HidDevice};
+use hidapi::HidApi;
 use log::*;
 use parking_lot::{Mutex, RwLock};
 // use std::sync::atomic::Ordering;
@@ -30,8 +30,8 @@ use std::sync::Arc;
 use crate::constants;
 
 use super::{
-    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError, MouseDevice,
-    MouseDeviceTrait, MouseHidEvent, RGBA,
+    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HidTransport, HwDeviceError,
+    MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -118,8 +118,8 @@ pub struct RoccatKoneProAir {
 
     pub is_opened: bool,
 
-    pub ctrl_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
-    pub led_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
+    pub ctrl_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
+    pub led_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
 
     pub button_states: Arc<Mutex<BitVec>>,
 
@@ -160,6 +160,32 @@ impl RoccatKoneProAir {
         }
     }
 
+    /// Binds the driver to already opened transports, used by the tests to drive it with mocks
+    #[cfg(test)]
+    pub fn with_transport(ctrl_dev: Box<dyn HidTransport>, led_dev: Box<dyn HidTransport>) -> Self {
+        Self {
+            is_initialized: false,
+
+            is_bound: true,
+
+            ctrl_hiddev_info: None,
+            led_hiddev_info: None,
+
+            is_opened: true,
+
+            ctrl_hiddev: Arc::new(Mutex::new(Some(ctrl_dev))),
+            led_hiddev: Arc::new(Mutex::new(Some(led_dev))),
+
+            button_states: Arc::new(Mutex::new(bitvec![0; constants::MAX_MOUSE_BUTTONS])),
+
+            has_failed: false,
+
+            brightness: 100,
+
+            device_status: DeviceStatus(HashMap::new()),
+        }
+    }
+
     // pub(self) fn query_ctrl_report(&mut self, id: u8) -> Result<()> {
     //     trace!("Querying control device feature report");
 
@@ -310,7 +336,7 @@ impl RoccatKoneProAir {
     // }
 
     fn update_device_status(&mut self) -> Result<()> {
-        fn read_results(led_dev: &HidDevice) -> Result<super::DeviceStatus> {
+        fn read_results(led_dev: &dyn HidTransport) -> Result<super::DeviceStatus> {
             let mut table = HashMap::new();
 
             let mut cntr = 0;
@@ -436,7 +462,7 @@ impl RoccatKoneProAir {
                 }
             }
 
-            let result = read_results(&led_dev)?;
+            let result = read_results(led_dev.as_ref())?;
 
             let buf: [u8; 65] = [
                 0x00, 0x90, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -458,7 +484,7 @@ impl RoccatKoneProAir {
                 }
             }
 
-            let result1 = read_results(&led_dev)?;
+            let result1 = read_results(led_dev.as_ref())?;
 
             self.device_status = DeviceStatus(
                 result
@@ -476,7 +502,11 @@ impl RoccatKoneProAir {
 
 impl DeviceInfoTrait for RoccatKoneProAir {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities {}
+        DeviceCapabilities {
+            num_leds: 2,
+            has_battery: true,
+            ..Default::default()
+        }
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -560,14 +590,14 @@ impl DeviceTrait for RoccatKoneProAir {
             trace!("Opening control device...");
 
             match self.ctrl_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.ctrl_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.ctrl_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
             trace!("Opening LED device...");
 
             match self.led_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.led_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.led_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
@@ -1240,3 +1270,51 @@ impl MouseDeviceTrait for RoccatKoneProAir {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwdevices::transport::mock::{led_map, MockTransport};
+
+    #[test]
+    fn send_led_map_updates_device_status() {
+        let led_dev = MockTransport::new();
+
+        // acknowledgement of the LED report, followed by the replies to the status queries
+        led_dev.push_input_report(&[0x00, 0x10]);
+        led_dev.push_input_report(&[0x90, 0x70, 0x00, 0x00, 100]);
+        led_dev.push_input_report(&[
+            0x90, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 80,
+        ]);
+
+        let mut device = RoccatKoneProAir::with_transport(
+            Box::new(MockTransport::new()),
+            Box::new(led_dev.clone()),
+        );
+        device.is_initialized = true;
+
+        device
+            .send_led_map(&led_map(&[(LED_0, 0xff102030), (LED_1, 0xff405060)]))
+            .unwrap();
+
+        let reports = led_dev.written_reports();
+
+        assert_eq!(reports.len(), 3);
+        assert!(reports.iter().all(|r| r.len() == 65));
+
+        assert_eq!(
+            reports[0][0..16],
+            [
+                0x00, 0x10, 0x10, 0x0b, 0x00, 0x09, 0x64, 0x64, 0x64, 0x06, 0x10, 0x20, 0x30, 0x40,
+                0x50, 0x60
+            ]
+        );
+        assert_eq!(reports[1][0..3], [0x00, 0x90, 0x70]);
+        assert_eq!(reports[2][0..3], [0x00, 0x90, 0x0a]);
+
+        let status = device.device_status().unwrap();
+
+        assert_eq!(status.0["signal-strength-percent"], "28");
+        assert_eq!(status.0["battery-level-percent"], "80");
+    }
+}
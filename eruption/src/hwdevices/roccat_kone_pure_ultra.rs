@@ -30,8 +30,8 @@ use std::{mem::size_of, sync::Arc};
 use crate::constants;
 
 use super::{
-    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError, MouseDevice,
-    MouseDeviceTrait, MouseHidEvent, RGBA,
+    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HidTransport, HwDeviceError,
+    MouseDevice, MouseDeviceTrait, MouseHidEvent, ValueRange, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -87,7 +87,7 @@ pub struct RoccatKonePureUltra {
     pub ctrl_hiddev_info: Option<hidapi::DeviceInfo>,
 
     pub is_opened: bool,
-    pub ctrl_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
+    pub ctrl_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
 
     pub button_states: Arc<Mutex<BitVec>>,
 
@@ -119,6 +119,26 @@ impl RoccatKonePureUltra {
         }
     }
 
+    /// Binds the driver to already opened transports, used by the tests to drive it with mocks
+    #[cfg(test)]
+    pub fn with_transport(ctrl_dev: Box<dyn HidTransport>) -> Self {
+        Self {
+            is_initialized: false,
+
+            is_bound: true,
+            ctrl_hiddev_info: None,
+
+            is_opened: true,
+            ctrl_hiddev: Arc::new(Mutex::new(Some(ctrl_dev))),
+
+            button_states: Arc::new(Mutex::new(bitvec![0; constants::MAX_MOUSE_BUTTONS])),
+
+            has_failed: false,
+
+            brightness: 100,
+        }
+    }
+
     // pub(self) fn query_ctrl_report(&mut self, id: u8) -> Result<()> {
     //     trace!("Querying control device feature report");
 
@@ -262,7 +282,16 @@ impl RoccatKonePureUltra {
 
 impl DeviceInfoTrait for RoccatKonePureUltra {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities {}
+        DeviceCapabilities {
+            num_leds: 1,
+            // the `dpi` parameter selects one of the 5 DPI stages
+            dpi: Some(ValueRange::new(0, 4, 1)),
+            polling_rates: vec![125, 250, 500, 1000],
+            num_profiles: 5,
+            has_angle_snapping: true,
+            has_debounce: true,
+            ..Default::default()
+        }
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -343,7 +372,7 @@ impl DeviceTrait for RoccatKonePureUltra {
             trace!("Opening control device...");
 
             match self.ctrl_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.ctrl_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.ctrl_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
@@ -708,7 +737,19 @@ impl MouseDeviceTrait for RoccatKonePureUltra {
                 Err(_) => Err(HwDeviceError::InvalidResult {}),
             }?;
 
-            buf[29] = rate as u8;
+            buf[29] = match rate {
+                125 => 0,
+                250 => 1,
+                500 => 2,
+                1000 => 3,
+
+                _ => {
+                    return Err(HwDeviceError::ValueError {
+                        description: format!("Unsupported poll rate: {}", rate),
+                    }
+                    .into())
+                }
+            };
 
             match ctrl_dev.send_feature_report(&buf) {
                 Ok(_result) => {
@@ -1184,3 +1225,51 @@ impl MouseDeviceTrait for RoccatKonePureUltra {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwdevices::transport::mock::{led_map, MockTransport};
+
+    #[test]
+    fn send_led_map() {
+        let ctrl_dev = MockTransport::new();
+
+        let mut device = RoccatKonePureUltra::with_transport(Box::new(ctrl_dev.clone()));
+        device.is_initialized = true;
+
+        device
+            .send_led_map(&led_map(&[(LED_0, 0xff102030)]))
+            .unwrap();
+
+        assert_eq!(
+            ctrl_dev.sent_feature_reports(),
+            vec![vec![
+                0x0d, 0x0b, 0x10, 0x20, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+            ]]
+        );
+    }
+
+    #[test]
+    fn validate_parameters() {
+        use crate::dbus_interface::{validate_parameter, DbusApiError};
+
+        let device = RoccatKonePureUltra::with_transport(Box::new(MockTransport::new()));
+        let capabilities = device.get_device_capabilities();
+
+        assert!(validate_parameter(&capabilities, "rate", "1000").is_ok());
+        assert!(validate_parameter(&capabilities, "dpi", "4").is_ok());
+
+        let error = validate_parameter(&capabilities, "dpi", "5").unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<DbusApiError>(),
+            Some(DbusApiError::InvalidValue {})
+        ));
+
+        let error = validate_parameter(&capabilities, "dcu", "1").unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<DbusApiError>(),
+            Some(DbusApiError::OpNotSupported {})
+        ));
+    }
+}
@@ -28,8 +28,8 @@ use std::{any::Any, collections::HashMap, mem::size_of, sync::Arc};
 use crate::{constants, hwdevices::DeviceStatus};
 
 use super::{
-    DeviceCapabilities, DeviceInfoTrait, DeviceTrait, HwDeviceError, MouseDevice, MouseDeviceTrait,
-    MouseHidEvent, RGBA,
+    DeviceCapabilities, DeviceInfoTrait, DeviceTrait, HidTransport, HwDeviceError, LedZone,
+    MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -84,7 +84,7 @@ pub struct RoccatKova2016 {
     pub ctrl_hiddev_info: Option<hidapi::DeviceInfo>,
 
     pub is_opened: bool,
-    pub ctrl_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
+    pub ctrl_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
 
     pub button_states: Arc<Mutex<BitVec>>,
 
@@ -116,6 +116,26 @@ impl RoccatKova2016 {
         }
     }
 
+    /// Binds the driver to already opened transports, used by the tests to drive it with mocks
+    #[cfg(test)]
+    pub fn with_transport(ctrl_dev: Box<dyn HidTransport>) -> Self {
+        Self {
+            is_initialized: false,
+
+            is_bound: true,
+            ctrl_hiddev_info: None,
+
+            is_opened: true,
+            ctrl_hiddev: Arc::new(Mutex::new(Some(ctrl_dev))),
+
+            button_states: Arc::new(Mutex::new(bitvec![0; constants::MAX_MOUSE_BUTTONS])),
+
+            has_failed: false,
+
+            brightness: 100,
+        }
+    }
+
     // pub(self) fn query_ctrl_report(&mut self, id: u8) -> Result<()> {
     //     trace!("Querying control device feature report");
 
@@ -257,7 +277,14 @@ impl RoccatKova2016 {
 
 impl DeviceInfoTrait for RoccatKova2016 {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities {}
+        DeviceCapabilities {
+            num_leds: 2,
+            led_zones: vec![
+                LedZone::new("scroll-wheel", 0, 1),
+                LedZone::new("logo", 1, 1),
+            ],
+            ..Default::default()
+        }
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -340,7 +367,7 @@ impl DeviceTrait for RoccatKova2016 {
             trace!("Opening control device...");
 
             match self.ctrl_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.ctrl_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.ctrl_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
@@ -879,3 +906,32 @@ impl MouseDeviceTrait for RoccatKova2016 {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwdevices::transport::mock::{led_map, MockTransport};
+
+    #[test]
+    fn send_led_map() {
+        let ctrl_dev = MockTransport::new();
+
+        let mut device = RoccatKova2016::with_transport(Box::new(ctrl_dev.clone()));
+        device.is_initialized = true;
+
+        device
+            .send_led_map(&led_map(&[(LED_0, 0xff102030), (LED_1, 0xff405060)]))
+            .unwrap();
+
+        let reports = ctrl_dev.sent_feature_reports();
+
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].len(), 28);
+
+        assert_eq!(reports[0][0..2], [0x06, 0x1c]);
+        assert_eq!(
+            reports[0][19..28],
+            [0x10, 0x20, 0x30, 0x03, 0x40, 0x50, 0x60, 0x8e, 0x04]
+        );
+    }
+}
@@ -29,8 +29,9 @@ use std::{sync::Arc, thread};
 use crate::constants;
 
 use super::{
-    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError, KeyboardDevice,
-    KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind, MouseDeviceTrait, RGBA,
+    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HidTransport, HwDeviceError,
+    KeyboardDevice, KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind, LedZone,
+    MouseDeviceTrait, StatusLed, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -103,8 +104,8 @@ pub struct RoccatMagma {
     pub led_hiddev_info: Option<hidapi::DeviceInfo>,
 
     pub is_opened: bool,
-    pub ctrl_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
-    pub led_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
+    pub ctrl_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
+    pub led_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
 
     pub has_failed: bool,
 
@@ -138,6 +139,28 @@ impl RoccatMagma {
         }
     }
 
+    /// Binds the driver to already opened transports, used by the tests to drive it with mocks
+    #[cfg(test)]
+    pub fn with_transport(ctrl_dev: Box<dyn HidTransport>, led_dev: Box<dyn HidTransport>) -> Self {
+        Self {
+            is_initialized: false,
+
+            is_bound: true,
+            ctrl_hiddev_info: None,
+            led_hiddev_info: None,
+
+            is_opened: true,
+            ctrl_hiddev: Arc::new(Mutex::new(Some(ctrl_dev))),
+            led_hiddev: Arc::new(Mutex::new(Some(led_dev))),
+
+            has_failed: false,
+
+            dial_mode: Arc::new(Mutex::new(DialMode::Brightness)),
+
+            brightness: 100,
+        }
+    }
+
     // pub(self) fn query_ctrl_report(&mut self, id: u8) -> Result<()> {
     //     trace!("Querying control device feature report");
     //
@@ -397,7 +420,20 @@ impl RoccatMagma {
 
 impl DeviceInfoTrait for RoccatMagma {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities {}
+        DeviceCapabilities {
+            // the LEDs of the 5 zones of the keyboard
+            num_leds: 5,
+            led_zones: (0..5)
+                .map(|i| LedZone::new(&format!("zone-{}", i + 1), i, 1))
+                .collect(),
+            status_leds: vec![
+                StatusLed::NumLock,
+                StatusLed::CapsLock,
+                StatusLed::ScrollLock,
+                StatusLed::GameMode,
+            ],
+            ..Default::default()
+        }
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -478,14 +514,14 @@ impl DeviceTrait for RoccatMagma {
             trace!("Opening control device...");
 
             match self.ctrl_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.ctrl_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.ctrl_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
             trace!("Opening LED device...");
 
             match self.led_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.led_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.led_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
@@ -1320,3 +1356,39 @@ pub const COLS_TOPOLOGY: [u8; 108] = [
     // ANSI model
     // TODO: Implement this
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwdevices::transport::mock::{led_map, MockTransport};
+
+    #[test]
+    fn send_led_map_averages_zones() {
+        let led_dev = MockTransport::new();
+
+        let mut device =
+            RoccatMagma::with_transport(Box::new(MockTransport::new()), Box::new(led_dev.clone()));
+        device.is_initialized = true;
+
+        // the first zone is half red, the last zone is fully blue
+        let mut leds = (0..25)
+            .step_by(2)
+            .map(|i| (i, 0xff600000))
+            .collect::<Vec<_>>();
+        leds.extend((100..125).map(|i| (i, 0xff000060)));
+
+        device.send_led_map(&led_map(&leds)).unwrap();
+
+        let reports = led_dev.written_reports();
+
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].len(), 64);
+
+        assert_eq!(reports[0][0..3], [0xa1, 0x01, 0x40]);
+
+        // red, green and blue values of the five zones
+        assert_eq!(reports[0][3..8], [0x31, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(reports[0][8..13], [0x00, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(reports[0][13..18], [0x00, 0x00, 0x00, 0x00, 0x60]);
+    }
+}
@@ -28,8 +28,9 @@ use std::{any::Any, mem::size_of};
 use crate::constants;
 
 use super::{
-    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError, KeyboardDevice,
-    KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind, MouseDeviceTrait, RGBA,
+    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HidTransport, HwDeviceError,
+    KeyboardDevice, KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind,
+    MouseDeviceTrait, StatusLed, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -102,8 +103,8 @@ pub struct RoccatVulcan1xx {
     pub led_hiddev_info: Option<hidapi::DeviceInfo>,
 
     pub is_opened: bool,
-    pub ctrl_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
-    pub led_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
+    pub ctrl_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
+    pub led_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
 
     pub has_failed: bool,
 
@@ -137,6 +138,28 @@ impl RoccatVulcan1xx {
         }
     }
 
+    /// Binds the driver to already opened transports, used by the tests to drive it with mocks
+    #[cfg(test)]
+    pub fn with_transport(ctrl_dev: Box<dyn HidTransport>, led_dev: Box<dyn HidTransport>) -> Self {
+        Self {
+            is_initialized: false,
+
+            is_bound: true,
+            ctrl_hiddev_info: None,
+            led_hiddev_info: None,
+
+            is_opened: true,
+            ctrl_hiddev: Arc::new(Mutex::new(Some(ctrl_dev))),
+            led_hiddev: Arc::new(Mutex::new(Some(led_dev))),
+
+            has_failed: false,
+
+            dial_mode: Arc::new(Mutex::new(DialMode::Brightness)),
+
+            brightness: 100,
+        }
+    }
+
     // pub(self) fn query_ctrl_report(&mut self, id: u8) -> Result<()> {
     //     trace!("Querying control device feature report");
 
@@ -471,7 +494,16 @@ impl RoccatVulcan1xx {
 
 impl DeviceInfoTrait for RoccatVulcan1xx {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities {}
+        DeviceCapabilities {
+            num_leds: NUM_KEYS,
+            status_leds: vec![
+                StatusLed::NumLock,
+                StatusLed::CapsLock,
+                StatusLed::ScrollLock,
+                StatusLed::GameMode,
+            ],
+            ..Default::default()
+        }
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -552,14 +584,14 @@ impl DeviceTrait for RoccatVulcan1xx {
             trace!("Opening control device...");
 
             match self.ctrl_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.ctrl_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.ctrl_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
             trace!("Opening LED device...");
 
             match self.led_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.led_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.led_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
@@ -1410,3 +1442,109 @@ pub const COLS_TOPOLOGY: [u8; 252] = [
 	0x7c, 0x7d, 0x7e, 0x7f, 0x80, 0xff,
 	0x81, 0x82, 0x83, 0xff, 0xff, 0xff
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwdevices::transport::mock::{MockTransport, Transfer};
+
+    #[test]
+    fn init_sequence() {
+        let ctrl_dev = MockTransport::new();
+        ctrl_dev.set_feature_report(&[0x0f, 0x06, 136, 0x00, 0x00, 0x00]);
+        ctrl_dev.set_feature_report(&[0x04, 0x01, 0x00, 0x00]);
+
+        let mut device = RoccatVulcan1xx::with_transport(
+            Box::new(ctrl_dev.clone()),
+            Box::new(MockTransport::new()),
+        );
+        device.send_init_sequence().unwrap();
+
+        assert!(device.is_initialized);
+        assert_eq!(device.get_firmware_revision(), "1.36");
+
+        assert_eq!(ctrl_dev.transfers()[0], Transfer::GetFeatureReport(0x0f));
+
+        let reports = ctrl_dev.sent_feature_reports();
+
+        assert_eq!(
+            reports.iter().map(|r| r[0]).collect::<Vec<u8>>(),
+            vec![0x15, 0x05, 0x07, 0x0a, 0x0b, 0x06, 0x09, 0x0d, 0x13]
+        );
+        assert_eq!(reports[0], vec![0x15, 0x00, 0x01]);
+        assert_eq!(
+            reports[8],
+            vec![0x13, 0x08, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn send_led_map() {
+        let led_dev = MockTransport::new();
+
+        let mut device = RoccatVulcan1xx::with_transport(
+            Box::new(MockTransport::new()),
+            Box::new(led_dev.clone()),
+        );
+        device.is_initialized = true;
+        device.brightness = 50;
+
+        let mut led_map = [RGBA {
+            r: 0x00,
+            g: 0x00,
+            b: 0x00,
+            a: 0x00,
+        }; constants::CANVAS_SIZE];
+
+        led_map[0] = RGBA {
+            r: 0xff,
+            g: 0x80,
+            b: 0x02,
+            a: 0xff,
+        };
+        led_map[13] = RGBA {
+            r: 0x10,
+            g: 0x20,
+            b: 0x30,
+            a: 0xff,
+        };
+
+        device.send_led_map(&led_map).unwrap();
+
+        let packets = led_dev.written_reports();
+
+        assert_eq!(packets.len(), 7);
+        assert!(packets.iter().all(|p| p.len() == 65 && p[0] == 0x00));
+
+        // red, green and blue values are stored in separate planes of 12 keys each
+        assert_eq!(packets[0][1..5], [0xa1, 0x01, 0x01, 0xb4]);
+        assert_eq!(
+            (packets[0][5], packets[0][17], packets[0][29]),
+            (0x80, 0x40, 0x01)
+        );
+        assert_eq!((packets[0][42], packets[0][54]), (0x08, 0x10));
+        assert_eq!(packets[1][2], 0x18);
+    }
+
+    #[test]
+    fn send_led_map_fails_on_disconnect() {
+        let led_dev = MockTransport::new();
+        led_dev.disconnect();
+
+        let mut device = RoccatVulcan1xx::with_transport(
+            Box::new(MockTransport::new()),
+            Box::new(led_dev.clone()),
+        );
+        device.is_initialized = true;
+
+        let led_map = [RGBA {
+            r: 0x00,
+            g: 0x00,
+            b: 0x00,
+            a: 0x00,
+        }; constants::CANVAS_SIZE];
+
+        assert!(device.send_led_map(&led_map).is_err());
+        assert!(device.has_failed);
+    }
+}
@@ -29,8 +29,9 @@ use std::{sync::Arc, thread};
 use crate::constants;
 
 use super::{
-    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError, KeyboardDevice,
-    KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind, MouseDeviceTrait, RGBA,
+    DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HidTransport, HwDeviceError,
+    KeyboardDevice, KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind,
+    MouseDeviceTrait, StatusLed, RGBA,
 };
 
 pub type Result<T> = super::Result<T>;
@@ -105,8 +106,8 @@ pub struct RoccatVulcanProTKL {
     pub led_hiddev_info: Option<hidapi::DeviceInfo>,
 
     pub is_opened: bool,
-    pub ctrl_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
-    pub led_hiddev: Arc<Mutex<Option<hidapi::HidDevice>>>,
+    pub ctrl_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
+    pub led_hiddev: Arc<Mutex<Option<Box<dyn HidTransport>>>>,
 
     pub has_failed: bool,
 
@@ -140,6 +141,28 @@ impl RoccatVulcanProTKL {
         }
     }
 
+    /// Binds the driver to already opened transports, used by the tests to drive it with mocks
+    #[cfg(test)]
+    pub fn with_transport(ctrl_dev: Box<dyn HidTransport>, led_dev: Box<dyn HidTransport>) -> Self {
+        Self {
+            is_initialized: false,
+
+            is_bound: true,
+            ctrl_hiddev_info: None,
+            led_hiddev_info: None,
+
+            is_opened: true,
+            ctrl_hiddev: Arc::new(Mutex::new(Some(ctrl_dev))),
+            led_hiddev: Arc::new(Mutex::new(Some(led_dev))),
+
+            has_failed: false,
+
+            dial_mode: Arc::new(Mutex::new(DialMode::Brightness)),
+
+            brightness: 100,
+        }
+    }
+
     // pub(self) fn query_ctrl_report(&mut self, id: u8) -> Result<()> {
     //     trace!("Querying control device feature report");
 
@@ -331,7 +354,16 @@ impl RoccatVulcanProTKL {
 
 impl DeviceInfoTrait for RoccatVulcanProTKL {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities {}
+        DeviceCapabilities {
+            num_leds: NUM_KEYS,
+            status_leds: vec![
+                StatusLed::NumLock,
+                StatusLed::CapsLock,
+                StatusLed::ScrollLock,
+                StatusLed::GameMode,
+            ],
+            ..Default::default()
+        }
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -412,14 +444,14 @@ impl DeviceTrait for RoccatVulcanProTKL {
             trace!("Opening control device...");
 
             match self.ctrl_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.ctrl_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.ctrl_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
             trace!("Opening LED device...");
 
             match self.led_hiddev_info.as_ref().unwrap().open_device(api) {
-                Ok(dev) => *self.led_hiddev.lock() = Some(dev),
+                Ok(dev) => *self.led_hiddev.lock() = Some(Box::new(dev)),
                 Err(_) => return Err(HwDeviceError::DeviceOpenError {}.into()),
             };
 
@@ -1160,3 +1192,40 @@ pub const COLS_TOPOLOGY: [u8; 108] = [
     // ANSI model
     // TODO: Implement this
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwdevices::transport::mock::{led_map, MockTransport};
+
+    #[test]
+    fn send_led_map() {
+        let led_dev = MockTransport::new();
+
+        let mut device = RoccatVulcanProTKL::with_transport(
+            Box::new(MockTransport::new()),
+            Box::new(led_dev.clone()),
+        );
+        device.is_initialized = true;
+        device.brightness = 50;
+
+        device
+            .send_led_map(&led_map(&[(0, 0xffff8002), (13, 0xff102030)]))
+            .unwrap();
+
+        let packets = led_dev.written_reports();
+
+        assert_eq!(packets.len(), 5);
+        assert!(packets.iter().all(|p| p.len() == 64));
+
+        assert_eq!(packets[0][0..4], [0xa1, 0x01, 0x34, 0x01]);
+        assert_eq!(packets[1][0..4], [0xa1, 0x02, 0x00, 0x00]);
+
+        // red, green and blue values are stored in separate planes of 12 keys each
+        assert_eq!(
+            (packets[0][4], packets[0][16], packets[0][28]),
+            (0x80, 0x40, 0x01)
+        );
+        assert_eq!((packets[0][41], packets[0][53]), (0x08, 0x10));
+    }
+}
@@ -0,0 +1,235 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use hidapi::HidResult;
+
+/// Abstraction over the transport of HID reports to and from a device. The drivers only talk
+/// to their devices through this trait, so that they may be tested without the hardware
+pub trait HidTransport: Send {
+    /// Write an output report
+    fn write(&self, data: &[u8]) -> HidResult<usize>;
+
+    /// Read an input report (blocking)
+    fn read(&self, buf: &mut [u8]) -> HidResult<usize>;
+
+    /// Read an input report, wait for at most `timeout` milliseconds
+    fn read_timeout(&self, buf: &mut [u8], timeout: i32) -> HidResult<usize>;
+
+    /// Send a feature report, the first byte holds the report ID
+    fn send_feature_report(&self, data: &[u8]) -> HidResult<()>;
+
+    /// Get a feature report, the report ID has to be set in the first byte of `buf`
+    fn get_feature_report(&self, buf: &mut [u8]) -> HidResult<usize>;
+}
+
+impl HidTransport for hidapi::HidDevice {
+    fn write(&self, data: &[u8]) -> HidResult<usize> {
+        hidapi::HidDevice::write(self, data)
+    }
+
+    fn read(&self, buf: &mut [u8]) -> HidResult<usize> {
+        hidapi::HidDevice::read(self, buf)
+    }
+
+    fn read_timeout(&self, buf: &mut [u8], timeout: i32) -> HidResult<usize> {
+        hidapi::HidDevice::read_timeout(self, buf, timeout)
+    }
+
+    fn send_feature_report(&self, data: &[u8]) -> HidResult<()> {
+        hidapi::HidDevice::send_feature_report(self, data)
+    }
+
+    fn get_feature_report(&self, buf: &mut [u8]) -> HidResult<usize> {
+        hidapi::HidDevice::get_feature_report(self, buf)
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use hidapi::{HidError, HidResult};
+    use parking_lot::Mutex;
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use super::HidTransport;
+    use crate::constants;
+    use crate::hwdevices::RGBA;
+
+    /// Returns a blank canvas, with the LEDs at the given indices set to the given colors.
+    /// Colors are specified as 0xAARRGGBB, like in the Lua scripts
+    pub fn led_map(leds: &[(usize, u32)]) -> Vec<RGBA> {
+        let mut result = vec![
+            RGBA {
+                r: 0x00,
+                g: 0x00,
+                b: 0x00,
+                a: 0x00,
+            };
+            constants::CANVAS_SIZE
+        ];
+
+        for (index, color) in leds {
+            result[*index] = RGBA {
+                r: (color >> 16 & 0xff) as u8,
+                g: (color >> 8 & 0xff) as u8,
+                b: (color & 0xff) as u8,
+                a: (color >> 24 & 0xff) as u8,
+            };
+        }
+
+        result
+    }
+
+    /// A single transfer to or from the mock device
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Transfer {
+        Write(Vec<u8>),
+        SendFeatureReport(Vec<u8>),
+        GetFeatureReport(u8),
+        Read,
+    }
+
+    /// A scripted HID device. It records every transfer and plays back canned responses.
+    /// Clones share their state, so a test may keep a handle to a mock that is owned by a driver
+    #[derive(Debug, Clone, Default)]
+    pub struct MockTransport {
+        transfers: Arc<Mutex<Vec<Transfer>>>,
+
+        input_reports: Arc<Mutex<VecDeque<Vec<u8>>>>,
+        feature_reports: Arc<Mutex<HashMap<u8, Vec<u8>>>>,
+
+        disconnected: Arc<AtomicBool>,
+    }
+
+    impl MockTransport {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Queue an input report, that will be returned by the next read
+        pub fn push_input_report(&self, data: &[u8]) {
+            self.input_reports.lock().push_back(data.to_vec());
+        }
+
+        /// Set the canned response for `get_feature_report`, the first byte holds the report ID
+        pub fn set_feature_report(&self, data: &[u8]) {
+            self.feature_reports.lock().insert(data[0], data.to_vec());
+        }
+
+        /// Let all subsequent transfers fail, as if the device had been unplugged
+        pub fn disconnect(&self) {
+            self.disconnected.store(true, Ordering::SeqCst);
+        }
+
+        /// Returns all transfers that have been recorded so far
+        pub fn transfers(&self) -> Vec<Transfer> {
+            self.transfers.lock().clone()
+        }
+
+        /// Returns the data of the feature reports that have been sent so far
+        pub fn sent_feature_reports(&self) -> Vec<Vec<u8>> {
+            self.transfers
+                .lock()
+                .iter()
+                .filter_map(|transfer| match transfer {
+                    Transfer::SendFeatureReport(data) => Some(data.clone()),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        /// Returns the data of the output reports that have been written so far
+        pub fn written_reports(&self) -> Vec<Vec<u8>> {
+            self.transfers
+                .lock()
+                .iter()
+                .filter_map(|transfer| match transfer {
+                    Transfer::Write(data) => Some(data.clone()),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        fn record(&self, transfer: Transfer) -> HidResult<()> {
+            if self.disconnected.load(Ordering::SeqCst) {
+                return Err(HidError::HidApiError {
+                    message: "Device disconnected".to_string(),
+                });
+            }
+
+            self.transfers.lock().push(transfer);
+
+            Ok(())
+        }
+
+        fn next_input_report(&self, buf: &mut [u8]) -> HidResult<usize> {
+            self.record(Transfer::Read)?;
+
+            match self.input_reports.lock().pop_front() {
+                Some(data) => {
+                    let len = data.len().min(buf.len());
+                    buf[..len].copy_from_slice(&data[..len]);
+
+                    Ok(len)
+                }
+
+                // no pending input reports, behave like a timeout
+                None => Ok(0),
+            }
+        }
+    }
+
+    impl HidTransport for MockTransport {
+        fn write(&self, data: &[u8]) -> HidResult<usize> {
+            self.record(Transfer::Write(data.to_vec()))?;
+
+            Ok(data.len())
+        }
+
+        fn read(&self, buf: &mut [u8]) -> HidResult<usize> {
+            self.next_input_report(buf)
+        }
+
+        fn read_timeout(&self, buf: &mut [u8], _timeout: i32) -> HidResult<usize> {
+            self.next_input_report(buf)
+        }
+
+        fn send_feature_report(&self, data: &[u8]) -> HidResult<()> {
+            self.record(Transfer::SendFeatureReport(data.to_vec()))
+        }
+
+        fn get_feature_report(&self, buf: &mut [u8]) -> HidResult<usize> {
+            self.record(Transfer::GetFeatureReport(buf[0]))?;
+
+            match self.feature_reports.lock().get(&buf[0]) {
+                Some(data) => {
+                    let len = data.len().min(buf.len());
+                    buf[..len].copy_from_slice(&data[..len]);
+
+                    Ok(len)
+                }
+
+                None => Err(HidError::HidApiError {
+                    message: format!("No canned response for feature report 0x{:02x}", buf[0]),
+                }),
+            }
+        }
+    }
+}
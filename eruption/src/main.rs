@@ -56,16 +56,24 @@ use hwdevices::{KeyboardDevice, KeyboardHidEvent, MiscDevice, MouseDevice, Mouse
 mod constants;
 mod dbus_interface;
 mod events;
+mod headless;
+mod hotplug;
+mod overlay;
 mod plugin_manager;
 mod plugins;
 mod profiles;
 mod scripting;
 mod state;
+mod zones;
 
 use plugins::macros;
 use profiles::Profile;
+use scripting::budget;
+use scripting::compositing;
+use scripting::harness;
 use scripting::manifest::Manifest;
 use scripting::script;
+use scripting::supervisor;
 
 use crate::hwdevices::{DeviceStatus, MaturityLevel, RGBA};
 use crate::plugins::{sdk_support, uleds};
@@ -190,6 +198,11 @@ lazy_static! {
     /// Global "keyboard brightness" modifier
     pub static ref BRIGHTNESS: AtomicIsize = AtomicIsize::new(100);
 
+    /// Dimensions of the canvas, may be overridden in eruption.conf
+    pub static ref CANVAS_WIDTH: AtomicUsize = AtomicUsize::new(constants::CANVAS_WIDTH);
+    pub static ref CANVAS_HEIGHT: AtomicUsize = AtomicUsize::new(constants::CANVAS_HEIGHT);
+    pub static ref CANVAS_SIZE: AtomicUsize = AtomicUsize::new(constants::CANVAS_SIZE);
+
     /// AFK timer
     pub static ref LAST_INPUT_TIME: Arc<Mutex<Instant>> = Arc::new(Mutex::new(Instant::now()));
 
@@ -322,7 +335,7 @@ pub enum EventAction {
 #[derive(Debug, Clone)]
 pub enum FileSystemEvent {
     ProfileChanged { action: EventAction, path: PathBuf },
-    ScriptChanged,
+    ScriptChanged { path: PathBuf },
 }
 
 fn print_header() {
@@ -372,6 +385,100 @@ fn parse_commandline() -> clap::ArgMatches {
                 .help("Sets the configuration file to use")
                 .takes_value(true),
         )
+        .subcommand(
+            Command::new("render")
+                .about("Render Lua effects without any hardware attached, e.g. for previews or regression tests")
+                .arg(
+                    Arg::new("profile")
+                        .short('p')
+                        .long("profile")
+                        .value_name("PROFILE")
+                        .help("Render the active scripts of a profile")
+                        .takes_value(true)
+                        .conflicts_with("script"),
+                )
+                .arg(
+                    Arg::new("script")
+                        .short('s')
+                        .long("script")
+                        .value_name("SCRIPT")
+                        .help("Render a single script, with its default parameters")
+                        .takes_value(true)
+                        .required_unless_present("profile"),
+                )
+                .arg(
+                    Arg::new("frames")
+                        .short('n')
+                        .long("frames")
+                        .value_name("COUNT")
+                        .help("Number of frames to render")
+                        .takes_value(true)
+                        .default_value("100"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("PATH")
+                        .help("Output directory for PNG and raw frames, or output file for GIF images")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Output format")
+                        .takes_value(true)
+                        .possible_values(["png", "gif", "raw"])
+                        .default_value("png"),
+                )
+                .arg(
+                    Arg::new("scale")
+                        .long("scale")
+                        .value_name("PIXELS")
+                        .help("Scale each LED to n x n pixels (PNG and GIF only)")
+                        .takes_value(true)
+                        .default_value("1"),
+                )
+                .arg(
+                    Arg::new("event")
+                        .short('e')
+                        .long("event")
+                        .value_name("FRAME:KIND:VALUE")
+                        .help("Inject a synthetic event before a frame is rendered, KIND is one of key-down, key-up, loudness or spectrum")
+                        .takes_value(true)
+                        .multiple_occurrences(true),
+                ),
+        )
+        .subcommand(
+            Command::new("test")
+                .about("Run the unit tests of Lua scripts and macros, with mocked input injection and profile switching")
+                .arg(
+                    Arg::new("script")
+                        .short('s')
+                        .long("script")
+                        .value_name("SCRIPT")
+                        .help("The script under test, defaults to the name of the test file without the .test suffix")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("device")
+                        .short('d')
+                        .long("device")
+                        .value_name("DEVICE")
+                        .help("Emulate a device, e.g. keyboards/roccat_vulcan_1xx")
+                        .takes_value(true)
+                        .multiple_occurrences(true),
+                )
+                .arg(
+                    Arg::new("FILES")
+                        .help("The test files to run")
+                        .required(true)
+                        .multiple_values(true),
+                ),
+        )
         // .arg(
         //     Arg::new("completions")
         //         .long("completions")
@@ -411,23 +518,59 @@ fn spawn_dbus_api_thread(
 
                 // process events, destined for the dbus api
                 match dbus_api_rx.recv_timeout(Duration::from_millis(timeout)) {
-                    Ok(result) => match result {
-                        DbusApiEvent::ProfilesChanged => dbus.notify_profiles_changed()?,
+                    Ok(result) => {
+                        // forward changes of the daemon state to the internal event observers as well
+                        let event = match result {
+                            DbusApiEvent::ActiveProfileChanged => {
+                                Some(events::Event::ProfileChanged(
+                                    ACTIVE_PROFILE
+                                        .lock()
+                                        .as_ref()
+                                        .map(|profile| profile.profile_file.clone()),
+                                ))
+                            }
 
-                        DbusApiEvent::ActiveProfileChanged => {
-                            dbus.notify_active_profile_changed()?
+                            DbusApiEvent::ActiveSlotChanged => Some(events::Event::SlotChanged(
+                                ACTIVE_SLOT.load(Ordering::SeqCst),
+                            )),
+
+                            DbusApiEvent::BrightnessChanged => Some(
+                                events::Event::BrightnessChanged(BRIGHTNESS.load(Ordering::SeqCst)),
+                            ),
+
+                            DbusApiEvent::DeviceHotplug(device_info, remove) => {
+                                Some(events::Event::DeviceHotplug(device_info, remove))
+                            }
+
+                            _ => None,
+                        };
+
+                        if let Some(event) = event {
+                            events::notify_observers(event).unwrap_or_else(|e| {
+                                error!("Error during notification of observers: {}", e)
+                            });
                         }
 
-                        DbusApiEvent::ActiveSlotChanged => dbus.notify_active_slot_changed()?,
+                        match result {
+                            DbusApiEvent::ProfilesChanged => dbus.notify_profiles_changed()?,
+
+                            DbusApiEvent::ActiveProfileChanged => {
+                                dbus.notify_active_profile_changed()?
+                            }
 
-                        DbusApiEvent::BrightnessChanged => dbus.notify_brightness_changed()?,
+                            DbusApiEvent::ActiveSlotChanged => dbus.notify_active_slot_changed()?,
 
-                        DbusApiEvent::DeviceStatusChanged => dbus.notify_device_status_changed()?,
+                            DbusApiEvent::BrightnessChanged => dbus.notify_brightness_changed()?,
 
-                        DbusApiEvent::DeviceHotplug(device_info, remove) => {
-                            dbus.notify_device_hotplug(device_info, remove)?
+                            DbusApiEvent::DeviceStatusChanged => {
+                                dbus.notify_device_status_changed()?
+                            }
+
+                            DbusApiEvent::DeviceHotplug(device_info, remove) => {
+                                dbus.notify_device_hotplug(device_info, remove)?
+                            }
                         }
-                    },
+                    }
 
                     Err(_e) => {
                         event_received = dbus.get_next_event_timeout(0).unwrap_or_else(|e| {
@@ -1031,30 +1174,77 @@ fn spawn_lua_thread(
     ));
 
     builder.spawn(move || -> Result<()> {
-        #[allow(clippy::never_loop)]
         loop {
-            let result = script::run_script(script_path.clone(), profile, &lua_rx);
+            let start_time = Instant::now();
+
+            let result = script::run_script(script_path.clone(), profile.clone(), &lua_rx);
 
-            match result {
+            let error = match result {
                 Ok(script::RunScriptResult::TerminatedGracefully) => return Ok(()),
 
                 Ok(script::RunScriptResult::TerminatedWithErrors) => {
-                    error!("Script execution failed");
+                    error!("Script execution failed: {}", script_path.display());
 
-                    LUA_TXS.lock().get_mut(thread_idx).unwrap().is_failed = true;
-                    REQUEST_FAILSAFE_MODE.store(true, Ordering::SeqCst);
+                    script::take_last_error().unwrap_or_else(|| "Unknown error".to_string())
+                }
+
+                Err(e) => {
+                    error!(
+                        "Script execution failed due to an unknown error: {}",
+                        script_path.display()
+                    );
 
-                    return Err(MainError::ScriptExecError {}.into());
+                    format!("{}", e)
                 }
+            };
 
-                Err(_e) => {
-                    error!("Script execution failed due to an unknown error");
+            let consecutive_crashes =
+                supervisor::record_crash(&script_path, error, start_time.elapsed());
 
-                    LUA_TXS.lock().get_mut(thread_idx).unwrap().is_failed = true;
-                    REQUEST_FAILSAFE_MODE.store(true, Ordering::SeqCst);
+            let is_disabled = budget::is_disabled();
+
+            if is_disabled || consecutive_crashes > constants::MAX_SCRIPT_RESTARTS {
+                if is_disabled {
+                    error!(
+                        "Disabled script {}, it kept exceeding its time budget",
+                        script_path.display()
+                    );
+                } else {
+                    error!(
+                        "Giving up on script {} after {} consecutive crashes",
+                        script_path.display(),
+                        consecutive_crashes
+                    );
+                }
 
-                    return Err(MainError::ScriptExecError {}.into());
+                let mut lua_txs = LUA_TXS.lock();
+
+                if let Some(lua_tx) = lua_txs.get_mut(thread_idx) {
+                    if lua_tx.script_file == script_path {
+                        lua_tx.is_failed = true;
+                    }
+                }
+
+                // only resort to the failsafe profile if no script is left running
+                if lua_txs.iter().all(|lua_tx| lua_tx.is_failed) {
+                    REQUEST_FAILSAFE_MODE.store(true, Ordering::SeqCst);
                 }
+
+                return Err(MainError::ScriptExecError {}.into());
+            }
+
+            // restart only the crashed script, the other scripts of the profile keep running
+            let delay = supervisor::backoff_delay(consecutive_crashes);
+
+            warn!(
+                "Restarting script {} in {} milliseconds",
+                script_path.display(),
+                delay.as_millis()
+            );
+
+            if supervisor::wait_for_restart(&lua_rx, delay) == supervisor::BackoffResult::Terminate
+            {
+                return Ok(());
             }
         }
     })?;
@@ -1139,6 +1329,8 @@ fn switch_profile(
 
         // be safe and clear any leftover channels
         LUA_TXS.lock().clear();
+        supervisor::clear();
+        budget::clear();
 
         switch_to_failsafe_profile(dbus_api_tx, notify)?;
         REQUEST_FAILSAFE_MODE.store(false, Ordering::SeqCst);
@@ -1163,7 +1355,7 @@ fn switch_profile(
             let mut errors_present = false;
 
             // verify script files first; better fail early if we can
-            let script_files = profile.active_scripts.clone();
+            let script_files = profile.get_active_scripts();
             for script_file in script_files.iter() {
                 let script_path = util::match_script_path(&script_file);
 
@@ -1216,6 +1408,8 @@ fn switch_profile(
 
             // be safe and clear any leftover channels
             LUA_TXS.lock().clear();
+            supervisor::clear();
+            budget::clear();
 
             // we passed the point of no return, from here on we can't just go back
             // but need to switch to failsafe mode when we encounter any critical errors
@@ -1301,6 +1495,71 @@ fn switch_profile(
     }
 }
 
+/// Reload the Lua VMs that run the modified script file `path` in place, without switching
+/// profiles. A modified library file reloads all scripts, since any of them may depend on it
+fn reload_scripts(path: &Path) {
+    // a modified manifest reloads the script it belongs to
+    let script_path = if path.extension().unwrap_or_default() == "manifest" {
+        path.with_extension("")
+    } else {
+        path.to_path_buf()
+    };
+
+    if script_path.extension().unwrap_or_default() != "lua" {
+        return;
+    }
+
+    let script_path = script_path.canonicalize().unwrap_or(script_path);
+    let is_same_script = |script_file: &Path| {
+        script_file
+            .canonicalize()
+            .unwrap_or_else(|_| script_file.to_path_buf())
+            == script_path
+    };
+
+    // libraries are loaded from the `lib` sub directory of each of the script directories,
+    // and may be required by any of the running scripts
+    let is_library = util::get_script_dirs().iter().any(|script_dir| {
+        let lib_dir = script_dir.join("lib");
+        let lib_dir = lib_dir.canonicalize().unwrap_or(lib_dir);
+
+        script_path.starts_with(lib_dir)
+    });
+
+    let mut lua_txs = LUA_TXS.lock();
+
+    for (thread_idx, lua_tx) in lua_txs.iter_mut().enumerate() {
+        if !is_library && !is_same_script(&lua_tx.script_file) {
+            continue;
+        }
+
+        info!("Reloading Lua script: {}", lua_tx.script_file.display());
+
+        supervisor::reset(&lua_tx.script_file);
+
+        if lua_tx.is_failed {
+            // we gave up on the previous version of the script, so start it from scratch
+            let profile = ACTIVE_PROFILE.lock().clone();
+
+            let (tx, rx) = unbounded();
+
+            match spawn_lua_thread(thread_idx, rx, lua_tx.script_file.clone(), profile) {
+                Ok(()) => *lua_tx = LuaTx::new(lua_tx.script_file.clone(), tx),
+
+                Err(e) => error!(
+                    "Could not restart Lua script {}: {}",
+                    lua_tx.script_file.display(),
+                    e
+                ),
+            }
+        } else {
+            lua_tx
+                .send(script::Message::Reload)
+                .unwrap_or_else(|e| error!("Could not send an event to a Lua VM: {}", e));
+        }
+    }
+}
+
 /// Process file system related events
 async fn process_filesystem_event(
     fsevent: &FileSystemEvent,
@@ -1319,7 +1578,9 @@ async fn process_filesystem_event(
             REQUEST_PROFILE_RELOAD.store(true, Ordering::SeqCst);
         }
 
-        FileSystemEvent::ScriptChanged => {}
+        FileSystemEvent::ScriptChanged { path } => {
+            reload_scripts(path);
+        }
     }
 
     Ok(())
@@ -2169,6 +2430,48 @@ async fn process_keyboard_event(
     Ok(())
 }
 
+/// Instruct the Lua VMs to realize their color maps, in order, e.g. to blend their local color
+/// maps with the canvas. Failed scripts are skipped, so that the remaining scripts of the profile
+/// keep rendering. Returns `false` if the frame has to be dropped
+fn realize_color_maps(lua_txs: &[LuaTx], failed_txs: &mut HashSet<usize>) -> bool {
+    *COLOR_MAPS_READY_CONDITION.0.lock() = (0..lua_txs.len())
+        .filter(|index| !failed_txs.contains(index))
+        .count();
+
+    for (index, lua_tx) in lua_txs.iter().enumerate() {
+        // if this tx failed previously, then skip it completely
+        if failed_txs.contains(&index) {
+            continue;
+        }
+
+        // guarantee the right order of execution for the alpha blend
+        // operations, so we have to wait for the current Lua VM to
+        // complete its blending code, before continuing
+        let mut pending = COLOR_MAPS_READY_CONDITION.0.lock();
+
+        if let Err(e) = lua_tx.send(script::Message::RealizeColorMap) {
+            error!("Send error during realization of color maps: {}", e);
+
+            failed_txs.insert(index);
+            *pending = pending.saturating_sub(1);
+
+            continue;
+        }
+
+        let result = COLOR_MAPS_READY_CONDITION.1.wait_for(
+            &mut pending,
+            Duration::from_millis(constants::TIMEOUT_CONDITION_MILLIS),
+        );
+
+        if result.timed_out() {
+            warn!("Frame dropped: Timeout while waiting for a lock!");
+            return false;
+        }
+    }
+
+    true
+}
+
 async fn run_main_loop(
     dbus_api_tx: &Sender<DbusApiEvent>,
     ctrl_c_rx: &Receiver<bool>,
@@ -2556,6 +2859,9 @@ async fn run_main_loop(
                 }
             }
 
+            // fall back to the output of the scripts, when the D-Bus overlay expired
+            overlay::expire_overlay();
+
             // finally, update the LEDs if necessary
             let current_frame_generation = script::FRAME_GENERATION_COUNTER.load(Ordering::SeqCst);
             if saved_frame_generation.load(Ordering::SeqCst) < current_frame_generation {
@@ -2566,91 +2872,81 @@ async fn run_main_loop(
                 let mut drop_frame = false;
 
                 // first, clear the canvas
-                script::LED_MAP.write().copy_from_slice(
-                    &[hwdevices::RGBA {
-                        r: 0,
-                        g: 0,
-                        b: 0,
-                        a: 0,
-                    }; constants::CANVAS_SIZE],
-                );
+                script::LED_MAP.write().fill(hwdevices::RGBA {
+                    r: 0,
+                    g: 0,
+                    b: 0,
+                    a: 0,
+                });
 
                 // instruct Lua VMs to realize their color maps,
                 // e.g. to blend their local color maps with the canvas
-                *COLOR_MAPS_READY_CONDITION.0.lock() = LUA_TXS.lock().len() - failed_txs.len();
+                if !realize_color_maps(&LUA_TXS.lock(), &mut failed_txs) {
+                    drop_frame = true;
+                }
 
-                for (index, lua_tx) in LUA_TXS.lock().iter().enumerate() {
-                    // if this tx failed previously, then skip it completely
-                    if !failed_txs.contains(&index) {
-                        // guarantee the right order of execution for the alpha blend
-                        // operations, so we have to wait for the current Lua VM to
-                        // complete its blending code, before continuing
-                        let mut pending = COLOR_MAPS_READY_CONDITION.0.lock();
+                // apply the static key overrides of the active profile
+                if let Some(profile) = ACTIVE_PROFILE.lock().as_ref() {
+                    let overrides = profile.get_overrides();
 
-                        lua_tx
-                            .send(script::Message::RealizeColorMap)
-                            .unwrap_or_else(|e| {
-                                error!("Send error during realization of color maps: {}", e);
-                                failed_txs.insert(index);
-                            });
+                    if !overrides.is_empty() {
+                        let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
 
-                        let result = COLOR_MAPS_READY_CONDITION.1.wait_for(
-                            &mut pending,
-                            Duration::from_millis(constants::TIMEOUT_CONDITION_MILLIS),
+                        compositing::apply_overrides(
+                            &mut script::LED_MAP.write(),
+                            &overrides,
+                            brightness as i32,
                         );
-
-                        if result.timed_out() {
-                            drop_frame = true;
-                            warn!("Frame dropped: Timeout while waiting for a lock!");
-                            break;
-                        }
-                    } else {
-                        drop_frame = true;
                     }
                 }
 
                 if ULEDS_SUPPORT_ACTIVE.load(Ordering::SeqCst) {
                     // blend the LED map of the Userspace LEDs support plugin
-                    let uleds_led_map = uleds::LED_MAP.read();
                     let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
 
-                    for (idx, background) in script::LED_MAP.write().iter_mut().enumerate() {
-                        let bg = &background;
-                        let fg = uleds_led_map[idx];
-
-                        #[rustfmt::skip]
-                        let color = RGBA {
-                            r: ((((fg.a as f64) * fg.r as f64 + (255 - fg.a) as f64 * bg.r as f64).abs() * brightness as f64 / 100.0) as u32 >> 8) as u8,
-                            g: ((((fg.a as f64) * fg.g as f64 + (255 - fg.a) as f64 * bg.g as f64).abs() * brightness as f64 / 100.0) as u32 >> 8) as u8,
-                            b: ((((fg.a as f64) * fg.b as f64 + (255 - fg.a) as f64 * bg.b as f64).abs() * brightness as f64 / 100.0) as u32 >> 8) as u8,
-                            a: fg.a as u8,
-                        };
-
-                        *background = color;
-                    }
+                    compositing::blend_over(
+                        &mut script::LED_MAP.write(),
+                        &uleds::LED_MAP.read(),
+                        brightness as i32,
+                    );
                 }
 
                 if SDK_SUPPORT_ACTIVE.load(Ordering::SeqCst) {
-                    // finally, blend the LED map of the SDK support plugin
-                    let sdk_led_map = sdk_support::LED_MAP.read();
+                    // finally, blend the canvas layers of the SDK clients, from the
+                    // bottom-most to the top-most layer
+                    let layers = sdk_support::LAYERS.read();
                     let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
 
-                    for (idx, background) in script::LED_MAP.write().iter_mut().enumerate() {
-                        let bg = &background;
-                        let fg = sdk_led_map[idx];
-
-                        #[rustfmt::skip]
-                        let color = RGBA {
-                            r: ((((fg.a as f64) * fg.r as f64 + (255 - fg.a) as f64 * bg.r as f64).abs() * brightness as f64 / 100.0) as u32 >> 8) as u8,
-                            g: ((((fg.a as f64) * fg.g as f64 + (255 - fg.a) as f64 * bg.g as f64).abs() * brightness as f64 / 100.0) as u32 >> 8) as u8,
-                            b: ((((fg.a as f64) * fg.b as f64 + (255 - fg.a) as f64 * bg.b as f64).abs() * brightness as f64 / 100.0) as u32 >> 8) as u8,
-                            a: fg.a as u8,
-                        };
+                    for layer in sdk_support::ordered_layers(&layers) {
+                        // apply the opacity of the layer
+                        let foreground = layer
+                            .led_map
+                            .iter()
+                            .map(|fg| RGBA {
+                                a: (fg.a as u32 * layer.opacity as u32 / 255) as u8,
+                                ..*fg
+                            })
+                            .collect::<Vec<RGBA>>();
 
-                        *background = color;
+                        compositing::blend_over(
+                            &mut script::LED_MAP.write(),
+                            &foreground,
+                            brightness as i32,
+                        );
                     }
                 }
 
+                if let Some(overlay) = overlay::OVERLAY.read().as_ref() {
+                    // blend the overlay that has been set via the D-Bus API on top of everything else
+                    let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
+
+                    compositing::blend_over(
+                        &mut script::LED_MAP.write(),
+                        &overlay.led_map,
+                        brightness as i32,
+                    );
+                }
+
                 // number of pending blend ops should have reached zero by now
                 // may currently occur during switching of profiles
                 let ops_pending = *COLOR_MAPS_READY_CONDITION.0.lock();
@@ -2661,27 +2957,61 @@ async fn run_main_loop(
                     );
                 }
 
-                // send the final (combined) color map to all of the devices
+                // send the final (combined) color map to all of the devices, devices with an
+                // allocated zone only receive their part of the canvas. The canvas is always
+                // mapped onto the default layout, since that is what the device drivers expect
                 if !drop_frame {
+                    let mut instances = zones::InstanceCounter::default();
+                    let mut zone_led_map = vec![
+                        hwdevices::RGBA {
+                            r: 0,
+                            g: 0,
+                            b: 0,
+                            a: 0,
+                        };
+                        constants::CANVAS_SIZE
+                    ];
+
                     for device in crate::KEYBOARD_DEVICES.read().iter() {
+                        let device_id = instances
+                            .next_id(device.read().get_usb_vid(), device.read().get_usb_pid());
+
                         if device.read().is_initialized()? {
-                            device.write().send_led_map(&script::LED_MAP.read())?;
+                            let zone = zones::get_zone(&device_id)
+                                .unwrap_or_else(zones::Zone::full_canvas);
+
+                            zone.map_canvas(&script::LED_MAP.read(), &mut zone_led_map);
+                            device.write().send_led_map(&zone_led_map)?;
                         } else {
                             warn!("Skipping uninitialized device")
                         }
                     }
 
                     for device in crate::MOUSE_DEVICES.read().iter() {
+                        let device_id = instances
+                            .next_id(device.read().get_usb_vid(), device.read().get_usb_pid());
+
                         if device.read().is_initialized()? {
-                            device.write().send_led_map(&script::LED_MAP.read())?;
+                            let zone = zones::get_zone(&device_id)
+                                .unwrap_or_else(zones::Zone::full_canvas);
+
+                            zone.map_canvas(&script::LED_MAP.read(), &mut zone_led_map);
+                            device.write().send_led_map(&zone_led_map)?;
                         } else {
                             warn!("Skipping uninitialized device")
                         }
                     }
 
                     for device in crate::MISC_DEVICES.read().iter() {
+                        let device_id = instances
+                            .next_id(device.read().get_usb_vid(), device.read().get_usb_pid());
+
                         if device.read().is_initialized()? {
-                            device.write().send_led_map(&script::LED_MAP.read())?;
+                            let zone = zones::get_zone(&device_id)
+                                .unwrap_or_else(zones::Zone::full_canvas);
+
+                            zone.map_canvas(&script::LED_MAP.read(), &mut zone_led_map);
+                            device.write().send_led_map(&zone_led_map)?;
                         } else {
                             warn!("Skipping uninitialized device")
                         }
@@ -2693,6 +3023,10 @@ async fn run_main_loop(
                     script::LAST_RENDERED_LED_MAP
                         .write()
                         .copy_from_slice(&script::LED_MAP.read());
+
+                    events::notify_observers(events::Event::FrameRendered).unwrap_or_else(|e| {
+                        error!("Error during notification of observers: {}", e)
+                    });
                 }
 
                 fps_counter += 1;
@@ -2761,11 +3095,20 @@ fn remove_failed_devices() -> Result<bool> {
     let mut result = false;
 
     let mut keyboard_devices = crate::KEYBOARD_DEVICES.write();
-    if let Some(index) = keyboard_devices
-        .iter()
-        .position(|device: &hwdevices::KeyboardDevice| device.read().has_failed().unwrap_or(true))
+    while let Some(index) =
+        keyboard_devices
+            .iter()
+            .position(|device: &hwdevices::KeyboardDevice| {
+                let device = device.read();
+
+                device.has_failed().unwrap_or(true)
+                    || hotplug::is_removal_pending(&device.get_usb_path())
+            })
     {
-        info!("Unplugging a failed keyboard device...");
+        info!("Unplugging a failed or disconnected keyboard device...");
+
+        let usb_vid = keyboard_devices[index].read().get_usb_vid();
+        let usb_pid = keyboard_devices[index].read().get_usb_pid();
 
         let mut devices_rx = crate::KEYBOARD_DEVICES_RX.write();
         assert!(devices_rx.len() > index);
@@ -2782,16 +3125,24 @@ fn remove_failed_devices() -> Result<bool> {
         let dbus_api_tx = dbus_api_tx.as_ref().unwrap();
 
         dbus_api_tx
-            .send(DbusApiEvent::DeviceHotplug((0, 0), true))
+            .send(DbusApiEvent::DeviceHotplug((usb_vid, usb_pid), true))
             .unwrap_or_else(|e| error!("Could not send a pending dbus API event: {}", e));
     }
 
     let mut mouse_devices = crate::MOUSE_DEVICES.write();
-    if let Some(index) = mouse_devices
+    while let Some(index) = mouse_devices
         .iter()
-        .position(|device: &hwdevices::MouseDevice| device.read().has_failed().unwrap_or(true))
+        .position(|device: &hwdevices::MouseDevice| {
+            let device = device.read();
+
+            device.has_failed().unwrap_or(true)
+                || hotplug::is_removal_pending(&device.get_usb_path())
+        })
     {
-        info!("Unplugging a failed mouse device...");
+        info!("Unplugging a failed or disconnected mouse device...");
+
+        let usb_vid = mouse_devices[index].read().get_usb_vid();
+        let usb_pid = mouse_devices[index].read().get_usb_pid();
 
         let mut devices_rx = crate::MOUSE_DEVICES_RX.write();
         assert!(devices_rx.len() > index);
@@ -2808,16 +3159,24 @@ fn remove_failed_devices() -> Result<bool> {
         let dbus_api_tx = dbus_api_tx.as_ref().unwrap();
 
         dbus_api_tx
-            .send(DbusApiEvent::DeviceHotplug((0, 0), true))
+            .send(DbusApiEvent::DeviceHotplug((usb_vid, usb_pid), true))
             .unwrap_or_else(|e| error!("Could not send a pending dbus API event: {}", e));
     }
 
     let mut misc_devices = crate::MISC_DEVICES.write();
-    if let Some(index) = misc_devices
+    while let Some(index) = misc_devices
         .iter()
-        .position(|device: &hwdevices::MiscDevice| device.read().has_failed().unwrap_or(true))
+        .position(|device: &hwdevices::MiscDevice| {
+            let device = device.read();
+
+            device.has_failed().unwrap_or(true)
+                || hotplug::is_removal_pending(&device.get_usb_path())
+        })
     {
-        info!("Unplugging a failed misc device...");
+        info!("Unplugging a failed or disconnected misc device...");
+
+        let usb_vid = misc_devices[index].read().get_usb_vid();
+        let usb_pid = misc_devices[index].read().get_usb_pid();
 
         let mut devices_rx = crate::MISC_DEVICES_RX.write();
         assert!(devices_rx.len() > index);
@@ -2834,10 +3193,12 @@ fn remove_failed_devices() -> Result<bool> {
         let dbus_api_tx = dbus_api_tx.as_ref().unwrap();
 
         dbus_api_tx
-            .send(DbusApiEvent::DeviceHotplug((0, 0), true))
+            .send(DbusApiEvent::DeviceHotplug((usb_vid, usb_pid), true))
             .unwrap_or_else(|e| error!("Could not send a pending dbus API event: {}", e));
     }
 
+    hotplug::clear_pending_removals();
+
     Ok(result)
 }
 
@@ -2919,7 +3280,13 @@ pub fn register_filesystem_watcher(
                             .watch(&script_dir, move |event: Event| {
                                 info!("Script file or manifest changed: {:?}", event);
 
-                                fsevents_tx_c.send(FileSystemEvent::ScriptChanged).unwrap();
+                                match event {
+                                    Event::Write(path) | Event::Create(path) | Event::Rename(_, path) => {
+                                        fsevents_tx_c.send(FileSystemEvent::ScriptChanged { path }).unwrap();
+                                    }
+
+                                    _ => {}
+                                }
 
                                 Flow::Continue
                             })
@@ -3070,6 +3437,158 @@ fn init_misc_device(misc_device: &MiscDevice) {
     );
 }
 
+/// Set up the dimensions of the canvas, as specified in the configuration file
+fn init_canvas_geometry(config: &Config) {
+    let canvas_width = config
+        .get::<usize>("global.canvas_width")
+        .unwrap_or(constants::CANVAS_WIDTH);
+    let canvas_height = config
+        .get::<usize>("global.canvas_height")
+        .unwrap_or(constants::CANVAS_HEIGHT);
+
+    if (constants::CANVAS_WIDTH..=constants::MAX_CANVAS_WIDTH).contains(&canvas_width)
+        && (constants::CANVAS_HEIGHT..=constants::MAX_CANVAS_HEIGHT).contains(&canvas_height)
+    {
+        CANVAS_WIDTH.store(canvas_width, Ordering::SeqCst);
+        CANVAS_HEIGHT.store(canvas_height, Ordering::SeqCst);
+        CANVAS_SIZE.store(canvas_width * canvas_height, Ordering::SeqCst);
+    } else {
+        error!(
+            "Invalid canvas dimensions specified: {}x{}, the canvas has to be at least {}x{} and at most {}x{}",
+            canvas_width,
+            canvas_height,
+            constants::CANVAS_WIDTH,
+            constants::CANVAS_HEIGHT,
+            constants::MAX_CANVAS_WIDTH,
+            constants::MAX_CANVAS_HEIGHT
+        );
+    }
+
+    info!(
+        "Canvas dimensions: {}x{}",
+        CANVAS_WIDTH.load(Ordering::SeqCst),
+        CANVAS_HEIGHT.load(Ordering::SeqCst)
+    );
+}
+
+/// Prepare running Lua scripts without any hardware attached
+fn init_headless(matches: &clap::ArgMatches) -> std::result::Result<(), eyre::Error> {
+    // the configuration file is optional here, e.g. on a CI system
+    let config_file = matches
+        .value_of("config")
+        .unwrap_or(constants::DEFAULT_CONFIG_FILE);
+
+    let config = Config::builder()
+        .add_source(config::File::new(config_file, config::FileFormat::Toml).required(false))
+        .build()?;
+
+    *CONFIG.lock() = Some(config.clone());
+
+    init_canvas_geometry(&config);
+
+    plugins::register_plugins_headless();
+
+    Ok(())
+}
+
+/// Render Lua effects to image files, without any hardware attached
+fn render_headless(
+    matches: &clap::ArgMatches,
+    render_matches: &clap::ArgMatches,
+) -> std::result::Result<(), eyre::Error> {
+    init_headless(matches)?;
+
+    let events = render_matches
+        .values_of("event")
+        .unwrap_or_default()
+        .map(|event| event.parse::<headless::SyntheticEvent>())
+        .collect::<Result<Vec<_>>>()?;
+
+    let options = headless::RenderOptions {
+        profile: render_matches.value_of("profile").map(PathBuf::from),
+        script: render_matches.value_of("script").map(PathBuf::from),
+        frames: render_matches.value_of_t("frames")?,
+        output: PathBuf::from(render_matches.value_of("output").unwrap()),
+        format: render_matches.value_of("format").unwrap().parse()?,
+        scale: render_matches.value_of_t("scale")?,
+        events,
+    };
+
+    headless::render(&options)?;
+
+    Ok(())
+}
+
+/// Run the unit tests of Lua scripts and macros, without any hardware attached
+fn run_script_tests(
+    matches: &clap::ArgMatches,
+    test_matches: &clap::ArgMatches,
+) -> std::result::Result<(), eyre::Error> {
+    init_headless(matches)?;
+
+    let devices = test_matches
+        .values_of("device")
+        .map(|devices| devices.map(String::from).collect::<Vec<_>>());
+
+    *harness::EMULATED_DEVICES.write() = Some(devices.unwrap_or_default());
+
+    let mut total = 0;
+    let mut failed = 0;
+
+    for test_file in test_matches.values_of("FILES").unwrap().map(PathBuf::from) {
+        // `macros.test.lua` tests the script `macros.lua`
+        let script = match test_matches.value_of("script") {
+            Some(script) => PathBuf::from(script),
+
+            None => PathBuf::from(
+                test_file
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .replace(".test.lua", ".lua"),
+            ),
+        };
+
+        let script_file = if script.is_file() {
+            script
+        } else {
+            util::match_script_path(&script)?
+        };
+
+        println!(
+            "Running tests of {} against {}",
+            test_file.display(),
+            script_file.display()
+        );
+
+        for result in harness::run_tests(&script_file, &test_file)? {
+            total += 1;
+
+            match result.error {
+                None => println!("test {} ... ok", result.name),
+
+                Some(error) => {
+                    failed += 1;
+
+                    println!("test {} ... FAILED\n{}", result.name, error);
+                }
+            }
+        }
+    }
+
+    println!(
+        "\ntest result: {} passed, {} failed",
+        total - failed,
+        failed
+    );
+
+    if failed > 0 {
+        Err(harness::HarnessError::TestsFailed { failed, total }.into())
+    } else {
+        Ok(())
+    }
+}
+
 pub async fn async_main() -> std::result::Result<(), eyre::Error> {
     cfg_if::cfg_if! {
         if #[cfg(debug_assertions)] {
@@ -3084,7 +3603,13 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
         }
     }
 
-    if unsafe { libc::isatty(0) != 0 } {
+    let matches = parse_commandline();
+
+    // always log to the console when running headless, e.g. on a CI system
+    let is_headless = matches.subcommand_matches("render").is_some()
+        || matches.subcommand_matches("test").is_some();
+
+    if unsafe { libc::isatty(0) != 0 } || is_headless {
         // print a license header, except if we are generating shell completions
         if !env::args().any(|a| a.eq_ignore_ascii_case("completions")) && env::args().count() < 2 {
             print_header();
@@ -3136,7 +3661,13 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
     thread_util::deadlock_detector()
         .unwrap_or_else(|e| error!("Could not spawn deadlock detector thread: {}", e));
 
-    let matches = parse_commandline();
+    if let Some(render_matches) = matches.subcommand_matches("render") {
+        return render_headless(&matches, render_matches);
+    }
+
+    if let Some(test_matches) = matches.subcommand_matches("test") {
+        return run_script_tests(&matches, test_matches);
+    }
 
     info!(
         "Starting Eruption - Linux user-mode input and LED driver for keyboards, mice and other devices: Version {} ({}) ({} build)",
@@ -3211,6 +3742,9 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
         }
     }
 
+    // canvas geometry
+    init_canvas_geometry(&config);
+
     // load and initialize global runtime state
     info!("Loading saved state...");
     state::init_global_runtime_state()
@@ -3238,6 +3772,9 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
 
             info!("Plugins loaded and initialized successfully");
 
+            // register drivers for the devices described by device definition files
+            hwdevices::register_declarative_drivers();
+
             // enumerate devices
             info!("Enumerating connected devices...");
 
@@ -3392,6 +3929,11 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                 register_filesystem_watcher(fsevents_tx, PathBuf::from(&config_file))
                     .unwrap_or_else(|e| error!("Could not register file changes watcher: {}", e));
 
+                // bind and release devices while we are running, instead of being restarted
+                info!("Initializing device hotplug support...");
+                hotplug::spawn_udev_monitor_thread()
+                    .unwrap_or_else(|e| error!("Could not initialize the udev monitor: {}", e));
+
                 info!("Late initializations completed");
 
                 info!("Startup completed");
@@ -3548,3 +4090,49 @@ pub fn main() -> std::result::Result<(), eyre::Error> {
 
     runtime.block_on(async move { async_main().await })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_scripts_do_not_drop_frames() {
+        // the first script has been given up on, so its Lua VM is gone
+        let (failed_tx, failed_rx) = unbounded();
+        drop(failed_rx);
+
+        let (tx, rx) = unbounded();
+
+        let responder = thread::spawn(move || {
+            let mut count = 0;
+
+            while let Ok(script::Message::RealizeColorMap) = rx.recv() {
+                let mut pending = COLOR_MAPS_READY_CONDITION.0.lock();
+                *pending = pending.saturating_sub(1);
+
+                COLOR_MAPS_READY_CONDITION.1.notify_one();
+
+                count += 1;
+            }
+
+            count
+        });
+
+        let lua_txs = vec![
+            LuaTx::new(PathBuf::from("failed.lua"), failed_tx),
+            LuaTx::new(PathBuf::from("running.lua"), tx),
+        ];
+
+        let mut failed_txs = HashSet::new();
+
+        assert!(realize_color_maps(&lua_txs, &mut failed_txs));
+        assert!(failed_txs.contains(&0));
+
+        // the failed script is skipped from now on
+        assert!(realize_color_maps(&lua_txs, &mut failed_txs));
+
+        drop(lua_txs);
+
+        assert_eq!(responder.join().unwrap(), 2);
+    }
+}
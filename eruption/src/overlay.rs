@@ -0,0 +1,115 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::hwdevices::RGBA;
+use crate::script;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OverlayError {
+    #[error("Invalid LED index: {index}")]
+    InvalidIndex { index: usize },
+}
+
+lazy_static! {
+    /// The overlay set via the D-Bus API, blended above the output of the scripts
+    pub static ref OVERLAY: Arc<RwLock<Option<Overlay>>> = Arc::new(RwLock::new(None));
+}
+
+/// Colors of individual LEDs, that temporarily override the output of the scripts
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    pub led_map: Vec<RGBA>,
+    pub expiry: Instant,
+}
+
+/// Show `colors`, a list of LED indices and RGBA colors, above the output of the scripts
+/// for the duration of `timeout`. Replaces the current overlay, an empty list of colors
+/// or a timeout of zero removes it
+pub fn set_led_colors(colors: &[(u32, u8, u8, u8, u8)], timeout: Duration) -> Result<()> {
+    if colors.is_empty() || timeout.is_zero() {
+        *OVERLAY.write() = None;
+    } else {
+        let mut led_map = vec![
+            RGBA {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            };
+            crate::CANVAS_SIZE.load(Ordering::SeqCst)
+        ];
+
+        for (index, r, g, b, a) in colors.iter() {
+            let index = *index as usize;
+
+            let color = led_map
+                .get_mut(index)
+                .ok_or(OverlayError::InvalidIndex { index })?;
+
+            *color = RGBA {
+                r: *r,
+                g: *g,
+                b: *b,
+                a: *a,
+            };
+        }
+
+        *OVERLAY.write() = Some(Overlay {
+            led_map,
+            expiry: Instant::now() + timeout,
+        });
+    }
+
+    script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+    Ok(())
+}
+
+/// Remove the overlay once it expired, so that the output of the scripts is shown again
+pub fn expire_overlay() {
+    let expired =
+        matches!(OVERLAY.read().as_ref(), Some(overlay) if overlay.expiry <= Instant::now());
+
+    if expired {
+        *OVERLAY.write() = None;
+
+        script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_index_is_rejected() {
+        let index = crate::CANVAS_SIZE.load(Ordering::SeqCst) as u32;
+
+        assert!(set_led_colors(&[(index, 255, 0, 0, 255)], Duration::from_secs(1)).is_err());
+        assert!(OVERLAY.read().is_none());
+    }
+}
@@ -69,6 +69,18 @@ impl PluginManager {
         Ok(())
     }
 
+    /// Register a plugin without initializing it. Its Lua functions will be available to the
+    /// scripts, but it won't acquire any system resources, e.g. when rendering in headless mode
+    pub fn register_plugin_headless(&mut self, plugin: Box<PluginType>) {
+        debug!(
+            "Registering plugin (headless): {} - {}",
+            plugin.get_name(),
+            plugin.get_description()
+        );
+
+        self.registered_plugins.insert(plugin.get_name(), plugin);
+    }
+
     pub fn get_plugins(&self) -> Vec<&PluginType> {
         self.registered_plugins
             .values()
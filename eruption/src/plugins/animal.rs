@@ -33,12 +33,13 @@ use std::{
     cell::RefCell,
     cmp::min,
     collections::{HashMap, VecDeque},
+    sync::atomic::Ordering,
     sync::Arc,
     time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
+use crate::plugins;
 use crate::plugins::Plugin;
-use crate::{constants, plugins};
 
 pub type Result<T> = std::result::Result<T, eyre::Error>;
 
@@ -353,7 +354,7 @@ impl Animal {
             }
         }
 
-        result.resize_with(constants::CANVAS_SIZE, || 0x000000000);
+        result.resize_with(crate::CANVAS_SIZE.load(Ordering::SeqCst), || 0x000000000);
         result
     }
 }
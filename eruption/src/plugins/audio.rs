@@ -149,6 +149,16 @@ impl AudioPlugin {
         AUDIO_GRABBER_BUFFER.read().to_vec()
     }
 
+    /// Set the loudness that is reported to the Lua scripts, e.g. to feed synthetic audio data
+    pub fn set_audio_loudness(loudness: isize) {
+        CURRENT_RMS.store(loudness, Ordering::SeqCst);
+    }
+
+    /// Set all buckets of the spectrum analyzer to `level`, e.g. to feed synthetic audio data
+    pub fn set_audio_spectrum(level: f32) {
+        AUDIO_SPECTRUM.write().fill(level);
+    }
+
     pub fn get_audio_volume() -> isize {
         if let Some(backend) = &*AUDIO_BACKEND.lock() {
             backend.get_master_volume().unwrap_or(0) * 100 / u16::MAX as isize
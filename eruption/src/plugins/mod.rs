@@ -132,3 +132,31 @@ pub async fn register_plugins() -> Result<()> {
 
     Ok(())
 }
+
+/// Register all available plugins, without initializing them
+pub fn register_plugins_headless() {
+    trace!("Registering all available plugins (headless)...");
+
+    let mut plugin_manager = plugin_manager::PLUGIN_MANAGER.write();
+
+    let plugins: Vec<Box<dyn Plugin + Sync + Send>> = vec![
+        Box::new(KeyboardPlugin::new()),
+        Box::new(MousePlugin::new()),
+        Box::new(MacrosPlugin::new()),
+        Box::new(IntrospectionPlugin::new()),
+        Box::new(PersistencePlugin::new()),
+        Box::new(ProfilesPlugin::new()),
+        Box::new(SystemPlugin::new()),
+        Box::new(SensorsPlugin::new()),
+        Box::new(UledsPlugin::new()),
+        Box::new(SdkSupportPlugin::new()),
+        Box::new(AudioPlugin::new()),
+        Box::new(AnimalPlugin::new()),
+    ];
+
+    for plugin in plugins {
+        plugin_manager.register_plugin_headless(plugin);
+    }
+
+    trace!("Done registering all available plugins");
+}
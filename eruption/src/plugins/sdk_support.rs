@@ -18,29 +18,34 @@
 */
 
 use crate::{
-    constants, hwdevices, init_keyboard_device, init_misc_device, init_mouse_device, script,
-    spawn_keyboard_input_thread, spawn_misc_input_thread, spawn_mouse_input_thread, DbusApiEvent,
-    SDK_SUPPORT_ACTIVE,
+    constants, events, hwdevices, init_keyboard_device, init_misc_device, init_mouse_device,
+    script, spawn_keyboard_input_thread, spawn_misc_input_thread, spawn_mouse_input_thread,
+    DbusApiEvent, SDK_SUPPORT_ACTIVE,
 };
-use crossbeam::channel::unbounded;
+use crossbeam::channel::{unbounded, Receiver, Sender};
 use lazy_static::lazy_static;
-use log::{debug, error, info, trace};
+use log::{debug, error, info, trace, warn};
 use mlua::prelude::*;
 use nix::poll::{poll, PollFd, PollFlags};
-use nix::unistd::unlink;
+use nix::sys::eventfd::{eventfd, EfdFlags};
+use nix::unistd::{read, unlink, write};
 use parking_lot::{Mutex, RwLock};
 use prost::Message;
+use protocol::event::Payload as EventPayload;
 use protocol::request::Payload as RequestPayload;
 use protocol::response::Payload as ResponsePayload;
-use socket2::{Domain, SockAddr, Socket, Type};
+use sha2::{Digest, Sha256};
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
 use std::any::Any;
-use std::io::Cursor;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{Cursor, ErrorKind};
 use std::mem::MaybeUninit;
+use std::net::SocketAddr;
 use std::os::unix::fs::PermissionsExt;
-use std::os::unix::io::AsRawFd;
-use std::sync::atomic::Ordering;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{fs, thread};
 
 use crate::hwdevices::RGBA;
@@ -59,17 +64,160 @@ pub enum SdkPluginError {
 }
 
 lazy_static! {
-    /// Global LED map, the "canvas"
-    pub static ref LED_MAP: Arc<RwLock<Vec<RGBA>>> = Arc::new(RwLock::new(vec![RGBA {
-        r: 0x00,
-        g: 0x00,
-        b: 0x00,
-        a: 0x00,
-    }; constants::CANVAS_SIZE]));
+    /// Canvas layers of the connected SDK clients, keyed by client id
+    pub static ref LAYERS: Arc<RwLock<BTreeMap<u64, Layer>>> = Arc::new(RwLock::new(BTreeMap::new()));
+}
+
+/// The client id that will be assigned to the next SDK client
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// The canvas layer of an SDK client
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub led_map: Vec<RGBA>,
+
+    /// Layers with a higher priority are composited on top of layers with a lower priority
+    pub priority: i32,
+
+    /// Opacity of the whole layer
+    pub opacity: u8,
+}
+
+impl Default for Layer {
+    fn default() -> Self {
+        Self {
+            led_map: vec![
+                RGBA {
+                    r: 0x00,
+                    g: 0x00,
+                    b: 0x00,
+                    a: 0x00,
+                };
+                crate::CANVAS_SIZE.load(Ordering::SeqCst)
+            ],
+            priority: 0,
+            opacity: 255,
+        }
+    }
+}
+
+/// Returns the layers ordered from the bottom-most to the top-most layer. Layers of equal
+/// priority are stacked in the order in which their clients connected
+pub fn ordered_layers(layers: &BTreeMap<u64, Layer>) -> Vec<&Layer> {
+    let mut result = layers.values().collect::<Vec<_>>();
+    result.sort_by_key(|layer| layer.priority);
+
+    result
 }
 
 lazy_static! {
     pub static ref LISTENER: Arc<Mutex<Option<Socket>>> = Arc::new(Mutex::new(None));
+
+    /// TCP listener for remote SDK clients, only available if enabled in eruption.conf
+    pub static ref REMOTE_LISTENER: Arc<Mutex<Option<Socket>>> = Arc::new(Mutex::new(None));
+
+    /// Shared secret that remote SDK clients use to authenticate
+    static ref REMOTE_SECRET: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
+
+    /// Events destined for the subscribed SDK clients
+    static ref EVENTS: (Sender<protocol::Event>, Receiver<protocol::Event>) = unbounded();
+
+    /// Used to wake up the I/O loop, when there are pending events
+    static ref EVENT_FD: Arc<Mutex<Option<RawFd>>> = Arc::new(Mutex::new(None));
+}
+
+/// Bitmask of the event types that at least one SDK client has subscribed to
+static SUBSCRIBED_EVENTS: AtomicU32 = AtomicU32::new(0);
+
+/// Returns a bitmask with the bit of each of `event_types` set
+fn event_mask(event_types: &[i32]) -> u32 {
+    event_types
+        .iter()
+        .filter(|t| (0..32).contains(*t))
+        .fold(0, |mask, t| mask | (1 << t))
+}
+
+/// Returns `true` if at least one SDK client has subscribed to events of type `event_type`
+fn is_subscribed(event_type: protocol::EventType) -> bool {
+    SUBSCRIBED_EVENTS.load(Ordering::SeqCst) & event_mask(&[event_type as i32]) != 0
+}
+
+/// Encode `led_map` as a sequence of RGBA bytes
+fn encode_led_map(led_map: &[RGBA]) -> Vec<u8> {
+    led_map.iter().flat_map(|c| [c.r, c.g, c.b, c.a]).collect()
+}
+
+/// Translate an internal event to an SDK protocol event, returns `None` for event
+/// types that are not available to SDK clients
+fn make_event(event: &events::Event) -> Option<protocol::Event> {
+    let (event_type, payload) = match event {
+        events::Event::KeyDown(index) => (
+            protocol::EventType::KeyDown,
+            EventPayload::KeyIndex(*index as u32),
+        ),
+
+        events::Event::KeyUp(index) => (
+            protocol::EventType::KeyUp,
+            EventPayload::KeyIndex(*index as u32),
+        ),
+
+        events::Event::MouseButtonDown(index) => (
+            protocol::EventType::MouseButtonDown,
+            EventPayload::ButtonIndex(*index as u32),
+        ),
+
+        events::Event::MouseButtonUp(index) => (
+            protocol::EventType::MouseButtonUp,
+            EventPayload::ButtonIndex(*index as u32),
+        ),
+
+        events::Event::ProfileChanged(profile_file) => (
+            protocol::EventType::ProfileChanged,
+            EventPayload::ProfileFile(
+                profile_file
+                    .as_ref()
+                    .map(|f| f.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            ),
+        ),
+
+        events::Event::SlotChanged(slot) => (
+            protocol::EventType::SlotChanged,
+            EventPayload::Slot(*slot as u32),
+        ),
+
+        events::Event::BrightnessChanged(brightness) => (
+            protocol::EventType::BrightnessChanged,
+            EventPayload::Brightness(*brightness as i32),
+        ),
+
+        events::Event::DeviceHotplug((usb_vid, usb_pid), removed) => (
+            protocol::EventType::DeviceHotplug,
+            EventPayload::Hotplug(protocol::DeviceHotplug {
+                usb_vid: *usb_vid as u32,
+                usb_pid: *usb_pid as u32,
+                removed: *removed,
+            }),
+        ),
+
+        // only encode frames if they will actually be sent
+        events::Event::FrameRendered if is_subscribed(protocol::EventType::FrameRendered) => (
+            protocol::EventType::FrameRendered,
+            EventPayload::Frame(protocol::Frame {
+                width: crate::CANVAS_WIDTH.load(Ordering::SeqCst) as u32,
+                height: crate::CANVAS_HEIGHT.load(Ordering::SeqCst) as u32,
+                data: encode_led_map(&script::LAST_RENDERED_LED_MAP.read()),
+            }),
+        ),
+
+        _ => return None,
+    };
+
+    let mut result = protocol::Event::default();
+    result.set_event_type(event_type);
+    result.payload = Some(payload);
+
+    Some(result)
 }
 
 use bincode::{Decode, Encode};
@@ -93,6 +241,7 @@ pub fn claim_hotplugged_devices(_hotplug_info: &HotplugInfo) -> Result<()> {
                 if !crate::KEYBOARD_DEVICES.read().iter().any(|d| {
                     d.read().get_usb_vid() == device.read().get_usb_vid()
                         && d.read().get_usb_pid() == device.read().get_usb_pid()
+                        && d.read().get_usb_path() == device.read().get_usb_path()
                 }) {
                     info!("Initializing the hotplugged keyboard device...");
 
@@ -150,6 +299,7 @@ pub fn claim_hotplugged_devices(_hotplug_info: &HotplugInfo) -> Result<()> {
                     if !crate::MOUSE_DEVICES.read().iter().any(|d| {
                         d.read().get_usb_vid() == device.read().get_usb_vid()
                             && d.read().get_usb_pid() == device.read().get_usb_pid()
+                            && d.read().get_usb_path() == device.read().get_usb_path()
                     }) {
                         info!("Initializing the hotplugged mouse device...");
 
@@ -222,6 +372,7 @@ pub fn claim_hotplugged_devices(_hotplug_info: &HotplugInfo) -> Result<()> {
                 if !crate::MISC_DEVICES.read().iter().any(|d| {
                     d.read().get_usb_vid() == device.read().get_usb_vid()
                         && d.read().get_usb_pid() == device.read().get_usb_pid()
+                        && d.read().get_usb_path() == device.read().get_usb_path()
                 }) {
                     info!("Initializing the hotplugged misc device...");
 
@@ -291,6 +442,32 @@ pub fn claim_hotplugged_devices(_hotplug_info: &HotplugInfo) -> Result<()> {
     Ok(())
 }
 
+/// Compute the expected response to an authentication `challenge`
+fn auth_digest(challenge: &[u8], secret: &str) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(challenge);
+    hasher.update(secret.as_bytes());
+
+    hasher.finalize().to_vec()
+}
+
+/// Compare two byte slices in constant time
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Size of the receive buffer, large enough to hold a `SetCanvas` request for the current canvas
+fn buffer_capacity() -> usize {
+    // 4 bytes per pixel, plus some headroom for the length prefix and protobuf framing
+    constants::NET_BUFFER_CAPACITY.max(crate::CANVAS_SIZE.load(Ordering::SeqCst) * 4 + 64)
+}
+
+/// Max. number of bytes that may be queued for sending to a client. A client that
+/// does not keep up with receiving its responses and events will be disconnected
+fn max_send_queue_size() -> usize {
+    buffer_capacity() * constants::SDK_SEND_QUEUE_FRAMES
+}
+
 ///
 pub struct SdkSupportPlugin {}
 
@@ -320,6 +497,71 @@ impl SdkSupportPlugin {
         Ok(())
     }
 
+    pub fn initialize_remote_socket() -> Result<()> {
+        let (address, secret) = {
+            let config = crate::CONFIG.lock();
+            let config = config.as_ref().unwrap();
+
+            (
+                config.get::<String>("sdk.remote_listen_address").ok(),
+                config.get::<String>("sdk.remote_secret").ok(),
+            )
+        };
+
+        if let Some(address) = address {
+            match secret.filter(|secret| !secret.is_empty()) {
+                Some(secret) => {
+                    let address: SocketAddr = address.parse()?;
+
+                    // create, bind and store the TCP listener
+                    let listener = Socket::new(
+                        Domain::for_address(address),
+                        Type::STREAM,
+                        Some(Protocol::TCP),
+                    )?;
+                    listener.set_reuse_address(true)?;
+                    listener.bind(&address.into())?;
+
+                    info!("Listening for remote Eruption SDK clients on {}", address);
+
+                    REMOTE_LISTENER.lock().replace(listener);
+                    REMOTE_SECRET.lock().replace(secret);
+                }
+
+                None => {
+                    error!(
+                        "Remote access to the Eruption SDK requires a 'remote_secret', not listening on {}",
+                        address
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn initialize_events() -> Result<()> {
+        let event_fd = eventfd(0, EfdFlags::EFD_NONBLOCK | EfdFlags::EFD_CLOEXEC)?;
+        EVENT_FD.lock().replace(event_fd);
+
+        events::register_observer(|event: &events::Event| {
+            if SUBSCRIBED_EVENTS.load(Ordering::SeqCst) != 0 {
+                if let Some(event) = make_event(event).filter(|e| is_subscribed(e.event_type())) {
+                    EVENTS.0.send(event)?;
+
+                    // wake up the I/O loop
+                    if let Some(event_fd) = *EVENT_FD.lock() {
+                        write(event_fd, &1_u64.to_ne_bytes())?;
+                    }
+                }
+            }
+
+            Ok(true) // event has been processed
+        });
+
+        Ok(())
+    }
+
     pub fn start_control_thread() -> Result<()> {
         let builder = thread::Builder::new().name("control".into());
         builder
@@ -341,257 +583,593 @@ impl SdkSupportPlugin {
     }
 
     pub fn run_io_loop() -> Result<()> {
-        unsafe fn assume_init(buf: &[MaybeUninit<u8>]) -> &[u8] {
-            &*(buf as *const [MaybeUninit<u8>] as *const [u8])
+        let listener = LISTENER.lock();
+        let listener = listener
+            .as_ref()
+            .ok_or_else(|| SdkPluginError::PluginError {
+                description: "The control socket has not been initialized".to_owned(),
+            })?;
+
+        let remote_listener = REMOTE_LISTENER.lock();
+
+        // the listening sockets, and whether they accept remote clients
+        let listeners = [
+            Some((listener, false)),
+            remote_listener.as_ref().map(|l| (l, true)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+        for (listener, _remote) in listeners.iter() {
+            listener.listen(constants::MAX_SDK_CLIENTS as i32)?;
+            listener.set_nonblocking(true)?;
         }
 
+        // drop the layers of clients that were served by a previous run of the I/O loop
+        LAYERS.write().clear();
+        SDK_SUPPORT_ACTIVE.store(false, Ordering::SeqCst);
+
+        let event_fd = *EVENT_FD.lock();
+
+        let mut clients: Vec<Client> = Vec::new();
+
         'IO_LOOP: loop {
             if crate::QUIT.load(Ordering::SeqCst) {
                 break 'IO_LOOP;
             }
 
-            if let Some(listener) = LISTENER.lock().as_ref() {
-                listener.listen(1)?;
+            // wait for new connections, and for requests of the connected clients
+            let mut poll_fds = listeners
+                .iter()
+                .map(|(listener, _remote)| PollFd::new(listener.as_raw_fd(), PollFlags::POLLIN))
+                .collect::<Vec<_>>();
+
+            if let Some(event_fd) = event_fd {
+                poll_fds.push(PollFd::new(event_fd, PollFlags::POLLIN));
+            }
+
+            let clients_offset = poll_fds.len();
+
+            poll_fds.extend(clients.iter().map(|client| {
+                let mut flags = PollFlags::POLLIN | PollFlags::POLLHUP | PollFlags::POLLERR;
+
+                // wait until the socket is writable again, if there is queued data
+                if client.has_pending_data() {
+                    flags |= PollFlags::POLLOUT;
+                }
+
+                PollFd::new(client.socket.as_raw_fd(), flags)
+            }));
+
+            // don't skip the rest of the loop on timeout, since pending handshakes may have expired
+            poll(&mut poll_fds, constants::SLEEP_TIME_TIMEOUT as i32)?;
+
+            let mut disconnected = Vec::new();
+
+            // drop remote clients that did not complete the authentication in time
+            for client in clients
+                .iter()
+                .filter(|client| client.is_handshake_expired())
+            {
+                warn!(
+                    "Eruption SDK client {} did not authenticate in time, dropping it",
+                    client.id
+                );
+
+                disconnected.push(client.id);
+            }
+
+            for (client, poll_fd) in clients.iter_mut().zip(poll_fds.iter().skip(clients_offset)) {
+                let revents = poll_fd.revents().unwrap_or_else(PollFlags::empty);
+
+                if revents.contains(PollFlags::POLLIN) {
+                    if let Err(e) = Self::process_requests(client) {
+                        debug!("Eruption SDK client {}: {}", client.id, e);
+
+                        disconnected.push(client.id);
+                    }
+                } else if revents.intersects(PollFlags::POLLHUP | PollFlags::POLLERR) {
+                    disconnected.push(client.id);
+                }
+
+                if revents.contains(PollFlags::POLLOUT) {
+                    if let Err(e) = client.flush() {
+                        debug!("Eruption SDK client {}: {}", client.id, e);
+
+                        disconnected.push(client.id);
+                    }
+                }
+            }
+
+            // dispatch pending events to the subscribed clients
+            if let Some(event_fd) = event_fd {
+                let mut buf = [0_u8; 8];
+                let _result = read(event_fd, &mut buf);
+
+                for event in EVENTS.1.try_iter() {
+                    for client in clients
+                        .iter_mut()
+                        .filter(|client| client.subscription.contains(&event.event_type))
+                    {
+                        let mut response = protocol::Response::default();
+                        response.set_response_type(protocol::RequestType::Event);
+                        response.payload = Some(ResponsePayload::Event(event.clone()));
+
+                        if let Err(e) = client.send_response(&response) {
+                            debug!("Eruption SDK client {}: {}", client.id, e);
+
+                            disconnected.push(client.id);
+                        }
+                    }
+                }
+            }
+
+            if !disconnected.is_empty() {
+                disconnected.sort_unstable();
+                disconnected.dedup();
+
+                clients.retain(|client| !disconnected.contains(&client.id));
+
+                let mut layers = LAYERS.write();
+
+                for id in disconnected.iter() {
+                    debug!("Eruption SDK client {} disconnected", id);
+
+                    layers.remove(id);
+                }
+
+                SDK_SUPPORT_ACTIVE.store(!layers.is_empty(), Ordering::SeqCst);
+                script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+            }
+
+            SUBSCRIBED_EVENTS.store(
+                clients
+                    .iter()
+                    .fold(0, |mask, client| mask | event_mask(&client.subscription)),
+                Ordering::SeqCst,
+            );
+
+            // accept pending connections
+            for ((listener, remote), poll_fd) in listeners.iter().zip(poll_fds.iter()) {
+                if !poll_fd
+                    .revents()
+                    .unwrap_or_else(PollFlags::empty)
+                    .contains(PollFlags::POLLIN)
+                {
+                    continue;
+                }
 
                 match listener.accept() {
                     Ok((socket, _sockaddr)) => {
-                        debug!("Eruption SDK client connected");
-
-                        // socket.set_nodelay(true)?; // not supported on AF_UNIX on Linux
-                        socket.set_send_buffer_size(constants::NET_BUFFER_CAPACITY * 2)?;
-                        socket.set_recv_buffer_size(constants::NET_BUFFER_CAPACITY * 2)?;
+                        // local clients are always authenticated, so this only counts remote clients
+                        let unauthenticated = clients
+                            .iter()
+                            .filter(|client| !client.authenticated)
+                            .count();
+
+                        if clients.len() >= constants::MAX_SDK_CLIENTS {
+                            error!(
+                                "Rejected an Eruption SDK client, the max. number of clients is already connected"
+                            );
+                        } else if *remote
+                            && unauthenticated >= constants::MAX_SDK_UNAUTHENTICATED_CLIENTS
+                        {
+                            error!(
+                                "Rejected a remote Eruption SDK client, too many clients are pending authentication"
+                            );
+                        } else {
+                            match Client::new(socket, *remote) {
+                                Ok(client) => {
+                                    debug!("Eruption SDK client {} connected", client.id);
+
+                                    LAYERS.write().insert(client.id, Layer::default());
+                                    clients.push(client);
+                                }
 
-                        // connection successful, enter event loop now
-                        'EVENT_LOOP: loop {
-                            if crate::QUIT.load(Ordering::SeqCst) {
-                                break 'EVENT_LOOP;
+                                Err(e) => error!("Could not set up an Eruption SDK client: {}", e),
                             }
+                        }
+                    }
 
-                            // wait for socket to be ready
-                            let mut poll_fds = [PollFd::new(
-                                socket.as_raw_fd(),
-                                PollFlags::POLLIN
-                                    | PollFlags::POLLOUT
-                                    | PollFlags::POLLHUP
-                                    | PollFlags::POLLERR,
-                            )];
+                    Err(e) => {
+                        debug!("Could not accept an Eruption SDK client: {}", e);
+                    }
+                }
+            }
+        }
 
-                            let result = poll(&mut poll_fds, constants::SLEEP_TIME_TIMEOUT as i32)?;
+        Ok(())
+    }
 
-                            if poll_fds[0].revents().unwrap().contains(PollFlags::POLLHUP)
-                                | poll_fds[0].revents().unwrap().contains(PollFlags::POLLERR)
-                            {
-                                debug!("Eruption SDK client disconnected");
+    /// Receive and handle the pending requests of `client`. An error is returned if the
+    /// client disconnected, could not be reached anymore or violated the protocol
+    fn process_requests(client: &mut Client) -> Result<()> {
+        unsafe fn assume_init(buf: &[MaybeUninit<u8>]) -> &[u8] {
+            &*(buf as *const [MaybeUninit<u8>] as *const [u8])
+        }
 
-                                break 'EVENT_LOOP;
-                            }
+        // read data
+        let mut tmp = vec![MaybeUninit::zeroed(); buffer_capacity()];
+        match client.socket.recv(&mut tmp) {
+            Ok(0) => Err(SdkPluginError::PluginError {
+                description: "Connection closed by the Eruption SDK client".to_owned(),
+            }
+            .into()),
+
+            Ok(n) => {
+                trace!("Read {} bytes from control socket", n);
+
+                let tmp = unsafe { assume_init(&tmp[..n]) };
+                client.buffer.extend_from_slice(tmp);
+
+                // remote clients use a stream socket, so we may have received
+                // a partial request, or multiple requests at once
+                while let Some(frame) = client.next_frame()? {
+                    match protocol::Request::decode(&mut Cursor::new(&frame)) {
+                        Ok(request) => Self::handle_request(client, request)?,
+
+                        Err(e) => {
+                            error!("Protocol error: {}", e);
+                        }
+                    }
+                }
+
+                Ok(())
+            }
+
+            // spurious wakeup, there is no data available yet
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::Interrupted => {
+                Ok(())
+            }
+
+            Err(_e) => Err(SdkPluginError::PluginError {
+                description: "Lost connection to Eruption SDK client".to_owned(),
+            }
+            .into()),
+        }
+    }
+
+    fn handle_request(client: &mut Client, request: protocol::Request) -> Result<()> {
+        if !client.authenticated && request.request_type() != protocol::RequestType::Authenticate {
+            return Err(SdkPluginError::PluginError {
+                description: "Request of an unauthenticated client".to_owned(),
+            }
+            .into());
+        }
+
+        match request.request_type() {
+            protocol::RequestType::Status => {
+                trace!("Get Status");
+
+                let mut response = protocol::Response::default();
+                response.set_response_type(protocol::RequestType::Status);
+
+                let tmp = "Eruption";
+                response.payload = Some(ResponsePayload::Data(tmp.as_bytes().to_vec()));
+
+                response.canvas_width = crate::CANVAS_WIDTH.load(Ordering::SeqCst) as u32;
+                response.canvas_height = crate::CANVAS_HEIGHT.load(Ordering::SeqCst) as u32;
+
+                client.send_response(&response)?;
+            }
+
+            protocol::RequestType::GetCanvas => {
+                trace!("Get canvas");
+
+                let mut response = protocol::Response::default();
+                response.set_response_type(protocol::RequestType::GetCanvas);
+
+                response.payload = Some(ResponsePayload::Data(encode_led_map(
+                    &script::LAST_RENDERED_LED_MAP.read(),
+                )));
+
+                response.canvas_width = crate::CANVAS_WIDTH.load(Ordering::SeqCst) as u32;
+                response.canvas_height = crate::CANVAS_HEIGHT.load(Ordering::SeqCst) as u32;
 
-                            if result > 0
-                                && poll_fds[0].revents().unwrap().contains(PollFlags::POLLIN)
+                client.send_response(&response)?;
+            }
+
+            protocol::RequestType::SetCanvas => {
+                trace!("Set canvas");
+
+                match request.payload {
+                    Some(RequestPayload::Data(payload_map)) => {
+                        // the client may assume a differently sized canvas,
+                        // so excess pixels are dropped and missing ones stay transparent
+                        if let Some(layer) = LAYERS.write().get_mut(&client.id) {
+                            layer.led_map.fill(RGBA {
+                                r: 0,
+                                g: 0,
+                                b: 0,
+                                a: 0,
+                            });
+
+                            for (color, rgba) in
+                                layer.led_map.iter_mut().zip(payload_map.chunks_exact(4))
                             {
-                                // read data
-                                let mut tmp =
-                                    [MaybeUninit::zeroed(); constants::NET_BUFFER_CAPACITY];
-                                match socket.recv(&mut tmp) {
-                                    Ok(0) => {
-                                        debug!("Eruption SDK client disconnected");
-
-                                        break 'EVENT_LOOP;
-                                    }
-
-                                    Ok(n) => {
-                                        trace!("Read {} bytes from control socket", n);
-
-                                        let tmp = unsafe { assume_init(&tmp[..tmp.len()]) };
-
-                                        if tmp.len() != constants::NET_BUFFER_CAPACITY {
-                                            error!("Buffer length differs from BUFFER_CAPACITY! Length: {}", tmp.len());
-                                        }
-
-                                        let result = protocol::Request::decode_length_delimited(
-                                            &mut Cursor::new(&tmp),
-                                        );
-                                        match result {
-                                            Ok(request) => match request.request_type() {
-                                                protocol::RequestType::Status => {
-                                                    trace!("Get Status");
-
-                                                    let mut response =
-                                                        protocol::Response::default();
-                                                    response.set_response_type(
-                                                        protocol::RequestType::Status,
-                                                    );
-
-                                                    let tmp = "Eruption";
-                                                    response.payload = Some(ResponsePayload::Data(
-                                                        tmp.as_bytes().to_vec(),
-                                                    ));
-
-                                                    let mut buf = Vec::new();
-                                                    response.encode_length_delimited(&mut buf)?;
-
-                                                    // send data
-                                                    match socket.send(&buf) {
-                                                        Ok(_n) => {}
-
-                                                        Err(_e) => {
-                                                            return Err(SdkPluginError::PluginError {
-                                                                description: "Lost connection to Eruption SDK client".to_owned(),
-                                                            }
-                                                                .into());
-                                                        }
-                                                    }
-                                                }
-
-                                                protocol::RequestType::SetCanvas => {
-                                                    trace!("Set canvas");
-
-                                                    let RequestPayload::Data(payload_map) =
-                                                        request.payload.unwrap();
-
-                                                    let mut led_map = [RGBA {
-                                                        r: 0,
-                                                        g: 0,
-                                                        b: 0,
-                                                        a: 0,
-                                                    };
-                                                        constants::CANVAS_SIZE];
-
-                                                    let mut i = 0;
-                                                    let mut cntr = 0;
-
-                                                    loop {
-                                                        led_map[cntr] = RGBA {
-                                                            r: payload_map[i],
-                                                            g: payload_map[i + 1],
-                                                            b: payload_map[i + 2],
-                                                            a: payload_map[i + 3],
-                                                        };
-
-                                                        i += 4;
-                                                        cntr += 1;
-
-                                                        if cntr >= led_map.len()
-                                                            || i >= payload_map.len()
-                                                        {
-                                                            break;
-                                                        }
-                                                    }
-
-                                                    LED_MAP.write().copy_from_slice(&led_map);
-
-                                                    SDK_SUPPORT_ACTIVE
-                                                        .store(true, Ordering::SeqCst);
-
-                                                    script::FRAME_GENERATION_COUNTER
-                                                        .fetch_add(1, Ordering::SeqCst);
-
-                                                    let mut response =
-                                                        protocol::Response::default();
-                                                    response.set_response_type(
-                                                        protocol::RequestType::Noop,
-                                                    );
-
-                                                    let mut buf = Vec::new();
-                                                    response.encode_length_delimited(&mut buf)?;
-
-                                                    // send data
-                                                    match socket.send(&buf) {
-                                                        Ok(_n) => {}
-
-                                                        Err(_e) => {
-                                                            return Err(SdkPluginError::PluginError {
-                                                                description: "Lost connection to Eruption SDK client".to_owned(),
-                                                            }
-                                                                .into());
-                                                        }
-                                                    }
-                                                }
-
-                                                protocol::RequestType::NotifyHotplug => {
-                                                    trace!("Notify hotplug");
-
-                                                    let RequestPayload::Data(payload_hotplug_info) =
-                                                        request.payload.unwrap();
-
-                                                    let config = bincode::config::standard();
-                                                    let hotplug_info: HotplugInfo =
-                                                        bincode::decode_from_slice(
-                                                            &payload_hotplug_info,
-                                                            config,
-                                                        )?
-                                                        .0;
-
-                                                    info!("Hotplug event received, trying to claim newly added devices now...");
-
-                                                    claim_hotplugged_devices(&hotplug_info)?;
-
-                                                    // we need to terminate and then re-enter the main loop to update all global state
-                                                    crate::REENTER_MAIN_LOOP
-                                                        .store(true, Ordering::SeqCst);
-
-                                                    let mut response =
-                                                        protocol::Response::default();
-                                                    response.set_response_type(
-                                                        protocol::RequestType::Noop,
-                                                    );
-
-                                                    let mut buf = Vec::new();
-                                                    response.encode_length_delimited(&mut buf)?;
-
-                                                    // send data
-                                                    match socket.send(&buf) {
-                                                        Ok(_n) => {}
-
-                                                        Err(_e) => {
-                                                            return Err(SdkPluginError::PluginError {
-                                                                description: "Lost connection to Eruption SDK client".to_owned(),
-                                                            }
-                                                                .into());
-                                                        }
-                                                    }
-                                                }
-
-                                                protocol::RequestType::Noop => {
-                                                    /* Do nothing */
-
-                                                    trace!("NOOP");
-                                                }
-                                            },
-
-                                            Err(e) => {
-                                                error!("Protocol error: {}", e);
-
-                                                // break 'EVENT_LOOP;
-                                            }
-                                        }
-                                    }
-
-                                    Err(_e) => {
-                                        return Err(SdkPluginError::PluginError {
-                                            description: "Lost connection to Eruption SDK client"
-                                                .to_owned(),
-                                        }
-                                        .into());
-                                    }
-                                }
+                                *color = RGBA {
+                                    r: rgba[0],
+                                    g: rgba[1],
+                                    b: rgba[2],
+                                    a: rgba[3],
+                                };
                             }
+                        }
 
-                            if SDK_SUPPORT_ACTIVE.load(Ordering::SeqCst) {
-                                thread::sleep(Duration::from_millis(1));
-                            } else {
-                                thread::sleep(Duration::from_millis(15));
-                            }
+                        SDK_SUPPORT_ACTIVE.store(true, Ordering::SeqCst);
+                        script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+                    }
+
+                    _ => error!("Protocol error: Missing canvas in SET_CANVAS request"),
+                }
+
+                let mut response = protocol::Response::default();
+                response.set_response_type(protocol::RequestType::Noop);
+
+                client.send_response(&response)?;
+            }
+
+            protocol::RequestType::SetLayerParams => {
+                trace!("Set layer params");
+
+                match request.payload {
+                    Some(RequestPayload::LayerParams(params)) => {
+                        if let Some(layer) = LAYERS.write().get_mut(&client.id) {
+                            layer.priority = params.priority;
+                            layer.opacity = params.opacity.min(255) as u8;
                         }
+
+                        script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
                     }
 
-                    Err(_e) => {
-                        return Err(SdkPluginError::PluginError {
-                            description: "Lost connection to Eruption SDK client".to_owned(),
+                    _ => error!("Protocol error: Missing parameters in SET_LAYER_PARAMS request"),
+                }
+
+                let mut response = protocol::Response::default();
+                response.set_response_type(protocol::RequestType::Noop);
+
+                client.send_response(&response)?;
+            }
+
+            protocol::RequestType::NotifyHotplug => {
+                trace!("Notify hotplug");
+
+                match request.payload {
+                    Some(RequestPayload::Data(payload_hotplug_info)) => {
+                        let config = bincode::config::standard();
+                        let hotplug_info: HotplugInfo =
+                            bincode::decode_from_slice(&payload_hotplug_info, config)?.0;
+
+                        info!("Hotplug event received, trying to claim newly added devices now...");
+
+                        claim_hotplugged_devices(&hotplug_info)?;
+
+                        // we need to terminate and then re-enter the main loop to update all global state
+                        crate::REENTER_MAIN_LOOP.store(true, Ordering::SeqCst);
+                    }
+
+                    _ => error!("Protocol error: Missing hotplug info in NOTIFY_HOTPLUG request"),
+                }
+
+                let mut response = protocol::Response::default();
+                response.set_response_type(protocol::RequestType::Noop);
+
+                client.send_response(&response)?;
+            }
+
+            protocol::RequestType::Authenticate => {
+                trace!("Authenticate");
+
+                let mut response = protocol::Response::default();
+                response.set_response_type(protocol::RequestType::Authenticate);
+
+                // clients of the local control socket are always authenticated, they
+                // receive an empty response, since there is no challenge to solve
+                if !client.authenticated {
+                    match (request.payload, client.challenge.take()) {
+                        (None, _) => {
+                            let challenge = rand::random::<[u8; 32]>().to_vec();
+
+                            response.payload = Some(ResponsePayload::Data(challenge.clone()));
+                            client.challenge = Some(challenge);
+                        }
+
+                        (Some(RequestPayload::Data(digest)), Some(challenge))
+                            if constant_time_eq(
+                                &digest,
+                                &auth_digest(
+                                    &challenge,
+                                    REMOTE_SECRET.lock().as_deref().unwrap_or_default(),
+                                ),
+                            ) =>
+                        {
+                            info!("Eruption SDK client {} authenticated", client.id);
+
+                            client.authenticated = true;
                         }
-                        .into());
+
+                        _ => {
+                            warn!("Authentication of Eruption SDK client {} failed", client.id);
+
+                            return Err(SdkPluginError::PluginError {
+                                description: "Authentication failed".to_owned(),
+                            }
+                            .into());
+                        }
+                    }
+                }
+
+                client.send_response(&response)?;
+            }
+
+            protocol::RequestType::SubscribeEvents => {
+                trace!("Subscribe events");
+
+                match request.payload {
+                    Some(RequestPayload::Subscription(subscription)) => {
+                        client.subscription = subscription.event_types;
+                    }
+
+                    _ => error!("Protocol error: Missing subscription in SUBSCRIBE_EVENTS request"),
+                }
+
+                let mut response = protocol::Response::default();
+                response.set_response_type(protocol::RequestType::Noop);
+
+                client.send_response(&response)?;
+            }
+
+            protocol::RequestType::Event => {
+                error!("Protocol error: EVENT is not a valid request");
+            }
+
+            protocol::RequestType::Noop => {
+                /* Do nothing */
+
+                trace!("NOOP");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A connected SDK client, each client draws to its own canvas layer
+struct Client {
+    id: u64,
+    socket: Socket,
+
+    /// Remote clients have to authenticate before issuing any other requests
+    authenticated: bool,
+
+    /// The challenge of a pending authentication
+    challenge: Option<Vec<u8>>,
+
+    /// Point in time when the client connected, used to enforce the authentication timeout
+    connected_at: Instant,
+
+    /// Received data that has not been processed yet
+    buffer: Vec<u8>,
+
+    /// Encoded responses that have not been sent yet, since the socket would have blocked
+    send_queue: VecDeque<Vec<u8>>,
+
+    /// Number of bytes of the first queued response that have already been sent
+    send_offset: usize,
+
+    /// The types of events the client subscribed to
+    subscription: Vec<i32>,
+}
+
+impl Client {
+    fn new(socket: Socket, remote: bool) -> Result<Self> {
+        let buffer_capacity = buffer_capacity();
+
+        if remote {
+            socket.set_nodelay(true)?; // not supported on AF_UNIX on Linux
+        }
+
+        // all clients are served by the same thread, so a slow client must not block the others
+        socket.set_nonblocking(true)?;
+        socket.set_send_buffer_size(buffer_capacity * 2)?;
+        socket.set_recv_buffer_size(buffer_capacity * 2)?;
+
+        Ok(Self {
+            id: NEXT_CLIENT_ID.fetch_add(1, Ordering::SeqCst),
+            socket,
+            authenticated: !remote,
+            challenge: None,
+            connected_at: Instant::now(),
+            buffer: Vec::new(),
+            send_queue: VecDeque::new(),
+            send_offset: 0,
+            subscription: Vec::new(),
+        })
+    }
+
+    /// Returns `true` if the client failed to authenticate within the allotted time
+    fn is_handshake_expired(&self) -> bool {
+        !self.authenticated
+            && self.connected_at.elapsed()
+                > Duration::from_millis(constants::SDK_AUTH_TIMEOUT_MILLIS)
+    }
+
+    /// Split off the next complete, length delimited request from the receive buffer
+    fn next_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        match prost::decode_length_delimiter(&self.buffer[..]) {
+            Ok(len) => {
+                let offset = prost::length_delimiter_len(len);
+
+                if len > buffer_capacity() {
+                    Err(SdkPluginError::PluginError {
+                        description: format!("Request exceeds the max. size: {} bytes", len),
+                    }
+                    .into())
+                } else if self.buffer.len() < offset + len {
+                    Ok(None)
+                } else {
+                    let frame = self.buffer[offset..offset + len].to_vec();
+                    self.buffer.drain(..offset + len);
+
+                    Ok(Some(frame))
+                }
+            }
+
+            // the length delimiter itself may be incomplete, it spans at most 10 bytes
+            Err(_e) if self.buffer.len() < 10 => Ok(None),
+
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Queue `response` for sending, and send as much of the queued data as possible
+    fn send_response(&mut self, response: &protocol::Response) -> Result<()> {
+        let mut buf = Vec::new();
+        response.encode_length_delimited(&mut buf)?;
+
+        let queued: usize = self.send_queue.iter().map(|frame| frame.len()).sum();
+
+        if queued - self.send_offset + buf.len() > max_send_queue_size() {
+            return Err(SdkPluginError::PluginError {
+                description: "The Eruption SDK client does not keep up, dropping it".to_owned(),
+            }
+            .into());
+        }
+
+        self.send_queue.push_back(buf);
+
+        self.flush()
+    }
+
+    /// Returns `true` if there is queued data that has not been sent yet
+    fn has_pending_data(&self) -> bool {
+        !self.send_queue.is_empty()
+    }
+
+    /// Send queued data until the socket would block. Stream sockets may accept only
+    /// a part of a response, the remainder is sent when the socket is writable again
+    fn flush(&mut self) -> Result<()> {
+        while let Some(frame) = self.send_queue.front() {
+            match self.socket.send(&frame[self.send_offset..]) {
+                Ok(n) => {
+                    self.send_offset += n;
+
+                    if self.send_offset >= frame.len() {
+                        self.send_queue.pop_front();
+                        self.send_offset = 0;
                     }
                 }
+
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+
+                Err(_e) => {
+                    return Err(SdkPluginError::PluginError {
+                        description: "Lost connection to Eruption SDK client".to_owned(),
+                    }
+                    .into())
+                }
             }
         }
 
@@ -611,20 +1189,12 @@ impl Plugin for SdkSupportPlugin {
 
     async fn initialize(&mut self) -> plugins::Result<()> {
         Self::initialize_socket()?;
+        Self::initialize_remote_socket().unwrap_or_else(|e| {
+            error!("Could not enable remote access to the Eruption SDK: {}", e);
+        });
+        Self::initialize_events()?;
         Self::start_control_thread()?;
 
-        // events::register_observer(|event: &events::Event| {
-        //     match event {
-        //         events::Event::KeyDown(_index) => {}
-
-        //         events::Event::KeyUp(_index) => {}
-
-        //         _ => (),
-        //     };
-
-        //     Ok(true) // event has been processed
-        // });
-
         Ok(())
     }
 
@@ -36,7 +36,7 @@ use std::thread;
 use crate::hwdevices::RGBA;
 use crate::plugins::Plugin;
 use crate::scripting::script::FRAME_GENERATION_COUNTER;
-use crate::{constants, plugins, util, ULEDS_SUPPORT_ACTIVE};
+use crate::{plugins, util, ULEDS_SUPPORT_ACTIVE};
 
 pub type Result<T> = std::result::Result<T, eyre::Error>;
 
@@ -53,7 +53,7 @@ lazy_static! {
         g: 0x00,
         b: 0x00,
         a: 0x00,
-    }; constants::CANVAS_SIZE]));
+    }; crate::CANVAS_SIZE.load(Ordering::SeqCst)]));
 }
 
 lazy_static! {
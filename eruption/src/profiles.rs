@@ -26,6 +26,7 @@ use serde::{Deserialize, Serialize};
 use std::default::Default;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::{collections::HashMap, ffi::OsStr};
 use uuid::Uuid;
 
@@ -53,8 +54,14 @@ pub enum ProfileError {
 
     #[error("Could not parse a param value")]
     ParseParamError {},
+
+    #[error("Could not resolve the parent profile '{parent}': {msg}")]
+    ParentError { parent: String, msg: String },
 }
 
+/// Maximum length of a chain of inherited profiles
+const MAX_INHERITANCE_DEPTH: usize = 8;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, PartialOrd)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum ConfigParam {
@@ -88,6 +95,47 @@ pub enum ConfigParam {
         #[serde(default)]
         default: u32,
     },
+    Enum {
+        name: String,
+        value: String,
+        #[serde(default)]
+        default: String,
+    },
+    ColorArray {
+        name: String,
+        value: Vec<u32>,
+        #[serde(default)]
+        default: Vec<u32>,
+    },
+    KeySet {
+        name: String,
+        value: Vec<usize>,
+        #[serde(default)]
+        default: Vec<usize>,
+    },
+    Duration {
+        name: String,
+        value: u64,
+        #[serde(default)]
+        default: u64,
+    },
+}
+
+/// Format a list of colors, so that it may be parsed again as a script parameter
+pub fn format_colors(colors: &[u32]) -> String {
+    colors
+        .iter()
+        .map(|c| format!("#{:08x}", c))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Format a list of key indices, so that it may be parsed again as a script parameter
+pub fn format_keys(keys: &[usize]) -> String {
+    keys.iter()
+        .map(|k| k.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
 }
 
 pub trait GetAttr {
@@ -108,6 +156,14 @@ impl GetAttr for ConfigParam {
             ConfigParam::String { ref name, .. } => name,
 
             ConfigParam::Color { ref name, .. } => name,
+
+            ConfigParam::Enum { ref name, .. } => name,
+
+            ConfigParam::ColorArray { ref name, .. } => name,
+
+            ConfigParam::KeySet { ref name, .. } => name,
+
+            ConfigParam::Duration { ref name, .. } => name,
         }
     }
 
@@ -122,6 +178,14 @@ impl GetAttr for ConfigParam {
             ConfigParam::String { ref value, .. } => value.to_owned(),
 
             ConfigParam::Color { ref value, .. } => format!("#{:06x}", value),
+
+            ConfigParam::Enum { ref value, .. } => value.to_owned(),
+
+            ConfigParam::ColorArray { ref value, .. } => format_colors(value),
+
+            ConfigParam::KeySet { ref value, .. } => format_keys(value),
+
+            ConfigParam::Duration { ref value, .. } => format!("{}ms", value),
         }
     }
 
@@ -136,6 +200,167 @@ impl GetAttr for ConfigParam {
             ConfigParam::String { ref default, .. } => default.to_owned(),
 
             ConfigParam::Color { ref default, .. } => format!("#{:06x}", default),
+
+            ConfigParam::Enum { ref default, .. } => default.to_owned(),
+
+            ConfigParam::ColorArray { ref default, .. } => format_colors(default),
+
+            ConfigParam::KeySet { ref default, .. } => format_keys(default),
+
+            ConfigParam::Duration { ref default, .. } => format!("{}ms", default),
+        }
+    }
+}
+
+/// Specifies how the color map of a script is combined with the layers below it
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BlendMode {
+    /// Alpha blending, using the alpha channel of the color map
+    Alpha,
+    Add,
+    Multiply,
+    Screen,
+    Max,
+
+    /// Replace the layers below, ignoring the alpha channel of the color map
+    Replace,
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        BlendMode::Alpha
+    }
+}
+
+impl FromStr for BlendMode {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "alpha" => Ok(BlendMode::Alpha),
+            "add" => Ok(BlendMode::Add),
+            "multiply" => Ok(BlendMode::Multiply),
+            "screen" => Ok(BlendMode::Screen),
+            "max" => Ok(BlendMode::Max),
+            "replace" => Ok(BlendMode::Replace),
+
+            _ => Err(ProfileError::ParseParamError {}.into()),
+        }
+    }
+}
+
+/// A rectangular area of the canvas
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MaskZone {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl MaskZone {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+fn default_opacity() -> f64 {
+    1.0
+}
+
+/// Compositing settings of an active script
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Layer {
+    #[serde(default)]
+    pub blend_mode: BlendMode,
+
+    /// Opacity of the layer, in the range 0.0..=1.0
+    #[serde(default = "default_opacity")]
+    pub opacity: f64,
+
+    /// Restrict the layer to these areas of the canvas, an empty mask covers the whole canvas
+    #[serde(default)]
+    pub mask: Vec<MaskZone>,
+}
+
+impl Default for Layer {
+    fn default() -> Self {
+        Self {
+            blend_mode: BlendMode::default(),
+            opacity: default_opacity(),
+            mask: vec![],
+        }
+    }
+}
+
+/// A static color or gradient, that is applied to a set of keys after the Lua scripts ran
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KeyOverride {
+    /// A descriptive name, e.g. `WASD`
+    #[serde(default)]
+    pub name: String,
+
+    /// The key indices, as used by the Lua scripts
+    pub keys: Vec<usize>,
+
+    /// Use a single color for all of the keys. Colors are specified as `0xAARRGGBB`, colors
+    /// without an alpha channel, like e.g. `0xRRGGBB`, are opaque
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<u32>,
+
+    /// Use a gradient, that spans the keys in the order they are listed in
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gradient: Vec<u32>,
+
+    #[serde(default)]
+    pub blend_mode: BlendMode,
+
+    /// Opacity of the override, in the range 0.0..=1.0
+    #[serde(default = "default_opacity")]
+    pub opacity: f64,
+}
+
+impl KeyOverride {
+    /// Returns the color of the key at `position` in the list of keys
+    pub fn get_color(&self, position: usize) -> Option<u32> {
+        // a fully transparent override would be a no-op, so a missing alpha channel means opaque
+        let with_alpha = |color: u32| {
+            if color >> 24 == 0 {
+                color | 0xff000000
+            } else {
+                color
+            }
+        };
+
+        match self.gradient.len() {
+            0 => self.color.map(with_alpha),
+            1 => Some(with_alpha(self.gradient[0])),
+
+            len => {
+                let t = if self.keys.len() > 1 {
+                    position as f64 / (self.keys.len() - 1) as f64
+                } else {
+                    0.0
+                };
+
+                let segment = ((t * (len - 1) as f64) as usize).min(len - 2);
+                let t = t * (len - 1) as f64 - segment as f64;
+
+                let (start, end) = (
+                    with_alpha(self.gradient[segment]),
+                    with_alpha(self.gradient[segment + 1]),
+                );
+
+                let lerp = |shift: u32| {
+                    let a = ((start >> shift) & 0xff) as f64;
+                    let b = ((end >> shift) & 0xff) as f64;
+
+                    ((a + (b - a) * t).round() as u32) << shift
+                };
+
+                Some(lerp(24) | lerp(16) | lerp(8) | lerp(0))
+            }
         }
     }
 }
@@ -164,36 +389,39 @@ pub struct Profile {
     pub name: String,
     pub description: String,
 
-    #[serde(default = "default_script_file")]
+    /// Parent profile, referenced by UUID or by file name. Active scripts, parameters, layers
+    /// and key overrides are inherited from the parent profile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+
+    /// The parent profile, resolved from `extends` when the profile is loaded
+    #[serde(skip)]
+    pub parent: Option<Box<Profile>>,
+
+    #[serde(default)]
     pub active_scripts: Vec<PathBuf>,
 
     pub config: Option<HashMap<String, Vec<ConfigParam>>>,
+
+    /// Compositing settings of the active scripts, keyed by script file name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub layers: HashMap<String, Layer>,
+
+    /// Static colors of keys, applied on top of the output of the Lua scripts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overrides: Vec<KeyOverride>,
 }
 
 macro_rules! get_default_value {
     ($t:ident, $tval:ty, $rval:ty) => {
         paste! {
             pub fn [<get_default_ $t>] (&self, script_name: &str, name: &str) -> Option<$rval> {
-                match self.config.as_ref() {
-                    Some(config) =>
-                        match config.get(&script_name.to_owned()) {
-                        Some(script_config) =>
-                            match script_config.find_config_param(&name) {
-                                Some(p) => match p {
-                                    $tval {
-                                        name: _,
-                                        value: _,
-                                        default,
-                                    } => Some(default.clone()),
-
-                                    _ => None,
-                                },
-
-                                _ => None,
-                            },
-
-                        _ => None,
-                    }
+                match self.find_config_param(script_name, name) {
+                    Some($tval {
+                        name: _,
+                        value: _,
+                        default,
+                    }) => Some(default.clone()),
 
                     _ => None,
                 }
@@ -209,6 +437,10 @@ impl Profile {
     get_default_value!(bool, ConfigParam::Bool, bool);
     get_default_value!(string, ConfigParam::String, String);
     get_default_value!(color, ConfigParam::Color, u32);
+    get_default_value!(enum, ConfigParam::Enum, String);
+    get_default_value!(color_array, ConfigParam::ColorArray, Vec<u32>);
+    get_default_value!(keyset, ConfigParam::KeySet, Vec<usize>);
+    get_default_value!(duration, ConfigParam::Duration, u64);
 }
 
 pub trait FindConfig {
@@ -249,6 +481,30 @@ impl FindConfig for Vec<ConfigParam> {
                         return Some(p);
                     }
                 }
+
+                ConfigParam::Enum { name, .. } => {
+                    if name == param {
+                        return Some(p);
+                    }
+                }
+
+                ConfigParam::ColorArray { name, .. } => {
+                    if name == param {
+                        return Some(p);
+                    }
+                }
+
+                ConfigParam::KeySet { name, .. } => {
+                    if name == param {
+                        return Some(p);
+                    }
+                }
+
+                ConfigParam::Duration { name, .. } => {
+                    if name == param {
+                        return Some(p);
+                    }
+                }
             }
         }
 
@@ -287,6 +543,30 @@ impl FindConfig for Vec<ConfigParam> {
                         return Some(p);
                     }
                 }
+
+                ConfigParam::Enum { name, .. } => {
+                    if name == param {
+                        return Some(p);
+                    }
+                }
+
+                ConfigParam::ColorArray { name, .. } => {
+                    if name == param {
+                        return Some(p);
+                    }
+                }
+
+                ConfigParam::KeySet { name, .. } => {
+                    if name == param {
+                        return Some(p);
+                    }
+                }
+
+                ConfigParam::Duration { name, .. } => {
+                    if name == param {
+                        return Some(p);
+                    }
+                }
             }
         }
 
@@ -298,39 +578,27 @@ macro_rules! get_config_value {
     ($t:ident, $tval:ty, $pval:ty) => {
         paste::item! {
             pub fn [<get_ $t _value>](&self, script_name: &str, name: &str) -> Option<&$tval> {
-                if let Some(config) = &self.config {
-                    if let Some(cfg) = config.get(script_name) {
-                        match cfg.find_config_param(name) {
-                            Some(param) => match param {
-                                $pval { value, .. } =>
-                                {
-                                    debug!("Using value from .profile file for config param '{}' (value: '{}') [5]",  name, value);
-
-                                    Some(value)
-                                }
+                match self.find_config_param(script_name, name) {
+                    Some(param) => match param {
+                        $pval { value, .. } =>
+                        {
+                            debug!("Using value from .profile file for config param '{}' (value: '{}') [3]",  name, value);
 
-                                _ => {
-                                    debug!("Using default value for config param '{}' [4]", name);
-
-                                    None
-                                }
-                            },
+                            Some(value)
+                        }
 
-                            None => {
-                                debug!("Using default value for config param '{}' [3]", name);
+                        _ => {
+                            debug!("Using default value for config param '{}' [2]", name);
 
-                                None
-                            }
+                            None
                         }
-                    } else {
-                        debug!("Using default value for config param [2]");
+                    },
+
+                    None => {
+                        debug!("Using default value for config param '{}' [1]", name);
 
                         None
                     }
-                } else {
-                    debug!("Using default value for config param [1]");
-
-                    None
                 }
             }
         }
@@ -402,6 +670,8 @@ impl Profile {
                             result.config = Some(HashMap::new());
                         }
 
+                        result.resolve_parent(0)?;
+
                         Ok(result)
                     }
 
@@ -426,6 +696,15 @@ impl Profile {
     }
 
     pub fn from(profile_file: &Path) -> Result<Self> {
+        let mut result = Self::parse(profile_file)?;
+
+        result.resolve_parent(0)?;
+
+        Ok(result)
+    }
+
+    /// Load the profile `profile_file`, without resolving its parent profile
+    fn parse(profile_file: &Path) -> Result<Self> {
         // parse manifest
         match fs::read_to_string(profile_file) {
             Ok(toml) => {
@@ -455,6 +734,132 @@ impl Profile {
         }
     }
 
+    /// Load the chain of parent profiles, as specified by the `extends` field
+    fn resolve_parent(&mut self, depth: usize) -> Result<()> {
+        if let Some(extends) = self.extends.clone() {
+            if depth >= MAX_INHERITANCE_DEPTH {
+                return Err(ProfileError::ParentError {
+                    parent: extends,
+                    msg: "Inheritance chain is too long or circular".into(),
+                }
+                .into());
+            }
+
+            let parent_file = self.find_parent_file(&extends)?;
+
+            let mut parent = Self::parse(&parent_file)?;
+            parent.resolve_parent(depth + 1)?;
+
+            self.parent = Some(Box::new(parent));
+        }
+
+        Ok(())
+    }
+
+    /// Find the file of the parent profile `extends`, which may either be a UUID or a file name.
+    /// Profiles in the same directory take precedence over the other profile directories
+    fn find_parent_file(&self, extends: &str) -> Result<PathBuf> {
+        let is_match = |profile_file: &PathBuf| match Uuid::from_str(extends) {
+            Ok(uuid) => Self::parse(profile_file)
+                .map(|p| p.id == uuid)
+                .unwrap_or(false),
+
+            Err(_) => profile_file.file_name() == Some(OsStr::new(extends)),
+        };
+
+        if let Some(dir) = self.profile_file.parent() {
+            let profile_files = get_profile_files_from(&[dir.to_path_buf()])?;
+
+            if let Some(result) = profile_files.into_iter().find(is_match) {
+                return Ok(result);
+            }
+        }
+
+        get_profile_files()?
+            .into_iter()
+            .find(is_match)
+            .ok_or_else(|| {
+                ProfileError::ParentError {
+                    parent: extends.to_string(),
+                    msg: "No such profile".into(),
+                }
+                .into()
+            })
+    }
+
+    /// Returns the active scripts of the profile, including the ones inherited from the parent profile
+    pub fn get_active_scripts(&self) -> Vec<PathBuf> {
+        let mut result = self
+            .parent
+            .as_ref()
+            .map(|parent| parent.get_active_scripts())
+            .unwrap_or_default();
+
+        for script_file in self.active_scripts.iter() {
+            if !result.contains(script_file) {
+                result.push(script_file.clone());
+            }
+        }
+
+        if result.is_empty() {
+            result = default_script_file();
+        }
+
+        result
+    }
+
+    /// Returns the script parameters of the profile, merged with the ones inherited from the
+    /// parent profile. Parameters of the profile itself take precedence
+    pub fn get_config(&self) -> HashMap<String, Vec<ConfigParam>> {
+        let mut result = self
+            .parent
+            .as_ref()
+            .map(|parent| parent.get_config())
+            .unwrap_or_default();
+
+        for (script_name, params) in self.config.iter().flatten() {
+            let script_config = result.entry(script_name.clone()).or_default();
+
+            for param in params {
+                script_config.retain(|p| p.get_name() != param.get_name());
+                script_config.push(param.clone());
+            }
+        }
+
+        result
+    }
+
+    /// Find the parameter `name` of the script `script_name`, either in this profile, or in one of
+    /// its parent profiles
+    pub fn find_config_param(&self, script_name: &str, name: &str) -> Option<&ConfigParam> {
+        self.config
+            .as_ref()
+            .and_then(|config| config.get(script_name))
+            .and_then(|script_config| script_config.find_config_param(name))
+            .or_else(|| {
+                self.parent
+                    .as_ref()
+                    .and_then(|parent| parent.find_config_param(script_name, name))
+            })
+    }
+
+    /// Returns the static key overrides of the profile, including the ones inherited from the
+    /// parent profile. Overrides of the profile itself replace inherited ones of the same name
+    pub fn get_overrides(&self) -> Vec<KeyOverride> {
+        let mut result = self
+            .parent
+            .as_ref()
+            .map(|parent| parent.get_overrides())
+            .unwrap_or_default();
+
+        for key_override in self.overrides.iter() {
+            result.retain(|o| o.name != key_override.name);
+            result.push(key_override.clone());
+        }
+
+        result
+    }
+
     pub fn find_by_uuid(uuid: Uuid) -> Result<Self> {
         let mut result = Err(ProfileError::FindError {}.into());
 
@@ -492,6 +897,24 @@ impl Profile {
         Ok(())
     }
 
+    /// Serialize the profile file with its own static key overrides replaced by `overrides`.
+    /// The file is re-read from disk, so that runtime state, like the parameters loaded from
+    /// the `.profile.state` file, is not copied to the profile
+    pub fn serialize_with_overrides(&self, overrides: &[KeyOverride]) -> Result<String> {
+        let toml = fs::read_to_string(&self.profile_file)?;
+        let mut value: toml::Value = toml::de::from_str(&toml)?;
+
+        if let Some(table) = value.as_table_mut() {
+            if overrides.is_empty() {
+                table.remove("overrides");
+            } else {
+                table.insert("overrides".to_string(), toml::Value::try_from(overrides)?);
+            }
+        }
+
+        Ok(toml::ser::to_string_pretty(&value)?)
+    }
+
     pub fn load_params(&mut self) -> Result<()> {
         let path = self.profile_file.with_extension("profile.state");
         let json_string = fs::read_to_string(&path)?;
@@ -514,6 +937,20 @@ impl Profile {
         Ok(())
     }
 
+    /// Returns the compositing settings of the active script `script_file`
+    pub fn get_layer(&self, script_file: &Path) -> Layer {
+        self.layers
+            .iter()
+            .find(|(name, _)| Path::new(name).file_name() == script_file.file_name())
+            .map(|(_, layer)| layer.clone())
+            .or_else(|| {
+                self.parent
+                    .as_ref()
+                    .map(|parent| parent.get_layer(script_file))
+            })
+            .unwrap_or_default()
+    }
+
     get_config_value!(int, i64, ConfigParam::Int);
     set_config_value!(int, i64, ConfigParam::Int);
 
@@ -528,6 +965,28 @@ impl Profile {
 
     get_config_value!(color, u32, ConfigParam::Color);
     set_config_value!(color, u32, ConfigParam::Color);
+
+    get_config_value!(enum, str, ConfigParam::Enum);
+    set_config_value!(enum, str, ConfigParam::Enum);
+
+    get_config_value!(duration, u64, ConfigParam::Duration);
+    set_config_value!(duration, u64, ConfigParam::Duration);
+
+    pub fn get_color_array_value(&self, script_name: &str, name: &str) -> Option<&[u32]> {
+        match self.find_config_param(script_name, name)? {
+            ConfigParam::ColorArray { value, .. } => Some(value),
+
+            _ => None,
+        }
+    }
+
+    pub fn get_keyset_value(&self, script_name: &str, name: &str) -> Option<&[usize]> {
+        match self.find_config_param(script_name, name)? {
+            ConfigParam::KeySet { value, .. } => Some(value),
+
+            _ => None,
+        }
+    }
 }
 
 impl Default for Profile {
@@ -542,8 +1001,12 @@ impl Default for Profile {
             profile_file,
             name: "Default".into(),
             description: "Auto-generated profile".into(),
+            extends: None,
+            parent: None,
             active_scripts: vec![PathBuf::from(constants::DEFAULT_EFFECT_SCRIPT)],
             config,
+            layers: HashMap::new(),
+            overrides: vec![],
         }
     }
 }
@@ -652,7 +1115,7 @@ pub fn find_path_by_uuid_from(uuid: Uuid, profile_dirs: &Vec<PathBuf>) -> Option
     let mut result = None;
 
     'PROFILE_LOOP: for profile_file in profile_files.iter() {
-        match Profile::from(profile_file) {
+        match Profile::parse(profile_file) {
             Ok(profile) => {
                 if profile.id == uuid {
                     result = Some(profile_file.to_path_buf());
@@ -802,4 +1265,113 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn load_inherited_profile() -> super::Result<()> {
+        let path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+        let profile =
+            super::Profile::from(&path.join("../support/tests/assets/inherited.profile"))?;
+
+        assert_eq!(profile.parent.as_ref().unwrap().name, "Organic FX");
+
+        assert_eq!(
+            profile.get_active_scripts(),
+            vec![
+                PathBuf::from("organic.lua"),
+                PathBuf::from("shockwave.lua"),
+                PathBuf::from("macros.lua"),
+                PathBuf::from("water.lua"),
+            ]
+        );
+
+        // overridden by the profile itself
+        assert_eq!(
+            profile.get_bool_value("Shockwave", "mouse_events"),
+            Some(&false)
+        );
+
+        // inherited from the parent profile
+        assert_eq!(
+            profile.get_color_value("Shockwave", "color_step_shockwave"),
+            Some(&0x05010000)
+        );
+
+        assert_eq!(profile.get_config()["Shockwave"].len(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_profile_layers() -> super::Result<()> {
+        let profile = toml::de::from_str::<super::Profile>(
+            r#"
+            name = 'Layers'
+            description = 'Layers'
+            active_scripts = ['organic.lua', 'shockwave.lua']
+
+            [layers."shockwave.lua"]
+            blend_mode = 'screen'
+            opacity = 0.5
+            mask = [{ x = 0, y = 0, width = 4, height = 2 }]
+            "#,
+        )?;
+
+        let layer = profile.get_layer(&PathBuf::from("/usr/share/eruption/scripts/shockwave.lua"));
+
+        assert_eq!(layer.blend_mode, super::BlendMode::Screen);
+        assert_eq!(layer.opacity, 0.5);
+        assert!(layer.mask[0].contains(3, 1));
+        assert!(!layer.mask[0].contains(4, 0));
+
+        let layer = profile.get_layer(&PathBuf::from("organic.lua"));
+
+        assert_eq!(layer, super::Layer::default());
+
+        Ok(())
+    }
+
+    #[test]
+    fn key_override_gradient() {
+        let key_override = super::KeyOverride {
+            name: "Function keys".into(),
+            keys: vec![1, 2, 3],
+            color: None,
+            gradient: vec![0xff000000, 0xff0000ff],
+            blend_mode: super::BlendMode::Alpha,
+            opacity: 1.0,
+        };
+
+        assert_eq!(key_override.get_color(0), Some(0xff000000));
+        assert_eq!(key_override.get_color(1), Some(0xff000080));
+        assert_eq!(key_override.get_color(2), Some(0xff0000ff));
+    }
+
+    #[test]
+    fn serialize_with_overrides() -> super::Result<()> {
+        let path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+        let profile = super::Profile::from(&path.join("../support/tests/assets/default.profile"))?;
+
+        let key_override = super::KeyOverride {
+            name: "WASD".into(),
+            keys: vec![30, 44, 45, 46],
+            color: Some(0xffff0000),
+            gradient: vec![],
+            blend_mode: super::BlendMode::Replace,
+            opacity: 1.0,
+        };
+
+        let toml = profile.serialize_with_overrides(&[key_override.clone()])?;
+        let result: super::Profile = toml::de::from_str(&toml)?;
+
+        assert_eq!(result.name, profile.name);
+        assert_eq!(result.active_scripts, profile.active_scripts);
+        assert_eq!(result.overrides, vec![key_override]);
+
+        let toml = profile.serialize_with_overrides(&[])?;
+        let result: super::Profile = toml::de::from_str(&toml)?;
+
+        assert!(result.overrides.is_empty());
+
+        Ok(())
+    }
 }
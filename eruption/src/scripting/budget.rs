@@ -0,0 +1,310 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use mlua::prelude::*;
+use mlua::HookTriggers;
+use parking_lot::Mutex;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::constants;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BudgetError {
+    #[error(
+        "The script exceeded its time limit of {} milliseconds",
+        constants::SCRIPT_TIME_LIMIT_MILLIS
+    )]
+    TimeLimitExceeded {},
+
+    #[error("The script has been disabled, it exceeded its time budget {count} times in a row")]
+    Disabled { count: u32 },
+}
+
+lazy_static! {
+    /// Frame time statistics of the Lua scripts of the currently active profile
+    pub static ref SCRIPT_STATS: Arc<Mutex<HashMap<PathBuf, ScriptStats>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+}
+
+thread_local! {
+    /// The time budget of a single upcall of the script running in this thread
+    static TIME_BUDGET: Cell<Duration> = Cell::new(Duration::from_millis(constants::SCRIPT_TIME_BUDGET_MILLIS));
+
+    /// The currently running upcall will be aborted once this point in time has passed
+    static DEADLINE: Cell<Option<Instant>> = Cell::new(None);
+
+    /// Time spent by the currently running upcall in functions that are exempt from the budget
+    static EXEMPT_TIME: Cell<Duration> = Cell::new(Duration::ZERO);
+
+    /// Number of upcoming ticks that will be skipped, to throttle the script
+    static SKIP_TICKS: Cell<u32> = Cell::new(0);
+
+    /// Number of consecutive upcalls that exceeded the time budget
+    static CONSECUTIVE_OVERRUNS: Cell<u32> = Cell::new(0);
+
+    /// Set when the script has been disabled, since it kept exceeding its time budget
+    static DISABLED: Cell<bool> = Cell::new(false);
+}
+
+/// Frame time statistics of a single Lua script
+#[derive(Debug, Clone, Default)]
+pub struct ScriptStats {
+    /// Number of upcalls that have been executed
+    pub upcalls: u64,
+
+    /// Accumulated run time of all upcalls
+    pub total_time: Duration,
+
+    /// Run time of the slowest upcall
+    pub max_time: Duration,
+
+    /// Number of upcalls that exceeded the time budget
+    pub overruns: u64,
+
+    /// Number of ticks that have been skipped, to throttle the script
+    pub skipped_ticks: u64,
+}
+
+impl ScriptStats {
+    pub fn average_time(&self) -> Duration {
+        if self.upcalls > 0 {
+            Duration::from_nanos((self.total_time.as_nanos() / self.upcalls as u128) as u64)
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+/// Returns the frame time statistics of all scripts
+pub fn get_script_stats() -> Vec<(PathBuf, ScriptStats)> {
+    let mut result: Vec<(PathBuf, ScriptStats)> = SCRIPT_STATS
+        .lock()
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+
+    result.sort_by(|a, b| a.0.cmp(&b.0));
+
+    result
+}
+
+/// Forget the frame time statistics, e.g. after a switch of profiles
+pub fn clear() {
+    SCRIPT_STATS.lock().clear();
+}
+
+/// Reset the budget state of the current thread, before a script is (re-)started
+pub fn reset() {
+    let time_budget = crate::CONFIG
+        .lock()
+        .as_ref()
+        .and_then(|config| config.get::<u64>("global.script_time_budget_millis").ok())
+        .unwrap_or(constants::SCRIPT_TIME_BUDGET_MILLIS);
+
+    TIME_BUDGET.with(|f| f.set(Duration::from_millis(time_budget)));
+    DEADLINE.with(|f| f.set(None));
+    EXEMPT_TIME.with(|f| f.set(Duration::ZERO));
+    SKIP_TICKS.with(|f| f.set(0));
+    CONSECUTIVE_OVERRUNS.with(|f| f.set(0));
+    DISABLED.with(|f| f.set(false));
+}
+
+/// Returns true if the script running in the current thread has been disabled
+pub fn is_disabled() -> bool {
+    DISABLED.with(|f| f.get())
+}
+
+/// Install a hook that aborts upcalls of the Lua VM `lua_ctx`, once they exceed the time limit
+pub fn install_hook(lua_ctx: &Lua) -> mlua::Result<()> {
+    let triggers = HookTriggers {
+        every_nth_instruction: Some(constants::SCRIPT_HOOK_INSTRUCTIONS),
+        ..Default::default()
+    };
+
+    lua_ctx.set_hook(triggers, |_lua_ctx, _debug| {
+        match DEADLINE.with(|f| f.get()) {
+            Some(deadline) if Instant::now() > deadline => Err(LuaError::ExternalError(Arc::new(
+                BudgetError::TimeLimitExceeded {},
+            ))),
+
+            _ => Ok(()),
+        }
+    })
+}
+
+/// Run `f` with the time limit in place
+pub fn limit<R>(f: impl FnOnce() -> mlua::Result<R>) -> mlua::Result<R> {
+    DEADLINE.with(|d| {
+        d.set(Some(
+            Instant::now() + Duration::from_millis(constants::SCRIPT_TIME_LIMIT_MILLIS),
+        ))
+    });
+
+    let result = f();
+
+    DEADLINE.with(|d| d.set(None));
+
+    result
+}
+
+/// Run `f` without counting its run time against the time budget and the time limit
+/// of the currently running upcall, e.g. while the script is sleeping in `delay()`
+pub fn exempt<R>(f: impl FnOnce() -> R) -> R {
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+
+    EXEMPT_TIME.with(|t| t.set(t.get() + elapsed));
+    DEADLINE.with(|d| d.set(d.get().map(|deadline| deadline + elapsed)));
+
+    result
+}
+
+/// Run the upcall `f` of the script `script_file` with the time limit in place. The run time
+/// of the upcall counts against the time budget of the script, scripts that exceed their budget
+/// will be throttled, and disabled if they keep exceeding it
+pub fn upcall<R>(script_file: &Path, f: impl FnOnce() -> mlua::Result<R>) -> mlua::Result<R> {
+    EXEMPT_TIME.with(|t| t.set(Duration::ZERO));
+
+    let start = Instant::now();
+    let result = limit(f);
+    let elapsed = start
+        .elapsed()
+        .saturating_sub(EXEMPT_TIME.with(|t| t.get()));
+
+    let time_budget = TIME_BUDGET.with(|f| f.get());
+
+    let mut script_stats = SCRIPT_STATS.lock();
+    let stats = script_stats.entry(script_file.to_path_buf()).or_default();
+
+    stats.upcalls += 1;
+    stats.total_time += elapsed;
+    stats.max_time = stats.max_time.max(elapsed);
+
+    if elapsed > time_budget {
+        stats.overruns += 1;
+
+        // skip as many ticks as the script has exceeded its budget
+        let skip_ticks = (elapsed.as_nanos() / time_budget.as_nanos().max(1)) as u32;
+        SKIP_TICKS.with(|f| f.set(skip_ticks.min(constants::MAX_SKIPPED_TICKS)));
+
+        let count = CONSECUTIVE_OVERRUNS.with(|f| {
+            f.set(f.get() + 1);
+            f.get()
+        });
+
+        debug!(
+            "Script {} exceeded its time budget: {} ms",
+            script_file.display(),
+            elapsed.as_millis()
+        );
+
+        if count > constants::MAX_CONSECUTIVE_BUDGET_OVERRUNS {
+            DISABLED.with(|f| f.set(true));
+
+            return Err(LuaError::ExternalError(Arc::new(BudgetError::Disabled {
+                count,
+            })));
+        }
+    } else {
+        CONSECUTIVE_OVERRUNS.with(|f| f.set(0));
+    }
+
+    result
+}
+
+/// Returns true if the next tick of the script `script_file` shall be skipped
+pub fn throttle_tick(script_file: &Path) -> bool {
+    let skip = SKIP_TICKS.with(|f| {
+        let val = f.get();
+        f.set(val.saturating_sub(1));
+
+        val > 0
+    });
+
+    if skip {
+        if let Some(stats) = SCRIPT_STATS.lock().get_mut(script_file) {
+            stats.skipped_ticks += 1;
+        }
+    }
+
+    skip
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_upcalls_are_throttled() {
+        let script_file = Path::new("/tmp/budget-test.lua");
+
+        reset();
+        TIME_BUDGET.with(|f| f.set(Duration::from_millis(1)));
+
+        upcall(script_file, || {
+            std::thread::sleep(Duration::from_millis(5));
+            Ok(())
+        })
+        .unwrap();
+
+        assert!(throttle_tick(script_file));
+        assert!(!is_disabled());
+
+        let stats = SCRIPT_STATS.lock().get(script_file).cloned().unwrap();
+        assert_eq!(stats.upcalls, 1);
+        assert_eq!(stats.overruns, 1);
+        assert_eq!(stats.skipped_ticks, 1);
+    }
+
+    #[test]
+    fn delays_are_exempt() {
+        let script_file = Path::new("/tmp/budget-test-delay.lua");
+
+        reset();
+        TIME_BUDGET.with(|f| f.set(Duration::from_millis(1)));
+
+        upcall(script_file, || {
+            exempt(|| std::thread::sleep(Duration::from_millis(5)));
+            Ok(())
+        })
+        .unwrap();
+
+        assert!(!throttle_tick(script_file));
+
+        let stats = SCRIPT_STATS.lock().get(script_file).cloned().unwrap();
+        assert_eq!(stats.overruns, 0);
+    }
+
+    #[test]
+    fn endless_loops_are_aborted() {
+        let lua_ctx = Lua::new();
+        install_hook(&lua_ctx).unwrap();
+
+        let result = limit(|| lua_ctx.load("while true do end").exec());
+
+        assert!(result.is_err());
+    }
+}
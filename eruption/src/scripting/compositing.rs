@@ -0,0 +1,257 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use std::sync::atomic::Ordering;
+
+use crate::hwdevices::RGBA;
+use crate::profiles::{BlendMode, KeyOverride, Layer};
+
+/// Combine a single color channel of the foreground `fg` with the background `bg`
+fn blend_channel(mode: BlendMode, fg: u8, bg: u8) -> u8 {
+    let (fg, bg) = (fg as u32, bg as u32);
+
+    let result = match mode {
+        BlendMode::Alpha | BlendMode::Replace => fg,
+        BlendMode::Add => (fg + bg).min(255),
+        BlendMode::Multiply => fg * bg / 255,
+        BlendMode::Screen => 255 - (255 - fg) * (255 - bg) / 255,
+        BlendMode::Max => fg.max(bg),
+    };
+
+    result as u8
+}
+
+/// Mix the color `fg` into the color `bg` using the blend mode `mode`, based on the effective
+/// alpha value `alpha` of the foreground
+fn blend_color(mode: BlendMode, fg: RGBA, bg: RGBA, alpha: u32, brightness: i32) -> RGBA {
+    let mix = |fg: u8, bg: u8| {
+        let color = blend_channel(mode, fg, bg);
+
+        (((alpha as f64 * color as f64 + (255 - alpha) as f64 * bg as f64) * brightness as f64
+            / 100.0) as u32
+            >> 8) as u8
+    };
+
+    RGBA {
+        r: mix(fg.r, bg.r),
+        g: mix(fg.g, bg.g),
+        b: mix(fg.b, bg.b),
+        a: alpha as u8,
+    }
+}
+
+/// Mix the color `fg` into the color `bg` of the already dimmed canvas, so unlike `blend_color`
+/// only the foreground is scaled by `brightness`
+fn blend_color_over(mode: BlendMode, fg: RGBA, bg: RGBA, alpha: u32, brightness: i32) -> RGBA {
+    let mix = |fg: u8, bg: u8| {
+        let color = blend_channel(mode, fg, bg);
+
+        ((alpha as f64 * color as f64 * brightness as f64 / 100.0
+            + (255 - alpha) as f64 * bg as f64) as u32
+            >> 8) as u8
+    };
+
+    RGBA {
+        r: mix(fg.r, bg.r),
+        g: mix(fg.g, bg.g),
+        b: mix(fg.b, bg.b),
+        a: alpha as u8,
+    }
+}
+
+/// Blend the color map `foreground` of a script onto the canvas `background`, as specified by
+/// the compositing settings `layer`. The blended colors are mixed with the background based on
+/// the alpha channel of the foreground, the opacity and the mask of the layer
+pub fn blend(background: &mut [RGBA], foreground: &[RGBA], layer: &Layer, brightness: i32) {
+    let canvas_width = crate::CANVAS_WIDTH.load(Ordering::SeqCst).max(1);
+    let opacity = layer.opacity.clamp(0.0, 1.0);
+
+    for (idx, (bg, fg)) in background.iter_mut().zip(foreground.iter()).enumerate() {
+        let x = (idx % canvas_width) as i32;
+        let y = (idx / canvas_width) as i32;
+
+        let masked = !layer.mask.is_empty() && !layer.mask.iter().any(|m| m.contains(x, y));
+
+        let alpha = if masked {
+            0
+        } else if layer.blend_mode == BlendMode::Replace {
+            (255.0 * opacity) as u32
+        } else {
+            (fg.a as f64 * opacity) as u32
+        };
+
+        *bg = blend_color(layer.blend_mode, *fg, *bg, alpha, brightness);
+    }
+}
+
+/// Blend `foreground` on top of the fully composited canvas `background`, e.g. the overlay set
+/// via the D-Bus API. The canvas already is dimmed, so `brightness` only applies to the foreground.
+/// Cells with a fully transparent foreground keep the background
+pub fn blend_over(background: &mut [RGBA], foreground: &[RGBA], brightness: i32) {
+    for (bg, fg) in background.iter_mut().zip(foreground.iter()) {
+        if fg.a == 0 {
+            continue;
+        }
+
+        *bg = blend_color_over(BlendMode::Alpha, *fg, *bg, fg.a as u32, brightness);
+    }
+}
+
+/// Apply the static key overrides of a profile on top of the canvas. Key indices start at 1,
+/// just like the indices of the color maps of the Lua scripts
+pub fn apply_overrides(canvas: &mut [RGBA], overrides: &[KeyOverride], brightness: i32) {
+    for key_override in overrides {
+        let opacity = key_override.opacity.clamp(0.0, 1.0);
+
+        for (position, key_index) in key_override.keys.iter().enumerate() {
+            let idx = match key_index.checked_sub(1) {
+                Some(idx) if idx < canvas.len() => idx,
+                _ => continue,
+            };
+
+            if let Some(color) = key_override.get_color(position) {
+                let fg = RGBA {
+                    r: ((color >> 16) & 0xff) as u8,
+                    g: ((color >> 8) & 0xff) as u8,
+                    b: (color & 0xff) as u8,
+                    a: ((color >> 24) & 0xff) as u8,
+                };
+
+                let alpha = if key_override.blend_mode == BlendMode::Replace {
+                    (255.0 * opacity) as u32
+                } else {
+                    (fg.a as f64 * opacity) as u32
+                };
+
+                // the canvas already has been dimmed by the script layers
+                canvas[idx] =
+                    blend_color_over(key_override.blend_mode, fg, canvas[idx], alpha, brightness);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiles::MaskZone;
+
+    fn color(r: u8, g: u8, b: u8, a: u8) -> RGBA {
+        RGBA { r, g, b, a }
+    }
+
+    #[test]
+    fn blend_modes() {
+        assert_eq!(blend_channel(BlendMode::Add, 200, 100), 255);
+        assert_eq!(blend_channel(BlendMode::Multiply, 255, 100), 100);
+        assert_eq!(blend_channel(BlendMode::Screen, 0, 100), 100);
+        assert_eq!(blend_channel(BlendMode::Max, 50, 100), 100);
+        assert_eq!(blend_channel(BlendMode::Replace, 50, 100), 50);
+    }
+
+    #[test]
+    fn masked_cells_keep_the_background() {
+        let layer = Layer {
+            blend_mode: BlendMode::Replace,
+            mask: vec![MaskZone {
+                x: 0,
+                y: 0,
+                width: 1,
+                height: 1,
+            }],
+            ..Default::default()
+        };
+
+        let mut background = vec![color(0, 0, 255, 255); 2];
+        let foreground = vec![color(255, 0, 0, 0); 2];
+
+        blend(&mut background, &foreground, &layer, 100);
+
+        assert_eq!((background[0].r, background[0].b), (254, 0));
+        assert_eq!((background[1].r, background[1].b), (0, 254));
+    }
+
+    #[test]
+    fn blend_over_dims_the_foreground_only() {
+        let mut background = vec![color(0, 0, 200, 255); 2];
+        let foreground = vec![color(255, 0, 0, 255), color(255, 0, 0, 0)];
+
+        blend_over(&mut background, &foreground, 50);
+
+        assert_eq!((background[0].r, background[0].b), (127, 0));
+        assert_eq!((background[1].r, background[1].b), (0, 200));
+    }
+
+    #[test]
+    fn translucent_overrides_dim_the_override_only() {
+        let key_override = KeyOverride {
+            name: "Translucent".into(),
+            keys: vec![1, 2],
+            color: None,
+            gradient: vec![0x80ff0000, 0x80ff0000],
+            blend_mode: BlendMode::Alpha,
+            opacity: 1.0,
+        };
+
+        let mut canvas = vec![color(0, 0, 200, 255); 2];
+
+        apply_overrides(&mut canvas, &[key_override], 50);
+
+        assert_eq!((canvas[0].r, canvas[0].b), (63, 99));
+        assert_eq!((canvas[1].r, canvas[1].b), (63, 99));
+    }
+
+    #[test]
+    fn overrides_without_alpha_are_opaque() {
+        let key_override = KeyOverride {
+            name: "RGB".into(),
+            keys: vec![1],
+            color: Some(0xff0000),
+            gradient: vec![],
+            blend_mode: BlendMode::Alpha,
+            opacity: 1.0,
+        };
+
+        let mut canvas = vec![color(0, 0, 255, 255); 1];
+
+        apply_overrides(&mut canvas, &[key_override], 100);
+
+        assert_eq!((canvas[0].r, canvas[0].b), (254, 0));
+    }
+
+    #[test]
+    fn overrides_only_affect_their_keys() {
+        let key_override = KeyOverride {
+            name: "WASD".into(),
+            keys: vec![2, 0, 100],
+            color: Some(0xffff0000),
+            gradient: vec![],
+            blend_mode: BlendMode::Alpha,
+            opacity: 1.0,
+        };
+
+        let mut canvas = vec![color(0, 0, 255, 255); 3];
+
+        apply_overrides(&mut canvas, &[key_override], 100);
+
+        assert_eq!((canvas[0].r, canvas[0].b), (0, 255));
+        assert_eq!((canvas[1].r, canvas[1].b), (254, 0));
+        assert_eq!((canvas[2].r, canvas[2].b), (0, 255));
+    }
+}
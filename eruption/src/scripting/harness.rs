@@ -0,0 +1,453 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use mlua::prelude::*;
+use mlua::{Function, Table};
+use parking_lot::RwLock;
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::constants;
+use crate::hwdevices::RGBA;
+use crate::plugins::persistence;
+use crate::scripting::{budget, script};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+lazy_static! {
+    /// Support scripts of the devices that are emulated by the test harness, e.g. `keyboards/roccat_vulcan_1xx`
+    pub static ref EMULATED_DEVICES: Arc<RwLock<Option<Vec<String>>>> = Arc::new(RwLock::new(None));
+}
+
+thread_local! {
+    /// Events that have been injected by the script under test, via one of the mocked functions
+    static INJECTED_EVENTS: RefCell<Vec<InjectedEvent>> = RefCell::new(Vec::new());
+}
+
+/// Helper functions of the test harness, available to the test files via the `harness` table
+const PRELUDE: &str = r#"
+harness = { tests = {} }
+
+local function key_index(key)
+    if type(key) == "string" then
+        local index = 0
+        if key_name_to_index ~= nil then index = key_name_to_index(key) end
+
+        if index == 0 then error("Unknown key: " .. key, 3) end
+
+        return index
+    end
+
+    return key
+end
+
+local function format_events(events)
+    return "{ " .. table.concat(events, ", ") .. " }"
+end
+
+function harness.test(name, f)
+    table.insert(harness.tests, { name = name, f = f })
+end
+
+function harness.key_down(key)
+    local index = key_index(key)
+    harness.set_key_state(index, true)
+    if on_key_down ~= nil then on_key_down(index) end
+end
+
+function harness.key_up(key)
+    local index = key_index(key)
+    harness.set_key_state(index, false)
+    if on_key_up ~= nil then on_key_up(index) end
+end
+
+function harness.press(key)
+    harness.key_down(key)
+    harness.key_up(key)
+end
+
+function harness.hid_event(event_type, arg1)
+    if on_hid_event ~= nil then on_hid_event(event_type, arg1) end
+end
+
+function harness.hid_key_down(code) harness.hid_event(2, code) end
+
+function harness.hid_key_up(code) harness.hid_event(1, code) end
+
+function harness.mouse_button_down(button_index)
+    if on_mouse_button_down ~= nil then on_mouse_button_down(button_index) end
+end
+
+function harness.mouse_button_up(button_index)
+    if on_mouse_button_up ~= nil then on_mouse_button_up(button_index) end
+end
+
+function harness.mouse_wheel(direction)
+    if on_mouse_wheel ~= nil then on_mouse_wheel(direction) end
+end
+
+function harness.tick(count)
+    for i = 1, count or 1 do
+        if on_tick ~= nil then on_tick(harness.delta) end
+    end
+end
+
+function harness.assert_eq(actual, expected, message)
+    if actual ~= expected then
+        error((message or "Values differ") .. "\n  expected: " .. tostring(expected) ..
+                  "\n    actual: " .. tostring(actual), 2)
+    end
+end
+
+function harness.expect_injected(expected)
+    local actual = format_events(harness.take_injected())
+    expected = format_events(expected)
+
+    if actual ~= expected then
+        error("Injected events differ\n  expected: " .. expected .. "\n    actual: " .. actual, 2)
+    end
+end
+"#;
+
+#[derive(Debug, thiserror::Error)]
+pub enum HarnessError {
+    #[error("{failed} of {total} tests failed")]
+    TestsFailed { failed: usize, total: usize },
+}
+
+/// An event that has been injected by a script via one of the mocked functions
+#[derive(Debug, Clone, PartialEq)]
+pub enum InjectedEvent {
+    Key { code: u32, down: bool },
+    MouseButton { button: u32, down: bool },
+    MouseWheel { direction: u32 },
+    SwitchToSlot(usize),
+    SwitchToProfile(String),
+    System { command: String, args: Vec<String> },
+    Exit,
+}
+
+impl fmt::Display for InjectedEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = |down: &bool| if *down { "down" } else { "up" };
+
+        match self {
+            InjectedEvent::Key { code, down } => write!(f, "key({}, {})", code, state(down)),
+
+            InjectedEvent::MouseButton { button, down } => {
+                write!(f, "mouse_button({}, {})", button, state(down))
+            }
+
+            InjectedEvent::MouseWheel { direction } => write!(f, "mouse_wheel({})", direction),
+            InjectedEvent::SwitchToSlot(index) => write!(f, "slot({})", index),
+            InjectedEvent::SwitchToProfile(profile) => write!(f, "profile({})", profile),
+
+            InjectedEvent::System { command, args } => {
+                write!(
+                    f,
+                    "system({})",
+                    [&[command.clone()], &args[..]].concat().join(" ")
+                )
+            }
+
+            InjectedEvent::Exit => write!(f, "exit()"),
+        }
+    }
+}
+
+/// Outcome of a single test case
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub name: String,
+    pub error: Option<String>,
+}
+
+fn record(event: InjectedEvent) {
+    INJECTED_EVENTS.with(|events| events.borrow_mut().push(event));
+}
+
+/// Forget all state that a previous test case may have left behind
+fn reset_state() {
+    INJECTED_EVENTS.with(|events| events.borrow_mut().clear());
+
+    crate::KEY_STATES.write().fill(false);
+    crate::ACTIVE_SLOT.store(0, Ordering::SeqCst);
+    persistence::GLOBAL_EPHEMERAL_STORE.write().clear();
+
+    script::LOCAL_LED_MAP.with(|led_map| {
+        led_map.borrow_mut().fill(RGBA {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 0,
+        })
+    });
+}
+
+/// Replace the functions that would affect the system with mocks, that record the injected events.
+/// Functions that have been withheld by the sandbox stay unavailable, so scripts that lack a
+/// capability fail just like they would in the daemon
+fn install_mocks(lua_ctx: &Lua) -> mlua::Result<()> {
+    let globals = lua_ctx.globals();
+
+    if globals.contains_key("inject_key")? {
+        let inject_key = lua_ctx.create_function(|_, (code, down): (u32, bool)| {
+            record(InjectedEvent::Key { code, down });
+            Ok(())
+        })?;
+        globals.set("inject_key", inject_key)?;
+    }
+
+    if globals.contains_key("inject_key_with_delay")? {
+        let inject_key_with_delay =
+            lua_ctx.create_function(|_, (code, down, _millis): (u32, bool, u64)| {
+                record(InjectedEvent::Key { code, down });
+                Ok(())
+            })?;
+        globals.set("inject_key_with_delay", inject_key_with_delay)?;
+    }
+
+    if globals.contains_key("inject_mouse_button")? {
+        let inject_mouse_button = lua_ctx.create_function(|_, (button, down): (u32, bool)| {
+            record(InjectedEvent::MouseButton { button, down });
+            Ok(())
+        })?;
+        globals.set("inject_mouse_button", inject_mouse_button)?;
+    }
+
+    if globals.contains_key("inject_mouse_wheel")? {
+        let inject_mouse_wheel = lua_ctx.create_function(|_, direction: u32| {
+            record(InjectedEvent::MouseWheel { direction });
+            Ok(())
+        })?;
+        globals.set("inject_mouse_wheel", inject_mouse_wheel)?;
+    }
+
+    if globals.contains_key("switch_to_slot")? {
+        let switch_to_slot = lua_ctx.create_function(|_, index: usize| {
+            // keep get_current_slot() consistent with the switch
+            crate::ACTIVE_SLOT.store(index, Ordering::SeqCst);
+
+            record(InjectedEvent::SwitchToSlot(index));
+            Ok(())
+        })?;
+        globals.set("switch_to_slot", switch_to_slot)?;
+    }
+
+    if globals.contains_key("switch_to_profile")? {
+        let switch_to_profile = lua_ctx.create_function(|_, profile: String| {
+            record(InjectedEvent::SwitchToProfile(profile));
+            Ok(())
+        })?;
+        globals.set("switch_to_profile", switch_to_profile)?;
+    }
+
+    // never run commands or terminate the process on behalf of the script under test
+    if globals.contains_key("system")? {
+        let system = lua_ctx.create_function(|_, (command, args): (String, Vec<String>)| {
+            record(InjectedEvent::System { command, args });
+            Ok(0)
+        })?;
+        globals.set("system", system)?;
+    }
+
+    if globals.contains_key("exit")? {
+        let exit = lua_ctx.create_function(|_, ()| {
+            record(InjectedEvent::Exit);
+            Ok(())
+        })?;
+        globals.set("exit", exit)?;
+    }
+
+    let os: Table = globals.get("os")?;
+
+    if os.contains_key("execute")? {
+        let execute = lua_ctx.create_function(|_, command: Option<String>| {
+            record(InjectedEvent::System {
+                command: command.unwrap_or_default(),
+                args: vec![],
+            });
+            Ok(true)
+        })?;
+        os.set("execute", execute)?;
+    }
+
+    if os.contains_key("exit")? {
+        let exit = lua_ctx.create_function(|_, _args: mlua::MultiValue| {
+            record(InjectedEvent::Exit);
+            Ok(())
+        })?;
+        os.set("exit", exit)?;
+    }
+
+    Ok(())
+}
+
+fn register_harness_funcs(lua_ctx: &Lua) -> mlua::Result<()> {
+    let harness: Table = lua_ctx.globals().get("harness")?;
+
+    // use the same fixed frame time as the headless renderer
+    harness.set(
+        "delta",
+        (1000 / constants::TARGET_FPS / constants::TARGET_FPS) as u32,
+    )?;
+
+    let set_key_state = lua_ctx.create_function(|_, (index, pressed): (usize, bool)| {
+        if let Some(state) = crate::KEY_STATES.write().get_mut(index) {
+            *state = pressed;
+        }
+
+        Ok(())
+    })?;
+    harness.set("set_key_state", set_key_state)?;
+
+    let injected = lua_ctx.create_function(|_, ()| {
+        Ok(INJECTED_EVENTS.with(|events| {
+            events
+                .borrow()
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<String>>()
+        }))
+    })?;
+    harness.set("injected", injected)?;
+
+    let take_injected = lua_ctx.create_function(|_, ()| {
+        Ok(INJECTED_EVENTS.with(|events| {
+            events
+                .borrow_mut()
+                .drain(..)
+                .map(|e| e.to_string())
+                .collect::<Vec<String>>()
+        }))
+    })?;
+    harness.set("take_injected", take_injected)?;
+
+    // the color map that has been submitted most recently by the script under test
+    let canvas = lua_ctx.create_function(|_, ()| {
+        Ok(script::LOCAL_LED_MAP.with(|led_map| {
+            led_map
+                .borrow()
+                .iter()
+                .map(|c| (c.a as u32) << 24 | (c.r as u32) << 16 | (c.g as u32) << 8 | c.b as u32)
+                .collect::<Vec<u32>>()
+        }))
+    })?;
+    harness.set("canvas", canvas)?;
+
+    Ok(())
+}
+
+/// Load the script under test into a fresh Lua VM, followed by the test file.
+/// The mocks are installed before the top-level chunk of the script runs
+fn prepare(script_file: &Path, test_file: &Path, test_source: &str) -> Result<Lua> {
+    let lua_ctx = script::load_script_with(script_file, &None, install_mocks)?;
+
+    lua_ctx.load(PRELUDE).set_name("harness")?.exec()?;
+
+    register_harness_funcs(&lua_ctx)?;
+
+    lua_ctx
+        .load(test_source)
+        .set_name(&test_file.to_string_lossy().to_string())?
+        .exec()?;
+
+    // only record the events that are injected by the test case itself
+    INJECTED_EVENTS.with(|events| events.borrow_mut().clear());
+
+    Ok(lua_ctx)
+}
+
+/// Run the test cases of the file `test_file` against the script `script_file`.
+/// Each test case is run in a fresh Lua VM
+pub fn run_tests(script_file: &Path, test_file: &Path) -> Result<Vec<TestResult>> {
+    let test_source = fs::read_to_string(test_file)?;
+
+    let count = {
+        reset_state();
+
+        let lua_ctx = prepare(script_file, test_file, &test_source)?;
+        let tests: Table = lua_ctx.globals().get::<_, Table>("harness")?.get("tests")?;
+
+        tests.raw_len()
+    };
+
+    let mut results = Vec::new();
+
+    for index in 1..=count {
+        reset_state();
+
+        let lua_ctx = prepare(script_file, test_file, &test_source)?;
+        let tests: Table = lua_ctx.globals().get::<_, Table>("harness")?.get("tests")?;
+
+        let test: Table = tests.get(index)?;
+        let name: String = test.get("name")?;
+        let f: Function = test.get("f")?;
+
+        let error = budget::limit(|| f.call::<_, ()>(()))
+            .err()
+            .map(|e| e.to_string());
+
+        results.push(TestResult { name, error });
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn injected_events_are_formatted_for_comparison() {
+        assert_eq!(
+            InjectedEvent::Key {
+                code: 125,
+                down: true
+            }
+            .to_string(),
+            "key(125, down)"
+        );
+
+        assert_eq!(
+            InjectedEvent::MouseButton {
+                button: 1,
+                down: false
+            }
+            .to_string(),
+            "mouse_button(1, up)"
+        );
+
+        assert_eq!(InjectedEvent::SwitchToSlot(1).to_string(), "slot(1)");
+
+        assert_eq!(
+            InjectedEvent::System {
+                command: "notify-send".to_string(),
+                args: vec!["Eruption".to_string()]
+            }
+            .to_string(),
+            "system(notify-send Eruption)"
+        );
+    }
+}
@@ -89,6 +89,77 @@ pub enum ConfigParam {
         max: Option<u32>,
         default: u32,
     },
+    /// One of a list of allowed values
+    Enum {
+        name: String,
+        description: String,
+        values: Vec<String>,
+        default: String,
+    },
+    /// A list of colors, e.g. the stops of a gradient
+    ColorArray {
+        name: String,
+        description: String,
+        default: Vec<u32>,
+    },
+    /// A list of key indices
+    KeySet {
+        name: String,
+        description: String,
+        default: Vec<usize>,
+    },
+    /// A duration in milliseconds
+    Duration {
+        name: String,
+        description: String,
+        min: Option<u64>,
+        max: Option<u64>,
+        default: u64,
+    },
+}
+
+/// Parse a color, either in the hexadecimal format `#AARRGGBB`, or as a decimal number
+pub fn parse_color(val: &str) -> Result<u32> {
+    let val = val.trim();
+
+    let result = match val.strip_prefix('#') {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => u32::from_str(val),
+    };
+
+    Ok(result.map_err(|_e| ManifestError::ParseParamError {})?)
+}
+
+/// Parse a comma separated list of values
+fn parse_list<T>(val: &str, f: impl Fn(&str) -> Result<T>) -> Result<Vec<T>> {
+    val.split(',')
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(f)
+        .collect()
+}
+
+/// Parse a duration in milliseconds, either as a plain number, or with one of the units `ms` or `s`
+pub fn parse_duration(val: &str) -> Result<u64> {
+    let val = val.trim();
+
+    let result = if let Some(millis) = val.strip_suffix("ms") {
+        u64::from_str(millis.trim()).ok()
+    } else if let Some(secs) = val.strip_suffix('s') {
+        f64::from_str(secs.trim())
+            .ok()
+            .filter(|secs| *secs >= 0.0)
+            .map(|secs| (secs * 1000.0).round() as u64)
+    } else {
+        u64::from_str(val).ok()
+    };
+
+    Ok(result.ok_or(ManifestError::ParseParamError {})?)
+}
+
+/// Returns `true` if the duration `value` lies within the optional bounds `min` and `max`
+pub fn is_duration_in_range(value: u64, min: Option<u64>, max: Option<u64>) -> bool {
+    min.map_or(true, |min| value >= min) && max.map_or(true, |max| value <= max)
 }
 
 pub trait ParseConfig {
@@ -152,25 +223,88 @@ impl ParseConfig for Vec<ConfigParam> {
 
                 ConfigParam::Color { name, default, .. } => {
                     if name == param {
-                        if &val[0..1] == "#" {
-                            let value = u32::from_str_radix(&val[1..], 16)
-                                .map_err(|_e| ManifestError::ParseParamError {})?;
-
-                            return Ok(profiles::ConfigParam::Color {
-                                name: name.to_string(),
-                                value,
-                                default: *default,
-                            });
-                        } else {
-                            let value = u32::from_str(val)
-                                .map_err(|_e| ManifestError::ParseParamError {})?;
-
-                            return Ok(profiles::ConfigParam::Color {
-                                name: name.to_string(),
-                                value,
-                                default: *default,
-                            });
+                        let value = parse_color(val)?;
+
+                        return Ok(profiles::ConfigParam::Color {
+                            name: name.to_string(),
+                            value,
+                            default: *default,
+                        });
+                    }
+                }
+
+                ConfigParam::Enum {
+                    name,
+                    values,
+                    default,
+                    ..
+                } => {
+                    if name == param {
+                        if !values.iter().any(|v| v == val) {
+                            return Err(ManifestError::ParseParamError {}.into());
+                        }
+
+                        return Ok(profiles::ConfigParam::Enum {
+                            name: name.to_string(),
+                            value: val.to_owned(),
+                            default: default.to_owned(),
+                        });
+                    }
+                }
+
+                ConfigParam::ColorArray { name, default, .. } => {
+                    if name == param {
+                        let value = parse_list(val, parse_color)?;
+
+                        // scripts expect at least one color, e.g. to build a gradient
+                        if value.is_empty() {
+                            return Err(ManifestError::ParseParamError {}.into());
                         }
+
+                        return Ok(profiles::ConfigParam::ColorArray {
+                            name: name.to_string(),
+                            value,
+                            default: default.clone(),
+                        });
+                    }
+                }
+
+                ConfigParam::KeySet { name, default, .. } => {
+                    if name == param {
+                        let value = parse_list(val, |v| {
+                            usize::from_str(v)
+                                .ok()
+                                .filter(|index| *index > 0)
+                                .ok_or_else(|| ManifestError::ParseParamError {}.into())
+                        })?;
+
+                        return Ok(profiles::ConfigParam::KeySet {
+                            name: name.to_string(),
+                            value,
+                            default: default.clone(),
+                        });
+                    }
+                }
+
+                ConfigParam::Duration {
+                    name,
+                    min,
+                    max,
+                    default,
+                    ..
+                } => {
+                    if name == param {
+                        let value = parse_duration(val)?;
+
+                        if !is_duration_in_range(value, *min, *max) {
+                            return Err(ManifestError::ParseParamError {}.into());
+                        }
+
+                        return Ok(profiles::ConfigParam::Duration {
+                            name: name.to_string(),
+                            value,
+                            default: *default,
+                        });
                     }
                 }
             }
@@ -197,6 +331,14 @@ impl GetAttr for ConfigParam {
             ConfigParam::String { ref name, .. } => name,
 
             ConfigParam::Color { ref name, .. } => name,
+
+            ConfigParam::Enum { ref name, .. } => name,
+
+            ConfigParam::ColorArray { ref name, .. } => name,
+
+            ConfigParam::KeySet { ref name, .. } => name,
+
+            ConfigParam::Duration { ref name, .. } => name,
         }
     }
 
@@ -211,6 +353,14 @@ impl GetAttr for ConfigParam {
             ConfigParam::String { ref default, .. } => default.to_owned(),
 
             ConfigParam::Color { ref default, .. } => format!("#{:06x}", default),
+
+            ConfigParam::Enum { ref default, .. } => default.to_owned(),
+
+            ConfigParam::ColorArray { ref default, .. } => profiles::format_colors(default),
+
+            ConfigParam::KeySet { ref default, .. } => profiles::format_keys(default),
+
+            ConfigParam::Duration { ref default, .. } => format!("{}ms", default),
         }
     }
 }
@@ -228,6 +378,7 @@ pub struct Manifest {
     pub author: String,
     pub min_supported_version: String,
     pub tags: Option<Vec<ScriptTag>>,
+    pub capabilities: Option<Vec<Capability>>,
     pub config: Option<Vec<ConfigParam>>,
 }
 
@@ -445,3 +596,118 @@ impl ScriptTag {
         }
     }
 }
+
+/// Privileged functionality that a script has to request in its manifest,
+/// scripts are denied access to everything that they did not request
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub enum Capability {
+    /// Execute processes, or terminate the daemon
+    Process,
+
+    /// Inject keyboard and mouse input events
+    Input,
+
+    /// Store and load values that persist across runs of the script
+    Persistence,
+
+    /// Query hardware sensors and system statistics
+    Sensors,
+
+    /// Switch profiles and slots
+    Profiles,
+
+    /// Load native (C) Lua modules, e.g. LuaSocket
+    NativeModules,
+}
+
+impl Capability {
+    pub fn get_description(&self) -> String {
+        match *self {
+            Capability::Process => "Execute processes".into(),
+            Capability::Input => "Inject keyboard and mouse input".into(),
+            Capability::Persistence => "Store persistent data".into(),
+            Capability::Sensors => "Read sensors and system statistics".into(),
+            Capability::Profiles => "Switch profiles and slots".into(),
+            Capability::NativeModules => "Load native Lua modules".into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigParam, ParseConfig};
+    use crate::profiles;
+
+    #[test]
+    fn parse_typed_params() -> super::Result<()> {
+        let config = vec![
+            ConfigParam::Enum {
+                name: "direction".into(),
+                description: "Direction".into(),
+                values: vec!["left".into(), "right".into()],
+                default: "left".into(),
+            },
+            ConfigParam::ColorArray {
+                name: "gradient".into(),
+                description: "Gradient".into(),
+                default: vec![],
+            },
+            ConfigParam::KeySet {
+                name: "keys".into(),
+                description: "Keys".into(),
+                default: vec![],
+            },
+            ConfigParam::Duration {
+                name: "delay".into(),
+                description: "Delay".into(),
+                min: Some(100),
+                max: Some(5000),
+                default: 500,
+            },
+        ];
+
+        assert!(config.parse_config_param("direction", "up").is_err());
+        assert!(config.parse_config_param("gradient", " , ").is_err());
+        assert!(config.parse_config_param("keys", "1, x").is_err());
+        assert!(config.parse_config_param("delay", "10s").is_err());
+
+        assert_eq!(
+            config.parse_config_param("gradient", "#ffff0000, 255")?,
+            profiles::ConfigParam::ColorArray {
+                name: "gradient".into(),
+                value: vec![0xffff0000, 255],
+                default: vec![],
+            }
+        );
+
+        assert_eq!(
+            config.parse_config_param("keys", "1, 2,3")?,
+            profiles::ConfigParam::KeySet {
+                name: "keys".into(),
+                value: vec![1, 2, 3],
+                default: vec![],
+            }
+        );
+
+        assert_eq!(
+            config.parse_config_param("delay", "1.5s")?,
+            profiles::ConfigParam::Duration {
+                name: "delay".into(),
+                value: 1500,
+                default: 500,
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn duration_bounds() {
+        assert!(is_duration_in_range(100, Some(100), Some(5000)));
+        assert!(is_duration_in_range(5000, Some(100), Some(5000)));
+        assert!(is_duration_in_range(0, None, None));
+
+        assert!(!is_duration_in_range(99, Some(100), None));
+        assert!(!is_duration_in_range(5001, None, Some(5000)));
+    }
+}
@@ -17,5 +17,10 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
+pub mod budget;
+pub mod compositing;
+pub mod harness;
 pub mod manifest;
+pub mod sandbox;
 pub mod script;
+pub mod supervisor;
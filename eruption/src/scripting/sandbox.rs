@@ -0,0 +1,258 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use log::*;
+use mlua::prelude::*;
+
+use crate::scripting::manifest::Capability;
+
+/// All capabilities that may be requested by a script
+const CAPABILITIES: [Capability; 6] = [
+    Capability::Process,
+    Capability::Input,
+    Capability::Persistence,
+    Capability::Sensors,
+    Capability::Profiles,
+    Capability::NativeModules,
+];
+
+/// Globals that are never available to scripts, since they would allow a script to
+/// read or modify arbitrary files with the privileges of the daemon
+const DENIED_GLOBALS: [&str; 5] = ["dofile", "loadfile", "os.remove", "os.rename", "os.tmpname"];
+
+/// Data types supported by the functions of the persistence plugin
+const PERSISTENCE_TYPES: [&str; 7] = [
+    "int",
+    "float",
+    "bool",
+    "string",
+    "color",
+    "string_array",
+    "string_hash",
+];
+
+/// Returns the globals that require the capability `capability`. Fields of
+/// tables from the Lua standard library are specified as "table.field"
+pub fn get_gated_globals(capability: Capability) -> Vec<String> {
+    match capability {
+        Capability::Process => ["system", "exit", "os.execute", "os.exit"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+
+        Capability::Input => [
+            "inject_key",
+            "inject_key_with_delay",
+            "inject_mouse_button",
+            "inject_mouse_wheel",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect(),
+
+        Capability::Persistence => PERSISTENCE_TYPES
+            .iter()
+            .flat_map(|t| {
+                vec![
+                    format!("store_{}", t),
+                    format!("load_{}", t),
+                    format!("store_{}_transient", t),
+                    format!("load_{}_transient", t),
+                ]
+            })
+            .collect(),
+
+        Capability::Sensors => [
+            "get_package_temp",
+            "get_package_max_temp",
+            "get_mem_total_kb",
+            "get_mem_used_kb",
+            "get_swap_total_kb",
+            "get_swap_used_kb",
+            "get_current_load_avg_1",
+            "get_current_load_avg_5",
+            "get_current_load_avg_10",
+            "get_runnable_tasks",
+            "get_total_tasks",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect(),
+
+        Capability::Profiles => ["switch_to_slot", "switch_to_profile"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+
+        // the searchers for C modules are removed separately, see `remove_native_searchers`
+        Capability::NativeModules => ["package.loadlib", "package.cpath"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+    }
+}
+
+fn remove_global(globals: &LuaTable, name: &str) -> mlua::Result<()> {
+    match name.split_once('.') {
+        Some((table, field)) => {
+            if let Ok(table) = globals.get::<_, LuaTable>(table) {
+                table.raw_set(field, LuaValue::Nil)?;
+            }
+        }
+
+        None => globals.raw_set(name, LuaValue::Nil)?,
+    }
+
+    Ok(())
+}
+
+/// Only keep the searchers of `require` that load Lua source code, i.e. the
+/// preload searcher and the Lua searcher, and drop the searchers for C modules
+fn remove_native_searchers(lua_ctx: &Lua) -> mlua::Result<()> {
+    lua_ctx
+        .load(
+            r#"
+            local searchers = package.searchers
+            package.searchers = { searchers[1], searchers[2] }
+            "#,
+        )
+        .exec()
+}
+
+/// Only allow `load` to compile source code, since maliciously crafted
+/// precompiled chunks may corrupt the memory of the Lua VM
+fn restrict_load(lua_ctx: &Lua) -> mlua::Result<()> {
+    lua_ctx
+        .load(
+            r#"
+            local load = load
+            _G.load = function(chunk, name, _mode, ...)
+                return load(chunk, name, "t", ...)
+            end
+            "#,
+        )
+        .exec()
+}
+
+/// Remove all globals from the Lua environment `lua_ctx`, that require a
+/// capability which is not contained in `capabilities`, as well as the
+/// globals that are never available to scripts
+pub fn apply(lua_ctx: &Lua, capabilities: &[Capability]) -> mlua::Result<()> {
+    let globals = lua_ctx.globals();
+
+    for name in DENIED_GLOBALS.iter() {
+        remove_global(&globals, name)?;
+    }
+
+    restrict_load(lua_ctx)?;
+
+    for capability in CAPABILITIES.iter() {
+        if capabilities.contains(capability) {
+            continue;
+        }
+
+        if *capability == Capability::NativeModules {
+            remove_native_searchers(lua_ctx)?;
+        }
+
+        for name in get_gated_globals(*capability) {
+            remove_global(&globals, &name)?;
+        }
+    }
+
+    trace!("Granted capabilities: {:?}", capabilities);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_requested_capabilities_are_exposed() {
+        let lua_ctx = Lua::new();
+
+        lua_ctx
+            .load("system = 1; inject_key = 2; store_int = 3; switch_to_slot = 4")
+            .exec()
+            .unwrap();
+
+        apply(&lua_ctx, &[Capability::Input]).unwrap();
+
+        let globals = lua_ctx.globals();
+
+        assert_eq!(globals.get::<_, Option<i64>>("system").unwrap(), None);
+        assert_eq!(
+            globals.get::<_, Option<i64>>("inject_key").unwrap(),
+            Some(2)
+        );
+        assert_eq!(globals.get::<_, Option<i64>>("store_int").unwrap(), None);
+        assert_eq!(
+            globals.get::<_, Option<i64>>("switch_to_slot").unwrap(),
+            None
+        );
+
+        let os: LuaTable = globals.get("os").unwrap();
+        assert!(os
+            .get::<_, Option<LuaFunction>>("execute")
+            .unwrap()
+            .is_none());
+        assert!(os.get::<_, Option<LuaFunction>>("time").unwrap().is_some());
+        assert!(os
+            .get::<_, Option<LuaFunction>>("remove")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn native_modules_are_denied() {
+        let lua_ctx = Lua::new();
+
+        apply(&lua_ctx, &[]).unwrap();
+
+        let searchers: i64 = lua_ctx.load("return #package.searchers").eval().unwrap();
+        assert_eq!(searchers, 2);
+
+        let globals = lua_ctx.globals();
+        assert_eq!(
+            globals.get::<_, Option<LuaFunction>>("dofile").unwrap(),
+            None
+        );
+
+        let package: LuaTable = globals.get("package").unwrap();
+        assert!(package
+            .get::<_, Option<LuaFunction>>("loadlib")
+            .unwrap()
+            .is_none());
+
+        // source code may still be loaded, but precompiled chunks are rejected
+        let value: i64 = lua_ctx
+            .load(r#"return load("return 42")()"#)
+            .eval()
+            .unwrap();
+        assert_eq!(value, 42);
+
+        let result: Option<LuaFunction> = lua_ctx
+            .load(r#"return load(string.dump(function() end))"#)
+            .eval()
+            .unwrap();
+        assert!(result.is_none());
+    }
+}
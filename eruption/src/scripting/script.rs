@@ -30,7 +30,7 @@ use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::vec::Vec;
@@ -41,7 +41,8 @@ use crate::hwdevices::MouseHidEvent;
 use crate::hwdevices::RGBA;
 use crate::plugin_manager;
 use crate::profiles::Profile;
-use crate::scripting::manifest::{ConfigParam, Manifest};
+use crate::scripting::manifest::{is_duration_in_range, ConfigParam, Manifest};
+use crate::scripting::{budget, compositing, sandbox};
 
 use crate::ACTIVE_SCRIPTS;
 
@@ -71,6 +72,9 @@ pub enum Message {
     // Abort,
     Unload,
 
+    /// Reload the script from disk, the current version keeps running if the new one fails to load
+    Reload,
+
     /// blend LOCAL_LED_MAP with LED_MAP ("realize" the color map)
     RealizeColorMap,
 
@@ -87,7 +91,7 @@ lazy_static! {
         g: 0x00,
         b: 0x00,
         a: 0x00,
-    }; constants::CANVAS_SIZE]));
+    }; crate::CANVAS_SIZE.load(Ordering::SeqCst)]));
 
     /// The last successfully rendered canvas
     pub static ref LAST_RENDERED_LED_MAP: Arc<RwLock<Vec<RGBA>>> = Arc::new(RwLock::new(vec![RGBA {
@@ -95,7 +99,7 @@ lazy_static! {
         g: 0x00,
         b: 0x00,
         a: 0x00,
-    }; constants::CANVAS_SIZE]));
+    }; crate::CANVAS_SIZE.load(Ordering::SeqCst)]));
 
     /// Frame generation counter, used to detect if we need to submit the LED_MAP to the hardware
     pub static ref FRAME_GENERATION_COUNTER: AtomicUsize = AtomicUsize::new(0);
@@ -108,13 +112,16 @@ thread_local! {
         g: 0x00,
         b: 0x00,
         a: 0x00,
-    }; constants::CANVAS_SIZE]);
+    }; crate::CANVAS_SIZE.load(Ordering::SeqCst)]);
 
     /// True, if LED color map was modified at least once in this thread
     pub static LOCAL_LED_MAP_MODIFIED: RefCell<bool> = RefCell::new(false);
 
     /// Vec of allocated gradient objects
     pub static ALLOCATED_GRADIENTS: RefCell<HashMap<usize, colorgrad::Gradient>> = RefCell::new(HashMap::new());
+
+    /// The most recent Lua error (including the traceback) that occurred in this thread
+    pub static LAST_ERROR: RefCell<Option<String>> = RefCell::new(None);
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -125,6 +132,9 @@ pub enum ScriptingError {
     #[error("Invalid or inaccessible manifest file")]
     InaccessibleManifest {},
 
+    #[error("Could not load script")]
+    LoadError {},
+
     #[error("Invalid value")]
     ValueError {},
 }
@@ -140,6 +150,50 @@ impl fmt::Display for UnknownError {
     }
 }
 
+/// Log a Lua error that occurred in the script `file`, and remember it,
+/// so that it may be reported by the script supervisor
+fn report_lua_error(file: &Path, e: &mlua::Error) {
+    error!(
+        "Lua error in file {}: {}\n\t{:?}",
+        file.to_string_lossy(),
+        e,
+        e.source().unwrap_or(&UnknownError {})
+    );
+
+    LAST_ERROR.with(|f| *f.borrow_mut() = Some(format!("{}", e)));
+}
+
+/// Returns the most recent Lua error that occurred in the current thread
+pub fn take_last_error() -> Option<String> {
+    LAST_ERROR.with(|f| f.borrow_mut().take())
+}
+
+/// Signal completion of the upcall that `msg` requested, without actually
+/// running it. Used while no Lua VM is available to process the message
+pub fn acknowledge_message(msg: &Message) {
+    let condition = match msg {
+        Message::Quit(_) => &*crate::UPCALL_COMPLETED_ON_QUIT,
+        Message::KeyDown(_) => &*crate::UPCALL_COMPLETED_ON_KEY_DOWN,
+        Message::KeyUp(_) => &*crate::UPCALL_COMPLETED_ON_KEY_UP,
+        Message::KeyboardHidEvent(_) => &*crate::UPCALL_COMPLETED_ON_KEYBOARD_HID_EVENT,
+        Message::MouseHidEvent(_) => &*crate::UPCALL_COMPLETED_ON_MOUSE_HID_EVENT,
+        Message::MouseButtonDown(_) => &*crate::UPCALL_COMPLETED_ON_MOUSE_BUTTON_DOWN,
+        Message::MouseButtonUp(_) => &*crate::UPCALL_COMPLETED_ON_MOUSE_BUTTON_UP,
+        Message::MouseMove(..) => &*crate::UPCALL_COMPLETED_ON_MOUSE_MOVE,
+        Message::MouseWheelEvent(_) => &*crate::UPCALL_COMPLETED_ON_MOUSE_EVENT,
+        Message::RealizeColorMap => &*crate::COLOR_MAPS_READY_CONDITION,
+
+        Message::Tick(_) | Message::Unload | Message::Reload | Message::SetParameter { .. } => {
+            return
+        }
+    };
+
+    let mut val = condition.0.lock();
+    *val = val.saturating_sub(1);
+
+    condition.1.notify_all();
+}
+
 /// These functions are intended to be used from within Lua scripts
 mod callbacks {
     use byteorder::{ByteOrder, LittleEndian};
@@ -155,6 +209,7 @@ mod callbacks {
     use super::{LED_MAP, LOCAL_LED_MAP, LOCAL_LED_MAP_MODIFIED};
 
     use crate::plugins::macros;
+    use crate::scripting::budget;
     use crate::{constants, hwdevices::RGBA};
 
     pub type Result<T> = std::result::Result<T, eyre::Error>;
@@ -251,7 +306,8 @@ mod callbacks {
     /// Delays the execution of the lua script by `millis` milliseconds.
     pub(crate) fn delay(millis: u64) {
         // TODO: This will totally block the Lua VM, so not very useful currently.
-        thread::sleep(Duration::from_millis(millis));
+        // Macros rely on delays, so the time spent sleeping is exempt from the time budget
+        budget::exempt(|| thread::sleep(Duration::from_millis(millis)));
     }
 
     /// Returns the target framerate
@@ -261,6 +317,11 @@ mod callbacks {
 
     /// Returns the Lua support scripts for all connected devices
     pub(crate) fn get_support_script_files() -> Vec<String> {
+        // the test harness emulates devices that are not connected
+        if let Some(devices) = crate::scripting::harness::EMULATED_DEVICES.read().as_ref() {
+            return devices.clone();
+        }
+
         let mut result = Vec::new();
 
         for device in crate::KEYBOARD_DEVICES.read().iter() {
@@ -280,17 +341,17 @@ mod callbacks {
 
     /// Returns the number of "pixels" on the canvas
     pub(crate) fn get_canvas_size() -> usize {
-        constants::CANVAS_SIZE
+        crate::CANVAS_SIZE.load(Ordering::SeqCst)
     }
 
     /// Returns the height of the canvas
     pub(crate) fn get_canvas_height() -> usize {
-        constants::CANVAS_HEIGHT
+        crate::CANVAS_HEIGHT.load(Ordering::SeqCst)
     }
 
     /// Returns the width of the canvas
     pub(crate) fn get_canvas_width() -> usize {
-        constants::CANVAS_WIDTH
+        crate::CANVAS_WIDTH.load(Ordering::SeqCst)
     }
 
     /// Inject a key on the eruption virtual keyboard.
@@ -737,7 +798,7 @@ mod callbacks {
             })
             .collect::<Vec<u32>>();
 
-        assert!(result.len() == constants::CANVAS_SIZE);
+        assert!(result.len() == get_canvas_size());
 
         result
     }
@@ -756,12 +817,15 @@ mod callbacks {
         //     )
         // );
 
-        let mut led_map = [RGBA {
-            r: 0,
-            g: 0,
-            b: 0,
-            a: 0,
-        }; constants::CANVAS_SIZE];
+        let mut led_map = vec![
+            RGBA {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            };
+            get_canvas_size()
+        ];
 
         let mut i = 0;
         loop {
@@ -807,6 +871,81 @@ pub enum RunScriptResult {
     //ReExecuteOtherScript(PathBuf),
 }
 
+/// Initializes a Lua VM and loads the script `file` into it, this includes
+/// the execution of the `on_startup()` event handler
+pub fn load_script(file: &Path, profile: &Option<Profile>) -> Result<Lua> {
+    load_script_with(file, profile, |_| Ok(()))
+}
+
+/// Like `load_script`, but calls `setup` on the fully initialized Lua VM, right before
+/// the script is executed. Used by the test harness to install its mocks
+pub fn load_script_with<F>(file: &Path, profile: &Option<Profile>, setup: F) -> Result<Lua>
+where
+    F: FnOnce(&Lua) -> mlua::Result<()>,
+{
+    let script = fs::read_to_string(file).map_err(|_e| ScriptingError::OpenError {})?;
+
+    // the `io` and `debug` libraries are not loaded, since they would allow a script to
+    // escape the sandbox. Native modules may be loaded, if the manifest requests it
+    let libs = mlua::StdLib::COROUTINE
+        | mlua::StdLib::TABLE
+        | mlua::StdLib::OS
+        | mlua::StdLib::STRING
+        | mlua::StdLib::UTF8
+        | mlua::StdLib::MATH
+        | mlua::StdLib::PACKAGE;
+
+    let lua_ctx = unsafe { Lua::unsafe_new_with(libs, mlua::LuaOptions::default()) };
+
+    let manifest = Manifest::from(file).map_err(|error| {
+        error!(
+            "Could not parse manifest file for script {}: {}",
+            file.display(),
+            error
+        );
+
+        ScriptingError::InaccessibleManifest {}
+    })?;
+
+    budget::reset();
+
+    budget::install_hook(&lua_ctx).map_err(|_e| ScriptingError::LoadError {})?;
+
+    register_support_globals(&lua_ctx).map_err(|_e| ScriptingError::LoadError {})?;
+    register_support_funcs(&lua_ctx).map_err(|_e| ScriptingError::LoadError {})?;
+
+    // only expose the privileged functions that have been requested by the manifest
+    let capabilities = manifest.capabilities.clone().unwrap_or_default();
+
+    sandbox::apply(&lua_ctx, &capabilities).map_err(|_e| ScriptingError::LoadError {})?;
+
+    register_script_config(&lua_ctx, &manifest, profile)
+        .map_err(|_e| ScriptingError::LoadError {})?;
+
+    setup(&lua_ctx).map_err(|_e| ScriptingError::LoadError {})?;
+
+    // start execution of the Lua script
+    budget::limit(|| lua_ctx.load(&script).eval::<()>()).map_err(|e| {
+        report_lua_error(file, &e);
+        ScriptingError::LoadError {}
+    })?;
+
+    // call startup event handler, if present
+    if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_startup") {
+        budget::limit(|| handler.call::<_, ()>(())).map_err(|e| {
+            report_lua_error(file, &e);
+            ScriptingError::LoadError {}
+        })?;
+    }
+
+    // replace the manifest of a previously loaded version of the script
+    let mut active_scripts = ACTIVE_SCRIPTS.lock();
+    active_scripts.retain(|m| m.script_file != manifest.script_file);
+    active_scripts.push(manifest);
+
+    Ok(lua_ctx)
+}
+
 /// Loads and runs a lua script.
 /// Initializes a lua environment, loads the script and executes it
 pub fn run_script(
@@ -814,558 +953,453 @@ pub fn run_script(
     profile: Option<Profile>,
     rx: &Receiver<Message>,
 ) -> Result<RunScriptResult> {
-    match fs::read_to_string(file.clone()) {
-        Ok(script) => {
-            let lua_ctx =
-                unsafe { Lua::unsafe_new_with(mlua::StdLib::ALL, mlua::LuaOptions::default()) };
-
-            let manifest = Manifest::from(&file);
-            if let Err(error) = manifest {
-                error!(
-                    "Could not parse manifest file for script {}: {}",
-                    file.display(),
-                    error
-                );
+    let mut lua_ctx = match load_script(&file, &profile) {
+        Ok(lua_ctx) => lua_ctx,
 
-                return Err(ScriptingError::InaccessibleManifest {}.into());
-            } else {
-                ACTIVE_SCRIPTS
-                    .lock()
-                    .push(manifest.as_ref().unwrap().clone());
+        Err(e) => {
+            return match e.downcast_ref::<ScriptingError>() {
+                Some(ScriptingError::LoadError {}) => Ok(RunScriptResult::TerminatedWithErrors),
+                _ => Err(e),
             }
+        }
+    };
 
-            let mut errors_present = false;
+    // compositing settings of this script, as specified by the profile
+    let layer = profile
+        .as_ref()
+        .map(|profile| profile.get_layer(&file))
+        .unwrap_or_default();
+
+    // reduce CPU load by caching the event handler status
+    let mut has_tick_handler = true;
+    let mut has_mouse_move_handler = true;
+
+    loop {
+        if let Ok(msg) = rx.recv() {
+            match msg {
+                Message::Quit(param) => {
+                    let mut errors_present = false;
+
+                    if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_quit") {
+                        budget::limit(|| handler.call::<_, ()>(param)).unwrap_or_else(|e| {
+                            report_lua_error(&file, &e);
+                            errors_present = true;
+                        });
+                    }
 
-            if register_support_globals(&lua_ctx).is_err() {
-                return Ok(RunScriptResult::TerminatedWithErrors);
-            }
+                    let mut val = crate::UPCALL_COMPLETED_ON_QUIT.0.lock();
+                    *val = val.saturating_sub(1);
 
-            if register_support_funcs(&lua_ctx).is_err() {
-                return Ok(RunScriptResult::TerminatedWithErrors);
-            }
+                    crate::UPCALL_COMPLETED_ON_QUIT.1.notify_all();
 
-            if register_script_config(&lua_ctx, &manifest.unwrap(), &profile).is_err() {
-                return Ok(RunScriptResult::TerminatedWithErrors);
-            }
+                    if errors_present {
+                        return Ok(RunScriptResult::TerminatedWithErrors);
+                    }
+                }
 
-            // start execution of the Lua script
-            lua_ctx.load(&script).eval::<()>().unwrap_or_else(|e| {
-                error!(
-                    "Lua error in file {}: {}\n\t{:?}",
-                    file.to_string_lossy(),
-                    e,
-                    e.source().unwrap_or(&UnknownError {})
-                );
-                errors_present = true;
-            });
+                Message::Tick(param) => {
+                    // skip ticks while the script is throttled
+                    if has_tick_handler && !budget::throttle_tick(&file) {
+                        let mut errors_present = false;
 
-            if errors_present {
-                return Ok(RunScriptResult::TerminatedWithErrors);
-            }
+                        if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_tick") {
+                            budget::upcall(&file, || handler.call::<_, ()>(param)).unwrap_or_else(
+                                |e| {
+                                    report_lua_error(&file, &e);
+                                    errors_present = true;
+                                },
+                            )
+                        } else {
+                            has_tick_handler = false;
+                        }
 
-            // call startup event handler, if present
-            if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_startup") {
-                handler.call::<_, ()>(()).unwrap_or_else(|e| {
-                    error!(
-                        "Lua error in file {}: {}\n\t{:?}",
-                        file.to_string_lossy(),
-                        e,
-                        e.source().unwrap_or(&UnknownError {})
-                    );
-                    errors_present = true;
-                });
-            }
+                        if errors_present {
+                            return Ok(RunScriptResult::TerminatedWithErrors);
+                        }
+                    }
+                }
 
-            if errors_present {
-                return Ok(RunScriptResult::TerminatedWithErrors);
-            }
+                Message::RealizeColorMap => {
+                    if LOCAL_LED_MAP_MODIFIED.with(|f| *f.borrow()) {
+                        LOCAL_LED_MAP.with(|foreground| {
+                            let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
+
+                            compositing::blend(
+                                &mut LED_MAP.write(),
+                                &foreground.borrow(),
+                                &layer,
+                                brightness as i32,
+                            );
+                        });
+                    }
 
-            // reduce CPU load by caching the event handler status
-            let mut has_tick_handler = true;
-            let mut has_mouse_move_handler = true;
-
-            loop {
-                if let Ok(msg) = rx.recv() {
-                    match msg {
-                        Message::Quit(param) => {
-                            let mut errors_present = false;
-
-                            if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_quit") {
-                                handler.call::<_, ()>(param).unwrap_or_else(|e| {
-                                    error!(
-                                        "Lua error in file {}: {}\n\t{:?}",
-                                        file.to_string_lossy(),
-                                        e,
-                                        e.source().unwrap_or(&UnknownError {})
-                                    );
-                                    errors_present = true;
-                                });
-                            }
+                    // signal readiness / notify the main thread that we are done
+                    let val = { *crate::COLOR_MAPS_READY_CONDITION.0.lock() };
 
-                            let mut val = crate::UPCALL_COMPLETED_ON_QUIT.0.lock();
-                            *val = val.saturating_sub(1);
+                    let val = val.checked_sub(1).unwrap_or_else(|| {
+                        warn!("Incorrect state in locking code detected");
+                        0
+                    });
 
-                            crate::UPCALL_COMPLETED_ON_QUIT.1.notify_all();
+                    *crate::COLOR_MAPS_READY_CONDITION.0.lock() = val;
 
-                            if errors_present {
-                                return Ok(RunScriptResult::TerminatedWithErrors);
-                            }
-                        }
+                    crate::COLOR_MAPS_READY_CONDITION.1.notify_one();
+                }
 
-                        Message::Tick(param) => {
-                            if has_tick_handler {
-                                let mut errors_present = false;
-
-                                if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_tick")
-                                {
-                                    handler.call::<_, ()>(param).unwrap_or_else(|e| {
-                                        error!(
-                                            "Lua error in file {}: {}\n\t{:?}",
-                                            file.to_string_lossy(),
-                                            e,
-                                            e.source().unwrap_or(&UnknownError {})
-                                        );
-                                        errors_present = true;
-                                    })
-                                } else {
-                                    has_tick_handler = false;
-                                }
+                Message::KeyDown(param) => {
+                    let mut errors_present = false;
 
-                                if errors_present {
-                                    return Ok(RunScriptResult::TerminatedWithErrors);
-                                }
-                            }
-                        }
+                    if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_key_down") {
+                        budget::upcall(&file, || handler.call::<_, ()>(param)).unwrap_or_else(
+                            |e| {
+                                report_lua_error(&file, &e);
+                                errors_present = true;
+                            },
+                        );
+                    }
 
-                        Message::RealizeColorMap => {
-                            if LOCAL_LED_MAP_MODIFIED.with(|f| *f.borrow()) {
-                                LOCAL_LED_MAP.with(|foreground| {
-                                        for (idx, background) in LED_MAP.write().iter_mut().enumerate() {
-                                            let bg = &background;
-                                            let fg = foreground.borrow()[idx];
-
-                                            let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
-
-                                            #[rustfmt::skip]
-                                            let color = RGBA {
-                                                r: ((((fg.a as f64) * fg.r as f64 + (255 - fg.a) as f64 * bg.r as f64).abs() * brightness as f64 / 100.0) as u32 >> 8) as u8,
-                                                g: ((((fg.a as f64) * fg.g as f64 + (255 - fg.a) as f64 * bg.g as f64).abs() * brightness as f64 / 100.0) as u32 >> 8) as u8,
-                                                b: ((((fg.a as f64) * fg.b as f64 + (255 - fg.a) as f64 * bg.b as f64).abs() * brightness as f64 / 100.0) as u32 >> 8) as u8,
-                                                a: fg.a as u8,
-                                            };
-
-                                            *background = color;
-                                        }
-                                    });
-                            }
+                    let mut val = crate::UPCALL_COMPLETED_ON_KEY_DOWN.0.lock();
+                    *val = val.saturating_sub(1);
 
-                            // signal readiness / notify the main thread that we are done
-                            let val = { *crate::COLOR_MAPS_READY_CONDITION.0.lock() };
+                    crate::UPCALL_COMPLETED_ON_KEY_DOWN.1.notify_all();
 
-                            let val = val.checked_sub(1).unwrap_or_else(|| {
-                                warn!("Incorrect state in locking code detected");
-                                0
-                            });
+                    if errors_present {
+                        return Ok(RunScriptResult::TerminatedWithErrors);
+                    }
+                }
 
-                            *crate::COLOR_MAPS_READY_CONDITION.0.lock() = val;
+                Message::KeyUp(param) => {
+                    let mut errors_present = false;
 
-                            crate::COLOR_MAPS_READY_CONDITION.1.notify_one();
-                        }
+                    if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_key_up") {
+                        budget::upcall(&file, || handler.call::<_, ()>(param)).unwrap_or_else(
+                            |e| {
+                                report_lua_error(&file, &e);
+                                errors_present = true;
+                            },
+                        );
+                    }
 
-                        Message::KeyDown(param) => {
-                            let mut errors_present = false;
-
-                            if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_key_down")
-                            {
-                                handler.call::<_, ()>(param).unwrap_or_else(|e| {
-                                    error!(
-                                        "Lua error in file {}: {}\n\t{:?}",
-                                        file.to_string_lossy(),
-                                        e,
-                                        e.source().unwrap_or(&UnknownError {})
-                                    );
-                                    errors_present = true;
-                                });
-                            }
+                    let mut val = crate::UPCALL_COMPLETED_ON_KEY_UP.0.lock();
+                    *val = val.saturating_sub(1);
 
-                            let mut val = crate::UPCALL_COMPLETED_ON_KEY_DOWN.0.lock();
-                            *val = val.saturating_sub(1);
+                    crate::UPCALL_COMPLETED_ON_KEY_UP.1.notify_all();
 
-                            crate::UPCALL_COMPLETED_ON_KEY_DOWN.1.notify_all();
+                    if errors_present {
+                        return Ok(RunScriptResult::TerminatedWithErrors);
+                    }
+                }
 
-                            if errors_present {
-                                return Ok(RunScriptResult::TerminatedWithErrors);
+                Message::KeyboardHidEvent(param) => {
+                    let mut errors_present = false;
+
+                    if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_hid_event") {
+                        let arg1: u8;
+                        let event_type: u32 = match param {
+                            KeyboardHidEvent::KeyUp { code } => {
+                                arg1 = crate::KEYBOARD_DEVICES.read()[0]
+                                    .read()
+                                    .hid_event_code_to_report(&code);
+                                1
                             }
-                        }
 
-                        Message::KeyUp(param) => {
-                            let mut errors_present = false;
-
-                            if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_key_up") {
-                                handler.call::<_, ()>(param).unwrap_or_else(|e| {
-                                    error!(
-                                        "Lua error in file {}: {}\n\t{:?}",
-                                        file.to_string_lossy(),
-                                        e,
-                                        e.source().unwrap_or(&UnknownError {})
-                                    );
-                                    errors_present = true;
-                                });
+                            KeyboardHidEvent::KeyDown { code } => {
+                                arg1 = crate::KEYBOARD_DEVICES.read()[0]
+                                    .read()
+                                    .hid_event_code_to_report(&code);
+                                2
                             }
 
-                            let mut val = crate::UPCALL_COMPLETED_ON_KEY_UP.0.lock();
-                            *val = val.saturating_sub(1);
-
-                            crate::UPCALL_COMPLETED_ON_KEY_UP.1.notify_all();
-
-                            if errors_present {
-                                return Ok(RunScriptResult::TerminatedWithErrors);
+                            KeyboardHidEvent::MuteDown => {
+                                arg1 = 1;
+                                3
                             }
-                        }
 
-                        Message::KeyboardHidEvent(param) => {
-                            let mut errors_present = false;
-
-                            if let Ok(handler) =
-                                lua_ctx.globals().get::<_, Function>("on_hid_event")
-                            {
-                                let arg1: u8;
-                                let event_type: u32 = match param {
-                                    KeyboardHidEvent::KeyUp { code } => {
-                                        arg1 = crate::KEYBOARD_DEVICES.read()[0]
-                                            .read()
-                                            .hid_event_code_to_report(&code);
-                                        1
-                                    }
-
-                                    KeyboardHidEvent::KeyDown { code } => {
-                                        arg1 = crate::KEYBOARD_DEVICES.read()[0]
-                                            .read()
-                                            .hid_event_code_to_report(&code);
-                                        2
-                                    }
-
-                                    KeyboardHidEvent::MuteDown => {
-                                        arg1 = 1;
-                                        3
-                                    }
-
-                                    KeyboardHidEvent::MuteUp => {
-                                        arg1 = 0;
-                                        3
-                                    }
-
-                                    KeyboardHidEvent::VolumeDown => {
-                                        arg1 = 1;
-                                        4
-                                    }
-
-                                    KeyboardHidEvent::VolumeUp => {
-                                        arg1 = 0;
-                                        4
-                                    }
-
-                                    KeyboardHidEvent::BrightnessDown => {
-                                        arg1 = 1;
-                                        5
-                                    }
-
-                                    KeyboardHidEvent::BrightnessUp => {
-                                        arg1 = 0;
-                                        5
-                                    }
-
-                                    KeyboardHidEvent::SetBrightness(val) => {
-                                        arg1 = val;
-                                        6
-                                    }
-
-                                    KeyboardHidEvent::NextSlot => {
-                                        arg1 = 1;
-                                        7
-                                    }
-
-                                    KeyboardHidEvent::PreviousSlot => {
-                                        arg1 = 0;
-                                        7
-                                    }
-
-                                    _ => {
-                                        arg1 = 0;
-                                        0
-                                    }
-                                };
-
-                                handler
-                                    .call::<_, ()>((event_type, arg1))
-                                    .unwrap_or_else(|e| {
-                                        error!(
-                                            "Lua error in file {}: {}\n\t{:?}",
-                                            file.to_string_lossy(),
-                                            e,
-                                            e.source().unwrap_or(&UnknownError {})
-                                        );
-                                        errors_present = true;
-                                    });
+                            KeyboardHidEvent::MuteUp => {
+                                arg1 = 0;
+                                3
                             }
 
-                            let mut val = crate::UPCALL_COMPLETED_ON_KEYBOARD_HID_EVENT.0.lock();
-                            *val = val.saturating_sub(1);
+                            KeyboardHidEvent::VolumeDown => {
+                                arg1 = 1;
+                                4
+                            }
 
-                            crate::UPCALL_COMPLETED_ON_KEYBOARD_HID_EVENT.1.notify_all();
+                            KeyboardHidEvent::VolumeUp => {
+                                arg1 = 0;
+                                4
+                            }
 
-                            if errors_present {
-                                return Ok(RunScriptResult::TerminatedWithErrors);
+                            KeyboardHidEvent::BrightnessDown => {
+                                arg1 = 1;
+                                5
                             }
-                        }
 
-                        Message::MouseHidEvent(param) => {
-                            let mut errors_present = false;
-
-                            if let Ok(handler) =
-                                lua_ctx.globals().get::<_, Function>("on_mouse_hid_event")
-                            {
-                                let arg1: u8;
-                                let event_type: u32 = match param {
-                                    MouseHidEvent::DpiChange(dpi_slot) => {
-                                        arg1 = dpi_slot;
-                                        1
-                                    }
-
-                                    MouseHidEvent::ButtonDown(index) => {
-                                        arg1 = index + 1;
-                                        2
-                                    }
-
-                                    MouseHidEvent::ButtonUp(index) => {
-                                        arg1 = index + 1;
-                                        3
-                                    }
-
-                                    _ => {
-                                        arg1 = 0;
-                                        0
-                                    }
-                                };
-
-                                handler
-                                    .call::<_, ()>((event_type, arg1))
-                                    .unwrap_or_else(|e| {
-                                        error!(
-                                            "Lua error in file {}: {}\n\t{:?}",
-                                            file.to_string_lossy(),
-                                            e,
-                                            e.source().unwrap_or(&UnknownError {})
-                                        );
-                                        errors_present = true;
-                                    });
+                            KeyboardHidEvent::BrightnessUp => {
+                                arg1 = 0;
+                                5
                             }
 
-                            let mut val = crate::UPCALL_COMPLETED_ON_MOUSE_HID_EVENT.0.lock();
-                            *val = val.saturating_sub(1);
+                            KeyboardHidEvent::SetBrightness(val) => {
+                                arg1 = val;
+                                6
+                            }
 
-                            crate::UPCALL_COMPLETED_ON_MOUSE_HID_EVENT.1.notify_all();
+                            KeyboardHidEvent::NextSlot => {
+                                arg1 = 1;
+                                7
+                            }
 
-                            if errors_present {
-                                return Ok(RunScriptResult::TerminatedWithErrors);
+                            KeyboardHidEvent::PreviousSlot => {
+                                arg1 = 0;
+                                7
                             }
-                        }
 
-                        Message::MouseButtonDown(param) => {
-                            let mut errors_present = false;
-
-                            if let Ok(handler) =
-                                lua_ctx.globals().get::<_, Function>("on_mouse_button_down")
-                            {
-                                handler.call::<_, ()>(param).unwrap_or_else(|e| {
-                                    error!(
-                                        "Lua error in file {}: {}\n\t{:?}",
-                                        file.to_string_lossy(),
-                                        e,
-                                        e.source().unwrap_or(&UnknownError {})
-                                    );
-                                    errors_present = true;
-                                });
+                            _ => {
+                                arg1 = 0;
+                                0
                             }
+                        };
 
-                            let mut val = crate::UPCALL_COMPLETED_ON_MOUSE_BUTTON_DOWN.0.lock();
-                            *val = val.saturating_sub(1);
+                        budget::upcall(&file, || handler.call::<_, ()>((event_type, arg1)))
+                            .unwrap_or_else(|e| {
+                                report_lua_error(&file, &e);
+                                errors_present = true;
+                            });
+                    }
 
-                            crate::UPCALL_COMPLETED_ON_MOUSE_BUTTON_DOWN.1.notify_all();
+                    let mut val = crate::UPCALL_COMPLETED_ON_KEYBOARD_HID_EVENT.0.lock();
+                    *val = val.saturating_sub(1);
 
-                            if errors_present {
-                                return Ok(RunScriptResult::TerminatedWithErrors);
-                            }
-                        }
+                    crate::UPCALL_COMPLETED_ON_KEYBOARD_HID_EVENT.1.notify_all();
 
-                        Message::MouseButtonUp(param) => {
-                            let mut errors_present = false;
-
-                            if let Ok(handler) =
-                                lua_ctx.globals().get::<_, Function>("on_mouse_button_up")
-                            {
-                                handler.call::<_, ()>(param).unwrap_or_else(|e| {
-                                    error!(
-                                        "Lua error in file {}: {}\n\t{:?}",
-                                        file.to_string_lossy(),
-                                        e,
-                                        e.source().unwrap_or(&UnknownError {})
-                                    );
-                                    errors_present = true;
-                                });
-                            }
+                    if errors_present {
+                        return Ok(RunScriptResult::TerminatedWithErrors);
+                    }
+                }
 
-                            let mut val = crate::UPCALL_COMPLETED_ON_MOUSE_BUTTON_UP.0.lock();
-                            *val = val.saturating_sub(1);
+                Message::MouseHidEvent(param) => {
+                    let mut errors_present = false;
 
-                            crate::UPCALL_COMPLETED_ON_MOUSE_BUTTON_UP.1.notify_all();
+                    if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_mouse_hid_event")
+                    {
+                        let arg1: u8;
+                        let event_type: u32 = match param {
+                            MouseHidEvent::DpiChange(dpi_slot) => {
+                                arg1 = dpi_slot;
+                                1
+                            }
 
-                            if errors_present {
-                                return Ok(RunScriptResult::TerminatedWithErrors);
+                            MouseHidEvent::ButtonDown(index) => {
+                                arg1 = index + 1;
+                                2
                             }
-                        }
 
-                        Message::MouseMove(rel_x, rel_y, rel_z) => {
-                            if has_mouse_move_handler {
-                                let mut errors_present = false;
-
-                                if let Ok(handler) =
-                                    lua_ctx.globals().get::<_, Function>("on_mouse_move")
-                                {
-                                    handler.call::<_, ()>((rel_x, rel_y, rel_z)).unwrap_or_else(
-                                        |e| {
-                                            error!(
-                                                "Lua error in file {}: {}\n\t{:?}",
-                                                file.to_string_lossy(),
-                                                e,
-                                                e.source().unwrap_or(&UnknownError {})
-                                            );
-                                            errors_present = true;
-                                        },
-                                    );
-                                } else {
-                                    has_mouse_move_handler = false;
-                                }
+                            MouseHidEvent::ButtonUp(index) => {
+                                arg1 = index + 1;
+                                3
                             }
 
-                            let mut val = crate::UPCALL_COMPLETED_ON_MOUSE_MOVE.0.lock();
-                            *val = val.saturating_sub(1);
+                            _ => {
+                                arg1 = 0;
+                                0
+                            }
+                        };
 
-                            crate::UPCALL_COMPLETED_ON_MOUSE_MOVE.1.notify_all();
+                        budget::upcall(&file, || handler.call::<_, ()>((event_type, arg1)))
+                            .unwrap_or_else(|e| {
+                                report_lua_error(&file, &e);
+                                errors_present = true;
+                            });
+                    }
 
-                            if errors_present {
-                                return Ok(RunScriptResult::TerminatedWithErrors);
-                            }
-                        }
+                    let mut val = crate::UPCALL_COMPLETED_ON_MOUSE_HID_EVENT.0.lock();
+                    *val = val.saturating_sub(1);
+
+                    crate::UPCALL_COMPLETED_ON_MOUSE_HID_EVENT.1.notify_all();
 
-                        Message::MouseWheelEvent(param) => {
-                            let mut errors_present = false;
-
-                            if let Ok(handler) =
-                                lua_ctx.globals().get::<_, Function>("on_mouse_wheel")
-                            {
-                                handler.call::<_, ()>(param).unwrap_or_else(|e| {
-                                    error!(
-                                        "Lua error in file {}: {}\n\t{:?}",
-                                        file.display(),
-                                        e,
-                                        e.source().unwrap_or(&UnknownError {})
-                                    );
+                    if errors_present {
+                        return Ok(RunScriptResult::TerminatedWithErrors);
+                    }
+                }
+
+                Message::MouseButtonDown(param) => {
+                    let mut errors_present = false;
+
+                    if let Ok(handler) =
+                        lua_ctx.globals().get::<_, Function>("on_mouse_button_down")
+                    {
+                        budget::upcall(&file, || handler.call::<_, ()>(param)).unwrap_or_else(
+                            |e| {
+                                report_lua_error(&file, &e);
+                                errors_present = true;
+                            },
+                        );
+                    }
+
+                    let mut val = crate::UPCALL_COMPLETED_ON_MOUSE_BUTTON_DOWN.0.lock();
+                    *val = val.saturating_sub(1);
+
+                    crate::UPCALL_COMPLETED_ON_MOUSE_BUTTON_DOWN.1.notify_all();
+
+                    if errors_present {
+                        return Ok(RunScriptResult::TerminatedWithErrors);
+                    }
+                }
+
+                Message::MouseButtonUp(param) => {
+                    let mut errors_present = false;
+
+                    if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_mouse_button_up")
+                    {
+                        budget::upcall(&file, || handler.call::<_, ()>(param)).unwrap_or_else(
+                            |e| {
+                                report_lua_error(&file, &e);
+                                errors_present = true;
+                            },
+                        );
+                    }
+
+                    let mut val = crate::UPCALL_COMPLETED_ON_MOUSE_BUTTON_UP.0.lock();
+                    *val = val.saturating_sub(1);
+
+                    crate::UPCALL_COMPLETED_ON_MOUSE_BUTTON_UP.1.notify_all();
+
+                    if errors_present {
+                        return Ok(RunScriptResult::TerminatedWithErrors);
+                    }
+                }
+
+                Message::MouseMove(rel_x, rel_y, rel_z) => {
+                    let mut errors_present = false;
+
+                    if has_mouse_move_handler {
+                        if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_mouse_move") {
+                            budget::upcall(&file, || handler.call::<_, ()>((rel_x, rel_y, rel_z)))
+                                .unwrap_or_else(|e| {
+                                    report_lua_error(&file, &e);
                                     errors_present = true;
                                 });
-                            }
+                        } else {
+                            has_mouse_move_handler = false;
+                        }
+                    }
 
-                            let mut val = crate::UPCALL_COMPLETED_ON_MOUSE_EVENT.0.lock();
-                            *val = val.saturating_sub(1);
+                    let mut val = crate::UPCALL_COMPLETED_ON_MOUSE_MOVE.0.lock();
+                    *val = val.saturating_sub(1);
 
-                            crate::UPCALL_COMPLETED_ON_MOUSE_EVENT.1.notify_all();
+                    crate::UPCALL_COMPLETED_ON_MOUSE_MOVE.1.notify_all();
 
-                            if errors_present {
-                                return Ok(RunScriptResult::TerminatedWithErrors);
-                            }
-                        }
+                    if errors_present {
+                        return Ok(RunScriptResult::TerminatedWithErrors);
+                    }
+                }
 
-                        //Message::LoadScript(script_path) => {
-                        //return Ok(RunScriptResult::ReExecuteOtherScript(script_path))
-                        //}
-
-                        // Message::Abort => {
-                        //     error!("Lua script {} terminated with errors", file.display());
-                        //     return Ok(RunScriptResult::TerminatedWithErrors);
-                        // }
-                        Message::Unload => {
-                            let mut errors_present = false;
-
-                            if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_quit") {
-                                handler.call::<_, ()>(()).unwrap_or_else(|e| {
-                                    error!(
-                                        "Lua error in file {}: {}\n\t{:?}",
-                                        file.to_string_lossy(),
-                                        e,
-                                        e.source().unwrap_or(&UnknownError {})
-                                    );
-                                    errors_present = true;
-                                })
-                            }
+                Message::MouseWheelEvent(param) => {
+                    let mut errors_present = false;
 
-                            if errors_present {
-                                error!("Lua script {} terminated with errors", file.display());
-                                return Ok(RunScriptResult::TerminatedWithErrors);
-                            } else {
-                                debug!("Lua script {} terminated gracefully", file.display());
-                                return Ok(RunScriptResult::TerminatedGracefully);
-                            }
-                        }
+                    if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_mouse_wheel") {
+                        budget::upcall(&file, || handler.call::<_, ()>(param)).unwrap_or_else(
+                            |e| {
+                                report_lua_error(&file, &e);
+                                errors_present = true;
+                            },
+                        );
+                    }
 
-                        Message::SetParameter { param_name, value } => {
-                            let mut errors_present = false;
-
-                            if let Ok(handler) =
-                                lua_ctx.globals().get::<_, Function>("on_apply_parameter")
-                            {
-                                // the script declared an "on_apply_parameter" function
-                                handler
-                                    .call::<_, ()>((param_name, value))
-                                    .unwrap_or_else(|e| {
-                                        error!(
-                                            "Lua error in file {}: {}\n\t{:?}",
-                                            file.to_string_lossy(),
-                                            e,
-                                            e.source().unwrap_or(&UnknownError {})
-                                        );
-                                        errors_present = true;
-                                    })
-                            } else {
-                                // no special handling available in the script, so we just inject the parameter
-                                lua_ctx
-                                    .load(&format!("{} = {}", param_name, value))
-                                    .exec()
-                                    .unwrap_or_else(|e| {
-                                        error!(
-                                            "Lua error in file {}: {}\n\t{:?}",
-                                            file.to_string_lossy(),
-                                            e,
-                                            e.source().unwrap_or(&UnknownError {})
-                                        );
-                                        errors_present = true;
-                                    });
-                            }
+                    let mut val = crate::UPCALL_COMPLETED_ON_MOUSE_EVENT.0.lock();
+                    *val = val.saturating_sub(1);
 
-                            if errors_present {
-                                error!("Lua script {}: Could not apply parameter", file.display());
-                            } else {
-                                debug!(
-                                    "Lua script {}: Successfully applied parameter",
-                                    file.display()
-                                );
-                            }
+                    crate::UPCALL_COMPLETED_ON_MOUSE_EVENT.1.notify_all();
+
+                    if errors_present {
+                        return Ok(RunScriptResult::TerminatedWithErrors);
+                    }
+                }
+
+                //Message::LoadScript(script_path) => {
+                //return Ok(RunScriptResult::ReExecuteOtherScript(script_path))
+                //}
+
+                // Message::Abort => {
+                //     error!("Lua script {} terminated with errors", file.display());
+                //     return Ok(RunScriptResult::TerminatedWithErrors);
+                // }
+                Message::Unload => {
+                    let mut errors_present = false;
+
+                    if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_quit") {
+                        budget::limit(|| handler.call::<_, ()>(())).unwrap_or_else(|e| {
+                            report_lua_error(&file, &e);
+                            errors_present = true;
+                        })
+                    }
+
+                    if errors_present {
+                        error!("Lua script {} terminated with errors", file.display());
+                        return Ok(RunScriptResult::TerminatedWithErrors);
+                    } else {
+                        debug!("Lua script {} terminated gracefully", file.display());
+                        return Ok(RunScriptResult::TerminatedGracefully);
+                    }
+                }
+
+                Message::Reload => {
+                    // the transient store outlives the Lua VM, so the state of the script is kept
+                    match load_script(&file, &profile) {
+                        Ok(new_lua_ctx) => {
+                            lua_ctx = new_lua_ctx;
+
+                            has_tick_handler = true;
+                            has_mouse_move_handler = true;
+
+                            info!("Reloaded Lua script {}", file.display());
                         }
+
+                        Err(e) => {
+                            error!(
+                                "Could not reload Lua script {}, keeping the current version: {}",
+                                file.display(),
+                                e
+                            );
+                        }
+                    }
+                }
+
+                Message::SetParameter { param_name, value } => {
+                    let mut errors_present = false;
+
+                    if let Ok(handler) = lua_ctx.globals().get::<_, Function>("on_apply_parameter")
+                    {
+                        // the script declared an "on_apply_parameter" function
+                        budget::limit(|| handler.call::<_, ()>((param_name, value))).unwrap_or_else(
+                            |e| {
+                                report_lua_error(&file, &e);
+                                errors_present = true;
+                            },
+                        )
+                    } else {
+                        // no special handling available in the script, so we just inject the parameter
+                        lua_ctx
+                            .load(&format!("{} = {}", param_name, value))
+                            .exec()
+                            .unwrap_or_else(|e| {
+                                report_lua_error(&file, &e);
+                                errors_present = true;
+                            });
+                    }
+
+                    if errors_present {
+                        error!("Lua script {}: Could not apply parameter", file.display());
+                    } else {
+                        debug!(
+                            "Lua script {}: Successfully applied parameter",
+                            file.display()
+                        );
                     }
                 }
             }
         }
-
-        Err(_e) => Err(ScriptingError::OpenError {}.into()),
     }
 }
 
@@ -1812,6 +1846,127 @@ fn register_script_config(
                         globals.raw_set::<&str, u32>(name, *default)?;
                     }
                 }
+
+                ConfigParam::Enum {
+                    name,
+                    values,
+                    default,
+                    ..
+                } => {
+                    if let Some(profile) = profile {
+                        match profile.get_enum_value(script_name, name) {
+                            Some(val) if values.iter().any(|v| v == val) => {
+                                globals.raw_set::<&str, &str>(name, val)?;
+                            }
+
+                            Some(val) => {
+                                warn!("Invalid value '{}' for parameter '{}', using defaults from script manifest", val, name);
+
+                                globals.raw_set::<&str, &str>(name, &*default)?;
+                            }
+
+                            None => {
+                                debug!(
+                                    "Parameter is undefined, using defaults from script manifest"
+                                );
+
+                                globals.raw_set::<&str, &str>(name, &*default)?;
+                            }
+                        }
+                    } else {
+                        warn!("Active profile is undefined, using config parameters from script manifest");
+
+                        globals.raw_set::<&str, &str>(name, &*default)?;
+                    }
+                }
+
+                ConfigParam::ColorArray { name, default, .. } => {
+                    let colors = match profile {
+                        Some(profile) => {
+                            match profile.get_color_array_value(script_name, name) {
+                                Some(val) => val,
+
+                                None => {
+                                    debug!("Parameter is undefined, using defaults from script manifest");
+
+                                    &default[..]
+                                }
+                            }
+                        }
+
+                        None => {
+                            warn!("Active profile is undefined, using config parameters from script manifest");
+
+                            &default[..]
+                        }
+                    };
+
+                    globals.raw_set(
+                        name.as_str(),
+                        lua_ctx.create_sequence_from(colors.iter().copied())?,
+                    )?;
+                }
+
+                ConfigParam::KeySet { name, default, .. } => {
+                    let keys = match profile {
+                        Some(profile) => {
+                            match profile.get_keyset_value(script_name, name) {
+                                Some(val) => val,
+
+                                None => {
+                                    debug!("Parameter is undefined, using defaults from script manifest");
+
+                                    &default[..]
+                                }
+                            }
+                        }
+
+                        None => {
+                            warn!("Active profile is undefined, using config parameters from script manifest");
+
+                            &default[..]
+                        }
+                    };
+
+                    globals.raw_set(
+                        name.as_str(),
+                        lua_ctx.create_sequence_from(keys.iter().copied())?,
+                    )?;
+                }
+
+                ConfigParam::Duration {
+                    name,
+                    min,
+                    max,
+                    default,
+                    ..
+                } => {
+                    if let Some(profile) = profile {
+                        match profile.get_duration_value(script_name, name) {
+                            Some(val) if is_duration_in_range(*val, *min, *max) => {
+                                globals.raw_set::<&str, u64>(name, *val)?;
+                            }
+
+                            Some(val) => {
+                                warn!("Invalid value '{}ms' for parameter '{}', using defaults from script manifest", val, name);
+
+                                globals.raw_set::<&str, u64>(name, *default)?;
+                            }
+
+                            None => {
+                                debug!(
+                                    "Parameter is undefined, using defaults from script manifest"
+                                );
+
+                                globals.raw_set::<&str, u64>(name, *default)?;
+                            }
+                        }
+                    } else {
+                        warn!("Active profile is undefined, using config parameters from script manifest");
+
+                        globals.raw_set::<&str, u64>(name, *default)?;
+                    }
+                }
             }
         }
     }
@@ -0,0 +1,184 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use crossbeam::channel::{Receiver, RecvTimeoutError};
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::constants;
+use crate::scripting::script::{self, Message};
+
+lazy_static! {
+    /// Crash statistics of the Lua scripts of the currently active profile
+    pub static ref SCRIPT_STATUS: Arc<Mutex<HashMap<PathBuf, ScriptStatus>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+}
+
+/// Crash statistics of a single Lua script
+#[derive(Debug, Clone, Default)]
+pub struct ScriptStatus {
+    /// Number of crashes since the profile has been activated
+    pub crash_count: u32,
+
+    /// Number of crashes without a stable runtime in between, determines the backoff
+    pub consecutive_crashes: u32,
+
+    /// The most recent Lua error, including the traceback
+    pub last_error: Option<String>,
+}
+
+/// Outcome of waiting for the restart of a crashed Lua script
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BackoffResult {
+    /// The backoff delay elapsed or the script has been modified, restart the script
+    Restart,
+
+    /// The script has been unloaded in the meantime, e.g. by a switch of profiles
+    Terminate,
+}
+
+/// Record a crash of the script `script_file`, that ran for `runtime` before it failed
+/// with `error`. Returns the number of consecutive crashes of the script
+pub fn record_crash(script_file: &Path, error: String, runtime: Duration) -> u32 {
+    let mut script_status = SCRIPT_STATUS.lock();
+    let status = script_status.entry(script_file.to_path_buf()).or_default();
+
+    if runtime >= Duration::from_secs(constants::SCRIPT_STABLE_RUNTIME_SECS) {
+        status.consecutive_crashes = 0;
+    }
+
+    status.crash_count += 1;
+    status.consecutive_crashes += 1;
+    status.last_error = Some(error);
+
+    status.consecutive_crashes
+}
+
+/// Returns the crash statistics of all scripts that crashed at least once
+pub fn get_script_status() -> Vec<(PathBuf, ScriptStatus)> {
+    let mut result: Vec<(PathBuf, ScriptStatus)> = SCRIPT_STATUS
+        .lock()
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+
+    result.sort_by(|a, b| a.0.cmp(&b.0));
+
+    result
+}
+
+/// Forget the crash statistics, e.g. after a switch of profiles
+pub fn clear() {
+    SCRIPT_STATUS.lock().clear();
+}
+
+/// Forget the consecutive crashes of the script `script_file`, e.g. after it has been modified
+pub fn reset(script_file: &Path) {
+    if let Some(status) = SCRIPT_STATUS.lock().get_mut(script_file) {
+        status.consecutive_crashes = 0;
+    }
+}
+
+/// Compute the delay before a script is restarted after `consecutive_crashes` crashes
+pub fn backoff_delay(consecutive_crashes: u32) -> Duration {
+    let factor = 1_u64
+        .checked_shl(consecutive_crashes.saturating_sub(1))
+        .unwrap_or(u64::MAX);
+
+    Duration::from_millis(
+        constants::SCRIPT_RESTART_BACKOFF_MILLIS
+            .saturating_mul(factor)
+            .min(constants::MAX_SCRIPT_RESTART_BACKOFF_MILLIS),
+    )
+}
+
+/// Wait for `delay` before a crashed script may be restarted. Messages that are sent to the
+/// script in the meantime are acknowledged, so that the main loop does not stall on them
+pub fn wait_for_restart(rx: &Receiver<Message>, delay: Duration) -> BackoffResult {
+    let deadline = Instant::now() + delay;
+
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+
+        match rx.recv_timeout(timeout) {
+            Ok(Message::Unload) => return BackoffResult::Terminate,
+
+            // don't make the user wait for a fixed version of the script
+            Ok(Message::Reload) => return BackoffResult::Restart,
+
+            Ok(msg) => script::acknowledge_message(&msg),
+
+            Err(RecvTimeoutError::Timeout) => return BackoffResult::Restart,
+
+            // all senders have been dropped, the profile is no longer active
+            Err(RecvTimeoutError::Disconnected) => return BackoffResult::Terminate,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam::channel::unbounded;
+
+    #[test]
+    fn backoff_is_exponential_and_capped() {
+        assert_eq!(backoff_delay(1), Duration::from_millis(500));
+        assert_eq!(backoff_delay(2), Duration::from_millis(1000));
+        assert_eq!(backoff_delay(4), Duration::from_millis(4000));
+
+        assert_eq!(
+            backoff_delay(100),
+            Duration::from_millis(constants::MAX_SCRIPT_RESTART_BACKOFF_MILLIS)
+        );
+    }
+
+    #[test]
+    fn stable_runtime_resets_consecutive_crashes() {
+        let script_file = Path::new("/tmp/supervisor-test.lua");
+
+        assert_eq!(record_crash(script_file, "a".into(), Duration::ZERO), 1);
+        assert_eq!(record_crash(script_file, "b".into(), Duration::ZERO), 2);
+        assert_eq!(
+            record_crash(script_file, "c".into(), Duration::from_secs(3600)),
+            1
+        );
+
+        let status = SCRIPT_STATUS.lock().get(script_file).cloned().unwrap();
+        assert_eq!(status.crash_count, 3);
+        assert_eq!(status.last_error.as_deref(), Some("c"));
+    }
+
+    #[test]
+    fn unload_terminates_backoff() {
+        let (tx, rx) = unbounded();
+
+        tx.send(Message::Tick(1)).unwrap();
+        tx.send(Message::Unload).unwrap();
+
+        assert_eq!(
+            wait_for_restart(&rx, Duration::from_secs(60)),
+            BackoffResult::Terminate
+        );
+    }
+}
@@ -18,38 +18,28 @@
 require "declarations"
 require "debug"
 
--- set gradient stops
-gradient_stops = {
-    [1] = {
-        start = rgba_to_color(255, 0, 0, lerp(0, 255, opacity)),
-        dest = rgba_to_color(255, 165, 0, lerp(0, 255, opacity))
-    },
-    [2] = {
-        start = rgba_to_color(255, 165, 0, lerp(0, 255, opacity)),
-        dest = rgba_to_color(0, 255, 255, lerp(0, 255, opacity))
-    },
-    [3] = {
-        start = rgba_to_color(0, 255, 255, lerp(0, 255, opacity)),
-        dest = rgba_to_color(0, 255, 0, lerp(0, 255, opacity))
-    },
-    [4] = {
-        start = rgba_to_color(0, 255, 0, lerp(0, 255, opacity)),
-        dest = rgba_to_color(0, 0, 255, lerp(0, 255, opacity))
-    },
-    [5] = {
-        start = rgba_to_color(0, 0, 255, lerp(0, 255, opacity)),
-        dest = rgba_to_color(75, 0, 130, lerp(0, 255, opacity))
-    },
-    [6] = {
-        start = rgba_to_color(75, 0, 130, lerp(0, 255, opacity)),
-        dest = rgba_to_color(238, 130, 238, lerp(0, 255, opacity))
-    },
-    [7] = {
-        start = rgba_to_color(238, 130, 238, lerp(0, 255, opacity)),
-        dest = rgba_to_color(255, 0, 0, lerp(0, 255, opacity))
-    },
-    len = 7
-}
+-- set gradient stops, from the colors specified in the manifest or profile
+local function with_opacity(color)
+    local r, g, b, _ = color_to_rgba(color)
+    return rgba_to_color(r, g, b, lerp(0, 255, opacity))
+end
+
+-- an empty list of colors would leave us without any gradient stops
+if #gradient_colors == 0 then
+    warn("Multi gradient: No gradient colors specified, using the default colors")
+    gradient_colors = {
+        0xffff0000, 0xffffa500, 0xff00ffff, 0xff00ff00, 0xff0000ff, 0xff4b0082,
+        0xffee82ee
+    }
+end
+
+gradient_stops = { len = #gradient_colors }
+for i = 1, #gradient_colors do
+    gradient_stops[i] = {
+        start = with_opacity(gradient_colors[i]),
+        dest = with_opacity(gradient_colors[i % #gradient_colors + 1])
+    }
+end
 
 -- global state variables --
 color_map = {}
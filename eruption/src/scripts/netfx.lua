@@ -19,12 +19,50 @@ require "declarations"
 require "utilities"
 require "debug"
 
+-- protocol constants --
+local PROTOCOL_VERSION_1 = 1
+local PROTOCOL_VERSION_2 = 2
+
+-- v2 message types
+local MSG_FRAME = 0x01
+local MSG_COMMANDS = 0x02
+local MSG_STATUS = 0x03
+local MSG_CANVAS = 0x04
+local MSG_QUIT = 0x05
+
+-- error codes
+local ERROR_ILL_FORMED = 100
+local ERROR_COLOR_OUT_OF_RANGE = 110
+local ERROR_INVALID_INDEX = 120
+local ERROR_UNSUPPORTED_VERSION = 130
+local ERROR_UNKNOWN_MESSAGE = 140
+
+-- size of the header of a v2 message: type (u8) + payload length (u32)
+local HEADER_SIZE = 5
+
+-- max. accepted size of the payload of a v2 message
+local MAX_PAYLOAD_SIZE = 1024 * 1024
+
+-- max. size of a UDP datagram, larger datagrams would be truncated on receive
+local MAX_DATAGRAM_SIZE = 65507
+
+-- the reply to the STATUS command of the v1 protocol is kept unchanged, since
+-- existing clients may rely on it
+local STATUS_MESSAGE_V1 = "Eruption Network FX / Protocol version: 1.0"
+local STATUS_MESSAGE_V2 = "Eruption Network FX / Protocol version: 2.0"
+
 -- global state variables --
 color_map = {}
 server = nil
+udp_server = nil
 conn = nil
 initialized = false
 
+-- state of the current connection
+protocol_version = PROTOCOL_VERSION_1
+rx_buffer = ""
+pending_header = nil
+
 -- utility functions --
 local function split(str, pat)
     local t = {}
@@ -46,6 +84,239 @@ local function split(str, pat)
     return t
 end
 
+local function close_connection()
+    conn:close()
+    conn = nil
+
+    protocol_version = PROTOCOL_VERSION_1
+    rx_buffer = ""
+    pending_header = nil
+end
+
+-- apply a single text protocol command (ZONE:RED:GREEN:BLUE:ALPHA) to the color
+-- map, returns nil on success or an error code otherwise
+local function apply_command(data)
+    local result = split(data, ':')
+
+    -- validate request parameters
+    if #result ~= 5 then
+        error("Network FX: Request is ill-formed")
+        return ERROR_ILL_FORMED
+    end
+
+    local r, g, b, a = tonumber(result[2]), tonumber(result[3]),
+                       tonumber(result[4]), tonumber(result[5])
+
+    if r == nil or g == nil or b == nil or a == nil then
+        error("Network FX: Request is ill-formed")
+        return ERROR_ILL_FORMED
+    end
+
+    -- validate colors
+    if r < 0 or r > 255 or g < 0 or g > 255 or b < 0 or b > 255 or a < 0 or a >
+        255 then
+        error("Network FX: Color component value out of range")
+        return ERROR_COLOR_OUT_OF_RANGE
+    end
+
+    local color = rgba_to_color(r, g, b, a)
+
+    local components = split(result[1], ',')
+
+    for idx = 1, #components do
+        local spec = split(components[idx], '-')
+        if #spec < 2 then
+            if result[1] == "ALL" then
+                -- predefined zone: full canvas
+                for i = 1, canvas_size do color_map[i] = color end
+            else
+                -- set a single pixel on the canvas to a specific color
+                local num = tonumber(components[idx])
+                if n(num) > 0 and n(num) <= canvas_size then
+                    color_map[num] = color
+                else
+                    error("Network FX: Invalid index")
+                    return ERROR_INVALID_INDEX
+                end
+            end
+        else
+            -- set a range of pixels to a specific color
+            local low = tonumber(spec[1])
+            local high = tonumber(spec[2])
+
+            if n(low) > 0 and n(low) <= canvas_size and n(high) > 0 and
+                n(high) <= canvas_size then
+                for index = low, high do color_map[index] = color end
+            else
+                error("Network FX: Invalid index")
+                return ERROR_INVALID_INDEX
+            end
+        end
+    end
+
+    return nil
+end
+
+-- apply a v2 FRAME message, the payload holds RGBA quadruples for consecutive
+-- indices of the canvas, starting at the first index
+local function apply_frame(payload)
+    if #payload % 4 ~= 0 then
+        error("Network FX: Frame is ill-formed")
+        return ERROR_ILL_FORMED
+    end
+
+    local count = #payload // 4
+    if count > canvas_size then
+        error("Network FX: Frame exceeds the size of the canvas")
+        return ERROR_INVALID_INDEX
+    end
+
+    for i = 1, count do
+        local r, g, b, a = payload:byte((i - 1) * 4 + 1, i * 4)
+        color_map[i] = rgba_to_color(r, g, b, a)
+    end
+
+    return nil
+end
+
+-- apply a v2 COMMANDS message, the payload holds newline separated text
+-- protocol commands, processing stops at the first erroneous command
+local function apply_commands(payload)
+    for line in payload:gmatch("[^\r\n]+") do
+        local result = apply_command(line)
+        if result ~= nil then return result end
+    end
+
+    return nil
+end
+
+-- encode a reply to a v2 message: status (u8) + payload length (u16) + payload
+local function encode_reply(status, payload)
+    payload = payload or ""
+    return string.pack(">I1I2", status or 0, #payload) .. payload
+end
+
+-- process a single v2 message, returns the reply and whether the connection
+-- shall be kept open after sending the reply
+local function process_message(msg_type, payload)
+    if msg_type == MSG_FRAME then
+        local result = apply_frame(payload)
+        submit_color_map(color_map)
+
+        return encode_reply(result), true
+    elseif msg_type == MSG_COMMANDS then
+        local result = apply_commands(payload)
+        submit_color_map(color_map)
+
+        return encode_reply(result), true
+    elseif msg_type == MSG_STATUS then
+        return encode_reply(nil, STATUS_MESSAGE_V2), true
+    elseif msg_type == MSG_CANVAS then
+        return encode_reply(nil, string.pack(">I2I2", canvas_width,
+                                             canvas_height)), true
+    elseif msg_type == MSG_QUIT then
+        return encode_reply(nil), false
+    else
+        error("Network FX: Unknown message type: " .. msg_type)
+        return encode_reply(ERROR_UNKNOWN_MESSAGE), true
+    end
+end
+
+-- receive exactly `count` bytes from the current connection, partially
+-- received data is buffered until the next call
+local function receive_bytes(count)
+    local data, status, partial = conn:receive(count - #rx_buffer)
+
+    if data ~= nil then
+        local result = rx_buffer .. data
+        rx_buffer = ""
+
+        return result, nil
+    else
+        rx_buffer = rx_buffer .. (partial or "")
+
+        return nil, status
+    end
+end
+
+-- receive and process v2 messages, until no more data is available
+local function process_binary_requests()
+    repeat
+        local data, status
+
+        if pending_header == nil then
+            data, status = receive_bytes(HEADER_SIZE)
+
+            if data ~= nil then
+                local msg_type, length = string.unpack(">I1I4", data)
+
+                if length > MAX_PAYLOAD_SIZE then
+                    error("Network FX: Message exceeds the max. size")
+
+                    conn:send(encode_reply(ERROR_ILL_FORMED))
+                    close_connection()
+                    return
+                end
+
+                pending_header = {msg_type = msg_type, length = length}
+            end
+        else
+            if pending_header.length > 0 then
+                data, status = receive_bytes(pending_header.length)
+            else
+                data = ""
+            end
+
+            if data ~= nil then
+                local reply, keep_open =
+                    process_message(pending_header.msg_type, data)
+                pending_header = nil
+
+                conn:send(reply)
+
+                if not keep_open then
+                    close_connection()
+                    return
+                end
+            end
+        end
+
+        if data == nil and status == "closed" then
+            -- lost connection to client
+            info("Network FX: Client disconnected")
+
+            close_connection()
+            return
+        end
+    until data == nil
+end
+
+-- receive and process datagrams, each one carries a single v2 message
+-- and is never replied to
+local function process_datagrams()
+    repeat
+        local datagram = udp_server:receive(MAX_DATAGRAM_SIZE)
+
+        if datagram ~= nil then
+            if #datagram >= HEADER_SIZE then
+                local msg_type, length = string.unpack(">I1I4", datagram)
+                local payload = datagram:sub(HEADER_SIZE + 1)
+
+                if length > #payload then
+                    trace("Network FX: Dropped a truncated datagram")
+                elseif length == #payload and
+                    (msg_type == MSG_FRAME or msg_type == MSG_COMMANDS) then
+                    process_message(msg_type, payload)
+                else
+                    trace("Network FX: Dropped an ill-formed datagram")
+                end
+            else
+                trace("Network FX: Dropped an ill-formed datagram")
+            end
+        end
+    until datagram == nil
+end
+
 -- event handler functions --
 function on_startup(config)
     for i = 1, canvas_size do color_map[i] = 0x00000000 end
@@ -84,6 +355,22 @@ function on_startup(config)
         local ip, port = server:getsockname()
         info("Network FX: Server now listening on " .. ip .. ":" .. port)
 
+        if enable_udp then
+            udp_server = socket.udp()
+
+            local status, msg = udp_server:setsockname(bind_address, port)
+            if status == nil then
+                error(
+                    "Network FX: Could not bind UDP socket to the specified address: " ..
+                        msg)
+                udp_server = nil
+            else
+                udp_server:settimeout(0)
+
+                info("Network FX: Receiving datagrams on " .. ip .. ":" .. port)
+            end
+        end
+
         initialized = true
     end
 end
@@ -93,10 +380,14 @@ function on_quit()
         info("Network FX: Server shutting down")
         server:close()
     end
+
+    if udp_server ~= nil then udp_server:close() end
 end
 
 function on_tick(delta)
     if initialized then
+        if udp_server ~= nil then process_datagrams() end
+
         if conn == nil then
             -- we currently have no client connected, so poll for pending connection requests
             conn = server:accept()
@@ -109,6 +400,8 @@ function on_tick(delta)
                 local ip, port = conn:getpeername()
                 info("Network FX: Client connected from " .. ip .. ":" .. port)
             end
+        elseif protocol_version == PROTOCOL_VERSION_2 then
+            process_binary_requests()
         else
             -- receive and process data
             local data, status
@@ -122,94 +415,45 @@ function on_tick(delta)
                     -- check for, and process protocol commands
                     if data == "QUIT" then
                         conn:send("BYE\n")
-                        conn:close()
-                        conn = nil
+                        close_connection()
                         return
                     elseif data == "STATUS" then
-                        conn:send(
-                            "Eruption Network FX / Protocol version: 1.0\n")
+                        conn:send(STATUS_MESSAGE_V1 .. "\n")
                         return
-                    end
+                    elseif data == "CANVAS" then
+                        conn:send("CANVAS: " .. canvas_width .. "x" ..
+                                      canvas_height .. "\n")
+                        return
+                    elseif data:sub(1, 9) == "PROTOCOL " then
+                        local version = tonumber(data:sub(10))
 
-                    -- data is apparently not a command
-                    local result = split(data, ':')
+                        if version == PROTOCOL_VERSION_1 or version ==
+                            PROTOCOL_VERSION_2 then
+                            conn:send("OK: " .. version .. "\n")
 
-                    -- validate request parameters
-                    if #result ~= 5 then
-                        error("Network FX: Request is ill-formed")
+                            -- all subsequent requests use the negotiated version
+                            protocol_version = version
+                            if version == PROTOCOL_VERSION_2 then
+                                process_binary_requests()
+                            end
+                        else
+                            conn:send("ERROR: " .. ERROR_UNSUPPORTED_VERSION ..
+                                          "\n")
+                        end
 
-                        conn:send("ERROR: 100\n")
-                        conn:close()
-                        conn = nil
                         return
                     end
 
-                    local r, g, b, a = tonumber(result[2]), tonumber(result[3]),
-                                       tonumber(result[4]), tonumber(result[5])
-
-                    -- validate colors
-                    if r < 0 or r > 255 or g < 0 or g > 255 or b < 0 or b > 255 or
-                        a < 0 or a > 255 then
-                        error("Network FX: Color component value out of range")
+                    -- data is apparently not a command
+                    local result = apply_command(data)
 
-                        conn:send("ERROR: 110\n")
-                        conn:close()
-                        conn = nil
+                    if result ~= nil then
+                        conn:send("ERROR: " .. result .. "\n")
+                        close_connection()
                         return
                     end
 
-                    local color = rgba_to_color(r, g, b, a)
-
-                    local components = split(result[1], ',')
-
-                    for idx = 1, #components do
-                        local spec = split(components[idx], '-')
-                        if #spec < 2 then
-                            if result[1] == "ALL" then
-                                -- predefined zone: full canvas
-                                for i = 1, canvas_size do
-                                    color_map[i] = color
-                                end
-                            else
-                                -- set a single pixel on the canvas to a specific color
-                                local num = tonumber(components[idx])
-                                if n(num) > 0 and n(num) <= canvas_size then
-                                    local index = tonumber(components[idx])
-                                    color_map[index] = color
-                                else
-                                    error("Network FX: Invalid index")
-
-                                    conn:send("ERROR: 120\n")
-                                    conn:close()
-                                    conn = nil
-                                    return
-                                end
-                            end
-                        else
-                            -- set a range of pixels to a specific color
-                            if spec[1] ~= nil and spec[2] ~= nil and spec[1] ~=
-                                '' and spec[2] ~= '' and tonumber(spec[1]) > 0 and
-                                tonumber(spec[1]) <= canvas_size and
-                                tonumber(spec[2]) > 0 and tonumber(spec[2]) <=
-                                canvas_size then
-                                local low = tonumber(spec[1])
-                                local high = tonumber(spec[2])
-
-                                for index = low, high do
-                                    color_map[index] = color
-                                end
-                            else
-                                error("Network FX: Invalid index")
-
-                                conn:send("ERROR: 120\n")
-                                conn:close()
-                                conn = nil
-                                return
-                            end
-                        end
-
-                        submit_color_map(color_map)
-                    end
+                    submit_color_map(color_map)
 
                     conn:send("OK\n")
                 else
@@ -131,6 +131,25 @@ pub fn get_script_dirs() -> Vec<PathBuf> {
     result
 }
 
+/// Returns the directories that hold the device definition files
+pub fn get_device_definition_dirs() -> Vec<PathBuf> {
+    let config = crate::CONFIG.lock();
+
+    let device_definition_dirs = config
+        .as_ref()
+        .and_then(|config| {
+            config
+                .get::<Vec<String>>("global.device_definition_dirs")
+                .ok()
+        })
+        .unwrap_or_else(|| vec![constants::DEFAULT_DEVICE_DEFINITION_DIR.to_string()]);
+
+    device_definition_dirs
+        .iter()
+        .map(PathBuf::from)
+        .collect::<Vec<PathBuf>>()
+}
+
 pub fn match_script_path<P: AsRef<Path>>(script_file: &P) -> Result<PathBuf> {
     let script_file = script_file.as_ref();
 
@@ -0,0 +1,392 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::constants;
+use crate::hwdevices::RGBA;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ZoneError {
+    #[error("Invalid zone: {description}")]
+    InvalidZone { description: String },
+
+    #[error("Invalid device")]
+    InvalidDevice {},
+}
+
+lazy_static! {
+    /// Canvas zones allocated to the managed devices
+    pub static ref ZONES: Arc<RwLock<HashMap<DeviceId, Zone>>> = {
+        let zones = load_zones_from_config().unwrap_or_else(|e| {
+            error!("Could not load the canvas zones from the configuration file: {}", e);
+
+            HashMap::new()
+        });
+
+        Arc::new(RwLock::new(zones))
+    };
+}
+
+/// Identifies a device independent of its (volatile) device index.
+/// `instance` enumerates multiple devices that share the same USB IDs
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceId {
+    pub usb_vid: u16,
+    pub usb_pid: u16,
+    pub instance: usize,
+}
+
+impl DeviceId {
+    pub fn new(usb_vid: u16, usb_pid: u16, instance: usize) -> Self {
+        Self {
+            usb_vid,
+            usb_pid,
+            instance,
+        }
+    }
+}
+
+/// A rectangular area of the canvas, that will be mapped onto the LEDs of a device
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Zone {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Zone {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// A zone that spans the whole canvas
+    pub fn full_canvas() -> Self {
+        Self::new(
+            0,
+            0,
+            crate::CANVAS_WIDTH.load(Ordering::SeqCst) as i32,
+            crate::CANVAS_HEIGHT.load(Ordering::SeqCst) as i32,
+        )
+    }
+
+    /// Returns `true` if the zone has no extent, this is used to remove an allocation
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 && self.height == 0
+    }
+
+    /// Verify that the zone is not empty and lies within the bounds of the canvas
+    pub fn validate(&self) -> Result<()> {
+        let canvas_width = crate::CANVAS_WIDTH.load(Ordering::SeqCst) as i32;
+        let canvas_height = crate::CANVAS_HEIGHT.load(Ordering::SeqCst) as i32;
+
+        if self.width <= 0 || self.height <= 0 {
+            Err(ZoneError::InvalidZone {
+                description: format!("Empty zone: {}x{}", self.width, self.height),
+            }
+            .into())
+        } else if self.x < 0
+            || self.y < 0
+            || self.x + self.width > canvas_width
+            || self.y + self.height > canvas_height
+        {
+            Err(ZoneError::InvalidZone {
+                description: format!(
+                    "Zone {}x{}+{}+{} exceeds the canvas bounds of {}x{}",
+                    self.width, self.height, self.x, self.y, canvas_width, canvas_height
+                ),
+            }
+            .into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Maps the area of the `canvas` that is covered by this zone onto the full extent of
+    /// the device local LED map `led_map`, using nearest neighbor sampling. The LED map
+    /// uses the layout of the default canvas, since that is what the device drivers expect
+    pub fn map_canvas(&self, canvas: &[RGBA], led_map: &mut [RGBA]) {
+        let canvas_width = crate::CANVAS_WIDTH.load(Ordering::SeqCst);
+
+        let width = constants::CANVAS_WIDTH;
+        let height = constants::CANVAS_HEIGHT;
+
+        for (idx, color) in led_map.iter_mut().enumerate() {
+            let x = idx % width;
+            let y = (idx / width).min(height - 1);
+
+            let src_x = self.x as usize + (x * self.width as usize) / width;
+            let src_y = self.y as usize + (y * self.height as usize) / height;
+
+            *color = canvas[(src_y * canvas_width + src_x).min(canvas.len() - 1)];
+        }
+    }
+}
+
+/// Parse the `[[zones]]` declarations from eruption.conf
+pub fn load_zones_from_config() -> Result<HashMap<DeviceId, Zone>> {
+    let mut result = HashMap::new();
+
+    let config = crate::CONFIG.lock();
+
+    if let Some(config) = config.as_ref() {
+        let zones = config.get_array("zones").unwrap_or_else(|_e| vec![]);
+
+        for entry in zones.into_iter() {
+            let parsed = entry
+                .into_table()
+                .map_err(eyre::Error::from)
+                .and_then(|table| parse_zone_entry(&table));
+
+            match parsed {
+                Ok((device_id, zone)) => {
+                    info!(
+                        "Allocated canvas zone {:?} to device {:04x}:{:04x} ({})",
+                        zone, device_id.usb_vid, device_id.usb_pid, device_id.instance
+                    );
+
+                    result.insert(device_id, zone);
+                }
+
+                Err(e) => {
+                    error!(
+                        "Skipping invalid canvas zone specified in the configuration file: {}",
+                        e
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Parse and validate a single `[[zones]]` table
+fn parse_zone_entry(table: &HashMap<String, config::Value>) -> Result<(DeviceId, Zone)> {
+    let usb_vid = get_zone_value::<u16>(table, "vendor_id")?;
+    let usb_pid = get_zone_value::<u16>(table, "product_id")?;
+    let instance = match table.get("instance") {
+        Some(_) => get_zone_value::<usize>(table, "instance")?,
+        None => 0,
+    };
+
+    let zone = Zone::new(
+        get_zone_value::<i32>(table, "x")?,
+        get_zone_value::<i32>(table, "y")?,
+        get_zone_value::<i32>(table, "width")?,
+        get_zone_value::<i32>(table, "height")?,
+    );
+
+    zone.validate()?;
+
+    Ok((DeviceId::new(usb_vid, usb_pid, instance), zone))
+}
+
+/// Get the integer value of `key`, failing if it is missing or out of range
+fn get_zone_value<T: TryFrom<i64>>(table: &HashMap<String, config::Value>, key: &str) -> Result<T> {
+    let value = table
+        .get(key)
+        .ok_or_else(|| ZoneError::InvalidZone {
+            description: format!("Missing key '{}'", key),
+        })?
+        .clone()
+        .into_int()?;
+
+    let result = T::try_from(value).map_err(|_e| ZoneError::InvalidZone {
+        description: format!("Value of '{}' is out of range: {}", key, value),
+    })?;
+
+    Ok(result)
+}
+
+/// Returns the zone allocated to the device `device_id`, if any
+pub fn get_zone(device_id: &DeviceId) -> Option<Zone> {
+    ZONES.read().get(device_id).copied()
+}
+
+/// Allocate `zone` to the device `device_id`. An empty zone removes the
+/// allocation, so that the device mirrors the canvas again
+pub fn set_zone(device_id: DeviceId, zone: Zone) -> Result<()> {
+    if zone.is_empty() {
+        ZONES.write().remove(&device_id);
+    } else {
+        zone.validate()?;
+
+        ZONES.write().insert(device_id, zone);
+    }
+
+    Ok(())
+}
+
+/// Resolve a device index, as used by the D-Bus API, to a `DeviceId`
+pub fn get_device_id(device: u64) -> Result<DeviceId> {
+    let mut ids: Vec<(u16, u16)> = Vec::new();
+
+    ids.extend(
+        crate::KEYBOARD_DEVICES
+            .read()
+            .iter()
+            .map(|d| (d.read().get_usb_vid(), d.read().get_usb_pid())),
+    );
+
+    ids.extend(
+        crate::MOUSE_DEVICES
+            .read()
+            .iter()
+            .map(|d| (d.read().get_usb_vid(), d.read().get_usb_pid())),
+    );
+
+    ids.extend(
+        crate::MISC_DEVICES
+            .read()
+            .iter()
+            .map(|d| (d.read().get_usb_vid(), d.read().get_usb_pid())),
+    );
+
+    let index = device as usize;
+
+    if index < ids.len() {
+        let (usb_vid, usb_pid) = ids[index];
+        let instance = ids[..index]
+            .iter()
+            .filter(|id| **id == (usb_vid, usb_pid))
+            .count();
+
+        Ok(DeviceId::new(usb_vid, usb_pid, instance))
+    } else {
+        Err(ZoneError::InvalidDevice {}.into())
+    }
+}
+
+/// Keeps track of the instance numbers of devices, while iterating over all devices
+#[derive(Debug, Default)]
+pub struct InstanceCounter(HashMap<(u16, u16), usize>);
+
+impl InstanceCounter {
+    pub fn next_id(&mut self, usb_vid: u16, usb_pid: u16) -> DeviceId {
+        let counter = self.0.entry((usb_vid, usb_pid)).or_insert(0);
+        let result = DeviceId::new(usb_vid, usb_pid, *counter);
+
+        *counter += 1;
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_canvas() -> Vec<RGBA> {
+        (0..constants::CANVAS_SIZE)
+            .map(|i| RGBA {
+                r: (i % 256) as u8,
+                g: 0,
+                b: 0,
+                a: 255,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn full_canvas_zone_is_identity() {
+        let canvas = make_canvas();
+        let mut led_map = make_canvas();
+        led_map.iter_mut().for_each(|c| c.r = 0);
+
+        Zone::full_canvas().map_canvas(&canvas, &mut led_map);
+
+        assert!(canvas.iter().zip(led_map.iter()).all(|(a, b)| a.r == b.r));
+    }
+
+    #[test]
+    fn single_cell_zone_fills_led_map() {
+        let canvas = make_canvas();
+        let mut led_map = make_canvas();
+
+        Zone::new(3, 2, 1, 1).map_canvas(&canvas, &mut led_map);
+
+        let expected = canvas[2 * constants::CANVAS_WIDTH + 3].r;
+        assert!(led_map.iter().all(|c| c.r == expected));
+    }
+
+    #[test]
+    fn zone_validation() {
+        assert!(Zone::full_canvas().validate().is_ok());
+        assert!(Zone::new(0, 0, 0, 1).validate().is_err());
+        assert!(Zone::new(-1, 0, 2, 2).validate().is_err());
+        assert!(Zone::new(1, 0, constants::CANVAS_WIDTH as i32, 1)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn instance_counter() {
+        let mut counter = InstanceCounter::default();
+
+        assert_eq!(counter.next_id(0x1e7d, 0x2dd2).instance, 0);
+        assert_eq!(counter.next_id(0x1e7d, 0x3098).instance, 0);
+        assert_eq!(counter.next_id(0x1e7d, 0x2dd2).instance, 1);
+    }
+
+    #[test]
+    fn zone_entry_parsing() {
+        let mut table: HashMap<String, config::Value> = [
+            ("vendor_id", 0x1e7d),
+            ("product_id", 0x2dd2),
+            ("x", 0),
+            ("y", 0),
+            ("width", 2),
+            ("height", 2),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), config::Value::new(None, *v as i64)))
+        .collect();
+
+        let (device_id, zone) = parse_zone_entry(&table).unwrap();
+        assert_eq!(device_id, DeviceId::new(0x1e7d, 0x2dd2, 0));
+        assert_eq!(zone, Zone::new(0, 0, 2, 2));
+
+        table.insert(
+            "vendor_id".to_string(),
+            config::Value::new(None, 0x11e7d_i64),
+        );
+        assert!(parse_zone_entry(&table).is_err());
+
+        table.insert(
+            "vendor_id".to_string(),
+            config::Value::new(None, 0x1e7d_i64),
+        );
+        table.remove("height");
+        assert!(parse_zone_entry(&table).is_err());
+    }
+}
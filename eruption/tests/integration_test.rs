@@ -17,7 +17,26 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
-use std::{path::PathBuf, process::Command};
+use std::io::Write;
+use std::{fs, path::PathBuf, process::Command};
+
+/// Write a configuration file, that makes the daemon use the scripts of the source tree
+fn write_config_file() -> tempfile::NamedTempFile {
+    let mut config_file = tempfile::Builder::new()
+        .prefix("eruption-test-")
+        .suffix(".conf")
+        .tempfile()
+        .expect("Could not create the configuration file");
+
+    write!(
+        config_file,
+        "[global]\nscript_dirs = [\"{}/src/scripts/\"]\n",
+        env!("CARGO_MANIFEST_DIR")
+    )
+    .expect("Could not write the configuration file");
+
+    config_file
+}
 
 #[test]
 fn test_eruption_version() {
@@ -42,3 +61,87 @@ fn test_eruption_version() {
         )
     );
 }
+
+#[test]
+fn test_macros_lua() {
+    let command = PathBuf::from(&env!("CARGO_BIN_EXE_eruption"));
+
+    let config_file = write_config_file();
+
+    let output = Command::new(&command)
+        .args(&["--config", &config_file.path().to_string_lossy()])
+        .args(&["test", "--device", "keyboards/roccat_vulcan_1xx"])
+        .arg(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../support/tests/scripts/macros.test.lua"
+        ))
+        .output()
+        .expect("Failed to execute the test");
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+/// Render `frames` frames of the script `script` headless, and compare them to the golden frames
+/// in the directory `golden_dir`
+fn assert_golden_frames(script: &str, golden_dir: &str, frames: usize) {
+    let command = PathBuf::from(&env!("CARGO_BIN_EXE_eruption"));
+
+    let config_file = write_config_file();
+    let output_dir = tempfile::tempdir().expect("Could not create the output directory");
+
+    let golden_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../support/tests/golden")
+        .join(golden_dir);
+
+    let output = Command::new(&command)
+        .args(&["--config", &config_file.path().to_string_lossy()])
+        .args(&["render", "--script", script])
+        .args(&["--frames", &frames.to_string()])
+        .args(&["--format", "raw", "--output"])
+        .arg(output_dir.path())
+        .output()
+        .expect("Failed to execute the test");
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    for index in 0..frames {
+        let file_name = format!("frame-{:05}.rgba", index);
+
+        let rendered = fs::read(output_dir.path().join(&file_name))
+            .expect("Could not read the rendered frame");
+        let golden =
+            fs::read(golden_dir.join(&file_name)).expect("Could not read the golden frame");
+
+        assert!(
+            rendered == golden,
+            "{} differs from the golden frame",
+            file_name
+        );
+    }
+}
+
+#[test]
+fn test_render_golden_frames() {
+    assert_golden_frames("solid.lua", "solid", 5);
+}
+
+#[test]
+fn test_render_animated_golden_frames() {
+    // the frames of this effect depend on the fixed tick delta of the headless renderer
+    assert_golden_frames(
+        concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../support/tests/scripts/ticks.lua"
+        ),
+        "ticks",
+        5,
+    );
+}
@@ -0,0 +1 @@
+../../eruption/src/hwdevices/capabilities.rs
\ No newline at end of file
@@ -21,6 +21,7 @@
 
 use crate::constants;
 use dbus::blocking::Connection;
+use std::path::Path;
 use std::time::Duration;
 
 type Result<T> = std::result::Result<T, eyre::Error>;
@@ -45,6 +46,22 @@ pub fn set_parameter(
     Ok(())
 }
 
+/// Instruct the daemon to write a .profile file or a Lua script or manifest
+pub fn write_file<P: AsRef<Path>>(path: &P, data: &str) -> Result<()> {
+    use config::OrgEruptionConfig;
+
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/config",
+        Duration::from_secs(35),
+    );
+
+    let _result = proxy.write_file(&path.as_ref().to_string_lossy(), data)?;
+
+    Ok(())
+}
+
 // TODO: This currently fails with a dbus error, use util::get_slot_names() for now
 /// Fetches all slot names
 // pub fn get_slot_names() -> Result<Vec<String>> {
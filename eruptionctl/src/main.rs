@@ -17,6 +17,7 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
+use capabilities::DeviceCapabilities;
 use clap::{IntoApp, Parser};
 use clap_complete::Shell;
 use color_eyre::Help;
@@ -35,21 +36,26 @@ use i18n_embed::{
     DesktopLanguageRequester,
 };
 use lazy_static::lazy_static;
-use manifest::GetAttr;
+use manifest::{GetAttr, ParseConfig};
 use parking_lot::Mutex;
 use profiles::GetAttr as GetAttrProfile;
 use rust_embed::RustEmbed;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 use std::{env, thread};
 use std::{process, sync::Arc};
 
+mod capabilities;
 mod constants;
 mod dbus_client;
 mod device;
 mod manifest;
 mod profiles;
+mod trust;
 mod util;
 
 type Result<T> = std::result::Result<T, eyre::Error>;
@@ -255,6 +261,17 @@ pub enum DevicesSubcommands {
         device: String,
         enable: Option<bool>,
     },
+
+    /// Get or set the zone of the canvas that is mapped onto the LEDs of the device,
+    /// use a width and height of 0 to remove the zone
+    // #[clap(display_order = 10)]
+    Zone {
+        device: String,
+        x: Option<i32>,
+        y: Option<i32>,
+        width: Option<i32>,
+        height: Option<i32>,
+    },
 }
 
 /// Sub-commands of the "status" command
@@ -265,6 +282,9 @@ pub enum StatusSubcommands {
 
     /// Shows the currently active slot
     Slot,
+
+    /// Shows frame time statistics, crash counts and the last errors of the scripts of the active profile
+    Scripts,
 }
 
 /// Sub-commands of the "switch" command
@@ -288,6 +308,36 @@ pub enum ProfilesSubcommands {
 
     /// List available profiles
     List,
+
+    /// List the static key overrides of a profile
+    Overrides { profile_name: String },
+
+    /// Add a static color or gradient to a set of keys of a profile
+    AddOverride {
+        profile_name: String,
+
+        /// A descriptive name of the override, e.g. WASD
+        name: String,
+
+        /// The key indices, separated by commas
+        #[clap(short, long, use_value_delimiter = true, required = true)]
+        keys: Vec<usize>,
+
+        /// Colors in the format AARRGGBB, separated by commas. Multiple colors form a gradient
+        #[clap(short, long, use_value_delimiter = true, required = true)]
+        colors: Vec<String>,
+
+        /// One of alpha, add, multiply, screen, max or replace
+        #[clap(short, long, default_value = "alpha")]
+        blend_mode: String,
+
+        /// Opacity in the range 0.0 to 1.0
+        #[clap(short, long, default_value = "1.0")]
+        opacity: f64,
+    },
+
+    /// Remove a static key override from a profile
+    RemoveOverride { profile_name: String, name: String },
 }
 
 /// Subcommands of the "names" command
@@ -323,6 +373,30 @@ fn print_header() {
     println!();
 }
 
+/// Print the capabilities requested by the 3rd party scripts of the profile `profile_file`
+fn print_requested_capabilities(profile_file: &Path) -> Result<()> {
+    let profile = profiles::Profile::from(profile_file)?;
+
+    for script_file in profile.get_active_scripts().iter() {
+        let manifest = manifest::Manifest::from(&util::match_script_path(script_file)?)?;
+
+        if let Some(capabilities) = manifest.capabilities.as_ref() {
+            if !trust::is_system_script(&manifest.script_file) && !capabilities.is_empty() {
+                println!(
+                    "Script {} requests the following capabilities:",
+                    manifest.name.bold()
+                );
+
+                for capability in capabilities {
+                    println!("  - {}", capability.get_description());
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// Returns a connection to the D-Bus system bus using the specified `path`
 pub async fn dbus_system_bus(
     path: &str,
@@ -376,6 +450,27 @@ pub async fn get_active_profile() -> Result<String> {
     Ok(result)
 }
 
+/// Get the frame time statistics of the scripts of the active profile
+pub async fn get_script_stats() -> Result<Vec<(String, u64, f64, f64, u64, u64)>> {
+    let (result,): (Vec<(String, u64, f64, f64, u64, u64)>,) =
+        dbus_system_bus("/org/eruption/status")
+            .await?
+            .method_call("org.eruption.Status", "GetScriptStats", ())
+            .await?;
+
+    Ok(result)
+}
+
+/// Get the crash counts and the last errors of the scripts of the active profile
+pub async fn get_script_status() -> Result<Vec<(String, u32, String)>> {
+    let (result,): (Vec<(String, u32, String)>,) = dbus_system_bus("/org/eruption/status")
+        .await?
+        .method_call("org.eruption.Status", "GetScriptStatus", ())
+        .await?;
+
+    Ok(result)
+}
+
 /// Switch the currently active slot
 pub async fn switch_slot(index: usize) -> Result<()> {
     let (_result,): (bool,) = dbus_system_bus("/org/eruption/slot")
@@ -451,6 +546,18 @@ pub async fn get_device_status(device: u64) -> Result<HashMap<String, String>> {
     Ok(result)
 }
 
+/// Get the capabilities of a device
+pub async fn get_device_capabilities(device: u64) -> Result<DeviceCapabilities> {
+    let (capabilities,): (String,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "GetDeviceCapabilities", (device,))
+        .await?;
+
+    let result: DeviceCapabilities = serde_json::from_str(&capabilities)?;
+
+    Ok(result)
+}
+
 /// Get a device specific config param
 pub async fn get_device_config(device: u64, param: &str) -> Result<String> {
     let (result,): (String,) = dbus_system_bus("/org/eruption/devices")
@@ -479,6 +586,77 @@ pub async fn set_device_config(device: u64, param: &str, value: &str) -> Result<
     Ok(())
 }
 
+/// Get the zone of the canvas that is allocated to a device
+pub async fn get_device_zone(device: u64) -> Result<(i32, i32, i32, i32)> {
+    let (result,): ((i32, i32, i32, i32),) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "GetDeviceZone", (device,))
+        .await?;
+
+    Ok(result)
+}
+
+/// Allocate a zone of the canvas to a device
+pub async fn set_device_zone(device: u64, zone: (i32, i32, i32, i32)) -> Result<()> {
+    let (_result,): (bool,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call(
+            "org.eruption.Device",
+            "SetDeviceZone",
+            (device, zone.0, zone.1, zone.2, zone.3),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Describe the values that are accepted by the script parameter `param`
+fn param_hint(param: &manifest::ConfigParam) -> String {
+    match param {
+        manifest::ConfigParam::Enum { values, .. } => {
+            format!("Allowed values: {}", values.join(", "))
+        }
+
+        manifest::ConfigParam::ColorArray { .. } => {
+            "Please specify a comma separated list of colors, e.g.: '#ffff0000, #ff0000ff'".into()
+        }
+
+        manifest::ConfigParam::KeySet { .. } => {
+            "Please specify a comma separated list of key indices, e.g.: '1, 2, 3'".into()
+        }
+
+        manifest::ConfigParam::Duration { min, max, .. } => format!(
+            "Please specify a duration in milliseconds or seconds, e.g.: '500ms' or '2s' (min: {}, max: {})",
+            min.map(|v| format!("{}ms", v)).unwrap_or_else(|| "none".into()),
+            max.map(|v| format!("{}ms", v)).unwrap_or_else(|| "none".into()),
+        ),
+
+        _ => format!("Default value: {}", param.get_default()),
+    }
+}
+
+/// Verify that `value` is valid for the parameter `parameter` of the script `script`,
+/// before it is sent to the daemon
+fn validate_parameter(script: &manifest::Manifest, parameter: &str, value: &str) -> Result<()> {
+    let config = script.config.clone().unwrap_or_default();
+
+    let hint = match config.iter().find(|p| p.get_name() == parameter) {
+        Some(param) => param_hint(param),
+
+        None => format!(
+            "The script '{}' has no parameter '{}'",
+            script.name, parameter
+        ),
+    };
+
+    config
+        .parse_config_param(parameter, value)
+        .wrap_err_with(|| format!("Invalid value '{}' for parameter '{}'", value, parameter))
+        .suggestion(hint)?;
+
+    Ok(())
+}
+
 /// Enumerate all available scripts
 pub fn get_script_list() -> Result<Vec<(String, String)>> {
     let scripts = util::enumerate_scripts()?;
@@ -542,6 +720,79 @@ pub async fn set_sound_fx(enabled: bool) -> Result<()> {
     Ok(())
 }
 
+fn print_device_capabilities(capabilities: &DeviceCapabilities) {
+    let yes_no = |v: bool| if v { "yes" } else { "no" }.to_string();
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec!["Capability", "Value"]);
+
+    let zones = capabilities
+        .led_zones
+        .iter()
+        .map(|z| format!("{} ({})", z.name, z.num_leds))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    let dpi = capabilities
+        .dpi
+        .map(|r| format!("{}-{} (step: {})", r.min, r.max, r.step))
+        .unwrap_or_else(|| "n/a".to_string());
+
+    let polling_rates = capabilities
+        .polling_rates
+        .iter()
+        .map(|r| format!("{} Hz", r))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    let status_leds = capabilities
+        .status_leds
+        .iter()
+        .map(|l| format!("{:?}", l))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    let parameters = [
+        "brightness",
+        "profile",
+        "dpi",
+        "rate",
+        "dcu",
+        "angle-snapping",
+        "debounce",
+    ]
+    .iter()
+    .filter(|p| capabilities.supports(p))
+    .copied()
+    .collect::<Vec<&str>>()
+    .join(", ");
+
+    let rows = vec![
+        ("LEDs", capabilities.num_leds.to_string()),
+        ("LED zones", zones),
+        ("DPI", dpi),
+        ("Polling rates", polling_rates),
+        ("Onboard profiles", capabilities.num_profiles.to_string()),
+        ("Battery", yes_no(capabilities.has_battery)),
+        ("Status LEDs", status_leds),
+        ("Parameters", parameters),
+    ];
+
+    for (k, v) in rows {
+        table.add_row(vec![
+            Cell::new(k).set_alignment(CellAlignment::Left),
+            Cell::new(if v.is_empty() { "n/a".to_string() } else { v })
+                .set_alignment(CellAlignment::Left),
+        ]);
+    }
+
+    println!("{}", table);
+}
+
 async fn print_device_header(device: u64) -> Result<()> {
     let mut base_index = 0;
 
@@ -867,6 +1118,10 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                 let result = get_device_config(device, "info").await?;
 
                 println!("{}", format!("{}", result.bold()));
+
+                let capabilities = get_device_capabilities(device).await?;
+
+                print_device_capabilities(&capabilities);
             }
 
             DevicesSubcommands::Status { device } => {
@@ -1040,6 +1295,50 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                 }
             }
 
+            DevicesSubcommands::Zone {
+                device,
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let device = device.parse::<u64>()?;
+
+                print_device_header(device)
+                    .await
+                    .wrap_err("Could not connect to the Eruption daemon")
+                    .suggestion("Please verify that the Eruption daemon is running")?;
+
+                match (x, y, width, height) {
+                    (Some(x), Some(y), Some(width), Some(height)) => {
+                        set_device_zone(device, (x, y, width, height)).await?
+                    }
+
+                    (None, None, None, None) => {
+                        let (x, y, width, height) = get_device_zone(device).await?;
+
+                        if width == 0 && height == 0 {
+                            println!(
+                                "{}",
+                                format!("Zone: {}", "none (mirrors the canvas)".bold())
+                            );
+                        } else {
+                            println!(
+                                "{}",
+                                format!(
+                                    "Zone: {}",
+                                    format!("{}x{}+{}+{}", width, height, x, y).bold()
+                                )
+                            );
+                        }
+                    }
+
+                    _ => {
+                        eprintln!("Please specify either all or none of: x, y, width and height");
+                    }
+                }
+            }
+
             DevicesSubcommands::Brightness { device, brightness } => {
                 let device = device.parse::<u64>()?;
 
@@ -1085,10 +1384,121 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                 }
             }
 
-            ProfilesSubcommands::Info { profile_name } => {
+            ProfilesSubcommands::Overrides { profile_name } => {
+                let profiles = util::enumerate_profiles().unwrap_or_else(|_| vec![]);
+
+                if let Some(profile) = profiles
+                    .iter()
+                    .find(|p| *p.profile_file.to_string_lossy() == profile_name)
+                {
+                    let mut table = Table::new();
+
+                    table
+                        .load_preset(UTF8_FULL)
+                        .apply_modifier(UTF8_ROUND_CORNERS)
+                        .set_content_arrangement(ContentArrangement::Dynamic)
+                        .set_header(vec!["Name", "Keys", "Colors", "Blend mode", "Opacity"]);
+
+                    for key_override in profile.overrides.iter() {
+                        let colors = key_override
+                            .color
+                            .iter()
+                            .chain(key_override.gradient.iter())
+                            .map(|c| format!("{:08x}", c))
+                            .collect::<Vec<String>>()
+                            .join(", ");
+
+                        let keys = key_override
+                            .keys
+                            .iter()
+                            .map(|k| k.to_string())
+                            .collect::<Vec<String>>()
+                            .join(", ");
+
+                        table.add_row(vec![
+                            Cell::new(&key_override.name),
+                            Cell::new(keys),
+                            Cell::new(colors),
+                            Cell::new(format!("{:?}", key_override.blend_mode).to_lowercase()),
+                            Cell::new(format!("{:.2}", key_override.opacity))
+                                .set_alignment(CellAlignment::Right),
+                        ]);
+                    }
+
+                    println!("{}", table);
+                } else {
+                    eprintln!("No matches found");
+                }
+            }
+
+            ProfilesSubcommands::AddOverride {
+                profile_name,
+                name,
+                keys,
+                colors,
+                blend_mode,
+                opacity,
+            } => {
+                let profiles = util::enumerate_profiles().unwrap_or_else(|_| vec![]);
+
+                if let Some(profile) = profiles
+                    .iter()
+                    .find(|p| *p.profile_file.to_string_lossy() == profile_name)
+                {
+                    let colors = colors
+                        .iter()
+                        .map(|c| u32::from_str_radix(c.trim_start_matches('#'), 16))
+                        .collect::<std::result::Result<Vec<u32>, _>>()?;
+
+                    let (color, gradient) = if colors.len() == 1 {
+                        (Some(colors[0]), vec![])
+                    } else {
+                        (None, colors)
+                    };
+
+                    let mut overrides = profile.overrides.clone();
+
+                    overrides.retain(|o| o.name != name);
+                    overrides.push(profiles::KeyOverride {
+                        name,
+                        keys,
+                        color,
+                        gradient,
+                        blend_mode: blend_mode.parse()?,
+                        opacity: opacity.clamp(0.0, 1.0),
+                    });
+
+                    // the daemon writes the profile, and picks up the modification automatically
+                    let data = profile.serialize_with_overrides(&overrides)?;
+                    dbus_client::write_file(&profile.profile_file, &data)?;
+                } else {
+                    eprintln!("No matches found");
+                }
+            }
+
+            ProfilesSubcommands::RemoveOverride { profile_name, name } => {
                 let profiles = util::enumerate_profiles().unwrap_or_else(|_| vec![]);
 
-                let empty = HashMap::new();
+                if let Some(profile) = profiles
+                    .iter()
+                    .find(|p| *p.profile_file.to_string_lossy() == profile_name)
+                {
+                    let mut overrides = profile.overrides.clone();
+                    overrides.retain(|o| o.name != name);
+
+                    if overrides.len() < profile.overrides.len() {
+                        let data = profile.serialize_with_overrides(&overrides)?;
+                        dbus_client::write_file(&profile.profile_file, &data)?;
+                    } else {
+                        eprintln!("No matches found");
+                    }
+                } else {
+                    eprintln!("No matches found");
+                }
+            }
+
+            ProfilesSubcommands::Info { profile_name } => {
+                let profiles = util::enumerate_profiles().unwrap_or_else(|_| vec![]);
 
                 if let Some(profile) = profiles
                     .iter()
@@ -1099,8 +1509,8 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                         profile.name,
                         profile.id,
                         profile.description,
-                        profile.active_scripts,
-                        profile.config.as_ref().unwrap_or(&empty),
+                        profile.get_active_scripts(),
+                        profile.get_config(),
                     );
                 } else {
                     eprintln!("No matches found");
@@ -1175,14 +1585,17 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                     .iter()
                     .find(|s| *s.script_file.to_string_lossy() == script_name)
                 {
+                    let empty_capabilities = vec![];
+
                     println!(
-                        "Lua script:\t{} ({})\nDaemon version:\t{}\nAuthor:\t\t{}\nDescription:\t{}\nTags:\t\t{:?}",
+                        "Lua script:\t{} ({})\nDaemon version:\t{}\nAuthor:\t\t{}\nDescription:\t{}\nTags:\t\t{:?}\nCapabilities:\t{:?}",
                         script.name,
                         script.version,
                         script.min_supported_version,
                         script.author,
                         script.description,
                         script.tags.as_ref().unwrap_or(&empty),
+                        script.capabilities.as_ref().unwrap_or(&empty_capabilities),
                     );
                 } else {
                     eprintln!("No matches found");
@@ -1212,22 +1625,23 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                 {
                     println!(
                         "Profile:\t{} ({})\nDescription:\t{}\nScripts:\t{:?}\n",
-                        profile.name, profile.id, profile.description, profile.active_scripts,
+                        profile.name,
+                        profile.id,
+                        profile.description,
+                        profile.get_active_scripts(),
                     );
 
                     // dump parameters set in .profile file
 
                     println!("Profile parameters:\n");
 
-                    let empty = HashMap::new();
-
                     let scripts = util::enumerate_scripts()?;
 
                     for script in &scripts {
-                        if profile.active_scripts.contains(&PathBuf::from(
+                        if profile.get_active_scripts().contains(&PathBuf::from(
                             script.script_file.file_name().unwrap_or_default(),
                         )) {
-                            let config = profile.config.as_ref().unwrap_or(&empty);
+                            let config = profile.get_config();
                             let config_params = config.get(&script.name);
 
                             if let Some(config_params) = config_params {
@@ -1257,7 +1671,7 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                         println!("Available parameters:\n");
 
                         for script in &scripts {
-                            if profile.active_scripts.contains(&PathBuf::from(
+                            if profile.get_active_scripts().contains(&PathBuf::from(
                                 script.script_file.file_name().unwrap_or_default(),
                             )) {
                                 if let Some(config_params) = script.config.as_ref() {
@@ -1300,13 +1714,18 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                     {
                         println!(
                             "Profile:\t{} ({})\nDescription:\t{}\nScripts:\t{:?}\n",
-                            profile.name, profile.id, profile.description, profile.active_scripts,
+                            profile.name,
+                            profile.id,
+                            profile.description,
+                            profile.get_active_scripts(),
                         );
 
                         let scripts = util::enumerate_scripts()?;
 
                         for scr in scripts {
                             if scr.name == script {
+                                validate_parameter(&scr, &parameter, &value)?;
+
                                 // set param value
                                 dbus_client::set_parameter(
                                     &*profile.profile_file.to_string_lossy(),
@@ -1332,24 +1751,24 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                         .iter()
                         .find(|&p| *p.profile_file.to_string_lossy() == profile_name)
                     {
-                        let empty = HashMap::new();
-
                         let scripts = util::enumerate_scripts()?;
 
                         'OUTER_LOOP: for script in scripts {
-                            if profile.active_scripts.contains(&PathBuf::from(
+                            if profile.get_active_scripts().contains(&PathBuf::from(
                                 script.script_file.file_name().unwrap_or_default(),
                             )) {
-                                let config = profile.config.as_ref().unwrap_or(&empty);
+                                let config = profile.get_config();
                                 if let Some(config) = config.get(&script.name) {
                                     for config in config.iter() {
                                         if config.get_name() == &parameter {
                                             if let Some(value) = &value {
                                                 println!(
                                                     "Profile:\t{} ({})\nDescription:\t{}\nScripts:\t{:?}\n",
-                                                    profile.name, profile.id, profile.description, profile.active_scripts,
+                                                    profile.name, profile.id, profile.description, profile.get_active_scripts(),
                                                 );
 
+                                                validate_parameter(&script, &parameter, value)?;
+
                                                 // set param value
                                                 dbus_client::set_parameter(
                                                     &*profile.profile_file.to_string_lossy(),
@@ -1369,7 +1788,7 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                                             } else {
                                                 println!(
                                                     "Profile:\t{} ({})\nDescription:\t{}\nScripts:\t{:?}\n",
-                                                    profile.name, profile.id, profile.description, profile.active_scripts,
+                                                    profile.name, profile.id, profile.description, profile.get_active_scripts(),
                                                 );
 
                                                 // read param value
@@ -1405,6 +1824,10 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                                     param.get_name().bold(),
                                     param.get_default()
                                 );
+
+                                if let manifest::ConfigParam::Enum { .. } = param {
+                                    println!("  {}", param_hint(&param));
+                                }
                             }
                         }
                     }
@@ -1432,6 +1855,58 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                     + 1;
                 println!("Current slot: {}", format!("{}", index).bold());
             }
+
+            StatusSubcommands::Scripts => {
+                let script_stats = get_script_stats()
+                    .await
+                    .wrap_err("Could not connect to the Eruption daemon")
+                    .suggestion("Please verify that the Eruption daemon is running")?;
+
+                let mut table = Table::new();
+                table
+                    .load_preset(UTF8_FULL)
+                    .apply_modifier(UTF8_ROUND_CORNERS)
+                    .set_content_arrangement(ContentArrangement::Dynamic)
+                    .set_header(vec![
+                        "Script",
+                        "Upcalls",
+                        "Avg. ms",
+                        "Max. ms",
+                        "Over budget",
+                        "Skipped ticks",
+                    ]);
+
+                for (script_file, upcalls, average, max, overruns, skipped_ticks) in script_stats {
+                    table.add_row(vec![
+                        Cell::new(script_file).set_alignment(CellAlignment::Left),
+                        Cell::new(upcalls).set_alignment(CellAlignment::Right),
+                        Cell::new(format!("{:.2}", average)).set_alignment(CellAlignment::Right),
+                        Cell::new(format!("{:.2}", max)).set_alignment(CellAlignment::Right),
+                        Cell::new(overruns).set_alignment(CellAlignment::Right),
+                        Cell::new(skipped_ticks).set_alignment(CellAlignment::Right),
+                    ]);
+                }
+
+                println!("{}", table);
+
+                let script_status = get_script_status()
+                    .await
+                    .wrap_err("Could not connect to the Eruption daemon")
+                    .suggestion("Please verify that the Eruption daemon is running")?;
+
+                if script_status.is_empty() {
+                    println!("No script crashed since the profile has been activated");
+                } else {
+                    for (script_file, crash_count, last_error) in script_status {
+                        println!(
+                            "{}: {} crash(es)\n{}\n",
+                            script_file.bold(),
+                            format!("{}", crash_count).bold(),
+                            last_error
+                        );
+                    }
+                }
+            }
         },
 
         // convenience operations: switch profile or slot
@@ -1447,6 +1922,8 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
 
                 match profile_name {
                     Ok(profile_name) => {
+                        print_requested_capabilities(&profile_name)?;
+
                         println!(
                             "Switching to profile: {}",
                             profile_name.display().to_string().bold()
@@ -0,0 +1,35 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use std::path::Path;
+
+use crate::constants;
+
+/// Returns `true` if the script `script_file` has been installed to the system script
+/// directory. Only those scripts are trusted to use their requested capabilities
+/// without a confirmation by the user
+pub fn is_system_script(script_file: &Path) -> bool {
+    let system_dir = Path::new(constants::DEFAULT_SCRIPT_DIR);
+
+    match (script_file.canonicalize(), system_dir.canonicalize()) {
+        (Ok(script_file), Ok(system_dir)) => script_file.starts_with(system_dir),
+
+        _ => false,
+    }
+}
@@ -42,8 +42,8 @@ fn main() -> Result<(), eyre::Error> {
     let status = connection.get_server_status()?;
     println!("{:?}", status);
 
-    // create a new canvas
-    let mut canvas = Canvas::new();
+    // create a new canvas, matching the dimensions of the canvas of the daemon
+    let mut canvas = Canvas::with_dimensions(status.canvas_width, status.canvas_height);
 
     let red = Color::new(255, 0, 0, 128);
     let green = Color::new(0, 255, 0, 128);
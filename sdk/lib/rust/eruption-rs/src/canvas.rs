@@ -20,18 +20,64 @@
 use crate::color::Color;
 use std::ops;
 
-const CANVAS_SIZE: usize = 144 + 36;
+/// Width of the default canvas of the Eruption daemon
+pub const CANVAS_WIDTH: usize = 22 + 8;
 
-#[derive(Debug, Default, Clone)]
+/// Height of the default canvas of the Eruption daemon
+pub const CANVAS_HEIGHT: usize = 6;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Canvas {
     pub(crate) data: Vec<Color>,
+    width: usize,
+    height: usize,
 }
 
 impl Canvas {
+    /// Create a canvas with the dimensions of the default canvas
     pub fn new() -> Self {
+        Self::with_dimensions(CANVAS_WIDTH, CANVAS_HEIGHT)
+    }
+
+    /// Create a canvas with the specified dimensions, use the dimensions reported in
+    /// `ServerStatus` to match the canvas of the Eruption daemon
+    pub fn with_dimensions(width: usize, height: usize) -> Self {
         Self {
-            data: vec![Color::default(); CANVAS_SIZE],
+            data: vec![Color::default(); width * height],
+            width,
+            height,
+        }
+    }
+
+    /// Create a canvas from a sequence of RGBA bytes, as sent by the Eruption daemon
+    pub(crate) fn from_rgba(width: usize, height: usize, data: &[u8]) -> Self {
+        let mut result = Self::with_dimensions(width, height);
+
+        for (color, rgba) in result.data.iter_mut().zip(data.chunks_exact(4)) {
+            *color = Color::new(rgba[0], rgba[1], rgba[2], rgba[3]);
         }
+
+        result
+    }
+
+    /// Returns the width of the canvas
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height of the canvas
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the number of "pixels" on the canvas
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns `true` if the canvas has no "pixels"
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
     }
 
     /// Paint the canvas with the specified color
@@ -18,7 +18,7 @@
 */
 
 /// Implements a RGBA color value
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Color {
     r: u8,
     g: u8,
@@ -18,11 +18,13 @@
 */
 
 use crate::canvas::Canvas;
+use crate::events::{Event, EventType};
 use crate::hardware::HotplugInfo;
-use crate::transport::{LocalTransport, ServerStatus, Transport};
+use crate::transport::{LocalTransport, RemoteTransport, ServerStatus, Transport};
 use crate::Result;
 use parking_lot::Mutex;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct Connection {
@@ -32,7 +34,7 @@ pub struct Connection {
 impl Connection {
     pub fn new(connection_type: ConnectionType) -> Result<Self> {
         Ok(Self {
-            con: Arc::new(Mutex::new(make_transport(&connection_type)?)),
+            con: make_transport(&connection_type)?,
         })
     }
 
@@ -44,10 +46,22 @@ impl Connection {
         self.con.lock().disconnect()
     }
 
+    /// Get the most recently rendered canvas of the Eruption daemon
+    pub fn get_canvas(&self) -> Result<Canvas> {
+        self.con.lock().get_canvas()
+    }
+
     pub fn submit_canvas(&self, canvas: &Canvas) -> Result<()> {
         self.con.lock().submit_canvas(canvas)
     }
 
+    /// Set the parameters of the canvas layer of this connection. Layers with a higher
+    /// `priority` are composited on top of layers with a lower priority, `opacity`
+    /// applies to the whole layer
+    pub fn set_layer_params(&self, priority: i32, opacity: u8) -> Result<()> {
+        self.con.lock().set_layer_params(priority, opacity)
+    }
+
     pub fn get_server_status(&self) -> Result<ServerStatus> {
         self.con.lock().get_server_status()
     }
@@ -55,6 +69,19 @@ impl Connection {
     pub fn notify_device_hotplug(&self, hotplug_info: &HotplugInfo) -> Result<()> {
         self.con.lock().notify_device_hotplug(&hotplug_info)
     }
+
+    /// Subscribe to the events of the types `event_types`, an empty slice cancels the subscription
+    pub fn subscribe_events(&self, event_types: &[EventType]) -> Result<()> {
+        self.con.lock().subscribe_events(event_types)
+    }
+
+    /// Wait for the next event the connection is subscribed to. Returns `None` if no
+    /// event occurred until `timeout` elapsed, a `timeout` of `None` waits indefinitely.
+    /// Other requests on this connection are blocked while waiting, so consider using a
+    /// dedicated connection for events
+    pub fn next_event(&self, timeout: Option<Duration>) -> Result<Option<Event>> {
+        self.con.lock().next_event(timeout)
+    }
 }
 
 impl Drop for Connection {
@@ -72,10 +99,17 @@ pub enum ConnectionType {
     /// Local transport
     Local,
 
-    /// Type REMOTE is currently not implemented
-    Remote,
+    /// Remote transport, connects to the Eruption daemon over the network. The daemon
+    /// has to be configured to accept remote connections, using the same shared `secret`
+    Remote { address: String, secret: String },
 }
 
-fn make_transport(_connection_type: &ConnectionType) -> Result<impl Transport> {
-    LocalTransport::new()
+fn make_transport(connection_type: &ConnectionType) -> Result<Arc<Mutex<dyn Transport>>> {
+    match connection_type {
+        ConnectionType::Remote { address, secret } => {
+            Ok(Arc::new(Mutex::new(RemoteTransport::new(address, secret)?)))
+        }
+
+        _ => Ok(Arc::new(Mutex::new(LocalTransport::new()?))),
+    }
 }
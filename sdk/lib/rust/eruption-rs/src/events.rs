@@ -0,0 +1,205 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use crate::canvas::Canvas;
+use crate::transport::protocol;
+use protocol::event::Payload as EventPayload;
+
+/// Types of events that clients may subscribe to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EventType {
+    KeyDown,
+    KeyUp,
+    MouseButtonDown,
+    MouseButtonUp,
+    ProfileChanged,
+    SlotChanged,
+    BrightnessChanged,
+    DeviceHotplug,
+    FrameRendered,
+}
+
+impl EventType {
+    /// All of the available event types
+    pub fn all() -> Vec<EventType> {
+        vec![
+            EventType::KeyDown,
+            EventType::KeyUp,
+            EventType::MouseButtonDown,
+            EventType::MouseButtonUp,
+            EventType::ProfileChanged,
+            EventType::SlotChanged,
+            EventType::BrightnessChanged,
+            EventType::DeviceHotplug,
+            EventType::FrameRendered,
+        ]
+    }
+}
+
+impl From<EventType> for protocol::EventType {
+    fn from(event_type: EventType) -> Self {
+        match event_type {
+            EventType::KeyDown => protocol::EventType::KeyDown,
+            EventType::KeyUp => protocol::EventType::KeyUp,
+            EventType::MouseButtonDown => protocol::EventType::MouseButtonDown,
+            EventType::MouseButtonUp => protocol::EventType::MouseButtonUp,
+            EventType::ProfileChanged => protocol::EventType::ProfileChanged,
+            EventType::SlotChanged => protocol::EventType::SlotChanged,
+            EventType::BrightnessChanged => protocol::EventType::BrightnessChanged,
+            EventType::DeviceHotplug => protocol::EventType::DeviceHotplug,
+            EventType::FrameRendered => protocol::EventType::FrameRendered,
+        }
+    }
+}
+
+/// An event that occurred in the Eruption daemon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A key has been pressed, carries the key index
+    KeyDown(u32),
+
+    /// A key has been released, carries the key index
+    KeyUp(u32),
+
+    /// A mouse button has been pressed, carries the button index
+    MouseButtonDown(u32),
+
+    /// A mouse button has been released, carries the button index
+    MouseButtonUp(u32),
+
+    /// Another profile has been activated, carries the file name of the profile
+    ProfileChanged(Option<String>),
+
+    /// Another slot has been activated
+    SlotChanged(usize),
+
+    /// The global brightness has been changed
+    BrightnessChanged(i32),
+
+    /// A device has been added or removed
+    DeviceHotplug {
+        usb_vid: u16,
+        usb_pid: u16,
+        removed: bool,
+    },
+
+    /// A frame has been rendered, carries the contents of the canvas
+    FrameRendered(Canvas),
+}
+
+impl Event {
+    /// Convert an event received from the daemon, returns `None` for unknown events
+    pub(crate) fn from_protocol(event: protocol::Event) -> Option<Self> {
+        match (event.event_type(), event.payload?) {
+            (protocol::EventType::KeyDown, EventPayload::KeyIndex(index)) => {
+                Some(Event::KeyDown(index))
+            }
+
+            (protocol::EventType::KeyUp, EventPayload::KeyIndex(index)) => {
+                Some(Event::KeyUp(index))
+            }
+
+            (protocol::EventType::MouseButtonDown, EventPayload::ButtonIndex(index)) => {
+                Some(Event::MouseButtonDown(index))
+            }
+
+            (protocol::EventType::MouseButtonUp, EventPayload::ButtonIndex(index)) => {
+                Some(Event::MouseButtonUp(index))
+            }
+
+            (protocol::EventType::ProfileChanged, EventPayload::ProfileFile(profile_file)) => Some(
+                Event::ProfileChanged(Some(profile_file).filter(|f| !f.is_empty())),
+            ),
+
+            (protocol::EventType::SlotChanged, EventPayload::Slot(slot)) => {
+                Some(Event::SlotChanged(slot as usize))
+            }
+
+            (protocol::EventType::BrightnessChanged, EventPayload::Brightness(brightness)) => {
+                Some(Event::BrightnessChanged(brightness))
+            }
+
+            (protocol::EventType::DeviceHotplug, EventPayload::Hotplug(hotplug)) => {
+                Some(Event::DeviceHotplug {
+                    usb_vid: hotplug.usb_vid as u16,
+                    usb_pid: hotplug.usb_pid as u16,
+                    removed: hotplug.removed,
+                })
+            }
+
+            (protocol::EventType::FrameRendered, EventPayload::Frame(frame)) => {
+                Some(Event::FrameRendered(Canvas::from_rgba(
+                    frame.width as usize,
+                    frame.height as usize,
+                    &frame.data,
+                )))
+            }
+
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    #[test]
+    fn convert_protocol_events() {
+        let mut event = protocol::Event::default();
+        event.set_event_type(protocol::EventType::KeyDown);
+        event.payload = Some(EventPayload::KeyIndex(42));
+
+        assert_eq!(Event::from_protocol(event), Some(Event::KeyDown(42)));
+
+        let mut event = protocol::Event::default();
+        event.set_event_type(protocol::EventType::ProfileChanged);
+        event.payload = Some(EventPayload::ProfileFile(String::new()));
+
+        assert_eq!(
+            Event::from_protocol(event),
+            Some(Event::ProfileChanged(None))
+        );
+
+        // the payload does not match the event type
+        let mut event = protocol::Event::default();
+        event.set_event_type(protocol::EventType::SlotChanged);
+        event.payload = Some(EventPayload::KeyIndex(1));
+
+        assert_eq!(Event::from_protocol(event), None);
+
+        let mut event = protocol::Event::default();
+        event.set_event_type(protocol::EventType::FrameRendered);
+        event.payload = Some(EventPayload::Frame(protocol::Frame {
+            width: 2,
+            height: 1,
+            data: vec![1, 2, 3, 4, 5, 6, 7, 8],
+        }));
+
+        match Event::from_protocol(event) {
+            Some(Event::FrameRendered(canvas)) => {
+                assert_eq!(canvas.len(), 2);
+                assert_eq!(canvas[1], Color::new(5, 6, 7, 8));
+            }
+
+            event => panic!("Unexpected event: {:?}", event),
+        }
+    }
+}
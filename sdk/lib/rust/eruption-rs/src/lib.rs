@@ -20,6 +20,7 @@
 pub mod canvas;
 pub mod color;
 pub mod connection;
+pub mod events;
 pub mod hardware;
 pub mod transport;
 pub mod util;
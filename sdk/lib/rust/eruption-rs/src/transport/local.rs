@@ -18,8 +18,9 @@
 */
 
 use crate::canvas::Canvas;
+use crate::events::{Event, EventType};
 use crate::hardware::HotplugInfo;
-use crate::transport::{ServerStatus, Transport};
+use crate::transport::{protocol, ServerStatus, Transport};
 use crate::{util, Result};
 use eyre::eyre;
 use parking_lot::Mutex;
@@ -27,20 +28,22 @@ use prost::Message;
 use protocol::request::Payload as RequestPayload;
 use protocol::response::Payload as ResponsePayload;
 use socket2::{Domain, SockAddr, Socket, Type};
-use std::io::{Cursor, Write};
+use std::collections::VecDeque;
+use std::io::{self, Cursor, Write};
 use std::mem::MaybeUninit;
 use std::sync::Arc;
-
-pub mod protocol {
-    include!(concat!(env!("OUT_DIR"), "/sdk_support.rs"));
-}
+use std::time::Duration;
 
 const SOCKET_ADDRESS: &str = "/run/eruption/control.sock";
-const MAX_BUF: usize = 4096;
+/// Large enough to hold a frame of the largest possible canvas
+const MAX_BUF: usize = 65536;
 
 #[derive(Debug, Clone)]
 pub struct LocalTransport {
     pub(crate) socket: Arc<Mutex<Socket>>,
+
+    /// Events that have been received while waiting for a response
+    pending_events: Arc<Mutex<VecDeque<Event>>>,
 }
 
 impl LocalTransport {
@@ -51,8 +54,56 @@ impl LocalTransport {
                 Type::SEQPACKET,
                 None,
             )?)),
+            pending_events: Arc::new(Mutex::new(VecDeque::new())),
         })
     }
+
+    /// Receive a single message from the daemon
+    fn receive(socket: &Socket) -> Result<protocol::Response> {
+        let mut tmp = [MaybeUninit::zeroed(); MAX_BUF];
+
+        match socket.recv(&mut tmp) {
+            Ok(0) => Err(eyre!("Lost connection to Eruption")),
+
+            Ok(_n) => {
+                let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
+                let result = protocol::Response::decode_length_delimited(&mut Cursor::new(&tmp))?;
+
+                Ok(result)
+            }
+
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Send `request` and wait for the response of the daemon. Events that arrive in
+    /// the meantime are queued, so that they may be retrieved using `next_event()`
+    fn transact(&self, request: &protocol::Request) -> Result<protocol::Response> {
+        let mut buf = Vec::new();
+        request.encode_length_delimited(&mut buf)?;
+
+        // send data
+        let socket = self.socket.lock();
+        match socket.send(&buf) {
+            Ok(_n) => loop {
+                // read response
+                let response =
+                    Self::receive(&socket).map_err(|_e| eyre!("Lost connection to Eruption"))?;
+
+                match response.payload {
+                    Some(ResponsePayload::Event(event)) => {
+                        if let Some(event) = Event::from_protocol(event) {
+                            self.pending_events.lock().push_back(event);
+                        }
+                    }
+
+                    _ => break Ok(response),
+                }
+            },
+
+            Err(_e) => Err(eyre!("Lost connection to Eruption")),
+        }
+    }
 }
 
 impl Transport for LocalTransport {
@@ -74,35 +125,33 @@ impl Transport for LocalTransport {
         let mut request = protocol::Request::default();
         request.set_request_type(protocol::RequestType::Status);
 
-        let mut buf = Vec::new();
-        request.encode_length_delimited(&mut buf)?;
+        let result = self.transact(&request)?;
 
-        // send data
-        let socket = self.socket.lock();
-        match socket.send(&buf) {
-            Ok(_n) => {
-                // read response
-                let mut tmp = [MaybeUninit::zeroed(); MAX_BUF];
+        match result.payload {
+            Some(ResponsePayload::Data(payload)) => Ok(ServerStatus {
+                server: String::from_utf8_lossy(&payload).to_string(),
+                canvas_width: result.canvas_width as usize,
+                canvas_height: result.canvas_height as usize,
+            }),
 
-                match socket.recv(&mut tmp) {
-                    Ok(0) => Err(eyre!("Lost connection to Eruption")),
+            _ => Err(eyre!("Invalid response received from Eruption")),
+        }
+    }
 
-                    Ok(_n) => {
-                        let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
-                        let result =
-                            protocol::Response::decode_length_delimited(&mut Cursor::new(&tmp))?;
-                        let ResponsePayload::Data(payload) = result.payload.unwrap();
+    fn get_canvas(&self) -> Result<Canvas> {
+        let mut request = protocol::Request::default();
+        request.set_request_type(protocol::RequestType::GetCanvas);
 
-                        Ok(ServerStatus {
-                            server: String::from_utf8_lossy(&payload).to_string(),
-                        })
-                    }
+        let result = self.transact(&request)?;
 
-                    Err(_e) => Err(eyre!("Lost connection to Eruption")),
-                }
-            }
+        match result.payload {
+            Some(ResponsePayload::Data(payload)) => Ok(Canvas::from_rgba(
+                result.canvas_width as usize,
+                result.canvas_height as usize,
+                &payload,
+            )),
 
-            Err(_e) => Err(eyre!("Lost connection to Eruption")),
+            _ => Err(eyre!("Invalid response received from Eruption")),
         }
     }
 
@@ -118,33 +167,23 @@ impl Transport for LocalTransport {
 
         request.payload = Some(RequestPayload::Data(bytes));
 
-        let mut buf = Vec::new();
-        request.encode_length_delimited(&mut buf)?;
+        let _result = self.transact(&request)?;
 
-        // send data
-        let socket = self.socket.lock();
-        match socket.send(&buf) {
-            Ok(_n) => {
-                // read response
-                let mut tmp = [MaybeUninit::zeroed(); MAX_BUF];
+        Ok(())
+    }
 
-                match socket.recv(&mut tmp) {
-                    Ok(0) => Err(eyre!("Lost connection to Eruption")),
+    fn set_layer_params(&self, priority: i32, opacity: u8) -> Result<()> {
+        let mut request = protocol::Request::default();
+        request.set_request_type(protocol::RequestType::SetLayerParams);
 
-                    Ok(_n) => {
-                        let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
-                        let _result =
-                            protocol::Response::decode_length_delimited(&mut Cursor::new(&tmp))?;
+        request.payload = Some(RequestPayload::LayerParams(protocol::LayerParams {
+            priority,
+            opacity: opacity as u32,
+        }));
 
-                        Ok(())
-                    }
+        let _result = self.transact(&request)?;
 
-                    Err(_e) => Err(eyre!("Lost connection to Eruption")),
-                }
-            }
-
-            Err(_e) => Err(eyre!("Lost connection to Eruption")),
-        }
+        Ok(())
     }
 
     fn notify_device_hotplug(&self, hotplug_info: &HotplugInfo) -> Result<()> {
@@ -156,32 +195,51 @@ impl Transport for LocalTransport {
 
         request.payload = Some(RequestPayload::Data(bytes));
 
-        let mut buf = Vec::new();
-        request.encode_length_delimited(&mut buf)?;
+        let _result = self.transact(&request)?;
+
+        Ok(())
+    }
+
+    fn subscribe_events(&self, event_types: &[EventType]) -> Result<()> {
+        let mut request = protocol::Request::default();
+        request.set_request_type(protocol::RequestType::SubscribeEvents);
+
+        let mut subscription = protocol::Subscription::default();
+        for event_type in event_types {
+            subscription.push_event_types((*event_type).into());
+        }
+
+        request.payload = Some(RequestPayload::Subscription(subscription));
+
+        let _result = self.transact(&request)?;
+
+        Ok(())
+    }
+
+    fn next_event(&self, timeout: Option<Duration>) -> Result<Option<Event>> {
+        if let Some(event) = self.pending_events.lock().pop_front() {
+            return Ok(Some(event));
+        }
 
-        // send data
         let socket = self.socket.lock();
-        match socket.send(&buf) {
-            Ok(_n) => {
-                // read response
-                let mut tmp = [MaybeUninit::zeroed(); MAX_BUF];
+        socket.set_read_timeout(timeout)?;
 
-                match socket.recv(&mut tmp) {
-                    Ok(0) => Err(eyre!("Lost connection to Eruption")),
+        let result = Self::receive(&socket);
 
-                    Ok(_n) => {
-                        let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
-                        let _result =
-                            protocol::Response::decode_length_delimited(&mut Cursor::new(&tmp))?;
+        socket.set_read_timeout(None)?;
 
-                        Ok(())
-                    }
+        match result {
+            Ok(response) => match response.payload {
+                Some(ResponsePayload::Event(event)) => Ok(Event::from_protocol(event)),
 
-                    Err(_e) => Err(eyre!("Lost connection to Eruption")),
-                }
-            }
+                _ => Ok(None),
+            },
 
-            Err(_e) => Err(eyre!("Lost connection to Eruption")),
+            Err(e) => match e.downcast_ref::<io::Error>().map(|e| e.kind()) {
+                Some(io::ErrorKind::WouldBlock) | Some(io::ErrorKind::TimedOut) => Ok(None),
+
+                _ => Err(eyre!("Lost connection to Eruption")),
+            },
         }
     }
 }
@@ -19,22 +19,38 @@
 
 use crate::canvas::Canvas;
 use crate::Result;
+use crate::events::{Event, EventType};
 use crate::hardware::HotplugInfo;
+use std::time::Duration;
 
 mod local;
+mod remote;
+
 pub use local::*;
+pub use remote::*;
+
+pub mod protocol {
+    include!(concat!(env!("OUT_DIR"), "/sdk_support.rs"));
+}
 
 pub trait Transport {
     fn connect(&mut self) -> Result<()>;
     fn disconnect(&mut self) -> Result<()>;
 
     fn get_server_status(&self) -> Result<ServerStatus>;
+    fn get_canvas(&self) -> Result<Canvas>;
     fn submit_canvas(&self, canvas: &Canvas) -> Result<()>;
+    fn set_layer_params(&self, priority: i32, opacity: u8) -> Result<()>;
 
     fn notify_device_hotplug(&self, hotplug_info: &HotplugInfo) -> Result<()>;
+
+    fn subscribe_events(&self, event_types: &[EventType]) -> Result<()>;
+    fn next_event(&self, timeout: Option<Duration>) -> Result<Option<Event>>;
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct ServerStatus {
     pub server: String,
+    pub canvas_width: usize,
+    pub canvas_height: usize,
 }
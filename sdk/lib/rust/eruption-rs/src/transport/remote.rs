@@ -0,0 +1,400 @@
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use crate::canvas::Canvas;
+use crate::events::{Event, EventType};
+use crate::hardware::HotplugInfo;
+use crate::transport::{protocol, ServerStatus, Transport};
+use crate::Result;
+use eyre::eyre;
+use parking_lot::Mutex;
+use prost::Message;
+use protocol::request::Payload as RequestPayload;
+use protocol::response::Payload as ResponsePayload;
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Max. length of a varint encoded length delimiter
+const MAX_DELIMITER_LEN: usize = 10;
+
+/// Max. size of a response that we are willing to accept
+const MAX_BUF: usize = 65536;
+
+/// Connects to an Eruption daemon over the network, using TCP
+#[derive(Debug, Clone)]
+pub struct RemoteTransport {
+    address: String,
+    secret: String,
+    pub(crate) stream: Arc<Mutex<Option<TcpStream>>>,
+
+    /// Events that have been received while waiting for a response
+    pending_events: Arc<Mutex<VecDeque<Event>>>,
+}
+
+impl RemoteTransport {
+    /// Create a transport for the daemon listening on `address`, e.g. "192.168.1.10:2360",
+    /// that authenticates using the shared `secret`
+    pub fn new(address: &str, secret: &str) -> Result<Self> {
+        Ok(Self {
+            address: address.to_string(),
+            secret: secret.to_string(),
+            stream: Arc::new(Mutex::new(None)),
+            pending_events: Arc::new(Mutex::new(VecDeque::new())),
+        })
+    }
+
+    /// Send `request` and wait for the response of the daemon. Events that arrive in
+    /// the meantime are queued, so that they may be retrieved using `next_event()`
+    fn transact(&self, request: &protocol::Request) -> Result<protocol::Response> {
+        let mut stream = self.stream.lock();
+        let stream = stream
+            .as_mut()
+            .ok_or_else(|| eyre!("Not connected to Eruption"))?;
+
+        let mut buf = Vec::new();
+        request.encode_length_delimited(&mut buf)?;
+
+        // send data
+        stream
+            .write_all(&buf)
+            .map_err(|_e| eyre!("Lost connection to Eruption"))?;
+
+        loop {
+            // read response
+            let response: protocol::Response = read_message(stream)?;
+
+            match response.payload {
+                Some(ResponsePayload::Event(event)) => {
+                    if let Some(event) = Event::from_protocol(event) {
+                        self.pending_events.lock().push_back(event);
+                    }
+                }
+
+                _ => break Ok(response),
+            }
+        }
+    }
+
+    fn authenticate(&self) -> Result<()> {
+        let mut request = protocol::Request::default();
+        request.set_request_type(protocol::RequestType::Authenticate);
+
+        let response = self.transact(&request)?;
+
+        match response.payload {
+            Some(ResponsePayload::Data(challenge)) => {
+                request.payload = Some(RequestPayload::Data(auth_digest(&challenge, &self.secret)));
+
+                // the daemon closes the connection if the authentication failed
+                self.transact(&request)
+                    .map_err(|_e| eyre!("Authentication failed"))?;
+
+                Ok(())
+            }
+
+            // no challenge has been issued, so no authentication is required
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Transport for RemoteTransport {
+    fn connect(&mut self) -> Result<()> {
+        let stream = TcpStream::connect(&self.address)?;
+        stream.set_nodelay(true)?;
+
+        self.stream.lock().replace(stream);
+
+        self.authenticate()
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        if let Some(stream) = self.stream.lock().take() {
+            stream.shutdown(Shutdown::Both)?;
+        }
+
+        Ok(())
+    }
+
+    fn get_server_status(&self) -> Result<ServerStatus> {
+        let mut request = protocol::Request::default();
+        request.set_request_type(protocol::RequestType::Status);
+
+        let response = self.transact(&request)?;
+
+        match response.payload {
+            Some(ResponsePayload::Data(payload)) => Ok(ServerStatus {
+                server: String::from_utf8_lossy(&payload).to_string(),
+                canvas_width: response.canvas_width as usize,
+                canvas_height: response.canvas_height as usize,
+            }),
+
+            _ => Err(eyre!("Invalid response received from Eruption")),
+        }
+    }
+
+    fn get_canvas(&self) -> Result<Canvas> {
+        let mut request = protocol::Request::default();
+        request.set_request_type(protocol::RequestType::GetCanvas);
+
+        let response = self.transact(&request)?;
+
+        match response.payload {
+            Some(ResponsePayload::Data(payload)) => Ok(Canvas::from_rgba(
+                response.canvas_width as usize,
+                response.canvas_height as usize,
+                &payload,
+            )),
+
+            _ => Err(eyre!("Invalid response received from Eruption")),
+        }
+    }
+
+    fn submit_canvas(&self, canvas: &Canvas) -> Result<()> {
+        let mut request = protocol::Request::default();
+        request.set_request_type(protocol::RequestType::SetCanvas);
+
+        let bytes: Vec<u8> = canvas
+            .data
+            .iter()
+            .flat_map(|c| vec![c.r(), c.g(), c.b(), c.a()])
+            .collect();
+
+        request.payload = Some(RequestPayload::Data(bytes));
+
+        let _response = self.transact(&request)?;
+
+        Ok(())
+    }
+
+    fn set_layer_params(&self, priority: i32, opacity: u8) -> Result<()> {
+        let mut request = protocol::Request::default();
+        request.set_request_type(protocol::RequestType::SetLayerParams);
+
+        request.payload = Some(RequestPayload::LayerParams(protocol::LayerParams {
+            priority,
+            opacity: opacity as u32,
+        }));
+
+        let _response = self.transact(&request)?;
+
+        Ok(())
+    }
+
+    fn notify_device_hotplug(&self, hotplug_info: &HotplugInfo) -> Result<()> {
+        let mut request = protocol::Request::default();
+        request.set_request_type(protocol::RequestType::NotifyHotplug);
+
+        let config = bincode::config::standard();
+        let bytes: Vec<u8> = bincode::encode_to_vec(&hotplug_info, config)?;
+
+        request.payload = Some(RequestPayload::Data(bytes));
+
+        let _response = self.transact(&request)?;
+
+        Ok(())
+    }
+
+    fn subscribe_events(&self, event_types: &[EventType]) -> Result<()> {
+        let mut request = protocol::Request::default();
+        request.set_request_type(protocol::RequestType::SubscribeEvents);
+
+        let mut subscription = protocol::Subscription::default();
+        for event_type in event_types {
+            subscription.push_event_types((*event_type).into());
+        }
+
+        request.payload = Some(RequestPayload::Subscription(subscription));
+
+        let _response = self.transact(&request)?;
+
+        Ok(())
+    }
+
+    fn next_event(&self, timeout: Option<Duration>) -> Result<Option<Event>> {
+        if let Some(event) = self.pending_events.lock().pop_front() {
+            return Ok(Some(event));
+        }
+
+        let mut stream = self.stream.lock();
+        let stream = stream
+            .as_mut()
+            .ok_or_else(|| eyre!("Not connected to Eruption"))?;
+
+        // wait for the first byte of a message only, so that we
+        // never time out in the middle of a partially received message
+        stream.set_read_timeout(timeout)?;
+        let result = stream.peek(&mut [0_u8; 1]);
+        stream.set_read_timeout(None)?;
+
+        match result {
+            Ok(0) => Err(eyre!("Lost connection to Eruption")),
+
+            Ok(_n) => {
+                let response: protocol::Response = read_message(stream)?;
+
+                match response.payload {
+                    Some(ResponsePayload::Event(event)) => Ok(Event::from_protocol(event)),
+
+                    _ => Ok(None),
+                }
+            }
+
+            Err(e) => match e.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Ok(None),
+
+                _ => Err(eyre!("Lost connection to Eruption")),
+            },
+        }
+    }
+}
+
+impl Drop for RemoteTransport {
+    fn drop(&mut self) {
+        let _ = self.disconnect();
+    }
+}
+
+/// Compute the response to an authentication `challenge` issued by the daemon
+pub fn auth_digest(challenge: &[u8], secret: &str) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(challenge);
+    hasher.update(secret.as_bytes());
+
+    hasher.finalize().to_vec()
+}
+
+/// Read a single length delimited message from a stream
+fn read_message<M: Message + Default>(stream: &mut impl Read) -> Result<M> {
+    let mut delimiter = Vec::with_capacity(MAX_DELIMITER_LEN);
+
+    let len = loop {
+        let mut byte = [0u8; 1];
+        stream
+            .read_exact(&mut byte)
+            .map_err(|_e| eyre!("Lost connection to Eruption"))?;
+
+        delimiter.push(byte[0]);
+
+        // the most significant bit is cleared on the last byte of a varint
+        if byte[0] & 0x80 == 0 {
+            break prost::decode_length_delimiter(&delimiter[..])?;
+        } else if delimiter.len() >= MAX_DELIMITER_LEN {
+            return Err(eyre!("Invalid length delimiter"));
+        }
+    };
+
+    if len > MAX_BUF {
+        return Err(eyre!("Message exceeds the max. size: {} bytes", len));
+    }
+
+    let mut buf = vec![0; len];
+    stream
+        .read_exact(&mut buf)
+        .map_err(|_e| eyre!("Lost connection to Eruption"))?;
+
+    Ok(M::decode(&buf[..])?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    const SECRET: &str = "secret";
+
+    /// Spawn a minimal server on a loopback address, that serves a single client.
+    /// Returns the address and a handle that yields whether the client authenticated
+    fn spawn_server() -> (String, thread::JoinHandle<bool>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let handle = thread::spawn(move || {
+            let (mut stream, _sockaddr) = listener.accept().unwrap();
+
+            let challenge = vec![0x42; 32];
+            let mut authenticated = false;
+
+            while let Ok(request) = read_message::<protocol::Request>(&mut stream) {
+                let mut response = protocol::Response::default();
+                response.set_response_type(request.request_type());
+
+                match (request.request_type(), request.payload) {
+                    (protocol::RequestType::Authenticate, None) => {
+                        response.payload = Some(ResponsePayload::Data(challenge.clone()));
+                    }
+
+                    (protocol::RequestType::Authenticate, Some(RequestPayload::Data(digest))) => {
+                        if digest != auth_digest(&challenge, SECRET) {
+                            break;
+                        }
+
+                        authenticated = true;
+                    }
+
+                    (protocol::RequestType::Status, _) if authenticated => {
+                        response.payload = Some(ResponsePayload::Data(b"Eruption".to_vec()));
+                        response.canvas_width = 30;
+                        response.canvas_height = 6;
+                    }
+
+                    _ => break,
+                }
+
+                let mut buf = Vec::new();
+                response.encode_length_delimited(&mut buf).unwrap();
+                stream.write_all(&buf).unwrap();
+            }
+
+            authenticated
+        });
+
+        (address, handle)
+    }
+
+    #[test]
+    fn remote_transport_authenticates() {
+        let (address, handle) = spawn_server();
+
+        let mut transport = RemoteTransport::new(&address, SECRET).unwrap();
+        transport.connect().unwrap();
+
+        let status = transport.get_server_status().unwrap();
+        assert_eq!(status.server, "Eruption");
+        assert_eq!((status.canvas_width, status.canvas_height), (30, 6));
+
+        transport.disconnect().unwrap();
+        assert!(handle.join().unwrap());
+    }
+
+    #[test]
+    fn remote_transport_rejects_wrong_secret() {
+        let (address, handle) = spawn_server();
+
+        let mut transport = RemoteTransport::new(&address, "wrong").unwrap();
+        assert!(transport.connect().is_err());
+
+        assert!(!handle.join().unwrap());
+    }
+}
@@ -24,6 +24,8 @@ BuildRequires: pulseaudio-libs-devel
 BuildRequires: lua-devel
 BuildRequires: libX11-devel
 BuildRequires: libXrandr-devel
+BuildRequires: pipewire-devel
+BuildRequires: clang-devel
 BuildRequires: gtk3-devel
 BuildRequires: gtksourceview4-devel
 
@@ -392,6 +394,7 @@ install -Dp -m 0755 %{_builddir}/%{OrigName}-%{Commit}/target/release/eruption-g
 %{_datarootdir}/%{ShortName}/scripts/lib/hwdevices/mice/roccat_nyth.lua
 %{_datarootdir}/%{ShortName}/scripts/lib/hwdevices/misc/roccat_elo_71_air.lua
 %{_datarootdir}/%{ShortName}/scripts/lib/hwdevices/misc/roccat_aimo_pad.lua
+%{_datarootdir}/%{ShortName}/scripts/lib/hwdevices/misc/generic_misc.lua
 %{_datarootdir}/%{ShortName}/scripts/lib/hwdevices/misc/custom_serial_leds.lua
 %config %{_datarootdir}/%{ShortName}/scripts/lib/themes/default.lua
 %config %{_datarootdir}/%{ShortName}/scripts/lib/themes/gaming.lua
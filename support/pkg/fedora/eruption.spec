@@ -22,6 +22,8 @@ BuildRequires: pulseaudio-libs-devel
 BuildRequires: lua-devel
 BuildRequires: libX11-devel
 BuildRequires: libXrandr-devel
+BuildRequires: pipewire-devel
+BuildRequires: clang-devel
 #BuildRequires: gtk3-devel
 #BuildRequires: gtksourceview3-devel
 
@@ -391,6 +393,7 @@ install -Dp -m 0755 %{_builddir}/%{name}-releases-v%{version}/target/release/eru
 %{_datarootdir}/%{ShortName}/scripts/lib/hwdevices/mice/roccat_nyth.lua
 %{_datarootdir}/%{ShortName}/scripts/lib/hwdevices/misc/roccat_elo_71_air.lua
 %{_datarootdir}/%{ShortName}/scripts/lib/hwdevices/misc/roccat_aimo_pad.lua
+%{_datarootdir}/%{ShortName}/scripts/lib/hwdevices/misc/generic_misc.lua
 %{_datarootdir}/%{ShortName}/scripts/lib/hwdevices/misc/custom_serial_leds.lua
 %config %{_datarootdir}/%{ShortName}/scripts/lib/themes/default.lua
 %config %{_datarootdir}/%{ShortName}/scripts/lib/themes/gaming.lua
@@ -0,0 +1,31 @@
+#    This file is part of Eruption.
+#
+#    Eruption is free software: you can redistribute it and/or modify
+#    it under the terms of the GNU General Public License as published by
+#    the Free Software Foundation, either version 3 of the License, or
+#    (at your option) any later version.
+#
+#    Eruption is distributed in the hope that it will be useful,
+#    but WITHOUT ANY WARRANTY; without even the implied warranty of
+#    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+#    GNU General Public License for more details.
+#
+#    You should have received a copy of the GNU General Public License
+#    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+#
+#    Copyright (c) 2019-2022, The Eruption Development Team
+
+
+
+id = '5dc62fa6-e965-45cb-a0da-e87d29713094'
+name = 'Organic FX (water)'
+description = 'Organic effects, with additional water effect'
+extends = '5dc62fa6-e965-45cb-a0da-e87d29713093'
+active_scripts = [
+    'water.lua',
+]
+
+[[config.Shockwave]]
+type = 'bool'
+name = 'mouse_events'
+value = false
@@ -0,0 +1,72 @@
+-- This file is part of Eruption.
+--
+-- Eruption is free software: you can redistribute it and/or modify
+-- it under the terms of the GNU General Public License as published by
+-- the Free Software Foundation, either version 3 of the License, or
+-- (at your option) any later version.
+--
+-- Eruption is distributed in the hope that it will be useful,
+-- but WITHOUT ANY WARRANTY without even the implied warranty of
+-- MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+-- GNU General Public License for more details.
+--
+-- You should have received a copy of the GNU General Public License
+-- along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+--
+-- Copyright (c) 2019-2022, The Eruption Development Team
+--
+-- Unit tests of macros.lua and the default macro definitions, run them with:
+-- eruption test --device keyboards/roccat_vulcan_1xx support/tests/scripts/macros.test.lua
+
+harness.test("unmapped keys are not injected", function()
+    harness.press("A")
+
+    harness.expect_injected({})
+end)
+
+harness.test("FN + F2 switches to slot 2", function()
+    harness.hid_key_down(FN_KEY)
+    harness.key_down("F2")
+
+    -- the F2 key press is consumed
+    harness.expect_injected({ "key(0, up)", "slot(1)" })
+
+    harness.key_up("F2")
+    harness.hid_key_up(FN_KEY)
+
+    harness.expect_injected({})
+end)
+
+harness.test("F2 without the modifier key does not switch slots", function()
+    harness.press("F2")
+
+    harness.expect_injected({})
+end)
+
+harness.test("FN + SCROLL LOCK toggles game mode", function()
+    harness.hid_key_down(FN_KEY)
+    harness.hid_key_down(GAME_MODE_KEY)
+
+    harness.assert_eq(game_mode_enabled, true, "Game mode is enabled")
+
+    harness.hid_key_up(GAME_MODE_KEY)
+    harness.hid_key_down(GAME_MODE_KEY)
+
+    harness.assert_eq(game_mode_enabled, false, "Game mode is disabled")
+end)
+
+harness.test("FN highlights the active slot key", function()
+    local f1 = key_name_to_index("F1")
+
+    harness.tick()
+    harness.assert_eq(harness.canvas()[f1], 0, "Nothing is highlighted")
+
+    harness.hid_key_down(FN_KEY)
+    harness.tick()
+
+    local r, g, b, _ = color_to_rgba(COLOR_ACTIVE_SLOT)
+    harness.assert_eq(harness.canvas()[f1], rgba_to_color(r, g, b, 255),
+                      "F1 is highlighted as the active slot")
+
+    harness.hid_key_up(FN_KEY)
+end)
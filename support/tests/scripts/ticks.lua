@@ -0,0 +1,34 @@
+-- This file is part of Eruption.
+--
+-- Eruption is free software: you can redistribute it and/or modify
+-- it under the terms of the GNU General Public License as published by
+-- the Free Software Foundation, either version 3 of the License, or
+-- (at your option) any later version.
+--
+-- Eruption is distributed in the hope that it will be useful,
+-- but WITHOUT ANY WARRANTY without even the implied warranty of
+-- MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+-- GNU General Public License for more details.
+--
+-- You should have received a copy of the GNU General Public License
+-- along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+--
+-- Copyright (c) 2019-2022, The Eruption Development Team
+--
+-- Test effect for the golden frame tests, the brightness of the red channel follows the
+-- accumulated timer ticks, so each frame depends on the delta sent by the renderer
+require "declarations"
+
+-- global state variables --
+ticks = 0
+color_map = {}
+
+function on_tick(delta)
+    ticks = ticks + delta
+
+    for i = 1, canvas_size do
+        color_map[i] = rgba_to_color((ticks * 10) % 256, 0, 0, 255)
+    end
+
+    submit_color_map(color_map)
+end